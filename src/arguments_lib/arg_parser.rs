@@ -0,0 +1,130 @@
+//! Splits the command line arguments into option names and values.
+//!
+//! Every option can be given as `--name=value`, or as `--name value`. Only the first `=` separates the value,
+//! so values can contain `=` (for example, a URL with a query string).
+//! Boolean flags don't need a value: `--totals` is the same as `--totals=true`. To turn them off, use `--totals=false`.
+//!
+//! There are no positional arguments, so anything that is not an option is an error.
+
+pub struct ArgParser<'a> {
+    args: std::iter::Peekable<&'a mut dyn Iterator<Item = String>>,
+    boolean_flags: &'static [&'static str],
+}
+
+impl<'a> ArgParser<'a> {
+    /// *Create a parser over the arguments, with the names of the boolean flags (including their short aliases)*
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let mut args = ArgParser::new(&mut std::env::args().skip(1), &["--totals", "-t"]);
+    /// ```
+    pub fn new(
+        args: &'a mut dyn Iterator<Item = String>,
+        boolean_flags: &'static [&'static str],
+    ) -> Self {
+        Self {
+            args: args.peekable(),
+            boolean_flags,
+        }
+    }
+    /// *Return the next option name and its value, or None when there are no more arguments*
+    ///
+    /// ---
+    ///
+    /// Returns an error for an argument that is not an option, or an option that is missing its value.
+    /// A value given as the next argument can't start with `-`, except for `-` itself (the standard input),
+    /// so a forgotten value does not swallow the next option. Such values can still be given with `=`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// while let Some((arg_name, arg_value)) = args.next_arg()? {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn next_arg(&mut self) -> Result<Option<(String, String)>, String> {
+        let Some(arg) = self.args.next() else {
+            return Ok(None);
+        };
+
+        if !arg.starts_with('-') || arg == "-" {
+            return Err(format!(
+                "Unexpected argument: {}. Options start with -- (for example --log_dir=logs). Check your input!",
+                arg
+            ));
+        }
+
+        if let Some((arg_name, arg_value)) = arg.split_once('=') {
+            return Ok(Some((arg_name.to_string(), arg_value.to_string())));
+        }
+
+        if self.boolean_flags.contains(&arg.as_str()) {
+            return Ok(Some((arg, "true".to_string())));
+        }
+
+        match self
+            .args
+            .next_if(|value| !value.starts_with('-') || value == "-")
+        {
+            Some(arg_value) => Ok(Some((arg, arg_value))),
+            None => Err(format!("{} needs a value. Check your input!", arg)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(args: &[&str]) -> Result<Vec<(String, String)>, String> {
+        let mut args = args.iter().map(|arg| arg.to_string());
+        let mut arg_parser = ArgParser::new(&mut args, &["--totals", "-t"]);
+        let mut parsed: Vec<(String, String)> = Vec::new();
+
+        while let Some(arg) = arg_parser.next_arg()? {
+            parsed.push(arg);
+        }
+
+        Ok(parsed)
+    }
+
+    #[test]
+    fn test_arg_parser() {
+        let parsed = parse_all(&[
+            "--log_dir=logs",
+            "--formatter",
+            "json",
+            "-t",
+            "--input",
+            "-",
+            "--totals=false",
+            "--webhook-url=http://localhost/usage?key=a=b",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            parsed,
+            vec![
+                ("--log_dir".to_string(), "logs".to_string()),
+                ("--formatter".to_string(), "json".to_string()),
+                ("-t".to_string(), "true".to_string()),
+                ("--input".to_string(), "-".to_string()),
+                ("--totals".to_string(), "false".to_string()),
+                (
+                    "--webhook-url".to_string(),
+                    "http://localhost/usage?key=a=b".to_string()
+                ),
+            ]
+        );
+
+        for (args, error) in [
+            (vec!["--log_dir"], "--log_dir needs a value"),
+            (vec!["--log_dir", "--totals"], "--log_dir needs a value"),
+            (vec!["logs"], "Unexpected argument: logs"),
+            (vec!["-"], "Unexpected argument: -"),
+        ] {
+            assert!(parse_all(&args).unwrap_err().contains(error), "{:?}", args);
+        }
+    }
+}
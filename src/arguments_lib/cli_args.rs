@@ -1,27 +1,83 @@
 //! Struct for collecting all required arguments from the command line.
 //!
 //! When adding new arguments, a new field should be added, as well as a corresponding extractor code (potentially with validation).
-use super::super::FormatterFactory;
+use super::super::formatters::formatter_factory::FormatterFactory;
+use super::super::log_parser_lib::input_format_enum::InputFormat;
+use super::super::log_parser_lib::metric_schema::MetricSchema;
+use super::super::log_parser_lib::on_error_policy_enum::OnErrorPolicy;
+use super::super::log_parser_lib::overflow_policy_enum::OverflowPolicy;
+use super::super::log_parser_lib::time_bucket_enum::TimeBucket;
+use super::super::log_parser_lib::timestamp::parse_time_bound;
+use super::super::sinks::sink_factory::SinkFactory;
+use super::super::sinks::sink_options::SinkOptions;
+use super::super::utils::fs_utils::DiscoveryOptions;
+use super::super::utils::symlink_policy_enum::SymlinkPolicy;
+use super::arg_parser::ArgParser;
+
+/// Options that don't need a value. Given without one, they are true.
+pub const BOOLEAN_FLAGS: &[&str] = &["--totals", "-t", "--compact", "--checksum"];
+
 #[derive(Debug)]
 pub struct CLIArgs {
-    logs_dir: String,
+    logs_dirs: Vec<String>,
+    inputs: Vec<String>,
+    files_from: Option<String>,
     formatter: String,
+    totals: bool,
+    compact: bool,
+    workers: Option<usize>,
+    input_format: InputFormat,
+    on_error: OnErrorPolicy,
+    overflow_policy: OverflowPolicy,
+    dead_letter: Option<String>,
+    max_error_rate: Option<f64>,
+    schema: MetricSchema,
+    group_by: Vec<String>,
+    discovery: DiscoveryOptions,
+    bucket: Option<TimeBucket>,
+    since: Option<i64>,
+    until: Option<i64>,
+    incremental: Option<String>,
+    flush_interval: u64,
+    sink: String,
+    sink_options: SinkOptions,
+    store: Option<String>,
 }
 
 impl CLIArgs {
-    /// *Simple utility function, for extracting the log dir*
+    /// *Simple utility function, for extracting the log dirs*
     ///
     /// ---
     ///
+    /// `--log_dir` can be given multiple times.
+    ///
     /// ## Example
     ///
     /// ```
     /// let cli_args = CLIArgs::build(&mut vec!["--log_dir=test_dir".to_string()].into_iter()).unwrap();
     ///
-    /// assert_eq!(cli_args.get_logs_dir(), "test_dir");
+    /// assert_eq!(cli_args.get_logs_dirs(), &["test_dir".to_string()]);
+    /// ```
+    pub fn get_logs_dirs(&self) -> &[String] {
+        &self.logs_dirs
+    }
+    /// *Get the log files given directly, where `-` means the standard input*
+    ///
+    /// ---
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let cli_args = CLIArgs::build(&mut vec!["--input=-".to_string()].into_iter()).unwrap();
+    ///
+    /// assert_eq!(cli_args.get_inputs(), &["-".to_string()]);
     /// ```
-    pub fn get_logs_dir(&self) -> &String {
-        &self.logs_dir
+    pub fn get_inputs(&self) -> &[String] {
+        &self.inputs
+    }
+    /// *Get the manifest file, listing the log files to parse (one per line)*
+    pub fn get_files_from(&self) -> Option<&String> {
+        self.files_from.as_ref()
     }
 
     /// *Get the chosen formatter*
@@ -41,11 +97,244 @@ impl CLIArgs {
     pub fn get_formatter(&self) -> &String {
         &self.formatter
     }
+    /// *Get if a totals row should be added (csv and tsv formatters)*
+    pub fn get_totals(&self) -> bool {
+        self.totals
+    }
+    /// *Get if the json output should be compact, instead of pretty printed*
+    pub fn get_compact(&self) -> bool {
+        self.compact
+    }
+    /// *Get the number of workers, if the user provided it*
+    ///
+    /// ---
+    ///
+    /// None means that the number of workers should be decided automatically, depending on the CPU.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let cli_args = CLIArgs::build(&mut vec![
+    ///     "--log_dir=test_dir".to_string(),
+    ///     "--workers=4".to_string(),
+    /// ].into_iter()).unwrap();
+    ///
+    /// assert_eq!(cli_args.get_workers(), Some(4));
+    /// ```
+    pub fn get_workers(&self) -> Option<usize> {
+        self.workers
+    }
+    /// *Get the format of the log lines*
+    ///
+    /// ---
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let cli_args = CLIArgs::build(&mut vec![
+    ///     "--log_dir=test_dir".to_string(),
+    ///     "--input-format=combined".to_string(),
+    /// ].into_iter()).unwrap();
+    ///
+    /// assert_eq!(cli_args.get_input_format(), InputFormat::Combined);
+    /// ```
+    pub fn get_input_format(&self) -> InputFormat {
+        self.input_format
+    }
+    /// *Get what should happen with malformed log lines*
+    ///
+    /// ---
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let cli_args = CLIArgs::build(&mut vec![
+    ///     "--log_dir=test_dir".to_string(),
+    ///     "--on-error=skip".to_string(),
+    /// ].into_iter()).unwrap();
+    ///
+    /// assert_eq!(cli_args.get_on_error(), OnErrorPolicy::Skip);
+    /// ```
+    pub fn get_on_error(&self) -> OnErrorPolicy {
+        self.on_error
+    }
+    /// *Get what should happen when adding to a metric would overflow*
+    ///
+    /// ---
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let cli_args = CLIArgs::build(&mut vec![
+    ///     "--log_dir=test_dir".to_string(),
+    ///     "--on-overflow=saturate".to_string(),
+    /// ].into_iter()).unwrap();
+    ///
+    /// assert_eq!(cli_args.get_overflow_policy(), OverflowPolicy::Saturate);
+    /// ```
+    pub fn get_overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+    /// *Get the dead-letter file path, used in the quarantine mode*
+    pub fn get_dead_letter(&self) -> Option<&String> {
+        self.dead_letter.as_ref()
+    }
+    /// *Get the maximum share (between 0 and 1) of lines that can be rejected, before the run fails*
+    pub fn get_max_error_rate(&self) -> Option<f64> {
+        self.max_error_rate
+    }
+    /// *Get the metric schema*
+    ///
+    /// ---
+    ///
+    /// It is the built-in schema (video plays and ad impressions), unless a schema file is given with `--schema`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let cli_args = CLIArgs::build(&mut vec![
+    ///     "--log_dir=test_dir".to_string(),
+    ///     "--schema=schema.txt".to_string(),
+    /// ].into_iter()).unwrap();
+    ///
+    /// let metrics = cli_args.get_schema().get_metrics();
+    /// ```
+    pub fn get_schema(&self) -> &MetricSchema {
+        &self.schema
+    }
+    /// *Get the entities, the usage should be broken down by*
+    ///
+    /// ---
+    ///
+    /// Usage is always grouped by the owner, so `owner` is not included in the list.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let cli_args = CLIArgs::build(&mut vec![
+    ///     "--log_dir=test_dir".to_string(),
+    ///     "--group-by=owner,video".to_string(),
+    /// ].into_iter()).unwrap();
+    ///
+    /// assert_eq!(cli_args.get_group_by(), &["video".to_string()]);
+    /// ```
+    pub fn get_group_by(&self) -> &[String] {
+        &self.group_by
+    }
+    /// *Get the include / exclude globs, and the symlink policy, for finding the log files*
+    ///
+    /// ---
+    ///
+    /// Both `--include` and `--exclude` can be given multiple times.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let cli_args = CLIArgs::build(&mut vec![
+    ///     "--log_dir=test_dir".to_string(),
+    ///     "--include=*.log*".to_string(),
+    ///     "--exclude=*.tmp".to_string(),
+    /// ].into_iter()).unwrap();
+    ///
+    /// assert_eq!(cli_args.get_discovery().get_include(), &["*.log*".to_string()]);
+    /// ```
+    pub fn get_discovery(&self) -> &DiscoveryOptions {
+        &self.discovery
+    }
+    /// *Get the size of the time buckets, if the usage should be bucketed by time*
+    ///
+    /// ---
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let cli_args = CLIArgs::build(&mut vec![
+    ///     "--log_dir=test_dir".to_string(),
+    ///     "--bucket=day".to_string(),
+    /// ].into_iter()).unwrap();
+    ///
+    /// assert_eq!(cli_args.get_bucket(), Some(TimeBucket::Day));
+    /// ```
+    pub fn get_bucket(&self) -> Option<TimeBucket> {
+        self.bucket
+    }
+    /// *Get the inclusive start of the time window, in seconds since the Unix epoch*
+    pub fn get_since(&self) -> Option<i64> {
+        self.since
+    }
+    /// *Get the exclusive end of the time window, in seconds since the Unix epoch*
+    pub fn get_until(&self) -> Option<i64> {
+        self.until
+    }
+    /// *Get the state file, if only what was appended since the previous run should be parsed*
+    ///
+    /// ---
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let cli_args = CLIArgs::build(&mut vec![
+    ///     "--log_dir=test_dir".to_string(),
+    ///     "--incremental=usage-parse.state".to_string(),
+    /// ].into_iter()).unwrap();
+    ///
+    /// assert_eq!(cli_args.get_incremental(), Some(&"usage-parse.state".to_string()));
+    /// ```
+    pub fn get_incremental(&self) -> Option<&String> {
+        self.incremental.as_ref()
+    }
+    /// *Get how often the follow mode flushes the aggregate, in seconds. Defaults to 60.*
+    pub fn get_flush_interval(&self) -> u64 {
+        self.flush_interval
+    }
+    /// *Get the chosen sink*
+    ///
+    /// ---
+    ///
+    /// Defaults to `file` if there is an output file, and to `stdout` otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let cli_args = CLIArgs::build(&mut vec![
+    ///     "--log_dir=test_dir".to_string(),
+    ///     "--output=reports/usage.json".to_string(),
+    /// ].into_iter()).unwrap();
+    ///
+    /// assert_eq!(cli_args.get_sink(), "file");
+    /// assert_eq!(cli_args.get_sink_options().get_output(), Some("reports/usage.json"));
+    /// ```
+    pub fn get_sink(&self) -> &str {
+        &self.sink
+    }
+    /// *Get the options of the sinks, like the output file or the webhook URL*
+    pub fn get_sink_options(&self) -> &SinkOptions {
+        &self.sink_options
+    }
+    /// *Get the usage store, the run should be recorded into (see `usage_store.rs`)*
+    ///
+    /// ---
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let cli_args = CLIArgs::build(&mut vec![
+    ///     "--log_dir=test_dir".to_string(),
+    ///     "--store=usage.db".to_string(),
+    /// ].into_iter()).unwrap();
+    ///
+    /// assert_eq!(cli_args.get_store(), Some("usage.db"));
+    /// ```
+    pub fn get_store(&self) -> Option<&str> {
+        self.store.as_deref()
+    }
     /// *Get required arguments from the command line*
     ///
     /// ---
     ///
     /// All required arguments must be present, and be in valid format. Leave optional as they are.
+    /// Options are given as `--name=value` or `--name value`, and the boolean ones also as bare flags (see `arg_parser.rs`).
+    /// At least one input is required: a logs directory, a log file (or `-` for the standard input), or a manifest file.
     /// Method could return `Err(String)`, if something went wrong, so make sure to check for that.
     ///
     /// ---
@@ -62,47 +351,255 @@ impl CLIArgs {
     /// assert!(!cli_args.is_err());
     /// ```
     pub fn build(env_iterator: &mut dyn Iterator<Item = String>) -> Result<CLIArgs, String> {
-        let mut logs_dir = String::new();
+        let mut logs_dirs: Vec<String> = Vec::new();
+        let mut inputs: Vec<String> = Vec::new();
+        let mut files_from: Option<String> = None;
         let mut formatter = String::from("stdout");
+        let mut totals = false;
+        let mut compact = false;
+        let mut workers: Option<usize> = None;
+        let mut input_format = InputFormat::Url;
+        let mut on_error = OnErrorPolicy::Abort;
+        let mut overflow_policy = OverflowPolicy::Error;
+        let mut dead_letter: Option<String> = None;
+        let mut max_error_rate: Option<f64> = None;
+        let mut schema = MetricSchema::default();
+        let mut group_by: Vec<String> = Vec::new();
+        let mut include: Vec<String> = Vec::new();
+        let mut exclude: Vec<String> = Vec::new();
+        let mut symlinks = SymlinkPolicy::Follow;
+        let mut bucket: Option<TimeBucket> = None;
+        let mut since: Option<i64> = None;
+        let mut until: Option<i64> = None;
+        let mut incremental: Option<String> = None;
+        let mut flush_interval: u64 = 60;
+        let mut sink: Option<String> = None;
+        let mut output: Option<String> = None;
+        let mut checksum = false;
+        let mut webhook_url: Option<String> = None;
+        let mut webhook_retries: u32 = 3;
+        let mut webhook_timeout: u64 = 10;
+        let mut database: Option<String> = None;
+        let mut store: Option<String> = None;
 
-        for arg in env_iterator {
-            let mut split = arg.split("=");
+        let mut args = ArgParser::new(env_iterator, BOOLEAN_FLAGS);
 
-            let arg_name = match split.next() {
-                Some(arg_name) => arg_name,
-                None => {
-                    return Err(
-                        "Could not extract the first part from the argument. Check your input!"
-                            .to_string(),
-                    );
-                }
-            };
+        while let Some((arg_name, arg_value)) = args.next_arg()? {
+            let arg_value = arg_value.as_str();
 
-            let arg_value = match split.next() {
-                Some(arg_value) => arg_value,
-                None => {
-                    return Err(
-                        "Could not extract the second part from the argument. Check your input!"
-                            .to_string(),
-                    );
+            match arg_name.as_str() {
+                // At least one input is required.
+                // Can be given multiple times.
+                "--log_dir" | "-ld" => {
+                    logs_dirs.push(arg_value.trim().to_owned());
                 }
-            };
+                // Log file, or - for the standard input. Can be given multiple times.
+                "--input" | "-i" => {
+                    let input = arg_value.trim().to_owned();
 
-            match arg_name {
-                // Required
-                "--log_dir" | "-ld" => {
-                    logs_dir = arg_value.trim().to_owned();
+                    if input == "-" && inputs.contains(&input) {
+                        return Err("The standard input (-) can be given only once".to_string());
+                    }
+
+                    inputs.push(input);
+                }
+                // Manifest file, with one log file path per line.
+                "--files-from" | "-ff" => {
+                    files_from = Some(arg_value.trim().to_owned());
                 }
                 // Optional
                 // If present, must be a known formatter
                 "--formatter" | "-fmt" => {
                     if FormatterFactory::resolve_formatter(arg_value).is_err() {
-                        return Err("Unknown formatter. Run the CLI with the -h, to get the list of the available formatters".to_string());
+                        return Err("Unknown formatter. Run the CLI with the --help, to get the list of the available formatters".to_string());
                     }
 
                     formatter.clear();
                     formatter.push_str(arg_value);
                 }
+                // Optional
+                // A flag, or true | false. Adds a totals row, in the csv and tsv formatters.
+                "--totals" | "-t" => match arg_value.trim() {
+                    "true" => totals = true,
+                    "false" => totals = false,
+                    _ => {
+                        return Err("Totals must be true or false. Check your input!".to_string());
+                    }
+                },
+                // Optional
+                // A flag, or true | false. Writes the json output without any whitespace.
+                "--compact" => match arg_value.trim() {
+                    "true" => compact = true,
+                    "false" => compact = false,
+                    _ => {
+                        return Err("Compact must be true or false. Check your input!".to_string());
+                    }
+                },
+                // Optional
+                // If present, must be a positive integer
+                "--workers" | "-w" => match arg_value.trim().parse::<usize>() {
+                    Ok(number_of_workers) if number_of_workers > 0 => {
+                        workers = Some(number_of_workers);
+                    }
+                    _ => {
+                        return Err(
+                            "Number of workers must be a positive integer. Check your input!"
+                                .to_string(),
+                        );
+                    }
+                },
+                // Optional
+                // If present, must be one of url | common | combined
+                "--input-format" | "-if" => {
+                    input_format = InputFormat::resolve(arg_value.trim())?;
+                }
+                // Optional
+                // If present, must be one of abort | skip | quarantine
+                "--on-error" | "-oe" => {
+                    on_error = OnErrorPolicy::resolve(arg_value.trim())?;
+                }
+                // Optional
+                // If present, must be one of error | saturate | wrap-with-warning
+                "--on-overflow" | "-oo" => {
+                    overflow_policy = OverflowPolicy::resolve(arg_value.trim())?;
+                }
+                // Optional
+                // Where to write the rejected lines, in the quarantine mode. Defaults to dead_letter.txt
+                "--dead-letter" | "-dl" => {
+                    dead_letter = Some(arg_value.trim().to_owned());
+                }
+                // Optional
+                // If present, must be a number between 0 and 1
+                "--max-error-rate" | "-mer" => match arg_value.trim().parse::<f64>() {
+                    Ok(rate) if (0.0..=1.0).contains(&rate) => {
+                        max_error_rate = Some(rate);
+                    }
+                    _ => {
+                        return Err(
+                            "Maximum error rate must be a number between 0 and 1. Check your input!"
+                                .to_string(),
+                        );
+                    }
+                },
+                // Optional
+                // If present, must be a valid schema file
+                "--schema" | "-s" => {
+                    schema = MetricSchema::load(arg_value.trim())?;
+                }
+                // Optional
+                // Comma separated list of entities from the schema. Checked once all arguments are read.
+                "--group-by" | "-g" => {
+                    group_by = arg_value
+                        .split(',')
+                        .map(|entity| entity.trim())
+                        .filter(|entity| !entity.is_empty() && *entity != "owner")
+                        .map(|entity| entity.to_string())
+                        .collect();
+                }
+                // Optional
+                // Glob, can be given multiple times. Only files matching one of them are parsed.
+                "--include" | "-in" => {
+                    include.push(arg_value.trim().to_owned());
+                }
+                // Optional
+                // Glob, can be given multiple times. Files and directories matching any of them are skipped.
+                "--exclude" | "-ex" => {
+                    exclude.push(arg_value.trim().to_owned());
+                }
+                // Optional
+                // One of follow | skip
+                "--symlinks" => {
+                    symlinks = SymlinkPolicy::resolve(arg_value.trim())?;
+                }
+                // Optional
+                // One of hour | day | month.
+                "--bucket" | "-b" => {
+                    bucket = Some(TimeBucket::resolve(arg_value.trim())?);
+                }
+                // Optional
+                // RFC 3339 timestamp, or a date (YYYY-MM-DD). Inclusive.
+                "--since" => {
+                    since = Some(parse_time_bound(arg_value.trim())?);
+                }
+                // Optional
+                // RFC 3339 timestamp, or a date (YYYY-MM-DD). Exclusive.
+                "--until" => {
+                    until = Some(parse_time_bound(arg_value.trim())?);
+                }
+                // Optional
+                // Path of the state file. Created on the first run.
+                "--incremental" | "-inc" => {
+                    incremental = Some(arg_value.trim().to_owned());
+                }
+                // Optional, only used by the follow mode
+                // If present, must be a positive integer (seconds)
+                "--flush-interval" | "-fi" => match arg_value.trim().parse::<u64>() {
+                    Ok(seconds) if seconds > 0 => {
+                        flush_interval = seconds;
+                    }
+                    _ => {
+                        return Err(
+                            "Flush interval must be a positive number of seconds. Check your input!"
+                                .to_string(),
+                        );
+                    }
+                },
+                // Optional
+                // If present, must be one of stdout | file | webhook | sqlite. Checked once all arguments are read.
+                "--sink" => {
+                    sink = Some(arg_value.trim().to_owned());
+                }
+                // Optional
+                // Path of the output file, for the file sink. Written atomically.
+                "--output" | "-o" => {
+                    output = Some(arg_value.trim().to_owned());
+                }
+                // Optional
+                // A flag, or true | false. Writes <output>.sha256 next to the output file.
+                "--checksum" => match arg_value.trim() {
+                    "true" => checksum = true,
+                    "false" => checksum = false,
+                    _ => {
+                        return Err("Checksum must be true or false. Check your input!".to_string());
+                    }
+                },
+                // Optional
+                // http:// or https:// URL, for the webhook sink
+                "--webhook-url" => {
+                    webhook_url = Some(arg_value.trim().to_owned());
+                }
+                // Optional
+                // If present, must be a non-negative integer. Defaults to 3.
+                "--webhook-retries" => match arg_value.trim().parse::<u32>() {
+                    Ok(retries) => webhook_retries = retries,
+                    _ => {
+                        return Err(
+                            "Webhook retries must be a non-negative integer. Check your input!"
+                                .to_string(),
+                        );
+                    }
+                },
+                // Optional
+                // If present, must be a positive integer (seconds). Defaults to 10.
+                "--webhook-timeout" => match arg_value.trim().parse::<u64>() {
+                    Ok(seconds) if seconds > 0 => webhook_timeout = seconds,
+                    _ => {
+                        return Err(
+                            "Webhook timeout must be a positive number of seconds. Check your input!"
+                                .to_string(),
+                        );
+                    }
+                },
+                // Optional
+                // Path of the SQLite database file, for the sqlite sink. Created if it does not exist.
+                "--database" | "-db" => {
+                    database = Some(arg_value.trim().to_owned());
+                }
+                // Optional
+                // Path of the SQLite usage store, the run is recorded into. Created if it does not exist.
+                "--store" => {
+                    store = Some(arg_value.trim().to_owned());
+                }
 
                 unknown_arg_name => {
                     return Err(format!("Unknown parameter: {}", unknown_arg_name));
@@ -110,13 +607,109 @@ impl CLIArgs {
             }
         }
         // Check if any of the variables are left empty.
-        if logs_dir.is_empty() {
-            return Err("Logs directory parameter is missing! Check your input".to_string());
+        if logs_dirs.iter().any(|logs_dir| logs_dir.is_empty())
+            || inputs.iter().any(|input| input.is_empty())
+            || files_from
+                .as_ref()
+                .is_some_and(|files_from| files_from.is_empty())
+            || incremental
+                .as_ref()
+                .is_some_and(|incremental| incremental.is_empty())
+        {
+            return Err("Input paths must not be empty! Check your input".to_string());
+        }
+
+        if output.as_ref().is_some_and(|output| output.is_empty()) {
+            return Err("Output path must not be empty! Check your input".to_string());
+        }
+
+        if store.as_ref().is_some_and(|store| store.is_empty()) {
+            return Err("Store path must not be empty! Check your input".to_string());
+        }
+        // The store keeps its own checkpoints of the parsed files, and adds only what was read after them.
+        // The standard input has no checkpoint, and a state file would be a second one, out of sync with the store.
+        if store.is_some() && inputs.iter().any(|input| input == "-") {
+            return Err(
+                "--store can't be used with the standard input. Check your input!".to_string(),
+            );
+        }
+
+        if store.is_some() && incremental.is_some() {
+            return Err("--store can't be used with --incremental. Check your input!".to_string());
+        }
+
+        if checksum && output.is_none() {
+            return Err("--checksum can only be used with --output. Check your input!".to_string());
+        }
+
+        let sink =
+            sink.unwrap_or_else(|| if output.is_some() { "file" } else { "stdout" }.to_string());
+        let sink_options = SinkOptions::default()
+            .with_output(output)
+            .with_checksum(checksum)
+            .with_webhook_url(webhook_url)
+            .with_webhook_retries(webhook_retries)
+            .with_webhook_timeout(std::time::Duration::from_secs(webhook_timeout))
+            .with_database(database);
+        // Also checks that the sink has everything it needs (for example, the webhook URL).
+        SinkFactory::resolve_sink(&sink, &sink_options)?;
+
+        if logs_dirs.is_empty() && inputs.is_empty() && files_from.is_none() {
+            return Err(
+                "Logs directory parameter is missing! Use --log_dir, --input or --files-from. Check your input"
+                    .to_string(),
+            );
+        }
+
+        // Every entity to group by must exist in the schema (which could be given after the --group-by).
+        for entity in &group_by {
+            if schema.get_entity(entity).is_none() {
+                return Err(format!(
+                    "Unknown entity to group by: {}. Available entities: owner, {}",
+                    entity,
+                    schema
+                        .get_entities()
+                        .iter()
+                        .map(|entity| entity.get_name())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                ));
+            }
+        }
+
+        if let (Some(since), Some(until)) = (since, until)
+            && since >= until
+        {
+            return Err("--since must be before --until".to_string());
         }
 
         let cli_args: CLIArgs = CLIArgs {
-            logs_dir,
+            logs_dirs,
+            inputs,
+            files_from,
             formatter,
+            totals,
+            compact,
+            workers,
+            input_format,
+            on_error,
+            overflow_policy,
+            dead_letter,
+            max_error_rate,
+            schema,
+            group_by,
+            discovery: DiscoveryOptions::default()
+                .with_include(include)
+                .with_exclude(exclude)
+                .with_symlinks(symlinks),
+            bucket,
+            since,
+            until,
+            incremental,
+            flush_interval,
+            sink,
+            sink_options,
+            store,
         };
 
         Ok(cli_args)
@@ -133,7 +726,7 @@ mod tests {
 
         let cli_args = CLIArgs::build(&mut args_list.into_iter()).unwrap();
 
-        assert_eq!(cli_args.get_logs_dir(), "test_dir");
+        assert_eq!(cli_args.get_logs_dirs(), &["test_dir".to_string()]);
     }
 
     #[test]
@@ -142,7 +735,7 @@ mod tests {
 
         let cli_args = CLIArgs::build(&mut args_list.into_iter()).unwrap();
 
-        assert_eq!(cli_args.get_logs_dir(), "test_dir");
+        assert_eq!(cli_args.get_logs_dirs(), &["test_dir".to_string()]);
     }
 
     #[test]
@@ -150,15 +743,15 @@ mod tests {
         let cli_args = CLIArgs::build(&mut vec![].into_iter());
 
         assert!(cli_args.is_err());
-        assert!(cli_args.unwrap_err() == "Logs directory parameter is missing! Check your input");
+        assert!(
+            cli_args.unwrap_err()
+                == "Logs directory parameter is missing! Use --log_dir, --input or --files-from. Check your input"
+        );
 
         let cli_args = CLIArgs::build(&mut vec!["--log_dir".to_string()].into_iter());
 
         assert!(cli_args.is_err());
-        assert!(
-            cli_args.unwrap_err()
-                == "Could not extract the second part from the argument. Check your input!"
-        );
+        assert!(cli_args.unwrap_err() == "--log_dir needs a value. Check your input!");
 
         let cli_args =
             CLIArgs::build(&mut vec!["--non_existant_arg=some_value".to_string()].into_iter());
@@ -168,27 +761,67 @@ mod tests {
     }
 
     #[test]
-    fn test_formatter_arg() {
-        // Just to test if didn't returned an error, since this is an optional param.
-        let cli_args = CLIArgs::build(&mut vec!["--log_dir=test_dir".to_string()].into_iter());
-
-        assert!(!cli_args.is_err());
-
+    fn test_input_args() {
         let cli_args = CLIArgs::build(
             &mut vec![
-                "--log_dir=test_dir".to_string(),
-                "--formatter=json".to_string(),
+                "--log_dir=logs/nginx".to_string(),
+                "-ld=logs/apache".to_string(),
+                "--input=-".to_string(),
+                "-i=extra/access.log".to_string(),
+                "--files-from=manifest.txt".to_string(),
             ]
             .into_iter(),
         )
         .unwrap();
 
-        assert_eq!(cli_args.get_formatter(), "json");
+        assert_eq!(
+            cli_args.get_logs_dirs(),
+            &["logs/nginx".to_string(), "logs/apache".to_string()]
+        );
+        assert_eq!(
+            cli_args.get_inputs(),
+            &["-".to_string(), "extra/access.log".to_string()]
+        );
+        assert_eq!(cli_args.get_files_from(), Some(&"manifest.txt".to_string()));
 
-        let cli_args = CLIArgs::build(
-            &mut vec!["-ld=test_dir".to_string(), "-fmt=json".to_string()].into_iter(),
-        )
-        .unwrap();
+        // Any single input is enough.
+        let cli_args =
+            CLIArgs::build(&mut vec!["--files-from=manifest.txt".to_string()].into_iter()).unwrap();
+
+        assert!(cli_args.get_logs_dirs().is_empty());
+
+        let cli_args =
+            CLIArgs::build(&mut vec!["--input=-".to_string(), "-i=-".to_string()].into_iter());
+
+        assert!(cli_args.unwrap_err().contains("only once"));
+
+        let cli_args = CLIArgs::build(&mut vec!["--log_dir=".to_string()].into_iter());
+
+        assert!(cli_args.unwrap_err().contains("must not be empty"));
+    }
+
+    #[test]
+    fn test_formatter_arg() {
+        // Just to test if didn't returned an error, since this is an optional param.
+        let cli_args = CLIArgs::build(&mut vec!["--log_dir=test_dir".to_string()].into_iter());
+
+        assert!(cli_args.is_ok());
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=test_dir".to_string(),
+                "--formatter=json".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(cli_args.get_formatter(), "json");
+
+        let cli_args = CLIArgs::build(
+            &mut vec!["-ld=test_dir".to_string(), "-fmt=json".to_string()].into_iter(),
+        )
+        .unwrap();
 
         assert_eq!(cli_args.get_formatter(), "json");
 
@@ -219,5 +852,527 @@ mod tests {
         );
 
         assert!(cli_args.unwrap_err().contains("Unknown formatter"));
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "-ld=test_dir".to_string(),
+                "-fmt=csv".to_string(),
+                "--totals=true".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(cli_args.get_formatter(), "csv");
+        assert!(cli_args.get_totals());
+        assert!(!cli_args.get_compact());
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "-ld=test_dir".to_string(),
+                "-fmt=ndjson".to_string(),
+                "--compact=true".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(cli_args.get_formatter(), "ndjson");
+        assert!(cli_args.get_compact());
+
+        let cli_args =
+            CLIArgs::build(&mut vec!["-ld=test_dir".to_string(), "-t=yes".to_string()].into_iter());
+
+        assert!(cli_args.unwrap_err().contains("true or false"));
+    }
+
+    #[test]
+    fn test_workers_arg() {
+        let cli_args =
+            CLIArgs::build(&mut vec!["--log_dir=test_dir".to_string()].into_iter()).unwrap();
+
+        assert_eq!(cli_args.get_workers(), None);
+
+        let cli_args = CLIArgs::build(
+            &mut vec!["--log_dir=test_dir".to_string(), "--workers=8".to_string()].into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(cli_args.get_workers(), Some(8));
+
+        let cli_args =
+            CLIArgs::build(&mut vec!["-ld=test_dir".to_string(), "-w=2".to_string()].into_iter())
+                .unwrap();
+
+        assert_eq!(cli_args.get_workers(), Some(2));
+
+        let cli_args = CLIArgs::build(
+            &mut vec!["--log_dir=test_dir".to_string(), "--workers=0".to_string()].into_iter(),
+        );
+
+        assert!(cli_args.unwrap_err().contains("positive integer"));
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=test_dir".to_string(),
+                "--workers=abc".to_string(),
+            ]
+            .into_iter(),
+        );
+
+        assert!(cli_args.is_err());
+    }
+
+    #[test]
+    fn test_on_error_args() {
+        let cli_args =
+            CLIArgs::build(&mut vec!["--log_dir=test_dir".to_string()].into_iter()).unwrap();
+
+        assert_eq!(cli_args.get_on_error(), OnErrorPolicy::Abort);
+        assert_eq!(cli_args.get_dead_letter(), None);
+        assert_eq!(cli_args.get_max_error_rate(), None);
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=test_dir".to_string(),
+                "--on-error=quarantine".to_string(),
+                "--dead-letter=rejected.txt".to_string(),
+                "--max-error-rate=0.05".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(cli_args.get_on_error(), OnErrorPolicy::Quarantine);
+        assert_eq!(
+            cli_args.get_dead_letter(),
+            Some(&"rejected.txt".to_string())
+        );
+        assert_eq!(cli_args.get_max_error_rate(), Some(0.05));
+
+        let cli_args = CLIArgs::build(
+            &mut vec!["-ld=test_dir".to_string(), "-oe=ignore".to_string()].into_iter(),
+        );
+
+        assert!(cli_args.unwrap_err().contains("Unknown on-error policy"));
+
+        let cli_args = CLIArgs::build(
+            &mut vec!["-ld=test_dir".to_string(), "-mer=1.5".to_string()].into_iter(),
+        );
+
+        assert!(cli_args.unwrap_err().contains("between 0 and 1"));
+    }
+
+    #[test]
+    fn test_on_overflow_arg() {
+        let cli_args =
+            CLIArgs::build(&mut vec!["--log_dir=test_dir".to_string()].into_iter()).unwrap();
+
+        assert_eq!(cli_args.get_overflow_policy(), OverflowPolicy::Error);
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=test_dir".to_string(),
+                "-oo=wrap-with-warning".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            cli_args.get_overflow_policy(),
+            OverflowPolicy::WrapWithWarning
+        );
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "-ld=test_dir".to_string(),
+                "--on-overflow=ignore".to_string(),
+            ]
+            .into_iter(),
+        );
+
+        assert!(cli_args.unwrap_err().contains("Unknown overflow policy"));
+    }
+
+    #[test]
+    fn test_schema_arg() {
+        let cli_args =
+            CLIArgs::build(&mut vec!["--log_dir=test_dir".to_string()].into_iter()).unwrap();
+
+        assert_eq!(cli_args.get_schema(), &MetricSchema::default());
+
+        let test_schema_path = "test_cli_schema.txt";
+
+        std::fs::write(test_schema_path, "metric d watch_seconds sum\n").unwrap();
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=test_dir".to_string(),
+                format!("--schema={}", test_schema_path),
+            ]
+            .into_iter(),
+        );
+
+        std::fs::remove_file(test_schema_path).unwrap();
+
+        let cli_args = cli_args.unwrap();
+
+        assert_eq!(cli_args.get_schema().get_metrics().len(), 1);
+        assert_eq!(
+            cli_args.get_schema().get_metrics()[0].get_metric_name(),
+            "watch_seconds"
+        );
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "-ld=test_dir".to_string(),
+                "-s=not_existing_schema.txt".to_string(),
+            ]
+            .into_iter(),
+        );
+
+        assert!(
+            cli_args
+                .unwrap_err()
+                .contains("Could not read the schema file")
+        );
+    }
+
+    #[test]
+    fn test_group_by_arg() {
+        let cli_args =
+            CLIArgs::build(&mut vec!["--log_dir=test_dir".to_string()].into_iter()).unwrap();
+
+        assert!(cli_args.get_group_by().is_empty());
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=test_dir".to_string(),
+                "--group-by=owner, video,ad_unit".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            cli_args.get_group_by(),
+            &["video".to_string(), "ad_unit".to_string()]
+        );
+
+        let cli_args = CLIArgs::build(
+            &mut vec!["-ld=test_dir".to_string(), "-g=owner,player".to_string()].into_iter(),
+        );
+
+        assert!(
+            cli_args
+                .unwrap_err()
+                .contains("Unknown entity to group by: player")
+        );
+    }
+
+    #[test]
+    fn test_input_format_arg() {
+        let cli_args =
+            CLIArgs::build(&mut vec!["--log_dir=test_dir".to_string()].into_iter()).unwrap();
+
+        assert_eq!(cli_args.get_input_format(), InputFormat::Url);
+
+        let cli_args = CLIArgs::build(
+            &mut vec!["-ld=test_dir".to_string(), "-if=common".to_string()].into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(cli_args.get_input_format(), InputFormat::Common);
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "-ld=test_dir".to_string(),
+                "--input-format=nginx".to_string(),
+            ]
+            .into_iter(),
+        );
+
+        assert!(cli_args.unwrap_err().contains("Unknown input format"));
+    }
+
+    #[test]
+    fn test_discovery_args() {
+        let cli_args =
+            CLIArgs::build(&mut vec!["--log_dir=test_dir".to_string()].into_iter()).unwrap();
+
+        assert_eq!(cli_args.get_discovery(), &DiscoveryOptions::default());
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=test_dir".to_string(),
+                "--include=*.log*".to_string(),
+                "-in=*.txt".to_string(),
+                "--exclude=*.tmp".to_string(),
+                "--symlinks=skip".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            cli_args.get_discovery().get_include(),
+            &["*.log*".to_string(), "*.txt".to_string()]
+        );
+        assert_eq!(
+            cli_args.get_discovery().get_exclude(),
+            &["*.tmp".to_string()]
+        );
+        assert_eq!(cli_args.get_discovery().get_symlinks(), SymlinkPolicy::Skip);
+
+        let cli_args = CLIArgs::build(
+            &mut vec!["-ld=test_dir".to_string(), "--symlinks=ignore".to_string()].into_iter(),
+        );
+
+        assert!(cli_args.unwrap_err().contains("Unknown symlink policy"));
+    }
+
+    #[test]
+    fn test_time_args() {
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=test_dir".to_string(),
+                "--bucket=hour".to_string(),
+                "--since=2024-05-01".to_string(),
+                "--until=2024-05-01T12:00:00+02:00".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(cli_args.get_bucket(), Some(TimeBucket::Hour));
+        assert_eq!(cli_args.get_since(), Some(1_714_521_600));
+        assert_eq!(cli_args.get_until(), Some(1_714_557_600));
+
+        let cases: Vec<(&str, &str)> = vec![
+            ("--bucket=week", "Unknown bucket"),
+            ("--since=2024-05-32", "Invalid date"),
+            ("--until=yesterday", "Invalid RFC 3339 timestamp"),
+        ];
+
+        for (arg, expected_error) in cases {
+            let error =
+                CLIArgs::build(&mut vec!["-ld=test_dir".to_string(), arg.to_string()].into_iter())
+                    .unwrap_err();
+
+            assert!(error.contains(expected_error), "error: {}", error);
+        }
+
+        let error = CLIArgs::build(
+            &mut vec![
+                "-ld=test_dir".to_string(),
+                "--since=2024-05-02".to_string(),
+                "--until=2024-05-01".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap_err();
+
+        assert!(error.contains("--since must be before --until"));
+    }
+
+    #[test]
+    fn test_incremental_arg() {
+        let cli_args = CLIArgs::build(&mut vec!["--log_dir=logs".to_string()].into_iter()).unwrap();
+
+        assert_eq!(cli_args.get_incremental(), None);
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=logs".to_string(),
+                "-inc=state/usage-parse.state".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            cli_args.get_incremental(),
+            Some(&"state/usage-parse.state".to_string())
+        );
+
+        let cli_args = CLIArgs::build(
+            &mut vec!["--log_dir=logs".to_string(), "--incremental=".to_string()].into_iter(),
+        );
+
+        assert!(cli_args.unwrap_err().contains("must not be empty"));
+    }
+
+    #[test]
+    fn test_flush_interval_arg() {
+        let cli_args = CLIArgs::build(&mut vec!["--log_dir=logs".to_string()].into_iter()).unwrap();
+
+        assert_eq!(cli_args.get_flush_interval(), 60);
+
+        let cli_args = CLIArgs::build(
+            &mut vec!["--log_dir=logs".to_string(), "-fi=5".to_string()].into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(cli_args.get_flush_interval(), 5);
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=logs".to_string(),
+                "--flush-interval=0".to_string(),
+            ]
+            .into_iter(),
+        );
+
+        assert!(cli_args.unwrap_err().contains("positive number of seconds"));
+    }
+
+    #[test]
+    fn test_sink_args() {
+        let cli_args = CLIArgs::build(&mut vec!["--log_dir=logs".to_string()].into_iter()).unwrap();
+
+        assert_eq!(cli_args.get_sink(), "stdout");
+        assert_eq!(cli_args.get_sink_options(), &SinkOptions::default());
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=logs".to_string(),
+                "-o=reports/usage.json".to_string(),
+                "--checksum=true".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(cli_args.get_sink(), "file");
+        assert_eq!(
+            cli_args.get_sink_options().get_output(),
+            Some("reports/usage.json")
+        );
+        assert!(cli_args.get_sink_options().get_checksum());
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=logs".to_string(),
+                "--sink=webhook".to_string(),
+                "--webhook-url=http://localhost:8080/usage".to_string(),
+                "--webhook-retries=0".to_string(),
+                "--webhook-timeout=2".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+        let sink_options = cli_args.get_sink_options();
+
+        assert_eq!(cli_args.get_sink(), "webhook");
+        assert_eq!(
+            sink_options.get_webhook_url(),
+            Some("http://localhost:8080/usage")
+        );
+        assert_eq!(sink_options.get_webhook_retries(), 0);
+        assert_eq!(
+            sink_options.get_webhook_timeout(),
+            std::time::Duration::from_secs(2)
+        );
+
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir=logs".to_string(),
+                "--sink=sqlite".to_string(),
+                "-db=usage.db".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(cli_args.get_sink_options().get_database(), Some("usage.db"));
+
+        for (args, error) in [
+            (vec!["--output="], "must not be empty"),
+            (vec!["--checksum=true"], "only be used with --output"),
+            (vec!["--sink=webhook"], "needs a URL"),
+            (
+                vec!["--sink=webhook", "--webhook-url=ftp://example.com"],
+                "must be an http:// or https:// URL",
+            ),
+            (vec!["--sink=sqlite"], "needs a database file"),
+            (vec!["--sink=ftp"], "Unknown sink"),
+            (vec!["--webhook-retries=-1"], "non-negative integer"),
+        ] {
+            let cli_args = CLIArgs::build(
+                &mut std::iter::once("--log_dir=logs".to_string())
+                    .chain(args.iter().map(|arg| arg.to_string())),
+            );
+
+            assert!(cli_args.unwrap_err().contains(error), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_store_arg() {
+        let cli_args = CLIArgs::build(&mut vec!["--log_dir=logs".to_string()].into_iter()).unwrap();
+
+        assert_eq!(cli_args.get_store(), None);
+
+        let cli_args = CLIArgs::build(
+            &mut vec!["--log_dir=logs".to_string(), "--store=usage.db".to_string()].into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(cli_args.get_store(), Some("usage.db"));
+
+        for (args, error) in [
+            (vec!["--store="], "must not be empty"),
+            (vec!["--store=usage.db", "--input=-"], "standard input"),
+            (
+                vec!["--store=usage.db", "--incremental=usage-parse.state"],
+                "--incremental",
+            ),
+        ] {
+            let cli_args = CLIArgs::build(
+                &mut std::iter::once("--log_dir=logs".to_string())
+                    .chain(args.iter().map(|arg| arg.to_string())),
+            );
+
+            assert!(cli_args.unwrap_err().contains(error), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_space_separated_values_and_flags() {
+        let cli_args = CLIArgs::build(
+            &mut vec![
+                "--log_dir",
+                "logs",
+                "-fmt",
+                "csv",
+                "--totals",
+                "--compact=false",
+                "--output",
+                "usage.csv",
+                "--checksum",
+                "--input",
+                "-",
+            ]
+            .into_iter()
+            .map(|arg| arg.to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(cli_args.get_logs_dirs(), &["logs".to_string()]);
+        assert_eq!(cli_args.get_inputs(), &["-".to_string()]);
+        assert_eq!(cli_args.get_formatter(), "csv");
+        assert!(cli_args.get_totals());
+        assert!(!cli_args.get_compact());
+        assert_eq!(cli_args.get_sink_options().get_output(), Some("usage.csv"));
+        assert!(cli_args.get_sink_options().get_checksum());
+
+        let cli_args = CLIArgs::build(
+            &mut vec!["--log_dir", "--formatter=json"]
+                .into_iter()
+                .map(|arg| arg.to_string()),
+        );
+
+        assert!(cli_args.unwrap_err().contains("--log_dir needs a value"));
     }
 }
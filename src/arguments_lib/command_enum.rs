@@ -0,0 +1,255 @@
+//! Enum to represent the command, the CLI was started with.
+//!
+//! The first argument can be a command (parse | stats | validate | follow | merge | db query | help). Without one, it is `parse`.
+//! `--help` (or `-h`) and `--version` (or `-V`) can be given to any command, and win over every other argument.
+//!
+//! The commands that parse the logs also take their settings from the config file and the environment (see `settings.rs`).
+//! `merge` takes only its own settings from them (the output options and the overflow policy), and `db query` none.
+use super::cli_args::CLIArgs;
+use super::db_query_args::DbQueryArgs;
+use super::help::{help_text, version_text};
+use super::merge_args::MergeArgs;
+use super::settings::{MERGE_SETTINGS, merge_settings};
+
+#[derive(Debug)]
+pub enum Command {
+    /// Parse the logs, and write the report.
+    Parse(CLIArgs),
+    /// Parse the logs, and print only the statistics of the run.
+    Stats(CLIArgs),
+    /// Check every line of the logs, and print the data quality report, without aggregating anything.
+    Validate(CLIArgs),
+    /// Keep parsing the logs as they grow.
+    Follow(CLIArgs),
+    /// Sum the reports of several runs, and write the combined report.
+    Merge(MergeArgs),
+    /// Print the usage of an owner, from the usage store.
+    DbQuery(DbQueryArgs),
+    /// Print the text (the help or the version), and exit.
+    Print(String),
+}
+
+impl Command {
+    /// *Resolve the command, and build its arguments*
+    ///
+    /// ---
+    ///
+    /// Returns an error for an unknown command, or invalid arguments.
+    ///
+    /// ## Arguments
+    ///
+    /// - `env_iterator` - Any iterator type, that can provide CLI arguments one by one (without the binary name).
+    /// - `env_vars` - The environment variables, for the settings (see `settings.rs`)
+    /// - `default_config_file` - The config file to use if it exists, when none is given
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let command = Command::parse(&mut std::env::args().skip(1), std::env::vars(), DEFAULT_CONFIG_FILE)?;
+    /// ```
+    pub fn parse(
+        env_iterator: &mut dyn Iterator<Item = String>,
+        env_vars: impl Iterator<Item = (String, String)>,
+        default_config_file: &str,
+    ) -> Result<Command, String> {
+        let mut env_iterator = env_iterator.peekable();
+        let command = match env_iterator.next_if(|arg| !arg.starts_with('-')) {
+            Some(command) if command == "db" => match env_iterator.next() {
+                Some(db_command) if db_command == "query" => "db query".to_string(),
+                Some(db_command) => {
+                    return Err(format!(
+                        "Unknown db command: {}. Available commands: query",
+                        db_command
+                    ));
+                }
+                None => return Err("Missing the db command. Available commands: query".to_string()),
+            },
+            Some(command) => command,
+            None => "parse".to_string(),
+        };
+        let args: Vec<String> = env_iterator.collect();
+
+        if command == "help" {
+            let command = if args.is_empty() {
+                "parse".to_string()
+            } else {
+                args.join(" ")
+            };
+
+            return Ok(Command::Print(help_text(&command)?));
+        }
+
+        if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+            return Ok(Command::Print(help_text(&command)?));
+        }
+
+        if args.iter().any(|arg| arg == "--version" || arg == "-V") {
+            return Ok(Command::Print(version_text()));
+        }
+
+        let mut args = match command.as_str() {
+            "parse" | "stats" | "validate" | "follow" => {
+                merge_settings(args, env_vars, default_config_file, None)?.into_iter()
+            }
+            "merge" => merge_settings(args, env_vars, default_config_file, Some(&MERGE_SETTINGS))?
+                .into_iter(),
+            _ => args.into_iter(),
+        };
+
+        match command.as_str() {
+            "parse" => Ok(Command::Parse(CLIArgs::build(&mut args)?)),
+            "stats" => Ok(Command::Stats(CLIArgs::build(&mut args)?)),
+            "validate" => Ok(Command::Validate(CLIArgs::build(&mut args)?)),
+            "follow" => {
+                let cli_args = CLIArgs::build(&mut args)?;
+                // The store records finished runs. The follow mode never finishes, and keeps its state in memory.
+                if cli_args.get_store().is_some() {
+                    return Err(
+                        "--store can't be used in the follow mode. Check your input!".to_string(),
+                    );
+                }
+
+                Ok(Command::Follow(cli_args))
+            }
+            "merge" => Ok(Command::Merge(MergeArgs::build(&mut args)?)),
+            "db query" => Ok(Command::DbQuery(DbQueryArgs::build(&mut args)?)),
+            unknown_command => Err(format!(
+                "Unknown command: {}. Available commands: parse, stats, validate, follow, merge, db query, help",
+                unknown_command
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        Command::parse(
+            &mut args.iter().map(|arg| arg.to_string()),
+            std::iter::empty(),
+            "does_not_exist.toml",
+        )
+    }
+
+    #[test]
+    fn test_command_parse() {
+        assert!(matches!(
+            parse(&["--log_dir", "logs"]),
+            Ok(Command::Parse(_))
+        ));
+        assert!(matches!(
+            parse(&["parse", "-ld=logs"]),
+            Ok(Command::Parse(_))
+        ));
+        assert!(matches!(
+            parse(&["stats", "-ld=logs"]),
+            Ok(Command::Stats(_))
+        ));
+        assert!(matches!(
+            parse(&["validate", "-ld=logs"]),
+            Ok(Command::Validate(_))
+        ));
+        assert!(matches!(
+            parse(&["follow", "-ld=logs"]),
+            Ok(Command::Follow(_))
+        ));
+        assert!(matches!(
+            parse(&["merge", "-i=eu/usage.json", "-i=us/usage.json"]),
+            Ok(Command::Merge(_))
+        ));
+        assert!(matches!(
+            parse(&["db", "query", "--store=usage.db", "--owner=1"]),
+            Ok(Command::DbQuery(_))
+        ));
+
+        // Help and version don't need the required arguments.
+        for args in [
+            vec!["--help"],
+            vec!["-h"],
+            vec!["help"],
+            vec!["parse", "--log_dir", "-h"],
+        ] {
+            assert!(
+                matches!(parse(&args), Ok(Command::Print(help)) if help.contains("Commands:")),
+                "{:?}",
+                args
+            );
+        }
+
+        assert!(matches!(
+            parse(&["db", "query", "--help"]),
+            Ok(Command::Print(help)) if help.contains("--owner")
+        ));
+        assert!(matches!(
+            parse(&["help", "follow"]),
+            Ok(Command::Print(help)) if help.contains("--flush-interval")
+        ));
+        assert!(matches!(
+            parse(&["-V"]),
+            Ok(Command::Print(version)) if version == version_text()
+        ));
+
+        for (args, error) in [
+            (vec!["publish", "-ld=logs"], "Unknown command: publish"),
+            (vec!["db", "drop"], "Unknown db command: drop"),
+            (vec!["db"], "Missing the db command"),
+            (vec!["help", "publish"], "Unknown command: publish"),
+            (vec!["stats"], "Logs directory parameter is missing"),
+            (
+                vec!["follow", "-ld=logs", "--store=usage.db"],
+                "--store can't be used in the follow mode",
+            ),
+        ] {
+            assert!(parse(&args).unwrap_err().contains(error), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_command_parse_settings() {
+        let test_config = "test_command_settings.toml";
+
+        std::fs::write(test_config, "log_dir = [\"logs\"]\nformatter = \"json\"\n").unwrap();
+
+        let env_vars = || {
+            [("USAGE_PARSE_OUTPUT", "usage.json"), ("HOME", "/root")]
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .into_iter()
+        };
+        let parse_command = Command::parse(&mut std::iter::empty(), env_vars(), test_config);
+        // Merge takes the output options, but not the log directories.
+        let merge = Command::parse(
+            &mut ["merge", "-i=eu/usage.json"].map(String::from).into_iter(),
+            env_vars(),
+            test_config,
+        );
+        let db_query = Command::parse(
+            &mut ["db", "query", "--store=usage.db", "--owner=1"]
+                .map(String::from)
+                .into_iter(),
+            env_vars(),
+            test_config,
+        );
+
+        std::fs::remove_file(test_config).unwrap();
+
+        match parse_command {
+            Ok(Command::Parse(cli_args)) => {
+                assert_eq!(cli_args.get_formatter(), "json");
+                assert_eq!(cli_args.get_sink(), "file");
+            }
+            command => panic!("Unexpected command: {:?}", command),
+        }
+
+        match merge {
+            Ok(Command::Merge(merge_args)) => {
+                assert_eq!(merge_args.get_formatter(), "json");
+                assert_eq!(merge_args.get_sink(), "file");
+            }
+            command => panic!("Unexpected command: {:?}", command),
+        }
+
+        assert!(matches!(db_query, Ok(Command::DbQuery(_))));
+    }
+}
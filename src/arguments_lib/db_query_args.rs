@@ -0,0 +1,178 @@
+//! Struct for collecting the arguments of the `db query` command, which reads the usage store (see `usage_store.rs`).
+//!
+//! Same format as the main arguments: `--name=value` or `--name value` (see `arg_parser.rs`).
+use super::super::formatters::formatter_factory::FormatterFactory;
+use super::arg_parser::ArgParser;
+
+#[derive(Debug)]
+pub struct DbQueryArgs {
+    store: String,
+    owner: u32,
+    period: Option<String>,
+    formatter: String,
+}
+
+impl DbQueryArgs {
+    /// *Get the path of the usage store*
+    pub fn get_store(&self) -> &str {
+        &self.store
+    }
+    /// *Get the owner, whose usage is queried*
+    pub fn get_owner(&self) -> u32 {
+        self.owner
+    }
+    /// *Get the period (like `2026-10`), if the usage should be limited to it*
+    pub fn get_period(&self) -> Option<&str> {
+        self.period.as_deref()
+    }
+    /// *Get the chosen formatter. Defaults to stdout.*
+    pub fn get_formatter(&self) -> &str {
+        &self.formatter
+    }
+    /// *Get the query arguments from the command line*
+    ///
+    /// ---
+    ///
+    /// `--store` and `--owner` are required. Returns `Err(String)` for missing, unknown, or invalid arguments.
+    ///
+    /// ## Arguments
+    ///
+    /// - `env_iterator` - Any iterator type, that can provide CLI arguments one by one.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let db_query_args = DbQueryArgs::build(&mut vec![
+    ///     "--store=usage.db".to_string(),
+    ///     "--owner=123".to_string(),
+    ///     "--period=2026-10".to_string(),
+    /// ].into_iter())?;
+    ///
+    /// assert_eq!(db_query_args.get_owner(), 123);
+    /// ```
+    pub fn build(env_iterator: &mut dyn Iterator<Item = String>) -> Result<DbQueryArgs, String> {
+        let mut store: Option<String> = None;
+        let mut owner: Option<u32> = None;
+        let mut period: Option<String> = None;
+        let mut formatter = String::from("stdout");
+
+        let mut args = ArgParser::new(env_iterator, &[]);
+
+        while let Some((arg_name, arg_value)) = args.next_arg()? {
+            let arg_value = arg_value.trim();
+
+            match arg_name.as_str() {
+                // Required
+                "--store" => {
+                    store = Some(arg_value.to_owned());
+                }
+                // Required
+                // Must be a non-negative integer
+                "--owner" => match arg_value.parse::<u32>() {
+                    Ok(owner_id) => owner = Some(owner_id),
+                    _ => {
+                        return Err(
+                            "Owner must be a non-negative integer. Check your input!".to_string()
+                        );
+                    }
+                },
+                // Optional
+                // A bucket label, or a prefix of one, like 2026, 2026-10 or 2026-10-05
+                "--period" => {
+                    period = Some(arg_value.to_owned());
+                }
+                // Optional
+                // If present, must be a known formatter
+                "--formatter" | "-fmt" => {
+                    FormatterFactory::resolve_formatter(arg_value)?;
+
+                    formatter = arg_value.to_owned();
+                }
+
+                unknown_arg_name => {
+                    return Err(format!("Unknown parameter: {}", unknown_arg_name));
+                }
+            }
+        }
+
+        let store = match store {
+            Some(store) if !store.is_empty() => store,
+            _ => {
+                return Err(
+                    "The store parameter is missing! Use --store. Check your input".to_string(),
+                );
+            }
+        };
+        let Some(owner) = owner else {
+            return Err(
+                "The owner parameter is missing! Use --owner. Check your input".to_string(),
+            );
+        };
+
+        if period.as_ref().is_some_and(|period| period.is_empty()) {
+            return Err("Period must not be empty! Check your input".to_string());
+        }
+
+        Ok(DbQueryArgs {
+            store,
+            owner,
+            period,
+            formatter,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_db_query_args() {
+        let db_query_args = DbQueryArgs::build(
+            &mut vec![
+                "--store=usage.db".to_string(),
+                "--owner".to_string(),
+                "123".to_string(),
+                "--period=2026-10".to_string(),
+                "-fmt".to_string(),
+                "json".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(db_query_args.get_store(), "usage.db");
+        assert_eq!(db_query_args.get_owner(), 123);
+        assert_eq!(db_query_args.get_period(), Some("2026-10"));
+        assert_eq!(db_query_args.get_formatter(), "json");
+
+        for (args, error) in [
+            (vec!["--owner=123"], "--store"),
+            (vec!["--store=usage.db"], "--owner"),
+            (
+                vec!["--store=usage.db", "--owner=abc"],
+                "non-negative integer",
+            ),
+            (
+                vec!["--store=usage.db", "--owner=1", "--period="],
+                "must not be empty",
+            ),
+            (
+                vec!["--store=usage.db", "--owner=1", "--formatter=xml"],
+                "Unknown formatter",
+            ),
+            (
+                vec!["--store=usage.db", "--owner=1", "--log_dir=logs"],
+                "Unknown parameter",
+            ),
+            (
+                vec!["--store", "usage.db", "--owner"],
+                "--owner needs a value",
+            ),
+        ] {
+            let db_query_args = DbQueryArgs::build(&mut args.iter().map(|arg| arg.to_string()));
+
+            assert!(db_query_args.unwrap_err().contains(error), "{:?}", args);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Enum to represent the exit status of the CLI, so scripts and cron jobs can tell the failures apart.
+//!
+//! - 0 - Success
+//! - 1 - Any other failure (a worker panicked, the incremental state could not be used)
+//! - 2 - Invalid arguments, or an unknown command, formatter or sink
+//! - 3 - No log files were found, or they could not be read
+//! - 4 - The logs are not valid (a malformed line, an overflow, or too many rejected lines),
+//!   or an aggregate file given to `merge` is not valid
+//! - 5 - The report could not be delivered (the output file, the webhook, the database), or the dead-letter file could not be written
+//! - 6 - `validate` finished, but the logs are not clean (any line was rejected)
+use super::super::log_parser_lib::log_parser_error::LogParserError;
+use super::super::pipeline_lib::pipeline_error::PipelineError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitCode {
+    Success = 0,
+    Failure = 1,
+    Usage = 2,
+    NoInput = 3,
+    InvalidData = 4,
+    Output = 5,
+    NotClean = 6,
+}
+
+impl ExitCode {
+    /// *Return the code, to pass to `std::process::exit`*
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// std::process::exit(ExitCode::Usage.code());
+    /// ```
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+impl From<&PipelineError> for ExitCode {
+    fn from(error: &PipelineError) -> ExitCode {
+        match error {
+            PipelineError::InvalidConfig(_)
+            | PipelineError::UnknownFormatter(_)
+            | PipelineError::UnknownSink(_) => ExitCode::Usage,
+            PipelineError::NoFilesFound(_)
+            | PipelineError::Discovery { .. }
+            | PipelineError::Parse(LogParserError::Io { .. }) => ExitCode::NoInput,
+            PipelineError::Parse(_)
+            | PipelineError::Overflow { .. }
+            | PipelineError::InvalidAggregate { .. }
+            | PipelineError::ErrorBudgetExceeded { .. } => ExitCode::InvalidData,
+            PipelineError::Output(_)
+            | PipelineError::DeadLetter { .. }
+            | PipelineError::OutputFile { .. }
+            | PipelineError::Webhook { .. }
+            | PipelineError::Database { .. } => ExitCode::Output,
+            PipelineError::WorkerPanic(_) | PipelineError::State { .. } => ExitCode::Failure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::log_parser_lib::on_error_policy_enum::OnErrorPolicy;
+    use super::super::super::pipeline_lib::pipeline::{Pipeline, PipelineConfig};
+    use super::*;
+
+    #[test]
+    fn should_map_errors_to_exit_codes() {
+        assert_eq!(ExitCode::Success.code(), 0);
+        assert_eq!(ExitCode::Usage.code(), 2);
+        assert_eq!(ExitCode::NotClean.code(), 6);
+        assert_eq!(
+            ExitCode::from(&PipelineError::NoFilesFound("logs".to_string())),
+            ExitCode::NoInput
+        );
+        assert_eq!(
+            ExitCode::from(&PipelineError::Overflow {
+                owner: 1,
+                metric: "video_plays".to_string()
+            }),
+            ExitCode::InvalidData
+        );
+        assert_eq!(
+            ExitCode::from(&PipelineError::UnknownSink("Unknown sink: ftp".to_string())),
+            ExitCode::Usage
+        );
+        assert_eq!(
+            ExitCode::from(&PipelineError::Database {
+                path: "usage.db".to_string(),
+                reason: "locked".to_string()
+            }),
+            ExitCode::Output
+        );
+    }
+
+    #[test]
+    fn should_exit_with_no_input_for_a_missing_input_file() {
+        let pipeline = Pipeline::new(
+            PipelineConfig::default()
+                .with_inputs(vec!["test_exit_code_missing_input.log".to_string()]),
+        );
+        let error = pipeline.run().unwrap_err();
+
+        assert!(
+            matches!(error, PipelineError::Parse(LogParserError::Io { .. })),
+            "{:?}",
+            error
+        );
+        assert_eq!(ExitCode::from(&error), ExitCode::NoInput);
+    }
+
+    #[test]
+    fn should_exit_with_output_for_an_unwritable_dead_letter() {
+        let test_log_path = "test_exit_code_dead_letter.log";
+
+        std::fs::write(test_log_path, "https://x/p.gif?o=1&v=1\n").unwrap();
+
+        let pipeline = Pipeline::new(
+            PipelineConfig::default()
+                .with_inputs(vec![test_log_path.to_string()])
+                .with_on_error(OnErrorPolicy::Quarantine)
+                .with_dead_letter(Some(
+                    "test_exit_code_missing_dir/dead_letter.txt".to_string(),
+                )),
+        );
+        let error = pipeline.run().unwrap_err();
+
+        std::fs::remove_file(test_log_path).unwrap();
+
+        assert!(
+            matches!(error, PipelineError::DeadLetter { .. }),
+            "{:?}",
+            error
+        );
+        assert_eq!(ExitCode::from(&error), ExitCode::Output);
+    }
+}
@@ -0,0 +1,340 @@
+//! Help and version text of the CLI.
+//!
+//! The formatters and sinks are listed from their factories, so the help text can't get out of date when one is added.
+//! When adding a new argument, add it to the option list of its command here too.
+use super::super::formatters::formatter_factory::FormatterFactory;
+use super::super::sinks::sink_factory::SinkFactory;
+
+/// Name of the binary, as used in the help text.
+const BINARY_NAME: &str = "usage-parse";
+
+/// Every command, with its arguments and a short description.
+const COMMANDS: [(&str, &str); 7] = [
+    (
+        "[parse] [OPTIONS]",
+        "Parse the logs, and write the report (the default command)",
+    ),
+    (
+        "stats [OPTIONS]",
+        "Parse the logs, and print only the statistics of the run",
+    ),
+    (
+        "validate [OPTIONS]",
+        "Check every line of the logs, and print the data quality report",
+    ),
+    (
+        "follow [OPTIONS]",
+        "Keep parsing the logs as they grow, and write the report on every flush",
+    ),
+    (
+        "merge [OPTIONS]",
+        "Sum the reports of several runs (json, ndjson, csv or tsv), and write the combined report",
+    ),
+    (
+        "db query [OPTIONS]",
+        "Print the usage of an owner, from the usage store",
+    ),
+    ("help [COMMAND]", "Print the help of a command"),
+];
+
+/// *Return the version line, printed for `--version`*
+pub fn version_text() -> String {
+    format!("{} {}\n", BINARY_NAME, env!("CARGO_PKG_VERSION"))
+}
+
+/// *Return the help text of a command*
+///
+/// ---
+///
+/// Returns an error for an unknown command.
+///
+/// ## Arguments
+///
+/// - `command` - One of parse | stats | validate | follow | merge | db query
+///
+/// ## Example
+///
+/// ```
+/// print!("{}", help_text("stats")?);
+/// ```
+pub fn help_text(command: &str) -> Result<String, String> {
+    let (usage, options) = match command {
+        "parse" => (
+            format!("{} [COMMAND] [OPTIONS]", BINARY_NAME),
+            parse_options(),
+        ),
+        "stats" | "validate" => (
+            format!("{} {} [OPTIONS]", BINARY_NAME, command),
+            parse_options(),
+        ),
+        "follow" => {
+            let mut options = parse_options();
+
+            options.push((
+                "--flush-interval, -fi <SECONDS>",
+                "How often the report is written. Defaults to 60".to_string(),
+            ));
+
+            (format!("{} follow [OPTIONS]", BINARY_NAME), options)
+        }
+        "merge" => (format!("{} merge [OPTIONS]", BINARY_NAME), merge_options()),
+        "db query" => (
+            format!("{} db query [OPTIONS]", BINARY_NAME),
+            db_query_options(),
+        ),
+        unknown_command => return Err(format!("Unknown command: {}", unknown_command)),
+    };
+    let mut help = format!(
+        "{} {}\nParse usage log files, and aggregate the usage of every owner.\n\nUsage: {}\n",
+        BINARY_NAME,
+        env!("CARGO_PKG_VERSION"),
+        usage
+    );
+
+    if command == "parse" {
+        help.push_str("\nCommands:\n");
+
+        for (arguments, description) in COMMANDS {
+            help.push_str(&format!(
+                "  {} {:<24} {}\n",
+                BINARY_NAME, arguments, description
+            ));
+        }
+
+        help.push_str(&format!(
+            "\nRun `{} <command> --help` for the options of a command.\n",
+            BINARY_NAME
+        ));
+    }
+
+    help.push_str("\nOptions:\n");
+
+    for (names, description) in options {
+        help.push_str(&format!("  {}\n      {}\n", names, description));
+    }
+
+    help.push_str(
+        "  --help, -h\n      Print this help\n  --version, -V\n      Print the version\n",
+    );
+    help.push_str(
+        "\nOptions take a value as --name=value or --name value. Flags (like --totals) are true when given alone.\n",
+    );
+
+    if command != "merge" && command != "db query" {
+        help.push_str(
+            "Every option can also be set in the config file, or as a USAGE_PARSE_* environment variable (like USAGE_PARSE_LOG_DIR).\n",
+        );
+    }
+
+    Ok(help)
+}
+
+/// *Options of the parse, stats, validate and follow commands*
+fn parse_options() -> Vec<(&'static str, String)> {
+    let options: [(&'static str, &str); 30] = [
+        (
+            "--config, -c <FILE>",
+            "Config file. Defaults to usage-parse.toml, if it exists",
+        ),
+        (
+            "--log_dir, -ld <DIR>",
+            "Logs directory, walked recursively. Can be given multiple times",
+        ),
+        (
+            "--input, -i <FILE>",
+            "Log file, or - for the standard input. Can be given multiple times",
+        ),
+        (
+            "--files-from, -ff <FILE>",
+            "Manifest file, with one log file path per line",
+        ),
+        ("--formatter, -fmt <FORMATTER>", ""),
+        ("--totals, -t", "Add a totals row (csv and tsv)"),
+        ("--compact", "Write the json without any whitespace"),
+        (
+            "--workers, -w <NUMBER>",
+            "Number of worker threads. Defaults to one per CPU",
+        ),
+        (
+            "--input-format, -if <FORMAT>",
+            "One of url | common | combined. Defaults to url",
+        ),
+        ("--schema, -s <FILE>", "Metric schema file"),
+        (
+            "--group-by, -g <ENTITIES>",
+            "Comma separated entities from the schema, to break the usage down by",
+        ),
+        (
+            "--on-error, -oe <POLICY>",
+            "One of abort | skip | quarantine. Defaults to abort",
+        ),
+        (
+            "--dead-letter, -dl <FILE>",
+            "Where the quarantined lines go. Defaults to dead_letter.txt",
+        ),
+        (
+            "--max-error-rate, -mer <RATE>",
+            "Share of lines (0 to 1) that can be rejected, before the run fails",
+        ),
+        (
+            "--on-overflow, -oo <POLICY>",
+            "One of error | saturate | wrap-with-warning. Defaults to error",
+        ),
+        (
+            "--include, -in <GLOB>",
+            "Only parse the files matching it. Can be given multiple times",
+        ),
+        (
+            "--exclude, -ex <GLOB>",
+            "Skip the files and directories matching it. Can be given multiple times",
+        ),
+        (
+            "--symlinks <POLICY>",
+            "One of follow | skip. Defaults to follow",
+        ),
+        (
+            "--bucket, -b <SIZE>",
+            "Also aggregate into time buckets: hour | day | month",
+        ),
+        (
+            "--since <TIME>",
+            "Only count lines from this time (RFC 3339, or YYYY-MM-DD)",
+        ),
+        (
+            "--until <TIME>",
+            "Only count lines before this time (RFC 3339, or YYYY-MM-DD)",
+        ),
+        (
+            "--incremental, -inc <FILE>",
+            "State file. Only parse what was appended since the previous run",
+        ),
+        ("--sink <SINK>", ""),
+        (
+            "--output, -o <FILE>",
+            "Write the report to this file, atomically",
+        ),
+        ("--checksum", "Also write <output>.sha256"),
+        (
+            "--webhook-url <URL>",
+            "http:// or https:// URL, for the webhook sink",
+        ),
+        (
+            "--webhook-retries <NUMBER>",
+            "Retries of a failed webhook request. Defaults to 3",
+        ),
+        (
+            "--webhook-timeout <SECONDS>",
+            "Timeout of the webhook requests. Defaults to 10",
+        ),
+        (
+            "--database, -db <FILE>",
+            "SQLite database, for the sqlite sink",
+        ),
+        (
+            "--store <FILE>",
+            "SQLite usage store, to record the run into",
+        ),
+    ];
+
+    options
+        .into_iter()
+        .map(|(names, description)| {
+            let description = match names {
+                "--formatter, -fmt <FORMATTER>" => format!(
+                    "One of {}. Defaults to stdout",
+                    FormatterFactory::available_formatters().join(" | ")
+                ),
+                "--sink <SINK>" => format!(
+                    "One of {}. Defaults to file with --output, and to stdout otherwise",
+                    SinkFactory::available_sinks().join(" | ")
+                ),
+                _ => description.to_string(),
+            };
+
+            (names, description)
+        })
+        .collect()
+}
+
+/// *Options of the merge command*
+fn merge_options() -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "--input, -i <FILE>",
+            "Report written by the json, ndjson, csv or tsv formatter, or - for the standard input. Given once for every report (required)"
+                .to_string(),
+        ),
+        (
+            "--formatter, -fmt <FORMATTER>",
+            format!(
+                "One of {}. Defaults to stdout",
+                FormatterFactory::available_formatters().join(" | ")
+            ),
+        ),
+        ("--totals, -t", "Add a totals row (csv and tsv)".to_string()),
+        ("--compact", "Write the json without any whitespace".to_string()),
+        (
+            "--on-overflow, -oo <POLICY>",
+            "One of error | saturate | wrap-with-warning. Defaults to error".to_string(),
+        ),
+        (
+            "--output, -o <FILE>",
+            "Write the combined report to this file, atomically".to_string(),
+        ),
+        ("--checksum", "Also write <output>.sha256".to_string()),
+    ]
+}
+
+/// *Options of the db query command*
+fn db_query_options() -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "--store <FILE>",
+            "SQLite usage store (required)".to_string(),
+        ),
+        ("--owner <ID>", "Owner id (required)".to_string()),
+        (
+            "--period <PERIOD>",
+            "Only the usage in this period, like 2026, 2026-10 or 2026-10-05".to_string(),
+        ),
+        (
+            "--formatter, -fmt <FORMATTER>",
+            format!(
+                "One of {}. Defaults to stdout",
+                FormatterFactory::available_formatters().join(" | ")
+            ),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_help_text() {
+        let help = help_text("parse").unwrap();
+
+        assert!(help.starts_with(&version_text()));
+        assert!(help.contains("db query [OPTIONS]"));
+        assert!(help.contains("validate [OPTIONS]"));
+        assert!(help.contains("Usage: usage-parse [COMMAND] [OPTIONS]\n"));
+        assert!(help.contains("help [COMMAND]"));
+
+        for formatter in FormatterFactory::available_formatters() {
+            assert!(help.contains(formatter));
+        }
+
+        assert!(help.contains("One of stdout | file | webhook | sqlite"));
+        assert!(!help.contains("--flush-interval"));
+        assert!(help_text("follow").unwrap().contains("--flush-interval"));
+        assert!(
+            help_text("stats")
+                .unwrap()
+                .contains("Usage: usage-parse stats [OPTIONS]\n")
+        );
+        assert!(help_text("db query").unwrap().contains("--owner <ID>"));
+        assert!(help_text("merge").unwrap().contains("--on-overflow"));
+        assert!(help_text("publish").is_err());
+    }
+}
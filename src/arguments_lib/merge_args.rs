@@ -0,0 +1,230 @@
+//! Struct for collecting the arguments of the `merge` command, which sums previously written reports (see `aggregate_merge.rs`).
+//!
+//! Same format as the main arguments: `--name=value` or `--name value` (see `arg_parser.rs`).
+//! The output options are the same as for the parse command, so the combined report is written the same way.
+use super::super::formatters::formatter_factory::FormatterFactory;
+use super::super::formatters::formatter_options::FormatterOptions;
+use super::super::log_parser_lib::overflow_policy_enum::OverflowPolicy;
+use super::super::sinks::sink_options::SinkOptions;
+use super::arg_parser::ArgParser;
+use super::cli_args::BOOLEAN_FLAGS;
+
+#[derive(Debug)]
+pub struct MergeArgs {
+    inputs: Vec<String>,
+    formatter: String,
+    formatter_options: FormatterOptions,
+    overflow_policy: OverflowPolicy,
+    sink: String,
+    sink_options: SinkOptions,
+}
+
+impl MergeArgs {
+    /// *Get the reports to merge. `-` is the standard input.*
+    pub fn get_inputs(&self) -> &[String] {
+        &self.inputs
+    }
+    /// *Get the chosen formatter, for the combined report. Defaults to stdout.*
+    pub fn get_formatter(&self) -> &str {
+        &self.formatter
+    }
+    /// *Get the options of the formatter (totals and compact)*
+    pub fn get_formatter_options(&self) -> &FormatterOptions {
+        &self.formatter_options
+    }
+    /// *Get what happens when summing the reports would overflow. Defaults to error.*
+    pub fn get_overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+    /// *Get the sink: `file` with an output file, and `stdout` otherwise*
+    pub fn get_sink(&self) -> &str {
+        &self.sink
+    }
+    /// *Get the options of the sink (the output file, and the checksum)*
+    pub fn get_sink_options(&self) -> &SinkOptions {
+        &self.sink_options
+    }
+    /// *Get the merge arguments from the command line*
+    ///
+    /// ---
+    ///
+    /// At least one `--input` is required. Returns `Err(String)` for missing, unknown, or invalid arguments.
+    ///
+    /// ## Arguments
+    ///
+    /// - `env_iterator` - Any iterator type, that can provide CLI arguments one by one.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let merge_args = MergeArgs::build(&mut vec![
+    ///     "--input=eu/usage.json".to_string(),
+    ///     "--input=us/usage.json".to_string(),
+    ///     "--formatter=json".to_string(),
+    /// ].into_iter())?;
+    ///
+    /// assert_eq!(merge_args.get_inputs().len(), 2);
+    /// ```
+    pub fn build(env_iterator: &mut dyn Iterator<Item = String>) -> Result<MergeArgs, String> {
+        let mut inputs: Vec<String> = Vec::new();
+        let mut formatter = String::from("stdout");
+        let mut totals = false;
+        let mut compact = false;
+        let mut overflow_policy = OverflowPolicy::Error;
+        let mut output: Option<String> = None;
+        let mut checksum = false;
+
+        let mut args = ArgParser::new(env_iterator, BOOLEAN_FLAGS);
+
+        while let Some((arg_name, arg_value)) = args.next_arg()? {
+            let arg_value = arg_value.trim();
+
+            match arg_name.as_str() {
+                // Required, at least once
+                // A report written by the json, ndjson, csv or tsv formatter, or - for the standard input
+                "--input" | "-i" => {
+                    if arg_value == "-" && inputs.iter().any(|input| input == "-") {
+                        return Err("The standard input (-) can be given only once".to_string());
+                    }
+
+                    inputs.push(arg_value.to_owned());
+                }
+                // Optional
+                // If present, must be a known formatter
+                "--formatter" | "-fmt" => {
+                    FormatterFactory::resolve_formatter(arg_value)?;
+
+                    formatter = arg_value.to_owned();
+                }
+                // Optional
+                // A flag, or true | false. Adds a totals row, in the csv and tsv formatters.
+                "--totals" | "-t" => match arg_value {
+                    "true" => totals = true,
+                    "false" => totals = false,
+                    _ => {
+                        return Err("Totals must be true or false. Check your input!".to_string());
+                    }
+                },
+                // Optional
+                // A flag, or true | false. Writes the json output without any whitespace.
+                "--compact" => match arg_value {
+                    "true" => compact = true,
+                    "false" => compact = false,
+                    _ => {
+                        return Err("Compact must be true or false. Check your input!".to_string());
+                    }
+                },
+                // Optional
+                // If present, must be one of error | saturate | wrap-with-warning
+                "--on-overflow" | "-oo" => {
+                    overflow_policy = OverflowPolicy::resolve(arg_value)?;
+                }
+                // Optional
+                // Path of the combined report. Written atomically.
+                "--output" | "-o" => {
+                    output = Some(arg_value.to_owned());
+                }
+                // Optional
+                // A flag, or true | false. Writes <output>.sha256 next to the output file.
+                "--checksum" => match arg_value {
+                    "true" => checksum = true,
+                    "false" => checksum = false,
+                    _ => {
+                        return Err("Checksum must be true or false. Check your input!".to_string());
+                    }
+                },
+
+                unknown_arg_name => {
+                    return Err(format!("Unknown parameter: {}", unknown_arg_name));
+                }
+            }
+        }
+
+        if inputs.is_empty() || inputs.iter().any(|input| input.is_empty()) {
+            return Err(
+                "The input parameter is missing! Use --input for every report. Check your input"
+                    .to_string(),
+            );
+        }
+
+        if checksum && output.is_none() {
+            return Err("--checksum can only be used with --output. Check your input!".to_string());
+        }
+
+        let sink = if output.is_some() { "file" } else { "stdout" }.to_string();
+
+        Ok(MergeArgs {
+            inputs,
+            formatter,
+            formatter_options: FormatterOptions::default()
+                .with_totals(totals)
+                .with_compact(compact),
+            overflow_policy,
+            sink,
+            sink_options: SinkOptions::default()
+                .with_output(output)
+                .with_checksum(checksum),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_args() {
+        let merge_args = MergeArgs::build(
+            &mut vec![
+                "-i".to_string(),
+                "eu/usage.json".to_string(),
+                "--input=-".to_string(),
+                "-fmt".to_string(),
+                "csv".to_string(),
+                "--totals".to_string(),
+                "--on-overflow=saturate".to_string(),
+                "-o".to_string(),
+                "usage.csv".to_string(),
+                "--checksum".to_string(),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+        assert_eq!(merge_args.get_inputs(), ["eu/usage.json", "-"]);
+        assert_eq!(merge_args.get_formatter(), "csv");
+        assert!(merge_args.get_formatter_options().get_totals());
+        assert!(!merge_args.get_formatter_options().get_compact());
+        assert_eq!(merge_args.get_overflow_policy(), OverflowPolicy::Saturate);
+        assert_eq!(merge_args.get_sink(), "file");
+        assert_eq!(
+            merge_args.get_sink_options().get_output(),
+            Some("usage.csv")
+        );
+        assert!(merge_args.get_sink_options().get_checksum());
+
+        let merge_args =
+            MergeArgs::build(&mut vec!["--input=a.json".to_string()].into_iter()).unwrap();
+
+        assert_eq!(merge_args.get_formatter(), "stdout");
+        assert_eq!(merge_args.get_overflow_policy(), OverflowPolicy::Error);
+        assert_eq!(merge_args.get_sink(), "stdout");
+
+        for (args, error) in [
+            (vec![], "--input"),
+            (vec!["--input="], "--input"),
+            (vec!["-i=-", "-i=-"], "only once"),
+            (vec!["-i=a.json", "--formatter=xml"], "Unknown formatter"),
+            (vec!["-i=a.json", "--on-overflow=ignore"], "overflow"),
+            (
+                vec!["-i=a.json", "--checksum"],
+                "--checksum can only be used",
+            ),
+            (vec!["-i=a.json", "--log_dir=logs"], "Unknown parameter"),
+        ] {
+            let merge_args = MergeArgs::build(&mut args.iter().map(|arg| arg.to_string()));
+
+            assert!(merge_args.unwrap_err().contains(error), "{:?}", args);
+        }
+    }
+}
@@ -1 +1,8 @@
+pub mod arg_parser;
 pub mod cli_args;
+pub mod command_enum;
+pub mod db_query_args;
+pub mod exit_code_enum;
+pub mod help;
+pub mod merge_args;
+pub mod settings;
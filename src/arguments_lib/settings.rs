@@ -0,0 +1,485 @@
+//! Merges the settings from the config file, the environment variables and the command line, into the arguments for `CLIArgs::build`.
+//!
+//! Precedence, from the highest: the command line, then the `USAGE_PARSE_*` environment variables, then the config file, and then the defaults.
+//! A setting replaces the same setting from a lower level as a whole, so `--log_dir` on the command line replaces all `log_dir`s from the file.
+//! Everything ends up as command line arguments, so it is all validated in one place (`CLIArgs::build`).
+//!
+//! The config file is `--config=path` (or `-c`), or `USAGE_PARSE_CONFIG`. Without either, `usage-parse.toml` is used, if it exists
+//! in the working directory. It is TOML (see `toml.rs`), with the keys named like the options, in snake case:
+//!
+//! ```toml
+//! log_dir = ["/var/log/nginx", "/var/log/archive"]
+//! input_format = "combined"
+//! workers = 4
+//!
+//! [webhook]
+//! url = "http://localhost:8080/usage"
+//! retries = 5
+//! ```
+//!
+//! A `[table]` is a prefix of its keys, so `[webhook] url` is the same as `webhook_url`.
+//! Environment variables are the key in upper case, like `USAGE_PARSE_WEBHOOK_URL`. Settings that can be given multiple times
+//! (like `log_dir`) are comma separated there.
+//! Paths are relative to the working directory, not to the config file. Unknown keys and variables are errors, so typos don't go unnoticed.
+use super::super::utils::toml::TomlValue;
+use super::arg_parser::ArgParser;
+use super::cli_args::BOOLEAN_FLAGS;
+use std::collections::BTreeMap;
+
+/// The config file, used when no other is given.
+pub const DEFAULT_CONFIG_FILE: &str = "usage-parse.toml";
+
+/// Prefix of the environment variables.
+pub const ENV_PREFIX: &str = "USAGE_PARSE_";
+
+/// The settings of the `merge` command: the output options, and the overflow policy.
+pub const MERGE_SETTINGS: [&str; 6] = [
+    "formatter",
+    "totals",
+    "compact",
+    "on_overflow",
+    "output",
+    "checksum",
+];
+
+/// A setting, that can come from the config file or the environment, and the command line option it turns into.
+struct Setting {
+    key: &'static str,
+    long_name: &'static str,
+    short_name: Option<&'static str>,
+    /// Can be given multiple times (an array in the config file).
+    repeatable: bool,
+}
+
+const fn setting(
+    key: &'static str,
+    long_name: &'static str,
+    short_name: Option<&'static str>,
+    repeatable: bool,
+) -> Setting {
+    Setting {
+        key,
+        long_name,
+        short_name,
+        repeatable,
+    }
+}
+
+/// Every option of `CLIArgs`. When adding a new argument, add it here too.
+const SETTINGS: [Setting; 30] = [
+    setting("log_dir", "--log_dir", Some("-ld"), true),
+    setting("input", "--input", Some("-i"), true),
+    setting("files_from", "--files-from", Some("-ff"), false),
+    setting("formatter", "--formatter", Some("-fmt"), false),
+    setting("totals", "--totals", Some("-t"), false),
+    setting("compact", "--compact", None, false),
+    setting("workers", "--workers", Some("-w"), false),
+    setting("input_format", "--input-format", Some("-if"), false),
+    setting("on_error", "--on-error", Some("-oe"), false),
+    setting("on_overflow", "--on-overflow", Some("-oo"), false),
+    setting("dead_letter", "--dead-letter", Some("-dl"), false),
+    setting("max_error_rate", "--max-error-rate", Some("-mer"), false),
+    setting("schema", "--schema", Some("-s"), false),
+    setting("group_by", "--group-by", Some("-g"), false),
+    setting("include", "--include", Some("-in"), true),
+    setting("exclude", "--exclude", Some("-ex"), true),
+    setting("symlinks", "--symlinks", None, false),
+    setting("bucket", "--bucket", Some("-b"), false),
+    setting("since", "--since", None, false),
+    setting("until", "--until", None, false),
+    setting("incremental", "--incremental", Some("-inc"), false),
+    setting("flush_interval", "--flush-interval", Some("-fi"), false),
+    setting("sink", "--sink", None, false),
+    setting("output", "--output", Some("-o"), false),
+    setting("checksum", "--checksum", None, false),
+    setting("webhook_url", "--webhook-url", None, false),
+    setting("webhook_retries", "--webhook-retries", None, false),
+    setting("webhook_timeout", "--webhook-timeout", None, false),
+    setting("database", "--database", Some("-db"), false),
+    setting("store", "--store", None, false),
+];
+
+/// *Merge the config file, the environment variables and the command line arguments, into the arguments for `CLIArgs::build`*
+///
+/// ---
+///
+/// Returns an error if the config file can't be read or is not valid, or for an unknown key or environment variable.
+/// Unknown command line options are passed through, so `CLIArgs::build` reports them like any other invalid argument.
+///
+/// A command that takes only some of the settings (like `merge`) gives their keys in `only_keys`. The other settings
+/// of the config file and the environment are then left out, so the same config file can be used by every command.
+/// The command line arguments are always passed through, so the command still reports the ones it doesn't take.
+///
+/// ## Arguments
+///
+/// - `cli_args` - The command line arguments (after the command)
+/// - `env_vars` - The environment variables. Only those starting with `USAGE_PARSE_` are used.
+/// - `default_config_file` - The config file to use if it exists, when none is given
+/// - `only_keys` - The keys of the settings the command takes, or None for all of them
+///
+/// ## Example
+///
+/// ```
+/// let args = merge_settings(cli_args, std::env::vars(), DEFAULT_CONFIG_FILE, None)?;
+/// let cli_args = CLIArgs::build(&mut args.into_iter())?;
+/// ```
+pub fn merge_settings(
+    cli_args: Vec<String>,
+    env_vars: impl Iterator<Item = (String, String)>,
+    default_config_file: &str,
+    only_keys: Option<&[&str]>,
+) -> Result<Vec<String>, String> {
+    let mut cli_iterator = cli_args.into_iter();
+    let mut arg_parser = ArgParser::new(&mut cli_iterator, BOOLEAN_FLAGS);
+    let mut cli_layer: BTreeMap<&'static str, Vec<String>> = BTreeMap::new();
+    let mut unknown_args: Vec<String> = Vec::new();
+    let mut cli_config: Option<String> = None;
+
+    while let Some((arg_name, arg_value)) = arg_parser.next_arg()? {
+        if arg_name == "--config" || arg_name == "-c" {
+            cli_config = Some(arg_value);
+
+            continue;
+        }
+
+        match SETTINGS.iter().find(|setting| {
+            setting.long_name == arg_name || setting.short_name == Some(arg_name.as_str())
+        }) {
+            Some(setting) => cli_layer.entry(setting.key).or_default().push(arg_value),
+            None => unknown_args.push(format!("{}={}", arg_name, arg_value)),
+        }
+    }
+
+    let mut env_layer: BTreeMap<&'static str, Vec<String>> = BTreeMap::new();
+    let mut env_config: Option<String> = None;
+
+    for (name, value) in env_vars {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let key = key.to_lowercase();
+
+        if key == "config" {
+            env_config = Some(value);
+
+            continue;
+        }
+
+        let Some(setting) = find_setting(&key) else {
+            return Err(format!("Unknown environment variable: {}", name));
+        };
+        let values = if setting.repeatable {
+            value
+                .split(',')
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+                .collect()
+        } else {
+            vec![value]
+        };
+
+        env_layer.insert(setting.key, values);
+    }
+
+    let config_file = match cli_config.or(env_config) {
+        Some(config_file) => Some(config_file),
+        None if std::path::Path::new(default_config_file).is_file() => {
+            Some(default_config_file.to_string())
+        }
+        None => None,
+    };
+    let mut merged = match config_file {
+        Some(config_file) => read_config_file(&config_file)?,
+        None => BTreeMap::new(),
+    };
+
+    merged.extend(env_layer);
+
+    if let Some(only_keys) = only_keys {
+        merged.retain(|key, _| only_keys.contains(key));
+    }
+
+    merged.extend(cli_layer);
+
+    let mut args: Vec<String> = Vec::new();
+
+    for setting in &SETTINGS {
+        for value in merged.get(setting.key).into_iter().flatten() {
+            args.push(format!("{}={}", setting.long_name, value));
+        }
+    }
+
+    args.extend(unknown_args);
+
+    Ok(args)
+}
+
+/// *Read the settings from the config file*
+fn read_config_file(path: &str) -> Result<BTreeMap<&'static str, Vec<String>>, String> {
+    let config_error = |reason: String| format!("Config file {}: {}", path, reason);
+    let content = std::fs::read_to_string(path).map_err(|error| config_error(error.to_string()))?;
+    let mut settings: BTreeMap<&'static str, Vec<String>> = BTreeMap::new();
+
+    for (key, value) in TomlValue::parse_document(&content).map_err(config_error)? {
+        let Some(setting) = find_setting(&key) else {
+            return Err(config_error(format!("Unknown setting: {}", key)));
+        };
+        let values = match value {
+            TomlValue::Array(items) if setting.repeatable => items
+                .iter()
+                .map(|item| scalar_to_string(item).ok_or(()))
+                .collect::<Result<Vec<String>, ()>>(),
+            // A list of entities, which is comma separated on the command line.
+            TomlValue::Array(items) if setting.key == "group_by" => items
+                .iter()
+                .map(|item| scalar_to_string(item).ok_or(()))
+                .collect::<Result<Vec<String>, ()>>()
+                .map(|entities| vec![entities.join(",")]),
+            TomlValue::Array(_) => {
+                return Err(config_error(format!("{} takes a single value", key)));
+            }
+            scalar => scalar_to_string(&scalar).map(|value| vec![value]).ok_or(()),
+        }
+        .map_err(|_| config_error(format!("{} can't have nested arrays", key)))?;
+
+        settings.insert(setting.key, values);
+    }
+
+    Ok(settings)
+}
+
+/// *Find the setting by its key. Dots (from the tables) and dashes are the same as underscores.*
+fn find_setting(key: &str) -> Option<&'static Setting> {
+    let key = key.replace(['.', '-'], "_");
+
+    SETTINGS.iter().find(|setting| setting.key == key)
+}
+
+/// *Return the value as it would be given on the command line, or None for an array*
+fn scalar_to_string(value: &TomlValue) -> Option<String> {
+    match value {
+        TomlValue::String(string) => Some(string.clone()),
+        TomlValue::Integer(integer) => Some(integer.to_string()),
+        TomlValue::Float(float) => Some(float.to_string()),
+        TomlValue::Boolean(boolean) => Some(boolean.to_string()),
+        TomlValue::Array(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cli_args::CLIArgs;
+    use super::super::merge_args::MergeArgs;
+    use super::*;
+
+    fn to_strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<(String, String)>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_merge_settings_precedence() {
+        let test_config = "test_settings_precedence.toml";
+
+        std::fs::write(
+            test_config,
+            "log_dir = [\"logs/a\", \"logs/b\"]
+formatter = \"json\"
+workers = 2
+max_error_rate = 0.05
+group_by = [\"video\", \"ad_unit\"]
+compact = true
+
+[webhook]
+url = \"http://localhost:8080/usage\"
+retries = 5
+",
+        )
+        .unwrap();
+
+        // The file alone.
+        let file_only = merge_settings(
+            to_strings(&["--config", test_config]),
+            env(&[("HOME", "/root")]),
+            "does_not_exist.toml",
+            None,
+        );
+        // The environment over the file, and the command line over both.
+        let merged = merge_settings(
+            to_strings(&["-c", test_config, "-ld", "logs/c", "-fmt=csv", "--totals"]),
+            env(&[
+                ("USAGE_PARSE_LOG_DIR", "logs/d, logs/e"),
+                ("USAGE_PARSE_WORKERS", "8"),
+                ("USAGE_PARSE_FORMATTER", "ndjson"),
+            ]),
+            "does_not_exist.toml",
+            None,
+        );
+        // The config file can also come from the environment, or be the default one.
+        let from_env = merge_settings(
+            vec![],
+            env(&[("USAGE_PARSE_CONFIG", test_config)]),
+            "does_not_exist.toml",
+            None,
+        );
+        let default_file = merge_settings(vec![], env(&[]), test_config, None);
+        // Merge takes only the output options from the file and the environment.
+        let merge_only = merge_settings(
+            to_strings(&["-c", test_config, "-i", "eu/usage.json"]),
+            env(&[("USAGE_PARSE_TOTALS", "true"), ("USAGE_PARSE_WORKERS", "8")]),
+            "does_not_exist.toml",
+            Some(&MERGE_SETTINGS),
+        );
+
+        std::fs::remove_file(test_config).unwrap();
+
+        let file_only = file_only.unwrap();
+
+        assert_eq!(
+            file_only,
+            to_strings(&[
+                "--log_dir=logs/a",
+                "--log_dir=logs/b",
+                "--formatter=json",
+                "--compact=true",
+                "--workers=2",
+                "--max-error-rate=0.05",
+                "--group-by=video,ad_unit",
+                "--webhook-url=http://localhost:8080/usage",
+                "--webhook-retries=5",
+            ])
+        );
+        assert_eq!(
+            merged.unwrap(),
+            to_strings(&[
+                "--log_dir=logs/c",
+                "--formatter=csv",
+                "--totals=true",
+                "--compact=true",
+                "--workers=8",
+                "--max-error-rate=0.05",
+                "--group-by=video,ad_unit",
+                "--webhook-url=http://localhost:8080/usage",
+                "--webhook-retries=5",
+            ])
+        );
+        assert_eq!(from_env.unwrap(), file_only);
+        assert_eq!(default_file.unwrap(), file_only);
+        assert_eq!(
+            merge_only.unwrap(),
+            to_strings(&[
+                "--input=eu/usage.json",
+                "--formatter=json",
+                "--totals=true",
+                "--compact=true",
+            ])
+        );
+
+        // Validated in one place, like the command line arguments.
+        let cli_args = CLIArgs::build(&mut file_only.into_iter()).unwrap();
+
+        assert_eq!(cli_args.get_workers(), Some(2));
+        assert_eq!(cli_args.get_sink_options().get_webhook_retries(), 5);
+    }
+
+    #[test]
+    fn test_merge_settings_errors() {
+        let test_config = "test_settings_errors.toml";
+
+        for (config, error) in [
+            ("log_dirs = [\"logs\"]", "Unknown setting: log_dirs"),
+            (
+                "formatter = [\"json\", \"csv\"]",
+                "formatter takes a single value",
+            ),
+            ("log_dir = [[\"logs\"]]", "log_dir can't have nested arrays"),
+            ("workers = ", "Expected a value on line 1"),
+        ] {
+            std::fs::write(test_config, config).unwrap();
+
+            let result = merge_settings(
+                to_strings(&["--config", test_config]),
+                env(&[]),
+                "does_not_exist.toml",
+                None,
+            );
+
+            assert!(
+                result.as_ref().is_err_and(|reason| reason.contains(error)),
+                "{:?}: {:?}",
+                config,
+                result
+            );
+        }
+
+        std::fs::remove_file(test_config).unwrap();
+
+        assert!(
+            merge_settings(
+                vec![],
+                env(&[("USAGE_PARSE_WORKRES", "4")]),
+                "does_not_exist.toml",
+                None
+            )
+            .unwrap_err()
+            .contains("Unknown environment variable: USAGE_PARSE_WORKRES")
+        );
+        assert!(
+            merge_settings(
+                to_strings(&["--config=does_not_exist.toml"]),
+                env(&[]),
+                "does_not_exist.toml",
+                None
+            )
+            .unwrap_err()
+            .contains("Config file does_not_exist.toml")
+        );
+        // Unknown options are left for CLIArgs::build to report.
+        assert_eq!(
+            merge_settings(
+                to_strings(&["--unknown", "value"]),
+                env(&[]),
+                "does_not_exist.toml",
+                None
+            ),
+            Ok(to_strings(&["--unknown=value"]))
+        );
+    }
+
+    #[test]
+    fn test_every_setting_is_an_argument() {
+        for setting in &SETTINGS {
+            for name in std::iter::once(setting.long_name).chain(setting.short_name) {
+                let cli_args = CLIArgs::build(
+                    &mut to_strings(&["--log_dir=logs", &format!("{}=x", name)]).into_iter(),
+                );
+
+                assert!(
+                    !cli_args.is_err_and(|error| error.contains("Unknown parameter")),
+                    "{}",
+                    name
+                );
+            }
+        }
+
+        for key in MERGE_SETTINGS {
+            let setting = find_setting(key).unwrap();
+            let merge_args = MergeArgs::build(
+                &mut to_strings(&["--input=a.json", &format!("{}=x", setting.long_name)])
+                    .into_iter(),
+            );
+
+            assert!(
+                !merge_args.is_err_and(|error| error.contains("Unknown parameter")),
+                "{}",
+                key
+            );
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Waiting for changes in the log directories.
+//!
+//! On Linux, inotify is used (through `rustix`, which has safe bindings for it). Every watched directory reports
+//! new, modified, renamed and removed files in it, which covers appended lines, new files, and both kinds of log rotation
+//! (rename, and copy + truncate).
+//!
+//! On other platforms, there are no notifications. Waiting just sleeps, and every wake up is treated as a possible change.
+#[cfg(target_os = "linux")]
+use rustix::event::{PollFd, PollFlags, Timespec, poll};
+#[cfg(target_os = "linux")]
+use rustix::fs::inotify;
+#[cfg(target_os = "linux")]
+use rustix::io::Errno;
+use std::path::Path;
+use std::time::Duration;
+
+/// Watches directories for changes, see the module docs.
+pub struct FileWatcher {
+    #[cfg(target_os = "linux")]
+    fd: std::os::fd::OwnedFd,
+    #[cfg(target_os = "linux")]
+    watches: std::collections::HashMap<i32, std::path::PathBuf>,
+}
+
+#[cfg(target_os = "linux")]
+impl FileWatcher {
+    /// *Create a watcher, without any watched directories*
+    pub fn new() -> std::io::Result<Self> {
+        let fd = inotify::init(inotify::CreateFlags::NONBLOCK | inotify::CreateFlags::CLOEXEC)?;
+
+        Ok(Self {
+            fd,
+            watches: std::collections::HashMap::new(),
+        })
+    }
+    /// *Start watching a directory, for changes of the files directly inside of it*
+    ///
+    /// ---
+    ///
+    /// Watching an already watched directory does nothing. Subdirectories must be watched separately.
+    ///
+    /// ## Arguments
+    ///
+    /// - `dir` - Path of the directory
+    pub fn watch(&mut self, dir: &Path) -> std::io::Result<()> {
+        if self.watches.values().any(|watched| watched == dir) {
+            return Ok(());
+        }
+
+        let flags = inotify::WatchFlags::MODIFY
+            | inotify::WatchFlags::CLOSE_WRITE
+            | inotify::WatchFlags::MOVED_FROM
+            | inotify::WatchFlags::MOVED_TO
+            | inotify::WatchFlags::CREATE
+            | inotify::WatchFlags::DELETE;
+        let wd = inotify::add_watch(&self.fd, dir, flags)?;
+
+        self.watches.insert(wd, dir.to_path_buf());
+
+        Ok(())
+    }
+    /// *Wait until something changes in one of the watched directories, or the timeout passes*
+    ///
+    /// ---
+    ///
+    /// Returns true, if there was a change. A signal (like SIGTERM) interrupts the waiting, and returns false.
+    ///
+    /// ## Arguments
+    ///
+    /// - `timeout` - How long to wait at most
+    pub fn wait(&mut self, timeout: Duration) -> std::io::Result<bool> {
+        let timeout = Timespec {
+            tv_sec: i64::try_from(timeout.as_secs()).unwrap_or(i64::MAX),
+            tv_nsec: i64::from(timeout.subsec_nanos()),
+        };
+        let ready = match poll(&mut [PollFd::new(&self.fd, PollFlags::IN)], Some(&timeout)) {
+            Ok(ready) => ready,
+            Err(Errno::INTR) => return Ok(false),
+            Err(error) => return Err(error.into()),
+        };
+
+        if ready == 0 {
+            return Ok(false);
+        }
+
+        self.drain_events()?;
+
+        Ok(true)
+    }
+    /// *Read all pending events, and forget the watches the kernel removed*
+    fn drain_events(&mut self) -> std::io::Result<()> {
+        let mut buffer = [std::mem::MaybeUninit::<u8>::uninit(); 4096];
+        let mut events = inotify::Reader::new(&self.fd, &mut buffer);
+
+        loop {
+            match events.next() {
+                Ok(event) if event.events().contains(inotify::ReadFlags::IGNORED) => {
+                    self.watches.remove(&event.wd());
+                }
+                Ok(_) => {}
+                Err(Errno::AGAIN) => return Ok(()),
+                Err(Errno::INTR) => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+    }
+    /// *Return true, since changes are reported as they happen*
+    pub fn is_notifying(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl FileWatcher {
+    /// *Create a watcher. Without inotify, it only sleeps.*
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Self {})
+    }
+    /// *Does nothing, without inotify*
+    pub fn watch(&mut self, _dir: &Path) -> std::io::Result<()> {
+        Ok(())
+    }
+    /// *Sleep for the given time, and report a possible change*
+    pub fn wait(&mut self, timeout: Duration) -> std::io::Result<bool> {
+        std::thread::sleep(timeout);
+
+        Ok(true)
+    }
+    /// *Return false, since every wait has to be treated as a possible change*
+    pub fn is_notifying(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_file_watcher_reports_changes() {
+        let test_dir = "test_file_watcher_dir";
+        let test_file = format!("{}/access.log", test_dir);
+
+        std::fs::create_dir(test_dir).unwrap();
+        std::fs::write(&test_file, "first\n").unwrap();
+
+        let mut watcher = FileWatcher::new().unwrap();
+
+        watcher.watch(Path::new(test_dir)).unwrap();
+        watcher.watch(Path::new(test_dir)).unwrap();
+
+        let quiet = watcher.wait(Duration::from_millis(10)).unwrap();
+
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&test_file)
+            .unwrap()
+            .write_all(b"second\n")
+            .unwrap();
+
+        let appended = watcher.wait(Duration::from_secs(5)).unwrap();
+
+        std::fs::rename(&test_file, format!("{}/access.log.1", test_dir)).unwrap();
+
+        let renamed = watcher.wait(Duration::from_secs(5)).unwrap();
+        let missing_dir_result = watcher.watch(Path::new("test_file_watcher_missing_dir"));
+
+        std::fs::remove_dir_all(test_dir).unwrap();
+
+        assert!(!quiet);
+        assert!(appended);
+        assert!(renamed);
+        assert_eq!(watcher.watches.len(), 1);
+        assert!(missing_dir_result.is_err());
+    }
+}
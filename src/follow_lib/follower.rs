@@ -0,0 +1,258 @@
+//! The follow mode. Tails the log directories, like `tail -F`, and keeps a running aggregate.
+//!
+//! Every pass is an incremental run of the Pipeline, with the state kept in memory (see `state_store.rs`).
+//! So new files and appended lines are picked up, rotated files (renamed, or copied and truncated) are continued without counting anything twice,
+//! and a line is only counted once it's complete.
+//!
+//! A new pass starts whenever the file watcher reports a change. The aggregate is flushed on a fixed interval, and one last time when the
+//! stop flag is set (for example, by SIGTERM). With an incremental state file in the config, the state is saved on every flush,
+//! so a restarted follower continues where the previous one stopped.
+use super::super::pipeline_lib::pipeline::{Aggregate, Pipeline};
+use super::super::pipeline_lib::pipeline_error::PipelineError;
+use super::super::pipeline_lib::run_id::run_id_for_state;
+use super::super::state_lib::state_store::StateStore;
+use super::file_watcher::FileWatcher;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// How often the stop flag is checked, while there are no changes.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+/// How long to wait for a burst of changes (like a file being copied) to end, before a pass.
+const SETTLE_TIME: Duration = Duration::from_millis(50);
+
+pub struct Follower {
+    pipeline: Pipeline,
+    flush_interval: Duration,
+}
+
+impl Follower {
+    /// *Create a follower for a given pipeline, flushing every minute*
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let follower = Follower::new(Pipeline::new(PipelineConfig::new("logs")))
+    ///     .with_flush_interval(Duration::from_secs(10));
+    /// ```
+    pub fn new(pipeline: Pipeline) -> Self {
+        Self {
+            pipeline,
+            flush_interval: Duration::from_secs(60),
+        }
+    }
+    /// *Set how often the aggregate is flushed*
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+
+        self
+    }
+    /// *Return the pipeline that parses the logs*
+    pub fn get_pipeline(&self) -> &Pipeline {
+        &self.pipeline
+    }
+    /// *Return how often the aggregate is flushed*
+    pub fn get_flush_interval(&self) -> Duration {
+        self.flush_interval
+    }
+    /// *Follow the logs, until the stop flag is set*
+    ///
+    /// ---
+    ///
+    /// Every logs directory must exist, but it can be empty at first.
+    /// Any error stops the follower. What was flushed (and saved) before it, stays valid.
+    /// Returns the final aggregate, after the last flush.
+    ///
+    /// ## Arguments
+    ///
+    /// - `stop` - Flag that stops the follower, see `termination_signal.rs`
+    /// - `on_flush` - Called with the whole aggregate, and the identity of the run (see `run_id.rs`), on every flush
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let termination_requested = install_termination_handler()?;
+    ///
+    /// follower.run(termination_requested, &mut |aggregate, run_id| {
+    ///     follower.get_pipeline().write_output(aggregate, Some(run_id))
+    /// })?;
+    /// ```
+    pub fn run(
+        &self,
+        stop: &AtomicBool,
+        on_flush: &mut dyn FnMut(&Aggregate, &str) -> Result<(), PipelineError>,
+    ) -> Result<Aggregate, PipelineError> {
+        let config = self.pipeline.get_config();
+
+        for logs_dir in config.get_logs_dirs() {
+            if !Path::new(logs_dir).is_dir() {
+                return Err(PipelineError::NoFilesFound(logs_dir.to_string()));
+            }
+        }
+
+        let state_error = |reason: String| PipelineError::State {
+            path: config.get_incremental().unwrap_or_default().to_string(),
+            reason,
+        };
+        let mut state = match config.get_incremental() {
+            Some(state_path) => StateStore::load(state_path).map_err(state_error)?,
+            None => None,
+        }
+        .unwrap_or_else(|| StateStore::new(config.get_settings_fingerprint()));
+        let mut watcher = FileWatcher::new().map_err(|error| state_error(error.to_string()))?;
+        let mut next_flush = Instant::now() + self.flush_interval;
+        let mut changed = true;
+
+        loop {
+            // Read before the pass, so everything written before the stop is counted.
+            let stopping = stop.load(Ordering::SeqCst);
+
+            if changed || stopping {
+                match self.pipeline.run_incremental(&mut state) {
+                    // No log files yet. They will be picked up, once they are created.
+                    Ok(_) | Err(PipelineError::NoFilesFound(_)) => {}
+                    Err(error) => return Err(error),
+                }
+
+                self.watch_dirs(&mut watcher, &state);
+            }
+
+            if stopping || Instant::now() >= next_flush {
+                on_flush(state.get_aggregate(), &run_id_for_state(&state))?;
+
+                if let Some(state_path) = config.get_incremental() {
+                    state
+                        .save(state_path)
+                        .map_err(|error| state_error(error.to_string()))?;
+                }
+
+                next_flush = Instant::now() + self.flush_interval;
+            }
+
+            if stopping {
+                return Ok(state.into_aggregate());
+            }
+
+            let timeout = next_flush
+                .saturating_duration_since(Instant::now())
+                .min(STOP_CHECK_INTERVAL);
+
+            changed = watcher
+                .wait(timeout)
+                .map_err(|error| state_error(error.to_string()))?;
+
+            if changed && watcher.is_notifying() {
+                let settle_until = Instant::now() + STOP_CHECK_INTERVAL;
+
+                while Instant::now() < settle_until
+                    && watcher
+                        .wait(SETTLE_TIME)
+                        .map_err(|error| state_error(error.to_string()))?
+                {}
+            }
+        }
+    }
+    /// *Watch the logs directories, and the directories of all files read so far*
+    ///
+    /// ---
+    ///
+    /// Directories that can't be watched (for example, since they were just removed) are skipped. They are still read on every pass.
+    fn watch_dirs(&self, watcher: &mut FileWatcher, state: &StateStore) {
+        let config = self.pipeline.get_config();
+        let logs_dirs = config.get_logs_dirs().iter().map(Path::new);
+        let file_dirs = state
+            .get_checkpoints()
+            .iter()
+            .filter_map(|checkpoint| Path::new(checkpoint.get_path()).parent())
+            .map(|dir| {
+                if dir.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    dir
+                }
+            });
+
+        for dir in logs_dirs.chain(file_dirs) {
+            let _ = watcher.watch(dir);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::pipeline_lib::pipeline::PipelineConfig;
+    use super::*;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_follower_picks_up_appends_and_rotations() {
+        let test_log_dir = "test_follower_dir";
+        let test_log_file = format!("{}/access.log", test_log_dir);
+        let stop = Arc::new(AtomicBool::new(false));
+        let append = |content: &str| {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&test_log_file)
+                .unwrap()
+                .write_all(content.as_bytes())
+                .unwrap();
+        };
+
+        std::fs::create_dir(test_log_dir).unwrap();
+
+        let follower_stop = Arc::clone(&stop);
+        let follower_handle = std::thread::spawn(move || {
+            let follower = Follower::new(Pipeline::new(PipelineConfig::new(test_log_dir)))
+                .with_flush_interval(Duration::from_millis(50));
+            let mut flushes = 0;
+            let result = follower.run(&follower_stop, &mut |_aggregate, _run_id| {
+                flushes += 1;
+
+                Ok(())
+            });
+
+            (result, flushes)
+        });
+        let wait_for_pass = || std::thread::sleep(Duration::from_millis(300));
+
+        wait_for_pass();
+        append("https://x/p.gif?o=1&v=1\nhttps://x/p.gif?o=1&v=");
+        wait_for_pass();
+        append("1\n");
+        wait_for_pass();
+        // Rotated by rename, and a new file is started. The renamed file is not counted again.
+        std::fs::rename(&test_log_file, format!("{}/access.log.1", test_log_dir)).unwrap();
+        wait_for_pass();
+        append("https://x/p.gif?o=2&v=1\n");
+        wait_for_pass();
+        append("https://x/p.gif?o=3&v=1\n");
+        wait_for_pass();
+        // Rotated by copytruncate. The copy is not counted again, and the truncated file starts over.
+        std::fs::copy(&test_log_file, format!("{}/access.log.2", test_log_dir)).unwrap();
+        std::fs::File::create(&test_log_file).unwrap();
+        wait_for_pass();
+        append("https://x/p.gif?o=3&v=1\n");
+        wait_for_pass();
+        stop.store(true, Ordering::SeqCst);
+
+        let (result, flushes) = follower_handle.join().unwrap();
+
+        std::fs::remove_dir_all(test_log_dir).unwrap();
+
+        let aggregate = result.unwrap();
+
+        assert!(flushes >= 2);
+        assert_eq!(aggregate.get(&1).unwrap().get_metric("video_plays"), 2);
+        assert_eq!(aggregate.get(&2).unwrap().get_metric("video_plays"), 1);
+        assert_eq!(aggregate.get(&3).unwrap().get_metric("video_plays"), 2);
+        assert!(matches!(
+            Follower::new(Pipeline::new(PipelineConfig::new(
+                "test_follower_missing_dir"
+            )))
+            .run(&AtomicBool::new(true), &mut |_aggregate, _run_id| Ok(())),
+            Err(PipelineError::NoFilesFound(_))
+        ));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod file_watcher;
+pub mod follower;
+pub mod termination_signal;
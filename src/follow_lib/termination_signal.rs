@@ -0,0 +1,83 @@
+//! Graceful shutdown on SIGTERM and SIGINT.
+//!
+//! The follow mode runs until it is stopped. Instead of being killed in the middle (and losing everything since the last flush),
+//! the signal only sets a flag. The follow loop checks it, parses what is left, flushes the aggregate one last time, and exits.
+//!
+//! The handlers are installed with `signal-hook`, which only sets the flag in them.
+//! A blocking `poll(2)` is interrupted by the signal, so the flag is seen right away.
+use signal_hook::consts::signal::{SIGINT, SIGTERM};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+/// The signals, that request a graceful shutdown.
+const TERMINATION_SIGNALS: [i32; 2] = [SIGTERM, SIGINT];
+
+/// *Install the SIGTERM and SIGINT handlers, and return the flag they set*
+///
+/// ---
+///
+/// Every call installs new handlers, with a new flag.
+///
+/// ## Example
+///
+/// ```
+/// let termination_requested = install_termination_handler()?;
+///
+/// while !termination_requested.load(Ordering::SeqCst) {
+///     // Work...
+/// }
+/// ```
+pub fn install_termination_handler() -> std::io::Result<Arc<AtomicBool>> {
+    let termination_requested = Arc::new(AtomicBool::new(false));
+
+    register_all(&termination_requested, |signal, flag| {
+        signal_hook::flag::register(signal, flag).map(|_| ())
+    })?;
+
+    Ok(termination_requested)
+}
+
+/// *Register the flag for every termination signal*
+fn register_all(
+    termination_requested: &Arc<AtomicBool>,
+    mut register: impl FnMut(i32, Arc<AtomicBool>) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    for signal in TERMINATION_SIGNALS {
+        register(signal, Arc::clone(termination_requested))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn test_every_termination_signal_sets_the_flag() {
+        let termination_requested = Arc::new(AtomicBool::new(false));
+        let mut handlers: Vec<(i32, Arc<AtomicBool>)> = Vec::new();
+
+        register_all(&termination_requested, |signal, flag| {
+            handlers.push((signal, flag));
+
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            handlers
+                .iter()
+                .map(|(signal, _)| *signal)
+                .collect::<Vec<i32>>(),
+            vec![SIGTERM, SIGINT]
+        );
+        assert!(!termination_requested.load(Ordering::SeqCst));
+
+        // What the handler of SIGINT does.
+        handlers[1].1.store(true, Ordering::SeqCst);
+
+        assert!(termination_requested.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,503 @@
+//! Read an aggregate back, from the output of our own formatters (json, ndjson, csv and tsv).
+//!
+//! Used to merge the reports of several runs (for example, one per datacenter) into a combined one.
+//! The format is recognized from the content, so it works for any file name, and for the standard input:
+//!
+//! - `[` - json (see `json_formatter.rs`)
+//! - `{`, or an empty document - ndjson, one owner per line (see `ndjson_formatter.rs`)
+//! - `owner_id` - csv or tsv, told apart by the delimiter after it (see `delimited_formatter.rs`)
+//!
+//! Reading is strict, since the input must be our own output: unknown keys, an owner listed twice, or a value that is not a u64 are errors.
+//! This way, a damaged or foreign file fails the merge, instead of silently losing some usage.
+//!
+//! The json and ndjson output is read back exactly, with the breakdowns and the time series.
+//! The csv and tsv output has only the owner totals, and a metric an owner did not have is read back as 0.
+//! The totals row (`total` in the `owner_id` column) is skipped, it is recalculated when the merged aggregate is written.
+//! The stdout formatter is meant for people, and can't be read back.
+use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+use super::super::utils::json_value::JsonValue;
+use std::collections::HashMap;
+
+/// *Read an aggregate from the output of the json, ndjson, csv or tsv formatter*
+///
+/// ---
+///
+/// Returns an error if the format is not recognized, or the content is not valid.
+///
+/// ## Arguments
+///
+/// - `content` - The whole output of a formatter
+///
+/// ## Example
+///
+/// ```
+/// let aggregate = read_aggregate("owner_id,video_plays\r\n123,4\r\n")?;
+///
+/// assert_eq!(aggregate[&123].get_metric("video_plays"), 4);
+/// ```
+pub fn read_aggregate(content: &str) -> Result<HashMap<u32, OwnerUsage>, String> {
+    let trimmed = content.trim_start();
+
+    if trimmed.starts_with('[') {
+        read_json(trimmed)
+    } else if trimmed.is_empty() || trimmed.starts_with('{') {
+        read_ndjson(trimmed)
+    } else if let Some(rest) = trimmed.strip_prefix("owner_id") {
+        let delimiter = if rest.starts_with('\t') { '\t' } else { ',' };
+
+        read_delimited(trimmed, delimiter)
+    } else {
+        Err(
+            "Unknown format. Only the json, ndjson, csv and tsv output of usage-parse can be read back"
+                .to_string(),
+        )
+    }
+}
+
+/// *Read the output of the json formatter, an array of owner objects*
+fn read_json(content: &str) -> Result<HashMap<u32, OwnerUsage>, String> {
+    let document = JsonValue::parse(content)?;
+    let owners = document.as_array().ok_or("Expected an array of owners")?;
+    let mut aggregate: HashMap<u32, OwnerUsage> = HashMap::new();
+
+    for owner in owners {
+        insert_owner(&mut aggregate, owner)?;
+    }
+
+    Ok(aggregate)
+}
+
+/// *Read the output of the ndjson formatter, an owner object per line*
+fn read_ndjson(content: &str) -> Result<HashMap<u32, OwnerUsage>, String> {
+    let mut aggregate: HashMap<u32, OwnerUsage> = HashMap::new();
+
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let owner =
+            JsonValue::parse(line).map_err(|reason| format!("{} on line {}", reason, index + 1))?;
+
+        insert_owner(&mut aggregate, &owner)
+            .map_err(|reason| format!("{} on line {}", reason, index + 1))?;
+    }
+
+    Ok(aggregate)
+}
+
+/// *Read a single owner object, and add it to the aggregate*
+fn insert_owner(aggregate: &mut HashMap<u32, OwnerUsage>, owner: &JsonValue) -> Result<(), String> {
+    let members = owner.as_object().ok_or("Expected an owner object")?;
+    let owner_id = owner
+        .get("owner_id")
+        .and_then(JsonValue::as_u64)
+        .and_then(|owner_id| u32::try_from(owner_id).ok())
+        .ok_or("Expected a u32 owner_id in every owner")?;
+    let mut owner_usage = read_usage(owner.get("usage"), "usage")?;
+
+    for (key, value) in members {
+        match key.as_str() {
+            "owner_id" | "usage" => {}
+            "breakdown" => {
+                let entities = value.as_object().ok_or("Expected an object in breakdown")?;
+
+                for (entity, entity_usages) in entities {
+                    for entity_usage in entity_usages
+                        .as_array()
+                        .ok_or_else(|| format!("Expected an array in breakdown.{}", entity))?
+                    {
+                        let entity_id = entity_usage
+                            .get("id")
+                            .and_then(JsonValue::as_u64)
+                            .and_then(|entity_id| u32::try_from(entity_id).ok())
+                            .ok_or_else(|| format!("Expected a u32 id in breakdown.{}", entity))?;
+                        let usage = read_usage(entity_usage.get("usage"), "breakdown usage")?;
+
+                        if owner_usage.get_entity_usage(entity, entity_id).is_some() {
+                            return Err(format!(
+                                "{} {} is listed more than once, for owner {}",
+                                entity, entity_id, owner_id
+                            ));
+                        }
+
+                        *owner_usage.get_entity_usage_mut(entity, entity_id) = usage;
+                    }
+                }
+            }
+            "series" => {
+                for bucket_usage in value.as_array().ok_or("Expected an array in series")? {
+                    let bucket = bucket_usage
+                        .get("bucket")
+                        .and_then(JsonValue::as_str)
+                        .ok_or("Expected a bucket label in series")?;
+                    let usage = read_usage(bucket_usage.get("usage"), "series usage")?;
+
+                    if owner_usage.get_series().contains_key(bucket) {
+                        return Err(format!(
+                            "Bucket {} is listed more than once, for owner {}",
+                            bucket, owner_id
+                        ));
+                    }
+
+                    *owner_usage.get_bucket_usage_mut(bucket) = usage;
+                }
+            }
+            unknown_key => {
+                return Err(format!(
+                    "Unknown key {}, for owner {}",
+                    unknown_key, owner_id
+                ));
+            }
+        }
+    }
+
+    if aggregate.insert(owner_id, owner_usage).is_some() {
+        return Err(format!("Owner {} is listed more than once", owner_id));
+    }
+
+    Ok(())
+}
+
+/// *Read the metrics object of an usage (the owner, an entity, or a bucket)*
+fn read_usage(usage: Option<&JsonValue>, context: &str) -> Result<OwnerUsage, String> {
+    let metrics = usage
+        .and_then(JsonValue::as_object)
+        .ok_or_else(|| format!("Expected an object in {}", context))?;
+    let mut owner_usage = OwnerUsage::default();
+
+    for (metric_name, value) in metrics {
+        let value = value
+            .as_u64()
+            .ok_or_else(|| format!("Expected a u64 value of {} in {}", metric_name, context))?;
+
+        if owner_usage.get_metrics().contains_key(metric_name) {
+            return Err(format!(
+                "Metric {} is listed more than once in {}",
+                metric_name, context
+            ));
+        }
+
+        owner_usage.add_metric(metric_name, value);
+    }
+
+    Ok(owner_usage)
+}
+
+/// *Read the output of the csv or tsv formatter*
+fn read_delimited(content: &str, delimiter: char) -> Result<HashMap<u32, OwnerUsage>, String> {
+    let rows = split_rows(content, delimiter)?;
+    let mut rows = rows.iter().enumerate();
+    let Some((_, header)) = rows.next() else {
+        return Err("Missing the header row".to_string());
+    };
+    let metric_names = &header[1..];
+
+    for (index, metric_name) in metric_names.iter().enumerate() {
+        if metric_names[..index].contains(metric_name) {
+            return Err(format!(
+                "Metric {} is listed more than once in the header",
+                metric_name
+            ));
+        }
+    }
+
+    let mut aggregate: HashMap<u32, OwnerUsage> = HashMap::new();
+    let mut totals_row: Option<usize> = None;
+
+    for (index, row) in rows {
+        let row_no = index + 1;
+
+        if let Some(totals_row_no) = totals_row {
+            return Err(format!(
+                "The totals row must be the last one, but it is row {}",
+                totals_row_no
+            ));
+        }
+
+        if row.len() != header.len() {
+            return Err(format!(
+                "Expected {} fields, but found {} on row {}",
+                header.len(),
+                row.len(),
+                row_no
+            ));
+        }
+
+        if row[0] == "total" {
+            totals_row = Some(row_no);
+
+            continue;
+        }
+
+        let owner_id = row[0]
+            .parse::<u32>()
+            .map_err(|_| format!("Expected a u32 owner_id on row {}", row_no))?;
+        let mut owner_usage = OwnerUsage::default();
+
+        for (metric_name, value) in metric_names.iter().zip(&row[1..]) {
+            let value = value.parse::<u64>().map_err(|_| {
+                format!("Expected a u64 value of {} on row {}", metric_name, row_no)
+            })?;
+
+            owner_usage.add_metric(metric_name, value);
+        }
+
+        if aggregate.insert(owner_id, owner_usage).is_some() {
+            return Err(format!("Owner {} is listed more than once", owner_id));
+        }
+    }
+
+    Ok(aggregate)
+}
+
+/// *Split the delimited content into rows of unquoted fields (RFC 4180)*
+///
+/// ---
+///
+/// Rows end with CRLF, or just LF. A quoted field can have the delimiter, line breaks, and doubled quotes inside it.
+fn split_rows(content: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut characters = content.chars().peekable();
+    // Quoted fields can be empty, so it is not enough to check if the field is empty.
+    let mut quoted = false;
+
+    while let Some(character) = characters.next() {
+        match character {
+            '"' if field.is_empty() && !quoted => {
+                quoted = true;
+
+                loop {
+                    match characters.next() {
+                        Some('"') if characters.peek() == Some(&'"') => {
+                            characters.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(quoted_character) => field.push(quoted_character),
+                        None => {
+                            return Err(format!(
+                                "Unterminated quoted field on row {}",
+                                rows.len() + 1
+                            ));
+                        }
+                    }
+                }
+            }
+            '"' => {
+                return Err(format!("Unexpected quote on row {}", rows.len() + 1));
+            }
+            '\r' if characters.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+                quoted = false;
+            }
+            character if character == delimiter => {
+                row.push(std::mem::take(&mut field));
+                quoted = false;
+            }
+            character if quoted => {
+                return Err(format!(
+                    "Unexpected {:?} after a quoted field on row {}",
+                    character,
+                    rows.len() + 1
+                ));
+            }
+            character => field.push(character),
+        }
+    }
+    // The last row, without the line break.
+    if quoted || !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::formatter_factory::FormatterFactory;
+    use super::super::formatter_options::FormatterOptions;
+    use super::*;
+
+    fn format_to_string(
+        formatter: &str,
+        totals: bool,
+        aggregate: &HashMap<u32, OwnerUsage>,
+    ) -> String {
+        let options = FormatterOptions::default().with_totals(totals);
+        let mut output: Vec<u8> = Vec::new();
+
+        FormatterFactory::resolve_formatter_with_options(formatter, &options)
+            .unwrap()
+            .format(aggregate, &mut output)
+            .unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_read_aggregate_round_trip() {
+        // Every part of the output, and names that need escaping or quoting.
+        let metric_names = ["video_plays", "ad \"impressions\",\n\tx"];
+        let mut aggregate: HashMap<u32, OwnerUsage> = HashMap::new();
+        let mut owner_usage =
+            OwnerUsage::from_metrics(&[(metric_names[0], u64::MAX), (metric_names[1], 2)]);
+
+        owner_usage
+            .get_entity_usage_mut("video", 2222)
+            .add_metric(metric_names[0], 3);
+        owner_usage
+            .get_entity_usage_mut("ad_unit", u32::MAX)
+            .add_metric(metric_names[1], 1);
+        owner_usage
+            .get_bucket_usage_mut("2024-05-01")
+            .add_metric(metric_names[0], 1);
+        aggregate.insert(7, owner_usage);
+        aggregate.insert(
+            u32::MAX,
+            OwnerUsage::with_metric_names(metric_names.into_iter()),
+        );
+
+        for (formatter, compact) in [("json", false), ("json", true), ("ndjson", false)] {
+            let options = FormatterOptions::default().with_compact(compact);
+            let mut output: Vec<u8> = Vec::new();
+
+            FormatterFactory::resolve_formatter_with_options(formatter, &options)
+                .unwrap()
+                .format(&aggregate, &mut output)
+                .unwrap();
+
+            assert_eq!(
+                read_aggregate(&String::from_utf8(output).unwrap()),
+                Ok(aggregate.clone()),
+                "{} {}",
+                formatter,
+                compact
+            );
+        }
+
+        // Only the owner totals fit into the csv and tsv.
+        let totals: HashMap<u32, OwnerUsage> = aggregate
+            .iter()
+            .map(|(owner_id, owner_usage)| {
+                (
+                    *owner_id,
+                    OwnerUsage::from_metrics(
+                        &owner_usage
+                            .get_metrics()
+                            .iter()
+                            .map(|(metric_name, value)| (metric_name.as_str(), *value))
+                            .collect::<Vec<(&str, u64)>>(),
+                    ),
+                )
+            })
+            .collect();
+
+        for formatter in ["csv", "tsv"] {
+            for with_totals in [false, true] {
+                let output = format_to_string(formatter, with_totals, &aggregate);
+
+                assert_eq!(read_aggregate(&output), Ok(totals.clone()), "{}", formatter);
+            }
+        }
+
+        // A missing metric is read back as 0, so the output stays the same.
+        let mut sparse: HashMap<u32, OwnerUsage> = HashMap::new();
+
+        sparse.insert(1, OwnerUsage::from_metrics(&[("video_plays", 5)]));
+        sparse.insert(2, OwnerUsage::from_metrics(&[("ad_impressions", 3)]));
+
+        let output = format_to_string("csv", true, &sparse);
+
+        assert_eq!(
+            format_to_string("csv", true, &read_aggregate(&output).unwrap()),
+            output
+        );
+
+        // Empty aggregates.
+        for formatter in ["json", "ndjson", "csv", "tsv"] {
+            assert_eq!(
+                read_aggregate(&format_to_string(formatter, true, &HashMap::new())),
+                Ok(HashMap::new()),
+                "{}",
+                formatter
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_aggregate_errors() {
+        for (content, error) in [
+            ("Owner 1: video_plays: 2", "Unknown format"),
+            ("[{\"owner_id\": 1, \"usage\": {}},", "Unexpected end"),
+            (
+                "[{\"owner_id\": -1, \"usage\": {}}]",
+                "Expected a u32 owner_id",
+            ),
+            (
+                "[{\"owner_id\": 4294967296, \"usage\": {}}]",
+                "Expected a u32 owner_id",
+            ),
+            ("[{\"owner_id\": 1}]", "Expected an object in usage"),
+            (
+                "[{\"owner_id\": 1, \"usage\": {\"v\": 1.5}}]",
+                "Expected a u64 value of v in usage",
+            ),
+            (
+                "[{\"owner_id\": 1, \"usage\": {\"v\": 1, \"v\": 2}}]",
+                "Metric v is listed more than once",
+            ),
+            (
+                "[{\"owner_id\": 1, \"usage\": {}, \"totals\": {}}]",
+                "Unknown key totals, for owner 1",
+            ),
+            (
+                "[{\"owner_id\": 1, \"usage\": {}}, {\"owner_id\": 1, \"usage\": {}}]",
+                "Owner 1 is listed more than once",
+            ),
+            (
+                "[{\"owner_id\": 1, \"usage\": {}, \"series\": [{\"bucket\": \"2024\", \"usage\": {}}, {\"bucket\": \"2024\", \"usage\": {}}]}]",
+                "Bucket 2024 is listed more than once",
+            ),
+            (
+                "{\"owner_id\": 1, \"usage\": {}}\n{\"owner_id\": 2, \"usage\": []}\n",
+                "Expected an object in usage on line 2",
+            ),
+            (
+                "owner_id,v\r\n1,2,3\r\n",
+                "Expected 2 fields, but found 3 on row 2",
+            ),
+            ("owner_id,v\r\nx,2\r\n", "Expected a u32 owner_id on row 2"),
+            (
+                "owner_id,v\r\n1,-2\r\n",
+                "Expected a u64 value of v on row 2",
+            ),
+            ("owner_id,v,v\r\n", "Metric v is listed more than once"),
+            (
+                "owner_id,v\r\n1,2\r\n1,3\r\n",
+                "Owner 1 is listed more than once",
+            ),
+            (
+                "owner_id,v\r\ntotal,2\r\n1,2\r\n",
+                "The totals row must be the last one",
+            ),
+            ("owner_id,\"v\r\n1,2\r\n", "Unterminated quoted field"),
+            ("owner_id,\"v\"x\r\n", "Unexpected 'x' after a quoted field"),
+            ("owner_id,v\"x\r\n", "Unexpected quote"),
+        ] {
+            let result = read_aggregate(content);
+
+            assert!(
+                result.as_ref().is_err_and(|reason| reason.contains(error)),
+                "{:?}: {:?}",
+                content,
+                result
+            );
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Format the aggregate usage as a delimited table (CSV or TSV), for importing into spreadsheets.
+//!
+//! The first row is the header: `owner_id`, followed by a column for every metric found in the aggregate, sorted by the metric name.
+//! Then there is one row per owner, sorted by the owner id. Metrics an owner does not have are 0.
+//! With the totals option, the last row sums every metric across all owners, with `total` in the `owner_id` column.
+//!
+//! Fields are quoted as described in RFC 4180: a field containing the delimiter, a double quote, or a line break
+//! is wrapped in double quotes, and the double quotes inside it are doubled. Rows end with CRLF.
+//! TSV uses the same quoting, with a tab as the delimiter.
+//!
+//! Only the owner totals are written. Breakdowns and time series don't fit a flat table, use the json formatter for them.
+use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+use super::formatter_trait::Formatter;
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+
+pub struct DelimitedFormatter {
+    delimiter: char,
+    identifier: &'static str,
+    totals: bool,
+}
+
+impl DelimitedFormatter {
+    /// *Create a CSV formatter (comma delimited)*
+    pub fn csv(totals: bool) -> Self {
+        Self {
+            delimiter: ',',
+            identifier: "csv",
+            totals,
+        }
+    }
+    /// *Create a TSV formatter (tab delimited)*
+    pub fn tsv(totals: bool) -> Self {
+        Self {
+            delimiter: '\t',
+            identifier: "tsv",
+            totals,
+        }
+    }
+    /// *Quote a single field, if needed (see the module docs)*
+    fn quote(&self, field: &str) -> String {
+        if field.contains([self.delimiter, '"', '\r', '\n']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
+    /// *Write a single row, quoting the fields, and end it with CRLF*
+    fn write_row(&self, writer: &mut dyn Write, fields: &[String]) -> std::io::Result<()> {
+        let row = fields
+            .iter()
+            .map(|field| self.quote(field))
+            .collect::<Vec<String>>()
+            .join(&self.delimiter.to_string());
+
+        write!(writer, "{}\r\n", row)
+    }
+}
+
+impl Formatter for DelimitedFormatter {
+    /// Stream the aggregate as a delimited table, row by row.
+    ///
+    /// ---
+    ///
+    /// For the Arguments and Example, see [`Formatter`] trait.
+    fn format(
+        &self,
+        aggregate: &HashMap<u32, OwnerUsage>,
+        writer: &mut dyn Write,
+    ) -> std::io::Result<()> {
+        let metric_names: BTreeSet<&String> = aggregate
+            .values()
+            .flat_map(|owner_usage| owner_usage.get_metrics().keys())
+            .collect();
+        let mut owner_ids: Vec<&u32> = aggregate.keys().collect();
+        // Summed as u128, so the totals of u64 metrics can't overflow.
+        let mut totals: Vec<u128> = vec![0; metric_names.len()];
+
+        owner_ids.sort();
+
+        let header: Vec<String> = std::iter::once("owner_id".to_string())
+            .chain(
+                metric_names
+                    .iter()
+                    .map(|metric_name| metric_name.to_string()),
+            )
+            .collect();
+
+        self.write_row(writer, &header)?;
+
+        for owner_id in owner_ids {
+            let owner_usage = &aggregate[owner_id];
+            let mut fields: Vec<String> = vec![owner_id.to_string()];
+
+            for (index, metric_name) in metric_names.iter().enumerate() {
+                let value = owner_usage.get_metric(metric_name);
+
+                totals[index] += value as u128;
+                fields.push(value.to_string());
+            }
+
+            self.write_row(writer, &fields)?;
+        }
+
+        if self.totals {
+            let fields: Vec<String> = std::iter::once("total".to_string())
+                .chain(totals.iter().map(|total| total.to_string()))
+                .collect();
+
+            self.write_row(writer, &fields)?;
+        }
+
+        writer.flush()
+    }
+    /// @see [`Formatter`] trait.
+    fn content_type(&self) -> &'static str {
+        if self.delimiter == '\t' {
+            "text/tab-separated-values; charset=utf-8"
+        } else {
+            "text/csv; charset=utf-8"
+        }
+    }
+    /// @see [`Formatter`] trait.
+    fn identifier(&self) -> &'static str {
+        self.identifier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_to_string(formatter: &dyn Formatter, aggregate: &HashMap<u32, OwnerUsage>) -> String {
+        let mut output: Vec<u8> = Vec::new();
+
+        formatter.format(aggregate, &mut output).unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn should_format_sorted_rows_with_totals() {
+        let mut aggregate: HashMap<u32, OwnerUsage> = HashMap::new();
+
+        aggregate.insert(
+            20,
+            OwnerUsage::from_metrics(&[("video_plays", u64::MAX), ("ad_impressions", 1)]),
+        );
+        aggregate.insert(3, OwnerUsage::from_metrics(&[("video_plays", 2)]));
+        aggregate.insert(
+            100,
+            OwnerUsage::from_metrics(&[("video_plays", 1), ("watch \"time\", s", 7)]),
+        );
+
+        assert_eq!(
+            format_to_string(&DelimitedFormatter::csv(true), &aggregate),
+            "owner_id,ad_impressions,video_plays,\"watch \"\"time\"\", s\"\r\n\
+             3,0,2,0\r\n\
+             20,1,18446744073709551615,0\r\n\
+             100,0,1,7\r\n\
+             total,1,18446744073709551618,7\r\n"
+        );
+        assert_eq!(
+            format_to_string(&DelimitedFormatter::tsv(false), &aggregate),
+            "owner_id\tad_impressions\tvideo_plays\t\"watch \"\"time\"\", s\"\r\n\
+             3\t0\t2\t0\r\n\
+             20\t1\t18446744073709551615\t0\r\n\
+             100\t0\t1\t7\r\n"
+        );
+        assert_eq!(
+            format_to_string(&DelimitedFormatter::csv(true), &HashMap::new()),
+            "owner_id\r\ntotal\r\n"
+        );
+    }
+
+    #[test]
+    fn should_quote_only_when_needed() {
+        let csv = DelimitedFormatter::csv(false);
+        let tsv = DelimitedFormatter::tsv(false);
+
+        assert_eq!(csv.quote("video_plays"), "video_plays");
+        assert_eq!(csv.quote("a,b"), "\"a,b\"");
+        assert_eq!(csv.quote("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(csv.quote("a\tb"), "a\tb");
+        assert_eq!(tsv.quote("a\tb"), "\"a\tb\"");
+        assert_eq!(tsv.quote("a,b"), "a,b");
+    }
+}
@@ -1,13 +1,27 @@
 //! A factory struct, to return a correct Formatter instance, depending on the CLI arguments.
 //!
 //! Note: Think about refactoring the Box<dyn Formatter> argument, to avoid heap usage and allocation.
+use super::delimited_formatter::DelimitedFormatter;
+use super::formatter_options::FormatterOptions;
 use super::formatter_trait::Formatter;
 use super::json_formatter::JsonFormatter;
+use super::ndjson_formatter::NdjsonFormatter;
 use super::stdout_formatter::StdoutFormatter;
 
 pub struct FormatterFactory {}
 
+/// Names of all formatters, in the order they are listed in the help text.
+const AVAILABLE_FORMATTERS: [&str; 5] = ["stdout", "json", "ndjson", "csv", "tsv"];
+
 impl FormatterFactory {
+    /// *Return the names of all formatters, that `resolve_formatter` accepts*
+    ///
+    /// ---
+    ///
+    /// When adding a formatter, add its name here too, so it shows up in the help text.
+    pub fn available_formatters() -> &'static [&'static str] {
+        &AVAILABLE_FORMATTERS
+    }
     /// *Method for resolving and returing a correct instance of Formatter.*
     ///
     /// ---
@@ -16,11 +30,34 @@ impl FormatterFactory {
     ///
     /// Note: It could return an error, indicating that the user has misspelled a formatter name or something.
     pub fn resolve_formatter(formatter_from_cli: &str) -> Result<Box<dyn Formatter>, String> {
+        Self::resolve_formatter_with_options(formatter_from_cli, &FormatterOptions::default())
+    }
+    /// *Same as `resolve_formatter`, but with the options for the formatter*
+    ///
+    /// ---
+    ///
+    /// Options a formatter does not support are ignored (for example, the totals row in the json formatter).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let formatter = FormatterFactory::resolve_formatter_with_options("csv", &FormatterOptions::default().with_totals(true))?;
+    /// ```
+    pub fn resolve_formatter_with_options(
+        formatter_from_cli: &str,
+        options: &FormatterOptions,
+    ) -> Result<Box<dyn Formatter>, String> {
         match formatter_from_cli {
-            "json" => Ok(Box::new(JsonFormatter {})),
+            "json" => Ok(Box::new(JsonFormatter::new(!options.get_compact()))),
+
+            "ndjson" => Ok(Box::new(NdjsonFormatter {})),
 
             "stdout" => Ok(Box::new(StdoutFormatter {})),
 
+            "csv" => Ok(Box::new(DelimitedFormatter::csv(options.get_totals()))),
+
+            "tsv" => Ok(Box::new(DelimitedFormatter::tsv(options.get_totals()))),
+
             unknown_formatter => Err(format!("Unknown formatter: {}", unknown_formatter)),
         }
     }
@@ -33,17 +70,42 @@ mod tests {
     #[test]
     fn should_resolve_correct_formatter() {
         assert_eq!(
-            FormatterFactory::resolve_formatter(&"json")
+            FormatterFactory::resolve_formatter("json")
                 .unwrap()
                 .identifier(),
             "json"
         );
         assert_eq!(
-            FormatterFactory::resolve_formatter(&"stdout")
+            FormatterFactory::resolve_formatter("stdout")
                 .unwrap()
                 .identifier(),
             "stdout"
         );
-        assert!(FormatterFactory::resolve_formatter(&"unknown").is_err());
+        assert_eq!(
+            FormatterFactory::resolve_formatter("ndjson")
+                .unwrap()
+                .identifier(),
+            "ndjson"
+        );
+        assert_eq!(
+            FormatterFactory::resolve_formatter("csv")
+                .unwrap()
+                .identifier(),
+            "csv"
+        );
+        assert_eq!(
+            FormatterFactory::resolve_formatter_with_options(
+                "tsv",
+                &FormatterOptions::default().with_totals(true)
+            )
+            .unwrap()
+            .identifier(),
+            "tsv"
+        );
+        assert!(FormatterFactory::resolve_formatter("unknown").is_err());
+
+        for formatter in FormatterFactory::available_formatters() {
+            assert!(FormatterFactory::resolve_formatter(formatter).is_ok());
+        }
     }
 }
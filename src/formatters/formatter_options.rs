@@ -0,0 +1,31 @@
+//! Options for the formatters, that don't change which formatter is used.
+//!
+//! When adding new formatter options, add them here, so they reach every formatter through the factory.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormatterOptions {
+    totals: bool,
+    compact: bool,
+}
+
+impl FormatterOptions {
+    /// *Set if a totals row (summing every metric across all owners) should be added, where the format supports it*
+    pub fn with_totals(mut self, totals: bool) -> Self {
+        self.totals = totals;
+
+        self
+    }
+    /// *Set if the json output should be compact (without any whitespace), instead of pretty printed*
+    pub fn with_compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+
+        self
+    }
+    /// *Return if the json output should be compact*
+    pub fn get_compact(&self) -> bool {
+        self.compact
+    }
+    /// *Return if a totals row should be added*
+    pub fn get_totals(&self) -> bool {
+        self.totals
+    }
+}
@@ -5,19 +5,24 @@
 //! When adding new formatters, they should implement the trait as well.
 use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
 use std::collections::HashMap;
+use std::io::Write;
 pub trait Formatter {
-    /// *Format the aggregate as a String*
+    /// *Format the aggregate, streaming it into a writer*
     ///
     /// ---
     ///
-    /// It is returned as a string because the caller should decide what to do with the output.
-    /// The caller can just print it to the stdout, or send a HTTP request, or something similar.
+    /// The output is written as it is produced, so a big aggregate never has to be in memory as a single string.
+    /// The caller decides where it goes: the stdout, a file, a buffer for a HTTP request, or something similar.
+    /// Only the payload is written, without any banners, so the output stays machine readable.
+    ///
+    /// The writer is flushed at the end. Any error of the writer is returned as it is.
     ///
     /// ---
     ///
     /// ## Arguments
     ///
     /// - `aggregate` - Aggregate usage data for all users
+    /// - `writer` - Where the formatted output is written to
     ///
     /// ## Example
     ///
@@ -25,9 +30,23 @@ pub trait Formatter {
     /// let formatter = FormatterFactory::resolve_formatter(&"json");
     /// let formatter = formatter.unwrap();
     ///
-    /// let result = formatter.format(&aggregate);
+    /// formatter.format(&aggregate, &mut std::io::stdout().lock())?;
+    /// ```
+    fn format(
+        &self,
+        aggregate: &HashMap<u32, OwnerUsage>,
+        writer: &mut dyn Write,
+    ) -> std::io::Result<()>;
+    /// *Return the media type of the output, for example for the Content-Type header of a webhook*
+    ///
+    /// ---
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// assert_eq!(FormatterFactory::resolve_formatter(&"json").unwrap().content_type(), "application/json");
     /// ```
-    fn format(&self, aggregate: &HashMap<u32, OwnerUsage>) -> String;
+    fn content_type(&self) -> &'static str;
     /// *Return the identifier of the formatter, as a plain string*
     ///
     /// ---
@@ -1,52 +1,225 @@
-//! Format the aggregate usage, as a JSON string.
+//! Format the aggregate usage, as JSON.
 //!
 //! Could be used to be sent to a remote server or something similar.
+//!
+//! The output is an array with an object per owner, sorted by the owner id:
+//!
+//! ```json
+//! [
+//!   {
+//!     "owner_id": 123,
+//!     "usage": {"ad_impressions": 1, "video_plays": 3},
+//!     "breakdown": {"video": [{"id": 2222, "usage": {...}}]},
+//!     "series": [{"bucket": "2024-05-01", "usage": {...}}]
+//!   }
+//! ]
+//! ```
+//!
+//! `breakdown` and `series` are only there, if the usage was grouped by entities or bucketed by time.
+//! Everything is streamed with the JSON writer (see `json_writer.rs`), pretty printed or compact.
 use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+use super::super::utils::json_writer::JsonWriter;
 use super::formatter_trait::Formatter;
 use std::collections::HashMap;
+use std::io::Write;
+
+pub struct JsonFormatter {
+    pretty: bool,
+}
 
-pub struct JsonFormatter;
+impl JsonFormatter {
+    /// *Create a JSON formatter, pretty printed or compact*
+    pub fn new(pretty: bool) -> Self {
+        Self { pretty }
+    }
+}
 
 impl Formatter for JsonFormatter {
-    /// Format the aggregate as a JSON String.
+    /// Stream the aggregate as a JSON array.
     ///
     /// ---
     ///
     /// For the Arguments and Example, see [`Formatter`] trait.
-    fn format(&self, aggregate: &HashMap<u32, OwnerUsage>) -> String {
-        let mut json_output = String::new();
-
-        json_output.push('[');
-
-        let mut iterator = aggregate.iter().peekable();
-
-        while let Some((owner_id, owner_usage_hash_map)) = iterator.next() {
-            let raw = format!(
-                r#"{{
-                "owner_id": {owner_id},
-                "usage": {{
-                    "video_plays": {},
-                    "ad_impressions": {}
-                }}
-            }}"#,
-                owner_usage_hash_map.get_video_plays(),
-                owner_usage_hash_map.get_ad_impressions(),
-            );
+    fn format(
+        &self,
+        aggregate: &HashMap<u32, OwnerUsage>,
+        writer: &mut dyn Write,
+    ) -> std::io::Result<()> {
+        let mut json_writer = JsonWriter::new(writer, self.pretty);
 
-            json_output.push_str(&raw);
+        json_writer.begin_array()?;
 
-            if iterator.peek().is_some() {
-                json_output.push(',');
-            }
+        for owner_id in sorted_owner_ids(aggregate) {
+            write_owner(&mut json_writer, owner_id, &aggregate[&owner_id])?;
         }
 
-        json_output.push(']');
-
-        json_output
+        json_writer.end_array()?;
+        json_writer.into_inner().flush()
     }
 
+    /// @see [`Formatter`] trait.
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
     /// @see [`Formatter`] trait.
     fn identifier(&self) -> &'static str {
         "json"
     }
 }
+
+/// *Return the owner ids, sorted, so the output is always the same*
+pub(super) fn sorted_owner_ids(aggregate: &HashMap<u32, OwnerUsage>) -> Vec<u32> {
+    let mut owner_ids: Vec<u32> = aggregate.keys().copied().collect();
+
+    owner_ids.sort_unstable();
+
+    owner_ids
+}
+
+/// *Write a single owner, as an object (see the module docs)*
+pub(super) fn write_owner<W: Write>(
+    json_writer: &mut JsonWriter<W>,
+    owner_id: u32,
+    owner_usage: &OwnerUsage,
+) -> std::io::Result<()> {
+    json_writer.begin_object()?;
+    json_writer.key_number("owner_id", owner_id as u64)?;
+    json_writer.key("usage")?;
+    write_metrics(json_writer, owner_usage)?;
+
+    if !owner_usage.get_breakdowns().is_empty() {
+        json_writer.key("breakdown")?;
+        json_writer.begin_object()?;
+
+        for (entity, entity_usages) in owner_usage.get_breakdowns() {
+            json_writer.key(entity)?;
+            json_writer.begin_array()?;
+
+            for (entity_id, entity_usage) in entity_usages {
+                json_writer.begin_object()?;
+                json_writer.key_number("id", *entity_id as u64)?;
+                json_writer.key("usage")?;
+                write_metrics(json_writer, entity_usage)?;
+                json_writer.end_object()?;
+            }
+
+            json_writer.end_array()?;
+        }
+
+        json_writer.end_object()?;
+    }
+
+    if !owner_usage.get_series().is_empty() {
+        json_writer.key("series")?;
+        json_writer.begin_array()?;
+
+        for (bucket, bucket_usage) in owner_usage.get_series() {
+            json_writer.begin_object()?;
+            json_writer.key_string("bucket", bucket)?;
+            json_writer.key("usage")?;
+            write_metrics(json_writer, bucket_usage)?;
+            json_writer.end_object()?;
+        }
+
+        json_writer.end_array()?;
+    }
+
+    json_writer.end_object()
+}
+
+/// *Write every metric of an usage, as an object sorted by the metric name*
+fn write_metrics<W: Write>(
+    json_writer: &mut JsonWriter<W>,
+    usage: &OwnerUsage,
+) -> std::io::Result<()> {
+    json_writer.begin_object()?;
+
+    for (metric_name, value) in usage.get_metrics() {
+        json_writer.key_number(metric_name, *value)?;
+    }
+
+    json_writer.end_object()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::utils::json_value::JsonValue;
+    use super::*;
+
+    fn format_to_string(formatter: &dyn Formatter, aggregate: &HashMap<u32, OwnerUsage>) -> String {
+        let mut output: Vec<u8> = Vec::new();
+
+        formatter.format(aggregate, &mut output).unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    /// An aggregate with every part of the output, and names that need escaping.
+    fn sample_aggregate() -> HashMap<u32, OwnerUsage> {
+        let mut aggregate: HashMap<u32, OwnerUsage> = HashMap::new();
+        let mut owner_usage =
+            OwnerUsage::from_metrics(&[("video_plays", u64::MAX), ("ad \"impressions\"\n", 2)]);
+
+        owner_usage
+            .get_entity_usage_mut("video", 2222)
+            .add_metric("video_plays", 3);
+        owner_usage
+            .get_bucket_usage_mut("2024-05-01")
+            .add_metric("video_plays", 1);
+        aggregate.insert(7, owner_usage);
+        aggregate.insert(3, OwnerUsage::from_metrics(&[("video_plays", 1)]));
+
+        aggregate
+    }
+
+    #[test]
+    fn test_json_formatter_round_trip() {
+        let aggregate = sample_aggregate();
+
+        for pretty in [true, false] {
+            let output = format_to_string(&JsonFormatter::new(pretty), &aggregate);
+            let owners = JsonValue::parse(&output).unwrap();
+            let owners = owners.as_array().unwrap();
+
+            assert_eq!(output.contains('\n'), pretty);
+            assert_eq!(owners.len(), 2);
+            assert_eq!(owners[0].get("owner_id").unwrap().as_u64(), Some(3));
+            assert_eq!(owners[0].get("breakdown"), None);
+
+            let owner = &owners[1];
+            let usage = owner.get("usage").unwrap();
+
+            assert_eq!(owner.get("owner_id").unwrap().as_u64(), Some(7));
+            assert_eq!(usage.get("video_plays").unwrap().as_u64(), Some(u64::MAX));
+            assert_eq!(usage.get("ad \"impressions\"\n").unwrap().as_u64(), Some(2));
+
+            let video = &owner
+                .get("breakdown")
+                .unwrap()
+                .get("video")
+                .unwrap()
+                .as_array()
+                .unwrap()[0];
+
+            assert_eq!(video.get("id").unwrap().as_u64(), Some(2222));
+            assert_eq!(
+                video
+                    .get("usage")
+                    .unwrap()
+                    .get("video_plays")
+                    .unwrap()
+                    .as_u64(),
+                Some(3)
+            );
+
+            let bucket = &owner.get("series").unwrap().as_array().unwrap()[0];
+
+            assert_eq!(bucket.get("bucket").unwrap().as_str(), Some("2024-05-01"));
+        }
+
+        assert_eq!(
+            format_to_string(&JsonFormatter::new(true), &HashMap::new()),
+            "[]"
+        );
+    }
+}
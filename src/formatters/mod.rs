@@ -1,5 +1,9 @@
+mod delimited_formatter;
 mod json_formatter;
+mod ndjson_formatter;
 mod stdout_formatter;
 
+pub mod aggregate_reader;
 pub mod formatter_factory;
+pub mod formatter_options;
 pub mod formatter_trait;
@@ -0,0 +1,100 @@
+//! Format the aggregate usage as newline delimited JSON (one owner per line).
+//!
+//! Every line is a compact JSON object, the same as the owner objects of the json formatter (see `json_formatter.rs`).
+//! Owners are sorted by the owner id. Every line ends with a new line character, including the last one.
+//!
+//! Useful for big aggregates, since the consumer can process it line by line, without parsing one giant array.
+use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+use super::super::utils::json_writer::JsonWriter;
+use super::formatter_trait::Formatter;
+use super::json_formatter::{sorted_owner_ids, write_owner};
+use std::collections::HashMap;
+use std::io::Write;
+
+pub struct NdjsonFormatter;
+
+impl Formatter for NdjsonFormatter {
+    /// Stream the aggregate as NDJSON, one owner per line.
+    ///
+    /// ---
+    ///
+    /// For the Arguments and Example, see [`Formatter`] trait.
+    fn format(
+        &self,
+        aggregate: &HashMap<u32, OwnerUsage>,
+        writer: &mut dyn Write,
+    ) -> std::io::Result<()> {
+        for owner_id in sorted_owner_ids(aggregate) {
+            let mut json_writer = JsonWriter::new(&mut *writer, false);
+
+            write_owner(&mut json_writer, owner_id, &aggregate[&owner_id])?;
+            writer.write_all(b"\n")?;
+        }
+
+        writer.flush()
+    }
+    /// @see [`Formatter`] trait.
+    fn content_type(&self) -> &'static str {
+        "application/x-ndjson"
+    }
+    /// @see [`Formatter`] trait.
+    fn identifier(&self) -> &'static str {
+        "ndjson"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::utils::json_value::JsonValue;
+    use super::super::json_formatter::JsonFormatter;
+    use super::*;
+
+    fn format_to_string(formatter: &dyn Formatter, aggregate: &HashMap<u32, OwnerUsage>) -> String {
+        let mut output: Vec<u8> = Vec::new();
+
+        formatter.format(aggregate, &mut output).unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_ndjson_formatter_round_trip() {
+        let mut aggregate: HashMap<u32, OwnerUsage> = HashMap::new();
+        let mut owner_usage = OwnerUsage::from_metrics(&[("video \"plays\"\n", u64::MAX)]);
+
+        owner_usage
+            .get_entity_usage_mut("video", 2222)
+            .add_metric("video \"plays\"\n", 3);
+        aggregate.insert(7, owner_usage);
+        aggregate.insert(3, OwnerUsage::from_metrics(&[("ad_impressions", 1)]));
+
+        let output = format_to_string(&NdjsonFormatter {}, &aggregate);
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert!(output.ends_with('\n'));
+        assert_eq!(lines.len(), 2);
+
+        let owners: Vec<JsonValue> = lines
+            .iter()
+            .map(|line| JsonValue::parse(line).unwrap())
+            .collect();
+
+        assert_eq!(owners[0].get("owner_id").unwrap().as_u64(), Some(3));
+        assert_eq!(owners[1].get("owner_id").unwrap().as_u64(), Some(7));
+        assert_eq!(
+            owners[1]
+                .get("usage")
+                .unwrap()
+                .get("video \"plays\"\n")
+                .unwrap()
+                .as_u64(),
+            Some(u64::MAX)
+        );
+        // Every line is the same owner object as in the json output.
+        let json_owners =
+            JsonValue::parse(&format_to_string(&JsonFormatter::new(false), &aggregate)).unwrap();
+
+        assert_eq!(json_owners.as_array().unwrap(), owners.as_slice());
+        assert_eq!(format_to_string(&NdjsonFormatter {}, &HashMap::new()), "");
+    }
+}
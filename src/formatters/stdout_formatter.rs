@@ -4,41 +4,87 @@
 use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
 use super::formatter_trait::Formatter;
 use std::collections::HashMap;
+use std::io::Write;
 
 pub struct StdoutFormatter;
 
 impl Formatter for StdoutFormatter {
-    /// Standard formating, as plain strings.
+    /// Standard formating, as plain text.
     ///
     /// ---
     ///
     /// For the Arguments and Example, see [`Formatter`] trait.
-    fn format(&self, aggregate: &HashMap<u32, OwnerUsage>) -> String {
-        let mut output = String::new();
-
-        output.push_str("---------------------------------------\n");
+    fn format(
+        &self,
+        aggregate: &HashMap<u32, OwnerUsage>,
+        writer: &mut dyn Write,
+    ) -> std::io::Result<()> {
+        writeln!(writer, "---------------------------------------")?;
 
         for (owner_id, owner_usage) in aggregate {
-            output.push_str(&format!("Owner with id: {}\n\n", owner_id));
-            output.push_str("Usage\n\n");
+            writeln!(writer, "Owner with id: {}\n", owner_id)?;
+            writeln!(writer, "Usage\n")?;
+
+            for (metric_name, value) in owner_usage.get_metrics() {
+                writeln!(writer, "  {}: {}", humanize_metric_name(metric_name), value)?;
+            }
+
+            for (entity, entity_usages) in owner_usage.get_breakdowns() {
+                writeln!(writer, "\nUsage per {}\n", entity.replace('_', " "))?;
+
+                for (entity_id, entity_usage) in entity_usages {
+                    let usage = entity_usage
+                        .get_metrics()
+                        .iter()
+                        .map(|(metric_name, value)| {
+                            format!("{}: {}", humanize_metric_name(metric_name), value)
+                        })
+                        .collect::<Vec<String>>()
+                        .join(", ");
+
+                    writeln!(writer, "  {}: {}", entity_id, usage)?;
+                }
+            }
+
+            if !owner_usage.get_series().is_empty() {
+                writeln!(writer, "\nUsage over time\n")?;
+            }
 
-            output.push_str(&format!(
-                "  Video plays: {}\n",
-                owner_usage.get_video_plays()
-            ));
+            for (bucket, bucket_usage) in owner_usage.get_series() {
+                let usage = bucket_usage
+                    .get_metrics()
+                    .iter()
+                    .map(|(metric_name, value)| {
+                        format!("{}: {}", humanize_metric_name(metric_name), value)
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ");
 
-            output.push_str(&format!(
-                "  Ad Impressions: {}\n",
-                owner_usage.get_ad_impressions()
-            ));
+                writeln!(writer, "  {}: {}", bucket, usage)?;
+            }
 
-            output.push_str("---------------------------------------\n");
+            writeln!(writer, "---------------------------------------")?;
         }
 
-        output
+        writer.flush()
+    }
+    /// @see [`Formatter`] trait.
+    fn content_type(&self) -> &'static str {
+        "text/plain; charset=utf-8"
     }
     /// @see [`Formatter`] trait.
     fn identifier(&self) -> &'static str {
         "stdout"
     }
 }
+
+/// *Turn a metric name into a readable label, for example video_plays -> Video plays*
+fn humanize_metric_name(metric_name: &str) -> String {
+    let label = metric_name.replace('_', " ");
+    let mut chars = label.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => label,
+    }
+}
@@ -0,0 +1,26 @@
+//! Library part of the usage parser.
+//!
+//! Parses all usage log files in a directory, and assembles the aggregate usage for every owner.
+//! The binary (`main.rs`) is just a thin CLI wrapper around the [`Pipeline`].
+//!
+//! ## Example
+//!
+//! ```
+//! use usage_parse::{Pipeline, PipelineConfig};
+//!
+//! let pipeline = Pipeline::new(PipelineConfig::new("logs").with_formatter("json"));
+//! let aggregate = pipeline.run()?;
+//!
+//! pipeline.format(&aggregate, &mut std::io::stdout().lock())?;
+//! ```
+pub mod arguments_lib;
+pub mod follow_lib;
+pub mod formatters;
+pub mod log_parser_lib;
+pub mod pipeline_lib;
+pub mod sinks;
+pub mod state_lib;
+pub mod utils;
+
+pub use pipeline_lib::pipeline::{Aggregate, Pipeline, PipelineConfig, run};
+pub use pipeline_lib::pipeline_error::PipelineError;
@@ -0,0 +1,300 @@
+//! Parser for web server access log lines, in the common and combined log formats.
+//!
+//! Nginx and Apache write every pixel hit as a line like this (combined format):
+//!
+//! ```text
+//! 203.0.113.7 - - [01/May/2024:13:45:10 +0000] "GET /pixel.gif?o=123&v=2222 HTTP/1.1" 200 43 "https://ref.example/" "Mozilla/5.0"
+//! ```
+//!
+//! The common format is the same, without the referrer and the user agent at the end.
+//!
+//! - Fields are separated by a single space. Quoted fields can contain spaces, and escaped quotes (`\"`).
+//! - A `-` in the referrer or the user agent means it was not sent.
+//! - The request target (the second word of the request) is what the usage is read from, in the same way as a bare URL.
+//!
+//! Only successful hits (2xx and 3xx statuses) should be counted, see [`AccessLogEntry::is_success`].
+use super::input_format_enum::InputFormat;
+use super::timestamp::parse_clf_timestamp;
+
+/// A single, parsed access log line. Borrows from the line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessLogEntry<'a> {
+    remote_addr: &'a str,
+    timestamp: i64,
+    method: &'a str,
+    target: &'a str,
+    status: u16,
+    referrer: Option<&'a str>,
+    user_agent: Option<&'a str>,
+}
+
+impl<'a> AccessLogEntry<'a> {
+    /// *Parse an access log line*
+    ///
+    /// ---
+    ///
+    /// For the common format, anything after the response size is ignored. The combined format requires the referrer and the user agent.
+    /// Returns the reason, if the line is not a valid access log line.
+    ///
+    /// ## Arguments
+    ///
+    /// - `line` - A line in a log file, with or without the trailing new line character
+    /// - `format` - Either `InputFormat::Common` or `InputFormat::Combined`
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let entry = AccessLogEntry::parse(
+    ///     r#"127.0.0.1 - - [01/May/2024:13:45:10 +0000] "GET /pixel.gif?o=1 HTTP/1.1" 200 43"#,
+    ///     InputFormat::Common,
+    /// )?;
+    ///
+    /// assert_eq!(entry.get_target(), "/pixel.gif?o=1");
+    /// ```
+    pub fn parse(line: &'a str, format: InputFormat) -> Result<Self, String> {
+        let mut cursor = Cursor {
+            rest: line.trim_end_matches(['\n', '\r']),
+        };
+
+        let remote_addr = cursor.next_word()?;
+        // Identity and the authenticated user are not used.
+        cursor.next_word()?;
+        cursor.next_word()?;
+
+        let timestamp = parse_clf_timestamp(cursor.next_delimited('[', ']')?)?;
+        let request = cursor.next_quoted()?;
+        let status_field = cursor.next_word()?;
+        let status = status_field
+            .parse::<u16>()
+            .ok()
+            .filter(|status| (100..600).contains(status))
+            .ok_or_else(|| format!("Invalid status: {}", status_field))?;
+        // Response size is not used, but it must be there.
+        cursor.next_word()?;
+
+        let (referrer, user_agent) = match format {
+            InputFormat::Combined => (
+                not_dash(cursor.next_quoted()?),
+                not_dash(cursor.next_quoted()?),
+            ),
+            _ => (None, None),
+        };
+
+        let mut request_parts = request.split(' ').filter(|part| !part.is_empty());
+        let (method, target) = match (request_parts.next(), request_parts.next()) {
+            (Some(method), Some(target)) => (method, target),
+            _ => return Err(format!("Invalid request: \"{}\"", request)),
+        };
+
+        Ok(Self {
+            remote_addr,
+            timestamp,
+            method,
+            target,
+            status,
+            referrer,
+            user_agent,
+        })
+    }
+    /// *Return the address of the client*
+    pub fn get_remote_addr(&self) -> &'a str {
+        self.remote_addr
+    }
+    /// *Return the time of the request, in seconds since the Unix epoch*
+    pub fn get_timestamp(&self) -> i64 {
+        self.timestamp
+    }
+    /// *Return the request method, for example GET*
+    pub fn get_method(&self) -> &'a str {
+        self.method
+    }
+    /// *Return the request target, for example /pixel.gif?o=123&v=2222*
+    pub fn get_target(&self) -> &'a str {
+        self.target
+    }
+    /// *Return the response status*
+    pub fn get_status(&self) -> u16 {
+        self.status
+    }
+    /// *Return the referrer, if it was sent. Escaped characters are kept as logged.*
+    pub fn get_referrer(&self) -> Option<&'a str> {
+        self.referrer
+    }
+    /// *Return the user agent, if it was sent. Escaped characters are kept as logged.*
+    pub fn get_user_agent(&self) -> Option<&'a str> {
+        self.user_agent
+    }
+    /// *Check if the hit was successful (2xx or 3xx status), so it should be counted*
+    pub fn is_success(&self) -> bool {
+        (200..400).contains(&self.status)
+    }
+}
+
+/// *Treat the `-` placeholder as a missing value*
+fn not_dash(value: &str) -> Option<&str> {
+    if value == "-" { None } else { Some(value) }
+}
+
+/// Reads the fields of an access log line, one by one.
+struct Cursor<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Cursor<'a> {
+    /// *Read the next field, up to the next space*
+    fn next_word(&mut self) -> Result<&'a str, String> {
+        self.skip_separator();
+
+        let end = self.rest.find(' ').unwrap_or(self.rest.len());
+
+        if end == 0 {
+            return Err("Unexpected end of the line".to_string());
+        }
+
+        let word = &self.rest[..end];
+
+        self.rest = &self.rest[end..];
+
+        Ok(word)
+    }
+    /// *Read the next field, enclosed by the given characters. The enclosing characters are not returned.*
+    fn next_delimited(&mut self, open: char, close: char) -> Result<&'a str, String> {
+        self.skip_separator();
+
+        if !self.rest.starts_with(open) {
+            return Err(format!("Expected '{}'", open));
+        }
+
+        let end = self.rest[1..]
+            .find(close)
+            .ok_or_else(|| format!("Expected '{}'", close))?
+            + 1;
+        let value = &self.rest[1..end];
+
+        self.rest = &self.rest[end + 1..];
+
+        Ok(value)
+    }
+    /// *Read the next quoted field, allowing escaped quotes inside. The quotes are not returned.*
+    fn next_quoted(&mut self) -> Result<&'a str, String> {
+        self.skip_separator();
+
+        if !self.rest.starts_with('"') {
+            return Err("Expected '\"'".to_string());
+        }
+
+        let bytes = self.rest.as_bytes();
+        let mut position = 1;
+
+        while position < bytes.len() {
+            match bytes[position] {
+                b'\\' => position += 2,
+                b'"' => {
+                    let value = &self.rest[1..position];
+
+                    self.rest = &self.rest[position + 1..];
+
+                    return Ok(value);
+                }
+                _ => position += 1,
+            }
+        }
+
+        Err("Unterminated quoted field".to_string())
+    }
+    fn skip_separator(&mut self) {
+        self.rest = self.rest.trim_start_matches(' ');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_combined_line() {
+        let entry = AccessLogEntry::parse(
+            "203.0.113.7 - frank [01/May/2024:15:45:10 +0200] \"GET /pixel.gif?o=123&v=2222 HTTP/1.1\" 302 43 \"https://ref.example/?q=\\\"a b\\\"\" \"Mozilla/5.0 (X11; Linux)\"\n",
+            InputFormat::Combined,
+        )
+        .unwrap();
+
+        assert_eq!(entry.get_remote_addr(), "203.0.113.7");
+        assert_eq!(entry.get_timestamp(), 1_714_571_110);
+        assert_eq!(entry.get_method(), "GET");
+        assert_eq!(entry.get_target(), "/pixel.gif?o=123&v=2222");
+        assert_eq!(entry.get_status(), 302);
+        assert!(entry.is_success());
+        assert_eq!(
+            entry.get_referrer(),
+            Some("https://ref.example/?q=\\\"a b\\\"")
+        );
+        assert_eq!(entry.get_user_agent(), Some("Mozilla/5.0 (X11; Linux)"));
+
+        let entry = AccessLogEntry::parse(
+            r#"::1 - - [01/May/2024:13:45:10 +0000] "GET /pixel.gif?o=1 HTTP/1.1" 404 0 "-" "-""#,
+            InputFormat::Combined,
+        )
+        .unwrap();
+
+        assert!(!entry.is_success());
+        assert_eq!(entry.get_referrer(), None);
+        assert_eq!(entry.get_user_agent(), None);
+    }
+
+    #[test]
+    fn test_parse_common_line_and_errors() {
+        let entry = AccessLogEntry::parse(
+            r#"127.0.0.1 - - [01/May/2024:13:45:10 +0000] "GET /pixel.gif?o=1 HTTP/1.0" 200 -"#,
+            InputFormat::Common,
+        )
+        .unwrap();
+
+        assert_eq!(entry.get_target(), "/pixel.gif?o=1");
+        assert_eq!(entry.get_referrer(), None);
+
+        let cases: Vec<(&str, InputFormat, &str)> = vec![
+            (
+                "https://www.mysite.com/pixel.gif?o=1",
+                InputFormat::Common,
+                "Unexpected end",
+            ),
+            (
+                r#"127.0.0.1 - - 01/May/2024:13:45:10 "GET / HTTP/1.1" 200 43"#,
+                InputFormat::Common,
+                "Expected '['",
+            ),
+            (
+                r#"127.0.0.1 - - [01/May/2024:13:45:10 +0000] "GET / HTTP/1.1" 200 43"#,
+                InputFormat::Combined,
+                "Expected '\"'",
+            ),
+            (
+                r#"127.0.0.1 - - [01/May/2024:13:45:10 +0000] "GET / HTTP/1.1" 2xx 43"#,
+                InputFormat::Common,
+                "Invalid status",
+            ),
+            (
+                r#"127.0.0.1 - - [01/May/2024:13:45:10 +0000] "-" 400 0"#,
+                InputFormat::Common,
+                "Invalid request",
+            ),
+            (
+                r#"127.0.0.1 - - [01/May/2024:13:45:10 +0000] "GET / HTTP/1.1 200 43"#,
+                InputFormat::Common,
+                "Unterminated",
+            ),
+            (
+                r#"127.0.0.1 - - [32/May/2024:13:45:10 +0000] "GET / HTTP/1.1" 200 43"#,
+                InputFormat::Common,
+                "Invalid access log timestamp",
+            ),
+        ];
+
+        for (line, format, expected_error) in cases {
+            let error = AccessLogEntry::parse(line, format).unwrap_err();
+
+            assert!(error.contains(expected_error), "error: {}", error);
+        }
+    }
+}
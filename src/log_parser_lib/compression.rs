@@ -0,0 +1,332 @@
+//! Transparent decompression of the log files.
+//!
+//! Rotated logs are usually compressed (`access.log.1.gz`, `access.log.2.zst`...).
+//! Instead of decompressing them to disk first, the LogParser streams every file through a decompressing reader.
+//!
+//! The compression is detected from the magic bytes at the start of the file, and if they don't match any known format, from the file extension:
+//!
+//! - gzip - `1f 8b`, or the `.gz` extension. Multiple concatenated gzip members are read as one stream.
+//! - zstd - `28 b5 2f fd`, or the `.zst` extension.
+//!
+//! Everything else is read as plain text.
+//!
+//! The standard input is given as `-`. Its compression is detected from the magic bytes only, so `cat access.log.1.gz | usage-parse --input=-` works too.
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+
+/// Path meaning the standard input, instead of a file.
+pub const STDIN_PATH: &str = "-";
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// *Detect the compression of a file, from its first bytes and its path*
+    ///
+    /// ---
+    ///
+    /// Magic bytes win over the extension, so a misnamed file is still read correctly.
+    ///
+    /// ## Arguments
+    ///
+    /// - `file_name` - Path of the file
+    /// - `head` - First bytes of the file (at least 4, if the file is that big)
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// assert_eq!(Compression::detect("log.txt", &[0x1f, 0x8b, 0x08, 0x00]), Compression::Gzip);
+    /// assert_eq!(Compression::detect("log.zst", b""), Compression::Zstd);
+    /// ```
+    pub fn detect(file_name: &str, head: &[u8]) -> Self {
+        if head.starts_with(&GZIP_MAGIC) {
+            return Compression::Gzip;
+        }
+
+        if head.starts_with(&ZSTD_MAGIC) {
+            return Compression::Zstd;
+        }
+
+        if file_name.ends_with(".gz") {
+            Compression::Gzip
+        } else if file_name.ends_with(".zst") {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// *Open a log file for reading, decompressing it if needed*
+///
+/// ---
+///
+/// The file is streamed, it is never fully loaded into memory, and nothing is written to disk.
+/// Decompression errors (for example a corrupted or truncated archive) are returned as IO errors, while reading.
+/// Empty files are read as empty, whatever the extension (log rotation often leaves empty `.gz` files behind).
+///
+/// ## Arguments
+///
+/// - `file_name` - Path of the log file, or `-` for the standard input
+///
+/// ## Example
+///
+/// ```
+/// let mut reader = open_log_reader("logs/access.log.1.gz")?;
+/// let mut line = String::new();
+///
+/// reader.read_line(&mut line)?;
+/// ```
+pub fn open_log_reader(file_name: &str) -> std::io::Result<Box<dyn BufRead + Send>> {
+    if file_name == STDIN_PATH {
+        return decompressing_reader("", BufReader::new(std::io::stdin()));
+    }
+
+    decompressing_reader(file_name, BufReader::new(File::open(file_name)?))
+}
+
+/// *Open a log file for reading, starting at a given offset*
+///
+/// ---
+///
+/// Used to continue reading a file, from where the previous run stopped.
+/// The offset is counted in the decompressed content. A plain file is simply seeked to it, while a compressed one is decompressed and skipped up to it.
+/// It is an error if the file is shorter than the offset. The standard input can only be read from the start.
+///
+/// ## Arguments
+///
+/// - `file_name` - Path of the log file
+/// - `offset` - Number of (decompressed) bytes to skip
+///
+/// ## Example
+///
+/// ```
+/// let mut reader = open_log_reader_at("logs/access.log", 1024)?;
+/// ```
+pub fn open_log_reader_at(
+    file_name: &str,
+    offset: u64,
+) -> std::io::Result<Box<dyn BufRead + Send>> {
+    if offset == 0 {
+        return open_log_reader(file_name);
+    }
+
+    if file_name == STDIN_PATH {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "The standard input can't be read from an offset",
+        ));
+    }
+
+    let mut reader = BufReader::new(File::open(file_name)?);
+    let compression = Compression::detect(file_name, reader.fill_buf()?);
+    let file_size = reader.get_ref().metadata()?.len();
+
+    if compression == Compression::None || file_size == 0 {
+        if file_size < offset {
+            return Err(shorter_than_offset_error(file_size, offset));
+        }
+
+        reader.seek(SeekFrom::Start(offset))?;
+
+        return Ok(Box::new(reader));
+    }
+
+    let mut reader = decompressing_reader(file_name, reader)?;
+    let skipped = std::io::copy(&mut (&mut reader).take(offset), &mut std::io::sink())?;
+
+    if skipped < offset {
+        return Err(shorter_than_offset_error(skipped, offset));
+    }
+
+    Ok(reader)
+}
+
+/// *Detect the compression of a log file, the same way `open_log_reader` does*
+///
+/// ---
+///
+/// Empty files are never compressed, whatever the extension.
+pub fn detect_file_compression(file_name: &str) -> std::io::Result<Compression> {
+    let mut head: Vec<u8> = Vec::with_capacity(ZSTD_MAGIC.len());
+
+    File::open(file_name)?
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut head)?;
+
+    if head.is_empty() {
+        return Ok(Compression::None);
+    }
+
+    Ok(Compression::detect(file_name, &head))
+}
+
+fn shorter_than_offset_error(size: u64, offset: u64) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        format!(
+            "The file has only {} bytes, but reading should start at {}",
+            size, offset
+        ),
+    )
+}
+
+/// *Wrap a reader into a decompressing one, depending on its first bytes and the file name*
+fn decompressing_reader<R: BufRead + Send + 'static>(
+    file_name: &str,
+    mut reader: R,
+) -> std::io::Result<Box<dyn BufRead + Send>> {
+    // Peek at the first bytes, without consuming them.
+    let head = reader.fill_buf()?;
+
+    if head.is_empty() {
+        return Ok(Box::new(reader));
+    }
+
+    let compression = Compression::detect(file_name, head);
+
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(
+            reader,
+        )?)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_detect_compression() {
+        let cases: Vec<(&str, &[u8], Compression)> = vec![
+            ("log.txt", b"https://", Compression::None),
+            ("log.txt", &[0x1f, 0x8b, 0x08], Compression::Gzip),
+            ("log.txt", &[0x28, 0xb5, 0x2f, 0xfd], Compression::Zstd),
+            ("log.gz", b"", Compression::Gzip),
+            ("log.zst", b"", Compression::Zstd),
+            // Magic bytes win over the extension.
+            ("log.gz", &[0x28, 0xb5, 0x2f, 0xfd], Compression::Zstd),
+            ("log.zst.txt", b"https://", Compression::None),
+        ];
+
+        for (file_name, head, expected) in cases {
+            assert_eq!(
+                Compression::detect(file_name, head),
+                expected,
+                "{}",
+                file_name
+            );
+        }
+    }
+
+    #[test]
+    fn test_open_log_reader() {
+        let content =
+            "https://www.mysite.com/pixel.gif?o=1&v=2\nhttps://www.mysite.com/pixel.gif?o=2&i=3\n";
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+
+        gzip.write_all(content.as_bytes()).unwrap();
+
+        let gzip = gzip.finish().unwrap();
+        // Two concatenated gzip members, like `cat a.gz b.gz` produces.
+        let gzip_members = [gzip.clone(), gzip.clone()].concat();
+        let zstd = zstd::encode_all(content.as_bytes(), 1).unwrap();
+        let cases: Vec<(&str, Vec<u8>, String)> = vec![
+            (
+                "test_compression_plain.txt",
+                content.as_bytes().to_vec(),
+                content.to_string(),
+            ),
+            ("test_compression.gz", gzip.clone(), content.to_string()),
+            (
+                "test_compression_members.gz",
+                gzip_members,
+                content.repeat(2),
+            ),
+            ("test_compression.zst", zstd.clone(), content.to_string()),
+            // Detected from the magic bytes alone.
+            ("test_compression_zstd.log", zstd, content.to_string()),
+            ("test_compression_gzip.log", gzip, content.to_string()),
+            ("test_compression_empty.gz", Vec::new(), String::new()),
+        ];
+
+        for (file_name, bytes, expected) in cases {
+            std::fs::write(file_name, bytes).unwrap();
+
+            let mut decompressed = String::new();
+            let read_result = open_log_reader(file_name)
+                .and_then(|mut reader| reader.read_to_string(&mut decompressed));
+
+            std::fs::remove_file(file_name).unwrap();
+
+            read_result.unwrap();
+
+            assert_eq!(decompressed, expected, "{}", file_name);
+        }
+    }
+
+    #[test]
+    fn test_corrupted_archive_is_an_error() {
+        let file_name = "test_compression_corrupted.gz";
+        let mut bytes = GZIP_MAGIC.to_vec();
+
+        bytes.extend_from_slice(b"not really gzip");
+        std::fs::write(file_name, bytes).unwrap();
+
+        let mut decompressed = String::new();
+        let read_result = open_log_reader(file_name)
+            .and_then(|mut reader| reader.read_to_string(&mut decompressed));
+
+        std::fs::remove_file(file_name).unwrap();
+
+        assert!(read_result.is_err());
+    }
+
+    #[test]
+    fn test_open_log_reader_at() {
+        let content =
+            "https://www.mysite.com/pixel.gif?o=1&v=2\nhttps://www.mysite.com/pixel.gif?o=2&i=3\n";
+        let zstd = zstd::encode_all(content.as_bytes(), 1).unwrap();
+        let cases: Vec<(&str, Vec<u8>)> = vec![
+            ("test_compression_at.txt", content.as_bytes().to_vec()),
+            ("test_compression_at.zst", zstd),
+        ];
+
+        for (file_name, bytes) in cases {
+            std::fs::write(file_name, bytes).unwrap();
+
+            let mut rest = String::new();
+            let read_result = open_log_reader_at(file_name, 41)
+                .and_then(|mut reader| reader.read_to_string(&mut rest));
+            let too_far_result = open_log_reader_at(file_name, 1000);
+            let compression = detect_file_compression(file_name);
+
+            std::fs::remove_file(file_name).unwrap();
+
+            read_result.unwrap();
+
+            assert_eq!(
+                rest, "https://www.mysite.com/pixel.gif?o=2&i=3\n",
+                "{}",
+                file_name
+            );
+            assert!(too_far_result.is_err(), "{}", file_name);
+            assert_eq!(
+                compression.unwrap() == Compression::None,
+                file_name.ends_with(".txt")
+            );
+        }
+
+        assert!(open_log_reader_at(STDIN_PATH, 1).is_err());
+    }
+}
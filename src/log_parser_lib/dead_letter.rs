@@ -0,0 +1,136 @@
+//! Dead-letter file, for the log lines rejected in the quarantine mode.
+//!
+//! Shared between all workers, so every write is behind a mutex. One rejected line is one line in the file:
+//!
+//! `<file>:<line_no>\t<reason>\t<original line>`
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+
+pub struct DeadLetter {
+    path: String,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl DeadLetter {
+    /// *Create (or truncate) the dead-letter file*
+    ///
+    /// ## Arguments
+    ///
+    /// - `path` - Path of the dead-letter file
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let dead_letter = DeadLetter::create("rejected.txt")?;
+    /// ```
+    pub fn create(path: &str) -> std::io::Result<Self> {
+        let file = File::create(path)?;
+
+        Ok(Self {
+            path: path.to_string(),
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+    /// *Open the dead-letter file for appending, creating it if needed*
+    ///
+    /// ## Arguments
+    ///
+    /// - `path` - Path of the dead-letter file
+    pub fn open_append(path: &str) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        Ok(Self {
+            path: path.to_string(),
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+    /// *Return the path of the dead-letter file*
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+    /// *Write a single rejected line, with the reason it was rejected*
+    ///
+    /// ---
+    ///
+    /// Tabs and new lines in the reason and the line are replaced with spaces, so every record stays on a single line.
+    ///
+    /// ## Arguments
+    ///
+    /// - `file` - Log file the line comes from
+    /// - `line_no` - 1-based number of the line in the log file
+    /// - `reason` - Why the line was rejected
+    /// - `line` - Original line
+    pub fn write(
+        &self,
+        file: &str,
+        line_no: usize,
+        reason: &str,
+        line: &str,
+    ) -> std::io::Result<()> {
+        let sanitize = |value: &str| value.trim_end().replace(['\t', '\n', '\r'], " ");
+        let mut writer = match self.writer.lock() {
+            Ok(writer) => writer,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        writeln!(
+            writer,
+            "{}:{}\t{}\t{}",
+            file,
+            line_no,
+            sanitize(reason),
+            sanitize(line)
+        )
+    }
+    /// *Flush everything written so far to the disk*
+    pub fn flush(&self) -> std::io::Result<()> {
+        match self.writer.lock() {
+            Ok(mut writer) => writer.flush(),
+            Err(poisoned) => poisoned.into_inner().flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dead_letter_write() {
+        let test_dead_letter_path = "test_dead_letter.txt";
+        let dead_letter = DeadLetter::create(test_dead_letter_path).unwrap();
+
+        dead_letter
+            .write("logs/a.txt", 3, "missing owner", "https://x/p?v=1\n")
+            .unwrap();
+        dead_letter
+            .write("logs/b.txt", 1, "bad\treason", "line\twith tab")
+            .unwrap();
+        dead_letter.flush().unwrap();
+
+        let content = std::fs::read_to_string(test_dead_letter_path).unwrap();
+        let appending_dead_letter = DeadLetter::open_append(test_dead_letter_path).unwrap();
+
+        appending_dead_letter
+            .write("logs/a.txt", 4, "missing owner", "https://x/p?v=2\n")
+            .unwrap();
+        appending_dead_letter.flush().unwrap();
+
+        let appended_content = std::fs::read_to_string(test_dead_letter_path).unwrap();
+
+        std::fs::remove_file(test_dead_letter_path).unwrap();
+
+        assert_eq!(
+            content,
+            "logs/a.txt:3\tmissing owner\thttps://x/p?v=1\nlogs/b.txt:1\tbad reason\tline with tab\n"
+        );
+        assert_eq!(
+            appended_content,
+            format!("{}logs/a.txt:4\tmissing owner\thttps://x/p?v=2\n", content)
+        );
+    }
+}
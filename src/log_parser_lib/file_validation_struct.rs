@@ -0,0 +1,118 @@
+use super::log_parser_error::LogParserError;
+use std::collections::BTreeMap;
+
+/// How many sample lines are kept for every error category, in a single file.
+pub const MAX_SAMPLE_LINES: usize = 3;
+
+/// Sample lines longer than this (in characters) are cut, so a binary file doesn't flood the report.
+const MAX_SAMPLE_LENGTH: usize = 200;
+
+/// Struct representing the data quality of a single log file, as checked by the validation (a dry run, without any aggregation).
+///
+/// Every line is either valid, filtered out (an unsuccessful access log hit), or rejected with an error.
+/// The errors are grouped by their category (see `LogParserError::get_category`), with the first few offending lines as samples.
+///
+/// Query keys that are not in the metric schema are only reported. They are ignored when parsing, so they don't make the file invalid.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FileValidation {
+    file: String,
+    total_lines: usize,
+    valid_lines: usize,
+    filtered_lines: usize,
+    errors: BTreeMap<&'static str, ErrorCategory>,
+    unknown_query_keys: BTreeMap<String, usize>,
+}
+
+/// Rejected lines of a single error category.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ErrorCategory {
+    pub lines: usize,
+    /// The first rejected lines, as `line number: reason: line`.
+    pub samples: Vec<String>,
+}
+
+impl FileValidation {
+    /// *Create an empty validation, for a given file*
+    pub fn new(file: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            ..Default::default()
+        }
+    }
+    /// *Return the file this validation is for*
+    pub fn get_file(&self) -> &str {
+        &self.file
+    }
+    /// *Return the number of lines read from the file*
+    pub fn get_total_lines(&self) -> usize {
+        self.total_lines
+    }
+    /// *Return the number of lines without any error*
+    pub fn get_valid_lines(&self) -> usize {
+        self.valid_lines
+    }
+    /// *Return the number of lines that were filtered out (not counted, but not errors either)*
+    pub fn get_filtered_lines(&self) -> usize {
+        self.filtered_lines
+    }
+    /// *Return the number of lines rejected with an error*
+    pub fn get_invalid_lines(&self) -> usize {
+        self.errors.values().map(|category| category.lines).sum()
+    }
+    /// *Return the rejected lines, by the error category*
+    pub fn get_errors(&self) -> &BTreeMap<&'static str, ErrorCategory> {
+        &self.errors
+    }
+    /// *Return the query keys that are not in the metric schema, with the number of lines they were found in*
+    pub fn get_unknown_query_keys(&self) -> &BTreeMap<String, usize> {
+        &self.unknown_query_keys
+    }
+    /// *Check if no line was rejected*
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty()
+    }
+    /// *Record a valid line*
+    pub fn add_valid_line(&mut self) {
+        self.total_lines += 1;
+        self.valid_lines += 1;
+    }
+    /// *Record a line that was filtered out*
+    pub fn add_filtered_line(&mut self) {
+        self.total_lines += 1;
+        self.filtered_lines += 1;
+    }
+    /// *Record a rejected line, under the category of its error*
+    ///
+    /// ---
+    ///
+    /// ## Arguments
+    ///
+    /// - `error` - Why the line was rejected
+    /// - `line` - The line itself, with or without the trailing new line character
+    pub fn add_invalid_line(&mut self, error: &LogParserError, line: &str) {
+        self.total_lines += 1;
+
+        let category = self.errors.entry(error.get_category()).or_default();
+
+        category.lines += 1;
+
+        if category.samples.len() < MAX_SAMPLE_LINES {
+            let line = line.trim_end();
+            let line = match line.char_indices().nth(MAX_SAMPLE_LENGTH) {
+                Some((cut_at, _)) => format!("{}...", &line[..cut_at]),
+                None => line.to_string(),
+            };
+
+            category.samples.push(format!(
+                "{}: {}: {}",
+                error.get_line_no().unwrap_or_default(),
+                error.get_reason(),
+                line
+            ));
+        }
+    }
+    /// *Record a query key that is not in the metric schema, found in a line*
+    pub fn add_unknown_query_key(&mut self, key: &str) {
+        *self.unknown_query_keys.entry(key.to_string()).or_default() += 1;
+    }
+}
@@ -0,0 +1,53 @@
+//! Enum to represent the format of the log lines.
+//!
+//! By default, every line is a bare pixel URL (optionally prefixed with a timestamp).
+//! In production, the pixel hits usually arrive as web server access log lines instead (see `access_log.rs`).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum InputFormat {
+    /// A bare URL, optionally prefixed with an RFC 3339 timestamp.
+    #[default]
+    Url,
+    /// Common log format: `host ident user [date] "request" status bytes`.
+    Common,
+    /// Combined log format (Nginx and Apache default): the common format, followed by `"referrer" "user agent"`.
+    Combined,
+}
+
+impl InputFormat {
+    /// *Resolve the input format from its name, as given on the command line*
+    ///
+    /// ## Arguments
+    ///
+    /// - `name` - One of url | common | combined
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// assert_eq!(InputFormat::resolve("combined"), Ok(InputFormat::Combined));
+    /// ```
+    pub fn resolve(name: &str) -> Result<Self, String> {
+        match name {
+            "url" => Ok(InputFormat::Url),
+            "common" => Ok(InputFormat::Common),
+            "combined" => Ok(InputFormat::Combined),
+            unknown_format => Err(format!(
+                "Unknown input format: {}. Available formats: url, common, combined",
+                unknown_format
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_resolve_input_formats() {
+        assert_eq!(InputFormat::resolve("url"), Ok(InputFormat::Url));
+        assert_eq!(InputFormat::resolve("common"), Ok(InputFormat::Common));
+        assert_eq!(InputFormat::resolve("combined"), Ok(InputFormat::Combined));
+        assert!(InputFormat::resolve("nginx").is_err());
+        assert_eq!(InputFormat::default(), InputFormat::Url);
+    }
+}
@@ -2,22 +2,52 @@
 //!
 //! Every line has this format : https://www.mysite.com/pixel.gif?o=123&v=2222&i=555
 //!
-//! All parameters must be valid integers.
+//! Optionally, prefixed with an RFC 3339 timestamp : 2024-05-01T13:45:10Z https://www.mysite.com/pixel.gif?o=123&v=2222&i=555
+//!
+//! With the `common` or `combined` input format, every line is an access log line instead (see `access_log.rs`).
+//! The usage is read from the request target, and its timestamp is used for the time buckets and the time window.
+//! Only successful hits (2xx and 3xx statuses) are counted, all others are filtered out.
+//!
+//! Which params are counted, and how, is decided by the metric schema in the parse options (see `metric_schema.rs`).
+//! All counted parameters must be valid integers.
 //! Owner must exist in the URL.
 //! Other parameters are optional, and multiple of them can appear at the same time, or just one of them.
-//! In the future, consider returning the ids of the entities associated with this events (like player id, ad unit id, video id etc.)
+//! Params that are not in the schema are ignored.
+//! The usage can also be broken down by the ids of the entities associated with this events (like video id, ad unit id etc.), see `group_by` in the parse options.
+//! In that case, every line adds its metrics both to the owner, and to every entity found in the line.
+//!
+//! With a time bucket in the parse options, timestamped lines also add their metrics to the bucket of the owner's time series.
+//! Lines without a timestamp are still counted in the owner totals, but not in any bucket.
+//! With `since` / `until`, only lines with a timestamp inside the window are counted. All other lines are filtered out (not an error).
 //!
-//! For now, this struct only records how many times an event happend ( like video plays for example .)
+//! Log files compressed with gzip or zstd are read directly, see `compression.rs`.
+//!
+//! What happens with malformed lines is decided by the on-error policy in the parse options.
+//! By default, the first malformed line fails the whole file.
+//! Overflow is handled by the overflow policy instead (see `overflow_policy_enum.rs`). By default, it fails the whole file, regardless of the on-error policy.
+//!
+//! A file can also be only validated (see `validate`). Every line then goes through the same checks, but nothing is aggregated,
+//! and all malformed lines are collected into a data quality report, instead of stopping at the first one.
 use std::collections::HashMap;
 use std::io::BufRead;
 
+use super::access_log::AccessLogEntry;
+use super::compression::open_log_reader_at;
+use super::file_validation_struct::FileValidation;
+use super::input_format_enum::InputFormat;
 use super::log_parser_error::LogParserError;
+use super::metric_schema::{AggregationKind, MetricDefinition};
+use super::on_error_policy_enum::OnErrorPolicy;
+use super::overflow_policy_enum::OverflowPolicy;
 use super::owner_usage_struct::OwnerUsage;
-use super::query_string_params_enum::QueryStringParameters;
-use super::utils::{get_query_string, get_query_string_parameter_value};
+use super::parse_options::ParseOptions;
+use super::parse_report_struct::LogParseReport;
+use super::query_string::{QueryString, get_query_string};
+use super::timestamp::split_leading_timestamp;
 
 pub struct LogParser<'a> {
     file_name: &'a str,
+    options: ParseOptions,
 }
 
 impl<'a> LogParser<'a> {
@@ -35,74 +65,344 @@ impl<'a> LogParser<'a> {
     /// let log_parser_instance = LogParser::new(&log_file_path);
     /// ```
     pub fn new(file_name: &'a str) -> Self {
-        Self { file_name }
+        Self {
+            file_name,
+            options: ParseOptions::default(),
+        }
     }
-    /// *For a given usage param, increase the usage by 1.*
+    /// *Construct a new struct, with a given file_name and parse options*
     ///
     /// ---
-    /// Every param is found in every log line at most once. So it makes sense to just increment the fields.
     ///
-    /// Note: The method could return None, indicating that it could add to an existing usage param (Overflow happened for example).
-    /// So make sure to check for the None variant.
+    /// ## Arguments
+    /// - `file_name` - Full path to a given log file
+    /// - `options` - Options, such as the on-error policy
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let options = ParseOptions::default().with_on_error(OnErrorPolicy::Skip);
+    /// let log_parser_instance = LogParser::with_options("log_file.txt", options);
+    /// ```
+    pub fn with_options(file_name: &'a str, options: ParseOptions) -> Self {
+        Self { file_name, options }
+    }
+    /// *Handle a line that could not be parsed, depending on the on-error policy*
+    ///
+    /// ---
+    ///
+    /// Returns the error back if the parsing should stop. Overflow always stops the parsing.
+    fn reject_line(
+        &self,
+        error: LogParserError,
+        line: &str,
+        report: &mut LogParseReport,
+    ) -> Result<(), LogParserError> {
+        if matches!(error, LogParserError::Overflow { .. }) {
+            return Err(error);
+        }
+
+        match self.options.get_on_error() {
+            OnErrorPolicy::Abort => Err(error),
+            OnErrorPolicy::Skip => {
+                report.add_skipped_line();
+
+                Ok(())
+            }
+            OnErrorPolicy::Quarantine => {
+                report.add_skipped_line();
+
+                if let Some(dead_letter) = self.options.get_dead_letter() {
+                    let line_no = error.get_line_no().unwrap_or_default();
+
+                    if let Err(io_error) =
+                        dead_letter.write(self.file_name, line_no, &error.get_reason(), line)
+                    {
+                        return Err(LogParserError::DeadLetter {
+                            file: dead_letter.get_path().to_string(),
+                            error: io_error,
+                        });
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+    /// *Create the error for a line that is not valid UTF-8*
+    fn invalid_utf8_error(&self, line_no: usize, error: std::str::Utf8Error) -> LogParserError {
+        LogParserError::InvalidUtf8 {
+            file: self.file_name.to_string(),
+            line_no,
+            error,
+        }
+    }
+    /// *For a given metric, add the value from the query string to the usage.*
+    ///
+    /// ---
+    /// Every param is found in every log line at most once (the first occurrence wins).
+    /// A `count` metric is increased by 1, and a `sum` metric is increased by the value itself.
+    ///
+    /// Note: The method could return an error, indicating that it could not add to an existing usage metric (Overflow happened for example).
+    /// So make sure to check for the Err variant. That only happens with the `error` overflow policy.
+    /// With `wrap-with-warning`, a warning is added for every wrap.
     ///
     /// ---
     ///
     /// ## Arguments
     ///
     /// - `owner_usage` - A struct holding the current usage for a given owner
-    /// - `query_string` - Query string extracted from a log line
-    /// - `parameter` - Usage parameter as a single char (v | i | p | o etc etc)
-    /// - `map_field` - Which field on the usage struct to increment.
+    /// - `owner_id` - Id of the owner, used for error reporting
+    /// - `metric` - Metric definition from the schema
+    /// - `value` - Already validated value of the metric query key
+    /// - `line_no` - 1-based number of the line, used for error reporting
+    /// - `warnings` - Warnings about the metrics that wrapped around, so far
     ///
     /// # Example
     ///
     /// ```
-    /// let test_log_file = "log.txt";
+    /// let log_parser = LogParser::new("log.txt");
     /// let mut owner_usage_hash_map = OwnerUsage::default();
-    /// let query_string = "o=111&v=222&i=333";
-    /// let log_parser = LogParser::new(test_log_file);
+    /// let schema = MetricSchema::default();
+    /// let mut warnings = Vec::new();
     ///
-    /// log_parser.increment_hash_map_field(
-    ///     &mut owner_usage_hash_map,
-    ///     &query_string,
-    ///     &QueryStringParameters::resolve_query_string_parameter(&QueryStringParameters::VideoId),
-    ///     "video_plays",
-    /// ).unwrap();
+    /// log_parser.increment_hash_map_field(&mut owner_usage_hash_map, 111, &schema.get_metrics()[0], 222, 1, &mut warnings).unwrap();
     /// ```
     fn increment_hash_map_field(
         &self,
         owner_usage: &mut OwnerUsage,
-        query_string: &str,
-        parameter: &char,
-        map_field: &str,
-    ) -> Option<()> {
-        // It's ok if the parameter is missing. Not every single log line has to contain every parameter.
-        if let Some(value) = get_query_string_parameter_value(query_string, parameter) {
-            // At this point, the method could error.
-            // Parse error should definitely be signaled.
-            if value.parse::<u32>().is_ok() {
-                // map_field must be a valid one, and the addition must succeed.
-                let add_result = match map_field {
-                    "video_plays" => owner_usage.add_video_plays(1),
-                    "ad_impressions" => owner_usage.add_ad_impressions(1),
-                    _ => None,
-                };
-
-                return match add_result {
-                    Some(_) => Some(()),
-                    None => None,
-                };
-            } else {
-                return None;
+        owner_id: u32,
+        metric: &MetricDefinition,
+        value: u64,
+        line_no: usize,
+        warnings: &mut Vec<String>,
+    ) -> Result<(), LogParserError> {
+        let param = match metric.get_kind() {
+            AggregationKind::Count => 1,
+            AggregationKind::Sum => value,
+        };
+        let overflow = LogParserError::Overflow {
+            file: self.file_name.to_string(),
+            line_no,
+            owner: owner_id,
+            metric: metric.get_metric_name().to_string(),
+        };
+
+        match owner_usage.add_metric_with_policy(
+            metric.get_metric_name(),
+            param,
+            self.options.get_overflow_policy(),
+        ) {
+            None => return Err(overflow),
+            Some(true) if self.options.get_overflow_policy() == OverflowPolicy::WrapWithWarning => {
+                warnings.push(format!("{}, the value wrapped around", overflow));
             }
+            Some(_) => {}
         }
 
-        Some(())
+        Ok(())
+    }
+    /// *Parse a query string param as an integer (an id, or a metric value), signaling the error with the file and line*
+    fn parse_param_value<T: std::str::FromStr<Err = std::num::ParseIntError>>(
+        &self,
+        key: &str,
+        value: &str,
+        line_no: usize,
+    ) -> Result<T, LogParserError> {
+        value
+            .parse::<T>()
+            .map_err(|error| LogParserError::InvalidParam {
+                file: self.file_name.to_string(),
+                line_no,
+                key: key.to_string(),
+                value: value.to_string(),
+                error,
+            })
+    }
+    /// *Split a log line into its timestamp (if any) and the URL, depending on the input format*
+    ///
+    /// ---
+    ///
+    /// Returns None for an unsuccessful access log hit, which is filtered out.
+    fn split_line<'l>(
+        &self,
+        line: &'l str,
+        line_no: usize,
+    ) -> Result<Option<(Option<i64>, &'l str)>, LogParserError> {
+        match self.options.get_input_format() {
+            // Timestamp is optional, but if the line starts with one, it must be valid.
+            InputFormat::Url => split_leading_timestamp(line).map(Some).map_err(|reason| {
+                LogParserError::InvalidTimestamp {
+                    file: self.file_name.to_string(),
+                    line_no,
+                    reason,
+                }
+            }),
+            access_log_format => {
+                let entry = AccessLogEntry::parse(line, access_log_format).map_err(|reason| {
+                    LogParserError::InvalidAccessLog {
+                        file: self.file_name.to_string(),
+                        line_no,
+                        reason,
+                    }
+                })?;
+
+                if !entry.is_success() {
+                    return Ok(None);
+                }
+
+                Ok(Some((Some(entry.get_timestamp()), entry.get_target())))
+            }
+        }
+    }
+    /// *Parse a single log line, and add its usage to the output*
+    ///
+    /// ---
+    ///
+    /// ## Arguments
+    ///
+    /// - `line` - A single line from the log file, with or without the trailing new line character
+    /// - `line_no` - 1-based number of the line, used for error reporting
+    /// - `output` - Usage for all owners, parsed so far
+    /// - `warnings` - Warnings about the metrics that wrapped around, so far
+    ///
+    /// Returns false, if the line was filtered out (outside of the time window, or an unsuccessful hit), and was not counted.
+    fn parse_line(
+        &self,
+        line: &str,
+        line_no: usize,
+        output: &mut HashMap<u32, OwnerUsage>,
+        warnings: &mut Vec<String>,
+    ) -> Result<bool, LogParserError> {
+        let schema = self.options.get_schema();
+        let Some((timestamp, url)) = self.split_line(line, line_no)? else {
+            return Ok(false);
+        };
+
+        if !self.options.is_in_time_window(timestamp) {
+            return Ok(false);
+        }
+
+        // Query string must exists (everyting after the >>> ? <<< character in the string)
+        let query_string = match get_query_string(url) {
+            Some(query_string) => QueryString::parse(query_string),
+            None => {
+                return Err(LogParserError::MissingQueryString {
+                    file: self.file_name.to_string(),
+                    line_no,
+                    line: line.trim_end().to_string(),
+                });
+            }
+        };
+        // Owner is required, and must be parsed properly.
+        let owner_id = match query_string.get(schema.get_owner_key()) {
+            Some(owner_id) => self.parse_param_value(schema.get_owner_key(), owner_id, line_no)?,
+            None => {
+                return Err(LogParserError::MissingOwner {
+                    file: self.file_name.to_string(),
+                    line_no,
+                    line: line.trim_end().to_string(),
+                });
+            }
+        };
+        // Validate every metric param first, so a rejected line never leaves partial usage behind.
+        // It's ok if a param is missing. Not every single log line has to contain every param.
+        let mut metric_values: Vec<(&MetricDefinition, u64)> = Vec::new();
+
+        for metric in schema.get_metrics() {
+            if let Some(value) = query_string.get(metric.get_query_key()) {
+                metric_values.push((
+                    metric,
+                    self.parse_param_value(metric.get_query_key(), value, line_no)?,
+                ));
+            }
+        }
+
+        // Ids of the entities, the usage is broken down by (only those present in the line).
+        let mut entity_ids: Vec<(&str, u32)> = Vec::new();
+
+        for entity_name in self.options.get_group_by() {
+            if let Some(entity) = schema.get_entity(entity_name)
+                && let Some(entity_id) = query_string.get(entity.get_query_key())
+            {
+                entity_ids.push((
+                    entity.get_name(),
+                    self.parse_param_value(entity.get_query_key(), entity_id, line_no)?,
+                ));
+            }
+        }
+
+        let new_usage = || {
+            OwnerUsage::with_metric_names(
+                schema
+                    .get_metrics()
+                    .iter()
+                    .map(|metric| metric.get_metric_name()),
+            )
+        };
+        let owner_usage_instance = output.entry(owner_id).or_insert_with(new_usage);
+
+        for (metric, value) in &metric_values {
+            self.increment_hash_map_field(
+                owner_usage_instance,
+                owner_id,
+                metric,
+                *value,
+                line_no,
+                warnings,
+            )?;
+        }
+
+        for (entity_name, entity_id) in entity_ids {
+            let entity_usage = owner_usage_instance.get_entity_usage_mut(entity_name, entity_id);
+
+            if entity_usage.get_metrics().is_empty() {
+                *entity_usage = new_usage();
+            }
+
+            for (metric, value) in &metric_values {
+                self.increment_hash_map_field(
+                    entity_usage,
+                    owner_id,
+                    metric,
+                    *value,
+                    line_no,
+                    warnings,
+                )?;
+            }
+        }
+
+        if let Some(bucket) = self.options.get_bucket()
+            && let Some(timestamp) = timestamp
+        {
+            let bucket_usage =
+                owner_usage_instance.get_bucket_usage_mut(&bucket.get_label(timestamp));
+
+            if bucket_usage.get_metrics().is_empty() {
+                *bucket_usage = new_usage();
+            }
+
+            for (metric, value) in &metric_values {
+                self.increment_hash_map_field(
+                    bucket_usage,
+                    owner_id,
+                    metric,
+                    *value,
+                    line_no,
+                    warnings,
+                )?;
+            }
+        }
+
+        Ok(true)
     }
     /// *Start the parsing process for a given log file*
     ///
     /// ---
     ///
+    /// Every error carries the file path and the line number where it happened.
+    /// Malformed lines are handled depending on the on-error policy. The returned report says how many of them were skipped.
+    ///
     /// ## Arguments
     ///
     /// Method takes no arguments. All parameters are provided in the constructor
@@ -117,95 +417,224 @@ impl<'a> LogParser<'a> {
     ///
     /// assert!(parse_result.is_ok());
     ///
-    /// let owner_usage_hash_map: std::collections::HashMap<u32, OwnerUsage> = parse_result.unwrap();
+    /// let owner_usage_hash_map = parse_result.unwrap().take_usage();
     /// ```
-    pub fn parse(&self) -> Result<HashMap<u32, OwnerUsage>, LogParserError> {
-        let file = match std::fs::File::open(self.file_name) {
-            Ok(file) => file,
+    pub fn parse(&self) -> Result<LogParseReport, LogParserError> {
+        // Compressed files (gzip, zstd) are decompressed on the fly.
+        let reader = match open_log_reader_at(
+            self.file_name,
+            self.options.get_start_offset(self.file_name),
+        ) {
+            Ok(reader) => reader,
             Err(error) => {
-                return Err(LogParserError::Io(error));
+                return Err(LogParserError::Io {
+                    file: self.file_name.to_string(),
+                    error,
+                });
             }
         };
 
-        let mut reader = std::io::BufReader::new(file);
-        let mut line_string = String::new();
-        let mut output: HashMap<u32, OwnerUsage> = HashMap::new();
+        self.parse_reader(reader)
+    }
+    /// *Parse the log lines from any reader, instead of opening the file*
+    ///
+    /// ---
+    ///
+    /// The file name from the constructor is still used in the report and in the errors.
+    /// The reader is used as is, nothing is decompressed. It should already be at the start offset of the file (if any, see the parse options).
+    ///
+    /// With `complete_lines_only` in the parse options, a last line without the new line character is left for the next run.
+    /// It is not counted, and the offset in the report stays before it.
+    /// Line numbers are counted from the start offset.
+    ///
+    /// ## Arguments
+    ///
+    /// - `reader` - Reader of the log lines, for example the standard input or an in-memory buffer
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let log_parser = LogParser::new("memory");
+    /// let report = log_parser.parse_reader("https://www.mysite.com/pixel.gif?o=1&v=2\n".as_bytes())?;
+    /// ```
+    pub fn parse_reader<R: BufRead>(
+        &self,
+        mut reader: R,
+    ) -> Result<LogParseReport, LogParserError> {
+        let mut line_bytes: Vec<u8> = Vec::new();
+        let mut report = LogParseReport::new(self.file_name);
+        let mut overflow_warnings: Vec<String> = Vec::new();
+
+        report.set_offset(self.options.get_start_offset(self.file_name));
 
         loop {
-            let read_result = reader.read_line(&mut line_string);
+            // Read as bytes, so a line that is not valid UTF-8 is rejected like any other malformed line.
+            let read_result = reader.read_until(b'\n', &mut line_bytes);
 
             match read_result {
                 Ok(line_size) => {
                     if line_size == 0 {
                         break;
                     }
-                    // Query string must exists (everyting after the >>> ? <<< character in the string)
-                    let query_string = match get_query_string(&line_string) {
-                        Some(query_string) => query_string,
-                        None => {
-                            return Err(LogParserError::Custom(
-                                "No query string found!".to_string(),
-                            ));
-                        }
-                    };
-                    // Owner is required, and must be parsed properly.
-                    let owner_id = match get_query_string_parameter_value(query_string, &'o') {
-                        Some(owner_id) => match owner_id.parse::<u32>() {
-                            Ok(owner_id) => owner_id,
-                            Err(error) => return Err(LogParserError::ParseIntError(error)),
+                    // The line could still be being written.
+                    if self.options.get_complete_lines_only() && !line_bytes.ends_with(b"\n") {
+                        break;
+                    }
+
+                    report.add_line();
+
+                    let line_no = report.get_total_lines();
+
+                    match std::str::from_utf8(&line_bytes) {
+                        Ok(line_string) => match self.parse_line(
+                            line_string,
+                            line_no,
+                            report.get_usage_mut(),
+                            &mut overflow_warnings,
+                        ) {
+                            Ok(true) => {}
+                            Ok(false) => report.add_filtered_line(),
+                            Err(error) => self.reject_line(error, line_string, &mut report)?,
                         },
-                        None => {
-                            return Err(LogParserError::Custom(
-                                "No owner id found in the query string!".to_string(),
-                            ));
-                        }
-                    };
-
-                    let owner_usage_instance = output.entry(owner_id).or_default();
-
-                    if self
-                        .increment_hash_map_field(
-                            owner_usage_instance,
-                            query_string,
-                            &QueryStringParameters::resolve_query_string_parameter(
-                                &QueryStringParameters::VideoId,
-                            ),
-                            "video_plays",
-                        )
-                        .is_none()
-                    {
-                        return Err(LogParserError::Custom("Failed to add to the video_plays! Possible overflow situation, or a parse error".to_string()));
+                        Err(error) => self.reject_line(
+                            self.invalid_utf8_error(line_no, error),
+                            &String::from_utf8_lossy(&line_bytes),
+                            &mut report,
+                        )?,
                     }
 
-                    if self
-                        .increment_hash_map_field(
-                            owner_usage_instance,
-                            query_string,
-                            &QueryStringParameters::resolve_query_string_parameter(
-                                &QueryStringParameters::AdUnitId,
-                            ),
-                            "ad_impressions",
-                        )
-                        .is_none()
-                    {
-                        return Err(LogParserError::Custom("Failed to add to the ad_impressions! Possible overflow situation, or a parse error".to_string()));
+                    for warning in overflow_warnings.drain(..) {
+                        report.add_overflow_warning(warning);
                     }
 
-                    line_string.clear();
+                    report.advance_offset(line_size);
+                    line_bytes.clear();
                 }
 
                 Err(error) => {
-                    return Err(LogParserError::Io(error));
+                    return Err(LogParserError::Io {
+                        file: self.file_name.to_string(),
+                        error,
+                    });
+                }
+            }
+        }
+
+        Ok(report)
+    }
+    /// *Check every line of the log file, without aggregating the usage (a dry run)*
+    ///
+    /// ---
+    ///
+    /// Every line goes through the same checks as in `parse`, but the errors don't stop the validation, regardless of the on-error policy.
+    /// They are collected into the returned validation instead. Only an IO error stops it.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let file_validation = LogParser::new("log.txt").validate()?;
+    ///
+    /// println!("{} of {} lines are valid", file_validation.get_valid_lines(), file_validation.get_total_lines());
+    /// ```
+    pub fn validate(&self) -> Result<FileValidation, LogParserError> {
+        let reader = open_log_reader_at(
+            self.file_name,
+            self.options.get_start_offset(self.file_name),
+        )
+        .map_err(|error| LogParserError::Io {
+            file: self.file_name.to_string(),
+            error,
+        })?;
+
+        self.validate_reader(reader)
+    }
+    /// *Same as `validate`, but the log lines are read from any reader, instead of opening the file*
+    ///
+    /// ## Arguments
+    ///
+    /// - `reader` - Reader of the log lines, for example the standard input or an in-memory buffer
+    pub fn validate_reader<R: BufRead>(
+        &self,
+        mut reader: R,
+    ) -> Result<FileValidation, LogParserError> {
+        let mut line_bytes: Vec<u8> = Vec::new();
+        let mut file_validation = FileValidation::new(self.file_name);
+        // Every line is parsed on its own, so nothing is aggregated, and nothing can overflow.
+        let mut line_usage: HashMap<u32, OwnerUsage> = HashMap::new();
+        let mut overflow_warnings: Vec<String> = Vec::new();
+
+        loop {
+            let line_size =
+                reader
+                    .read_until(b'\n', &mut line_bytes)
+                    .map_err(|error| LogParserError::Io {
+                        file: self.file_name.to_string(),
+                        error,
+                    })?;
+
+            if line_size == 0 {
+                break;
+            }
+
+            let line_no = file_validation.get_total_lines() + 1;
+
+            match std::str::from_utf8(&line_bytes) {
+                Ok(line_string) => {
+                    match self.parse_line(
+                        line_string,
+                        line_no,
+                        &mut line_usage,
+                        &mut overflow_warnings,
+                    ) {
+                        Ok(true) => file_validation.add_valid_line(),
+                        Ok(false) => file_validation.add_filtered_line(),
+                        Err(error) => file_validation.add_invalid_line(&error, line_string),
+                    }
+
+                    for key in self.find_unknown_query_keys(line_string, line_no) {
+                        file_validation.add_unknown_query_key(&key);
+                    }
                 }
+                Err(error) => file_validation.add_invalid_line(
+                    &self.invalid_utf8_error(line_no, error),
+                    &String::from_utf8_lossy(&line_bytes),
+                ),
             }
+
+            line_usage.clear();
+            overflow_warnings.clear();
+            line_bytes.clear();
         }
 
-        Ok(output)
+        Ok(file_validation)
+    }
+    /// *Return the query keys of the line, which are not in the metric schema (every key once)*
+    fn find_unknown_query_keys(&self, line: &str, line_no: usize) -> Vec<String> {
+        let Ok(Some((_, url))) = self.split_line(line, line_no) else {
+            return Vec::new();
+        };
+        let Some(query_string) = get_query_string(url) else {
+            return Vec::new();
+        };
+        let mut unknown_keys: Vec<String> = Vec::new();
+
+        for (key, _) in QueryString::parse(query_string).iter() {
+            if !self.options.get_schema().is_known_query_key(key)
+                && !unknown_keys.iter().any(|unknown_key| unknown_key == key)
+            {
+                unknown_keys.push(key.to_string());
+            }
+        }
+
+        unknown_keys
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::metric_schema::MetricSchema;
+    use super::super::time_bucket_enum::TimeBucket;
+    use super::super::timestamp::parse_time_bound;
     use super::*;
     use std::io::Write;
 
@@ -221,25 +650,36 @@ https://www.mysite.com/pixel.gif?o=444&v=1111&i=4444
 
         file_handle.write_all(log_lines.as_bytes()).unwrap();
 
-        let log_parser = LogParser::new(&test_log_path);
+        let log_parser = LogParser::new(test_log_path);
         let owner_usage_hash_map = log_parser.parse();
 
         std::fs::remove_file(test_log_path).unwrap();
 
-        assert!(!owner_usage_hash_map.is_err());
+        assert!(owner_usage_hash_map.is_ok());
+
+        let mut report = owner_usage_hash_map.unwrap();
+
+        assert_eq!(report.get_total_lines(), 4);
+        assert_eq!(report.get_skipped_lines(), 0);
 
-        let owner_usage_hash_map = owner_usage_hash_map.unwrap();
+        let owner_usage_hash_map = report.take_usage();
 
         // Check first Owner
         let owner_usage_for_owner_123 = owner_usage_hash_map.get(&123).unwrap();
 
-        assert_eq!(owner_usage_for_owner_123.get_video_plays(), 3);
-        assert_eq!(owner_usage_for_owner_123.get_ad_impressions(), 1);
+        assert_eq!(owner_usage_for_owner_123.get_metric("video_plays"), 3);
+        assert_eq!(owner_usage_for_owner_123.get_metric("ad_impressions"), 1);
         // Check second owner
         let owner_usage_hash_map_for_owner_444 = owner_usage_hash_map.get(&444).unwrap();
 
-        assert_eq!(owner_usage_hash_map_for_owner_444.get_video_plays(), 1);
-        assert_eq!(owner_usage_hash_map_for_owner_444.get_ad_impressions(), 1);
+        assert_eq!(
+            owner_usage_hash_map_for_owner_444.get_metric("video_plays"),
+            1
+        );
+        assert_eq!(
+            owner_usage_hash_map_for_owner_444.get_metric("ad_impressions"),
+            1
+        );
     }
 
     #[test]
@@ -247,101 +687,705 @@ https://www.mysite.com/pixel.gif?o=444&v=1111&i=4444
         let test_log_file = "not_exist_log.txt";
         let log_parser = LogParser::new(test_log_file);
         let mut owner_usage_hash_map = OwnerUsage::default();
-        let query_string = "o=111&v=222&i=333";
+        let mut warnings: Vec<String> = Vec::new();
+        let schema = MetricSchema::new("o")
+            .with_metric("v", "video_plays", AggregationKind::Count)
+            .unwrap()
+            .with_metric("d", "watch_seconds", AggregationKind::Sum)
+            .unwrap();
+        let video_plays = &schema.get_metrics()[0];
+        let watch_seconds = &schema.get_metrics()[1];
+
+        for (metric, value, line_no) in [
+            (video_plays, 222, 1),
+            (video_plays, 333, 2),
+            (watch_seconds, 30, 2),
+            (watch_seconds, 12, 3),
+        ] {
+            log_parser
+                .increment_hash_map_field(
+                    &mut owner_usage_hash_map,
+                    111,
+                    metric,
+                    value,
+                    line_no,
+                    &mut warnings,
+                )
+                .unwrap();
+        }
+
+        // Count metrics are increased by 1, and sum metrics by the value itself.
+        assert_eq!(owner_usage_hash_map.get_metric("video_plays"), 2);
+        assert_eq!(owner_usage_hash_map.get_metric("watch_seconds"), 42);
+        // Test the overflow
+        let mut owner_usage_hash_map =
+            OwnerUsage::from_metrics(&[("video_plays", u64::MAX), ("watch_seconds", 1)]);
+
+        let increment_result = log_parser.increment_hash_map_field(
+            &mut owner_usage_hash_map,
+            111,
+            video_plays,
+            222,
+            5,
+            &mut warnings,
+        );
 
-        log_parser
+        assert!(matches!(
+            increment_result,
+            Err(LogParserError::Overflow { owner: 111, line_no: 5, ref metric, .. }) if metric == "video_plays"
+        ));
+
+        let increment_result = log_parser.increment_hash_map_field(
+            &mut owner_usage_hash_map,
+            111,
+            watch_seconds,
+            u64::MAX,
+            6,
+            &mut warnings,
+        );
+
+        assert!(matches!(
+            increment_result,
+            Err(LogParserError::Overflow {
+                owner: 111,
+                line_no: 6,
+                ..
+            })
+        ));
+        assert!(warnings.is_empty());
+        // Saturate keeps the maximum, wrap starts over with a warning.
+        let saturating_parser = LogParser::with_options(
+            test_log_file,
+            ParseOptions::default().with_overflow_policy(OverflowPolicy::Saturate),
+        );
+        let wrapping_parser = LogParser::with_options(
+            test_log_file,
+            ParseOptions::default().with_overflow_policy(OverflowPolicy::WrapWithWarning),
+        );
+
+        saturating_parser
             .increment_hash_map_field(
                 &mut owner_usage_hash_map,
-                &query_string,
-                &QueryStringParameters::resolve_query_string_parameter(
-                    &QueryStringParameters::VideoId,
-                ),
-                "video_plays",
+                111,
+                watch_seconds,
+                u64::MAX,
+                7,
+                &mut warnings,
             )
             .unwrap();
 
-        log_parser
+        assert_eq!(owner_usage_hash_map.get_metric("watch_seconds"), u64::MAX);
+        assert!(warnings.is_empty());
+
+        wrapping_parser
             .increment_hash_map_field(
                 &mut owner_usage_hash_map,
-                &query_string,
-                &QueryStringParameters::resolve_query_string_parameter(
-                    &QueryStringParameters::AdUnitId,
-                ),
-                "ad_impressions",
+                111,
+                watch_seconds,
+                5,
+                8,
+                &mut warnings,
             )
             .unwrap();
 
-        assert_eq!(owner_usage_hash_map.get_video_plays(), 1);
-        assert_eq!(owner_usage_hash_map.get_ad_impressions(), 1);
+        assert_eq!(owner_usage_hash_map.get_metric("watch_seconds"), 4);
+        assert_eq!(
+            warnings,
+            vec![
+                "not_exist_log.txt:8: Overflow when adding to watch_seconds for owner 111, the value wrapped around"
+                    .to_string()
+            ]
+        );
+    }
 
-        let query_string_with_v_only = "o=111&v=333";
+    #[test]
+    fn test_log_parser_with_custom_schema() {
+        let test_log_path = "test_log_custom_schema.txt";
+        let log_lines = r#"https://www.mysite.com/pixel.gif?owner=1&v=2222&d=30&i=abc
+https://www.mysite.com/pixel.gif?owner=1&d=15
+https://www.mysite.com/pixel.gif?owner=2&v=1
+"#;
+        let schema = MetricSchema::parse(
+            "owner owner\nmetric v video_plays count\nmetric d watch_seconds sum\n",
+        )
+        .unwrap();
 
-        log_parser
-            .increment_hash_map_field(
-                &mut owner_usage_hash_map,
-                &query_string_with_v_only,
-                &QueryStringParameters::resolve_query_string_parameter(
-                    &QueryStringParameters::VideoId,
-                ),
-                "video_plays",
-            )
-            .unwrap();
+        std::fs::write(test_log_path, log_lines).unwrap();
 
-        assert_eq!(owner_usage_hash_map.get_video_plays(), 2);
-        assert_eq!(owner_usage_hash_map.get_ad_impressions(), 1);
+        let parse_result = LogParser::with_options(
+            test_log_path,
+            ParseOptions::default().with_schema(std::sync::Arc::new(schema)),
+        )
+        .parse();
 
-        let query_string_with_i_only = "o=111&i=333";
+        std::fs::remove_file(test_log_path).unwrap();
 
-        log_parser
-            .increment_hash_map_field(
-                &mut owner_usage_hash_map,
-                &query_string_with_i_only,
-                &QueryStringParameters::resolve_query_string_parameter(
-                    &QueryStringParameters::AdUnitId,
-                ),
-                "ad_impressions",
+        let usage = parse_result.unwrap().take_usage();
+
+        // The "i" param is not in the schema, so its invalid value is ignored.
+        assert_eq!(usage.get(&1).unwrap().get_metric("video_plays"), 1);
+        assert_eq!(usage.get(&1).unwrap().get_metric("watch_seconds"), 45);
+        assert_eq!(usage.get(&1).unwrap().get_metrics().len(), 2);
+        // Every owner has all schema metrics, even those that never happened.
+        assert_eq!(usage.get(&2).unwrap().get_metric("video_plays"), 1);
+        assert_eq!(
+            usage.get(&2).unwrap().get_metrics().get("watch_seconds"),
+            Some(&0)
+        );
+    }
+
+    #[test]
+    fn test_log_parser_errors_have_file_and_line() {
+        let cases: Vec<(&str, &str, usize)> = vec![
+            (
+                "https://www.mysite.com/pixel.gif",
+                "No query string found",
+                2,
+            ),
+            (
+                "https://www.mysite.com/pixel.gif?v=2222",
+                "No owner id found",
+                2,
+            ),
+            (
+                "https://www.mysite.com/pixel.gif?o=abc",
+                "Invalid value for parameter \"o\"",
+                2,
+            ),
+            (
+                "https://www.mysite.com/pixel.gif?o=1&i=x",
+                "Invalid value for parameter \"i\"",
+                2,
+            ),
+        ];
+
+        for (index, (bad_line, expected_message, expected_line_no)) in cases.into_iter().enumerate()
+        {
+            let test_log_path = format!("test_log_errors_{}.txt", index);
+            let mut file_handle = std::fs::File::create(&test_log_path).unwrap();
+
+            writeln!(file_handle, "https://www.mysite.com/pixel.gif?o=1&v=2").unwrap();
+            writeln!(file_handle, "{}", bad_line).unwrap();
+
+            let parse_result = LogParser::new(&test_log_path).parse();
+
+            std::fs::remove_file(&test_log_path).unwrap();
+
+            let error = parse_result.unwrap_err();
+
+            assert_eq!(error.get_file(), test_log_path);
+            assert_eq!(error.get_line_no(), Some(expected_line_no));
+            assert!(
+                error.to_string().contains(expected_message),
+                "error: {}",
+                error
+            );
+            assert!(
+                error
+                    .to_string()
+                    .starts_with(&format!("{}:2:", test_log_path))
+            );
+        }
+
+        let error = LogParser::new("test_log_not_existing.txt")
+            .parse()
+            .unwrap_err();
+
+        assert!(matches!(error, LogParserError::Io { .. }));
+        assert_eq!(error.get_line_no(), None);
+    }
+
+    #[test]
+    fn test_log_parser_on_error_policies() {
+        let log_lines = r#"https://www.mysite.com/pixel.gif?o=123&v=2222&i=123
+https://www.mysite.com/pixel.gif?v=3333
+https://www.mysite.com/pixel.gif?o=123&v=4444&i=abc
+https://www.mysite.com/pixel.gif
+https://www.mysite.com/pixel.gif?o=444&v=1111
+"#;
+        let test_log_path = "test_log_policies.txt";
+        let test_dead_letter_path = "test_log_policies_dead_letter.txt";
+
+        std::fs::write(test_log_path, log_lines).unwrap();
+
+        let abort_result = LogParser::new(test_log_path).parse();
+        let mut skip_report = LogParser::with_options(
+            test_log_path,
+            ParseOptions::default().with_on_error(OnErrorPolicy::Skip),
+        )
+        .parse()
+        .unwrap();
+        let dead_letter = std::sync::Arc::new(
+            super::super::dead_letter::DeadLetter::create(test_dead_letter_path).unwrap(),
+        );
+        let quarantine_report = LogParser::with_options(
+            test_log_path,
+            ParseOptions::default()
+                .with_on_error(OnErrorPolicy::Quarantine)
+                .with_dead_letter(Some(dead_letter.clone())),
+        )
+        .parse()
+        .unwrap();
+
+        dead_letter.flush().unwrap();
+
+        let dead_letter_content = std::fs::read_to_string(test_dead_letter_path).unwrap();
+
+        std::fs::remove_file(test_log_path).unwrap();
+        std::fs::remove_file(test_dead_letter_path).unwrap();
+
+        assert!(matches!(
+            abort_result,
+            Err(LogParserError::MissingOwner { line_no: 2, .. })
+        ));
+
+        assert_eq!(skip_report.get_total_lines(), 5);
+        assert_eq!(skip_report.get_skipped_lines(), 3);
+        // The rejected line with the invalid ad unit must not count its video play either.
+        let usage = skip_report.take_usage();
+
+        assert_eq!(usage.get(&123).unwrap().get_metric("video_plays"), 1);
+        assert_eq!(usage.get(&123).unwrap().get_metric("ad_impressions"), 1);
+        assert_eq!(usage.get(&444).unwrap().get_metric("video_plays"), 1);
+
+        assert_eq!(quarantine_report.get_skipped_lines(), 3);
+
+        let dead_letter_lines: Vec<&str> = dead_letter_content.lines().collect();
+
+        assert_eq!(dead_letter_lines.len(), 3);
+        assert_eq!(
+            dead_letter_lines[0],
+            format!(
+                "{}:2\tNo owner id found\thttps://www.mysite.com/pixel.gif?v=3333",
+                test_log_path
             )
-            .unwrap();
+        );
+        assert!(dead_letter_lines[1].starts_with(&format!("{}:3\tInvalid value", test_log_path)));
+        assert!(dead_letter_lines[2].starts_with(&format!("{}:4\tNo query string", test_log_path)));
+    }
 
-        assert_eq!(owner_usage_hash_map.get_video_plays(), 2);
-        assert_eq!(owner_usage_hash_map.get_ad_impressions(), 2);
-        // Test the overflow
-        let mut owner_usage_hash_map = OwnerUsage::new(u32::MAX, u32::MAX);
+    #[test]
+    fn test_log_parser_invalid_utf8() {
+        let log_lines: &[u8] = b"https://www.mysite.com/pixel.gif?o=123&v=2222\n\
+https://www.mysite.com/pixel.gif?o=123&v=\xff\xfe\n\
+https://www.mysite.com/pixel.gif?o=444&v=1111\n";
+        let test_dead_letter_path = "test_log_invalid_utf8_dead_letter.txt";
 
-        let increment_result_none = log_parser.increment_hash_map_field(
-            &mut owner_usage_hash_map,
-            &query_string,
-            &QueryStringParameters::resolve_query_string_parameter(&QueryStringParameters::VideoId),
-            "video_plays",
+        let abort_result = LogParser::new("memory").parse_reader(log_lines);
+        let mut skip_report = LogParser::with_options(
+            "memory",
+            ParseOptions::default().with_on_error(OnErrorPolicy::Skip),
+        )
+        .parse_reader(log_lines)
+        .unwrap();
+        let dead_letter = std::sync::Arc::new(
+            super::super::dead_letter::DeadLetter::create(test_dead_letter_path).unwrap(),
         );
+        let quarantine_report = LogParser::with_options(
+            "memory",
+            ParseOptions::default()
+                .with_on_error(OnErrorPolicy::Quarantine)
+                .with_dead_letter(Some(dead_letter.clone())),
+        )
+        .parse_reader(log_lines)
+        .unwrap();
 
-        assert_eq!(increment_result_none, None);
+        dead_letter.flush().unwrap();
 
-        let increment_result_none = log_parser.increment_hash_map_field(
-            &mut owner_usage_hash_map,
-            &query_string,
-            &QueryStringParameters::resolve_query_string_parameter(
-                &QueryStringParameters::AdUnitId,
-            ),
-            "ad_impressions",
+        let dead_letter_content = std::fs::read_to_string(test_dead_letter_path).unwrap();
+
+        std::fs::remove_file(test_dead_letter_path).unwrap();
+
+        // Not an IO error, so the on-error policy applies.
+        assert!(matches!(
+            abort_result,
+            Err(LogParserError::InvalidUtf8 { line_no: 2, .. })
+        ));
+
+        assert_eq!(skip_report.get_total_lines(), 3);
+        assert_eq!(skip_report.get_skipped_lines(), 1);
+        assert_eq!(skip_report.get_offset(), log_lines.len() as u64);
+
+        let usage = skip_report.take_usage();
+
+        assert_eq!(usage.get(&123).unwrap().get_metric("video_plays"), 1);
+        assert_eq!(usage.get(&444).unwrap().get_metric("video_plays"), 1);
+
+        assert_eq!(quarantine_report.get_skipped_lines(), 1);
+        assert!(dead_letter_content.starts_with("memory:2\tInvalid UTF-8"));
+    }
+
+    #[test]
+    fn test_log_parser_group_by() {
+        let test_log_path = "test_log_group_by.txt";
+        let log_lines = r#"https://www.mysite.com/pixel.gif?o=123&v=2222&i=555
+https://www.mysite.com/pixel.gif?o=123&v=2222
+https://www.mysite.com/pixel.gif?o=123&v=3333&i=555
+https://www.mysite.com/pixel.gif?o=444&i=666
+"#;
+
+        std::fs::write(test_log_path, log_lines).unwrap();
+
+        let grouped = LogParser::with_options(
+            test_log_path,
+            ParseOptions::default().with_group_by(vec!["video".to_string(), "ad_unit".to_string()]),
+        )
+        .parse();
+        let not_grouped = LogParser::new(test_log_path).parse();
+
+        std::fs::remove_file(test_log_path).unwrap();
+
+        let usage = grouped.unwrap().take_usage();
+        let owner_123 = usage.get(&123).unwrap();
+
+        assert_eq!(owner_123.get_metric("video_plays"), 3);
+        assert_eq!(owner_123.get_metric("ad_impressions"), 2);
+
+        let video_2222 = owner_123.get_entity_usage("video", 2222).unwrap();
+
+        assert_eq!(video_2222.get_metric("video_plays"), 2);
+        assert_eq!(video_2222.get_metric("ad_impressions"), 1);
+
+        let ad_unit_555 = owner_123.get_entity_usage("ad_unit", 555).unwrap();
+
+        assert_eq!(ad_unit_555.get_metric("video_plays"), 2);
+        assert_eq!(ad_unit_555.get_metric("ad_impressions"), 2);
+
+        let owner_444 = usage.get(&444).unwrap();
+
+        assert!(owner_444.get_breakdowns().get("video").is_none());
+        assert_eq!(
+            owner_444
+                .get_entity_usage("ad_unit", 666)
+                .unwrap()
+                .get_metric("video_plays"),
+            0
         );
+        // Without the group by, there are no breakdowns.
+        let usage = not_grouped.unwrap().take_usage();
 
-        assert_eq!(increment_result_none, None);
+        assert!(usage.get(&123).unwrap().get_breakdowns().is_empty());
+    }
 
-        let test_log_file = "log.txt";
-        let mut owner_usage_hash_map = OwnerUsage::default();
-        let query_string = "o=111&v=222&i=333";
-        let log_parser = LogParser::new(test_log_file);
+    #[test]
+    fn test_log_parser_timestamps_and_buckets() {
+        let test_log_path = "test_log_timestamps.txt";
+        let log_lines = r#"2024-04-30T23:59:59Z https://www.mysite.com/pixel.gif?o=1&v=2222
+2024-05-01T00:00:00Z https://www.mysite.com/pixel.gif?o=1&v=2222&i=555
+2024-05-01T01:30:00+02:00 https://www.mysite.com/pixel.gif?o=1&v=3333
+2024-05-02T10:00:00.5Z https://www.mysite.com/pixel.gif?o=1&i=555
+https://www.mysite.com/pixel.gif?o=1&v=4444
+2024-05-03T00:00:00Z https://www.mysite.com/pixel.gif?o=2&v=4444
+"#;
 
-        log_parser
-            .increment_hash_map_field(
-                &mut owner_usage_hash_map,
-                &query_string,
-                &QueryStringParameters::resolve_query_string_parameter(
-                    &QueryStringParameters::VideoId,
-                ),
-                "video_plays",
+        std::fs::write(test_log_path, log_lines).unwrap();
+
+        let mut bucketed = LogParser::with_options(
+            test_log_path,
+            ParseOptions::default().with_bucket(Some(TimeBucket::Day)),
+        )
+        .parse()
+        .unwrap();
+        let mut windowed = LogParser::with_options(
+            test_log_path,
+            ParseOptions::default()
+                .with_bucket(Some(TimeBucket::Month))
+                .with_since(Some(parse_time_bound("2024-05-01").unwrap()))
+                .with_until(Some(parse_time_bound("2024-05-03").unwrap())),
+        )
+        .parse()
+        .unwrap();
+
+        std::fs::write(
+            test_log_path,
+            "2024-05-32T00:00:00Z https://www.mysite.com/pixel.gif?o=1&v=1\n",
+        )
+        .unwrap();
+
+        let invalid_result = LogParser::new(test_log_path).parse();
+
+        std::fs::remove_file(test_log_path).unwrap();
+
+        // The untimestamped line is in the totals, but not in any bucket.
+        let usage = bucketed.take_usage();
+        let owner_usage = usage.get(&1).unwrap();
+        let series: Vec<(&str, u64, u64)> = owner_usage
+            .get_series()
+            .iter()
+            .map(|(bucket, usage)| {
+                (
+                    bucket.as_str(),
+                    usage.get_metric("video_plays"),
+                    usage.get_metric("ad_impressions"),
+                )
+            })
+            .collect();
+
+        assert_eq!(owner_usage.get_metric("video_plays"), 4);
+        assert_eq!(
+            series,
+            vec![
+                ("2024-04-30", 2, 0),
+                ("2024-05-01", 1, 1),
+                ("2024-05-02", 0, 1)
+            ]
+        );
+        assert_eq!(bucketed.get_filtered_lines(), 0);
+
+        // Only the lines from 2024-05-01 and 2024-05-02 (in UTC) are inside the window.
+        let usage = windowed.take_usage();
+
+        assert_eq!(windowed.get_total_lines(), 6);
+        assert_eq!(windowed.get_filtered_lines(), 4);
+        assert_eq!(windowed.get_skipped_lines(), 0);
+        assert!(!usage.contains_key(&2));
+        assert_eq!(usage.get(&1).unwrap().get_metric("video_plays"), 1);
+        assert_eq!(usage.get(&1).unwrap().get_metric("ad_impressions"), 2);
+        assert_eq!(
+            usage
+                .get(&1)
+                .unwrap()
+                .get_series()
+                .get("2024-05")
+                .unwrap()
+                .get_metric("ad_impressions"),
+            2
+        );
+
+        assert!(matches!(
+            invalid_result,
+            Err(LogParserError::InvalidTimestamp { line_no: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_log_parser_access_log_formats() {
+        let test_log_path = "test_log_access_log.txt";
+        let log_lines = r#"203.0.113.7 - - [01/May/2024:13:45:10 +0000] "GET /pixel.gif?o=123&v=2222&i=555 HTTP/1.1" 200 43 "https://ref.example/" "Mozilla/5.0"
+203.0.113.7 - - [01/May/2024:13:45:11 +0000] "GET /pixel.gif?o=123&v=2222 HTTP/1.1" 304 0 "-" "Mozilla/5.0"
+203.0.113.8 - - [02/May/2024:09:00:00 +0000] "GET /pixel.gif?o=123&v=3333 HTTP/1.1" 404 0 "-" "curl/8.0"
+203.0.113.8 - - [02/May/2024:09:00:01 +0000] "GET /pixel.gif?o=123&v=3333 HTTP/1.1" 500 0 "-" "curl/8.0"
+203.0.113.9 - - [02/May/2024:10:00:00 +0000] "GET /pixel.gif?o=444&i=555 HTTP/1.1" 200 43 "-" "-"
+"#;
+
+        std::fs::write(test_log_path, log_lines).unwrap();
+
+        let mut combined_report = LogParser::with_options(
+            test_log_path,
+            ParseOptions::default()
+                .with_input_format(InputFormat::Combined)
+                .with_bucket(Some(TimeBucket::Day)),
+        )
+        .parse()
+        .unwrap();
+        // Combined lines are valid common lines too, the rest of the line is ignored.
+        let common_report = LogParser::with_options(
+            test_log_path,
+            ParseOptions::default().with_input_format(InputFormat::Common),
+        )
+        .parse()
+        .unwrap();
+        let url_result = LogParser::new(test_log_path).parse();
+
+        std::fs::write(test_log_path, "https://www.mysite.com/pixel.gif?o=1&v=1\n").unwrap();
+
+        let invalid_result = LogParser::with_options(
+            test_log_path,
+            ParseOptions::default().with_input_format(InputFormat::Combined),
+        )
+        .parse();
+
+        std::fs::remove_file(test_log_path).unwrap();
+
+        // Only the 2xx and 3xx hits are counted.
+        assert_eq!(combined_report.get_total_lines(), 5);
+        assert_eq!(combined_report.get_filtered_lines(), 2);
+        assert_eq!(common_report.get_filtered_lines(), 2);
+
+        let usage = combined_report.take_usage();
+        let owner_usage = usage.get(&123).unwrap();
+
+        assert_eq!(owner_usage.get_metric("video_plays"), 2);
+        assert_eq!(owner_usage.get_metric("ad_impressions"), 1);
+        assert_eq!(
+            owner_usage.get_series().keys().collect::<Vec<&String>>(),
+            vec!["2024-05-01"]
+        );
+        assert_eq!(usage.get(&444).unwrap().get_metric("ad_impressions"), 1);
+
+        // Access log lines are not bare URLs, the IP address looks like a broken timestamp.
+        assert!(matches!(
+            url_result,
+            Err(LogParserError::InvalidTimestamp { line_no: 1, .. })
+        ));
+        assert!(matches!(
+            invalid_result,
+            Err(LogParserError::InvalidAccessLog { line_no: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_log_parser_parse_reader() {
+        let log_parser = LogParser::new("memory");
+        let lines = "https://www.mysite.com/pixel.gif?o=1&v=2&i=3\nhttps://www.mysite.com/pixel.gif?o=1&v=1\nhttps://www.mysite.com/pixel.gif?o=2&i=1";
+        let mut report = log_parser.parse_reader(lines.as_bytes()).unwrap();
+
+        assert_eq!(report.get_file(), "memory");
+        assert_eq!(report.get_total_lines(), 3);
+
+        let usage = report.take_usage();
+
+        assert_eq!(usage[&1].get_metric("video_plays"), 2);
+        assert_eq!(usage[&1].get_metric("ad_impressions"), 1);
+        assert_eq!(usage[&2].get_metric("ad_impressions"), 1);
+
+        let error = log_parser
+            .parse_reader("https://www.mysite.com/pixel.gif?v=2".as_bytes())
+            .unwrap_err();
+
+        assert_eq!(error.get_file(), "memory");
+        assert_eq!(error.get_line_no(), Some(1));
+    }
+
+    #[test]
+    fn test_log_parser_offsets_and_complete_lines() {
+        let lines = "https://www.mysite.com/pixel.gif?o=1&v=2\nhttps://www.mysite.com/pixel.gif?o=1&v=3\nhttps://www.mysite.com/pixel.gif?o=1&v=4";
+        let test_log_file = "test_log_parser_offsets.txt";
+
+        std::fs::write(test_log_file, lines).unwrap();
+
+        let options = ParseOptions::default()
+            .with_start_offsets(std::sync::Arc::new(HashMap::from([(
+                test_log_file.to_string(),
+                41,
+            )])))
+            .with_complete_lines_only(true);
+        let resumed_result = LogParser::with_options(test_log_file, options).parse();
+        let full_result = LogParser::new(test_log_file).parse();
+
+        std::fs::remove_file(test_log_file).unwrap();
+
+        // Only the second line is read. The last one is not complete yet.
+        let mut resumed_report = resumed_result.unwrap();
+
+        assert_eq!(resumed_report.get_total_lines(), 1);
+        assert_eq!(resumed_report.get_offset(), 82);
+        assert_eq!(resumed_report.take_usage()[&1].get_metric("video_plays"), 1);
+
+        let full_report = full_result.unwrap();
+
+        assert_eq!(full_report.get_total_lines(), 3);
+        assert_eq!(full_report.get_offset(), lines.len() as u64);
+    }
+
+    #[test]
+    fn test_log_parser_compressed_files() {
+        let log_lines = "https://www.mysite.com/pixel.gif?o=123&v=2222&i=555\nhttps://www.mysite.com/pixel.gif?o=123&v=3333\n";
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+
+        gzip.write_all(log_lines.as_bytes()).unwrap();
+
+        let cases: Vec<(&str, Vec<u8>)> = vec![
+            ("test_log_compressed.log.gz", gzip.finish().unwrap()),
+            (
+                "test_log_compressed.log.zst",
+                zstd::encode_all(log_lines.as_bytes(), 1).unwrap(),
+            ),
+        ];
+
+        for (test_log_path, bytes) in cases {
+            std::fs::write(test_log_path, &bytes).unwrap();
+
+            let parse_result = LogParser::new(test_log_path).parse();
+            // A truncated archive fails with an IO error, pointing to the file.
+            std::fs::write(test_log_path, &bytes[..bytes.len() / 2]).unwrap();
+
+            let truncated_result = LogParser::new(test_log_path).parse();
+
+            std::fs::remove_file(test_log_path).unwrap();
+
+            let mut report = parse_result.unwrap();
+            let usage = report.take_usage();
+
+            assert_eq!(report.get_total_lines(), 2);
+            assert_eq!(usage.get(&123).unwrap().get_metric("video_plays"), 2);
+            assert_eq!(usage.get(&123).unwrap().get_metric("ad_impressions"), 1);
+            assert!(matches!(
+                truncated_result,
+                Err(LogParserError::Io { ref file, .. }) if file == test_log_path
+            ));
+        }
+    }
+
+    #[test]
+    fn test_log_parser_validate() {
+        let lines = "https://www.mysite.com/pixel.gif?o=1&v=2&utm_source=mail\n\
+https://www.mysite.com/pixel.gif\n\
+https://www.mysite.com/pixel.gif?v=2&utm_source=mail&utm_source=ads\n\
+https://www.mysite.com/pixel.gif?o=1&v=abc\n\
+https://www.mysite.com/pixel.gif?o=2&v=x\n\
+https://www.mysite.com/pixel.gif?o=3&v=y\n\
+https://www.mysite.com/pixel.gif?o=4&v=z&ref=1\n\
+2024-13-01T00:00:00Z https://www.mysite.com/pixel.gif?o=1\n";
+        // Errors don't stop the validation, even with the abort policy.
+        let file_validation = LogParser::new("memory")
+            .validate_reader(lines.as_bytes())
+            .unwrap();
+
+        assert_eq!(file_validation.get_file(), "memory");
+        assert_eq!(file_validation.get_total_lines(), 8);
+        assert_eq!(file_validation.get_valid_lines(), 1);
+        assert_eq!(file_validation.get_invalid_lines(), 7);
+        assert!(!file_validation.is_clean());
+
+        let errors = file_validation.get_errors();
+
+        assert_eq!(
+            errors.keys().copied().collect::<Vec<&str>>(),
+            vec![
+                "invalid param",
+                "invalid timestamp",
+                "missing owner",
+                "missing query string"
+            ]
+        );
+        assert_eq!(errors["invalid param"].lines, 4);
+        // Only the first few lines are kept as samples.
+        assert_eq!(errors["invalid param"].samples.len(), 3);
+        assert_eq!(
+            errors["invalid param"].samples[0],
+            "4: Invalid value for parameter \"v\": \"abc\": https://www.mysite.com/pixel.gif?o=1&v=abc"
+        );
+        assert_eq!(errors["missing owner"].lines, 1);
+        // Unknown keys are counted once per line, also in the rejected lines.
+        assert_eq!(
+            file_validation.get_unknown_query_keys(),
+            &std::collections::BTreeMap::from([
+                ("ref".to_string(), 1),
+                ("utm_source".to_string(), 2)
+            ])
+        );
+
+        // Unsuccessful access log hits are filtered out, not errors.
+        let access_log_lines = "127.0.0.1 - - [01/May/2024:13:45:10 +0000] \"GET /pixel.gif?o=1&v=2 HTTP/1.1\" 200 43\n\
+127.0.0.1 - - [01/May/2024:13:45:11 +0000] \"GET /pixel.gif?o=1&v=2 HTTP/1.1\" 404 43\n";
+        let options = ParseOptions::default().with_input_format(InputFormat::Common);
+        let file_validation = LogParser::with_options("memory", options)
+            .validate_reader(access_log_lines.as_bytes())
+            .unwrap();
+
+        assert_eq!(file_validation.get_valid_lines(), 1);
+        assert_eq!(file_validation.get_filtered_lines(), 1);
+        assert!(file_validation.is_clean());
+        // A line that is not valid UTF-8 is reported, and doesn't stop the validation.
+        let file_validation = LogParser::new("memory")
+            .validate_reader(
+                &b"https://www.mysite.com/pixel.gif?o=1&v=\xff\nhttps://www.mysite.com/pixel.gif?o=1&v=2\n"[..],
             )
             .unwrap();
+
+        assert_eq!(file_validation.get_total_lines(), 2);
+        assert_eq!(file_validation.get_valid_lines(), 1);
+        assert_eq!(file_validation.get_errors()["invalid utf-8"].lines, 1);
     }
 }
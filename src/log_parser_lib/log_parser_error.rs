@@ -1,33 +1,210 @@
 //! Custom error, which the main LogParser uses
 //!
 //! Should indicate the type of error that occured, since there can be more than one error type.
-//! Check the struct bellow.
+//! Every variant carries enough context (file path, 1-based line number, the offending line) to find the problem in a big run.
+//! Check the enum bellow.
 #[derive(Debug)]
 pub enum LogParserError {
     /// Indicates that the file could not be opened.
     /// Or, a reading from it returned an error.
-    Io(std::io::Error),
-    /// When reading the log lines, every parameter value in the query string should be an integer.
-    /// Parsing as integer could fail.
-    ParseIntError(std::num::ParseIntError),
-    /// Could indicate some other error, like a query string missing in the url for example.
-    Custom(String),
+    Io { file: String, error: std::io::Error },
+    /// A rejected line could not be written to the dead-letter file (the `file`).
+    DeadLetter { file: String, error: std::io::Error },
+    /// The line has no query string (nothing after the '?' character).
+    MissingQueryString {
+        file: String,
+        line_no: usize,
+        line: String,
+    },
+    /// The query string has no owner parameter. Owner is required.
+    MissingOwner {
+        file: String,
+        line_no: usize,
+        line: String,
+    },
+    /// Every parameter value in the query string should be an integer.
+    /// Parsing as integer failed for the given key.
+    InvalidParam {
+        file: String,
+        line_no: usize,
+        key: String,
+        value: String,
+        error: std::num::ParseIntError,
+    },
+    /// The line starts with a timestamp, which is not a valid RFC 3339 timestamp.
+    InvalidTimestamp {
+        file: String,
+        line_no: usize,
+        reason: String,
+    },
+    /// The line is not valid UTF-8.
+    InvalidUtf8 {
+        file: String,
+        line_no: usize,
+        error: std::str::Utf8Error,
+    },
+    /// The line is not a valid access log line, in the configured input format.
+    InvalidAccessLog {
+        file: String,
+        line_no: usize,
+        reason: String,
+    },
+    /// Adding to the usage metric of a given owner would overflow.
+    Overflow {
+        file: String,
+        line_no: usize,
+        owner: u32,
+        metric: String,
+    },
 }
 
-impl From<std::io::Error> for LogParserError {
-    fn from(err: std::io::Error) -> LogParserError {
-        LogParserError::Io(err)
+impl LogParserError {
+    /// *Return the file in which the error occured*
+    pub fn get_file(&self) -> &str {
+        match self {
+            LogParserError::Io { file, .. }
+            | LogParserError::DeadLetter { file, .. }
+            | LogParserError::MissingQueryString { file, .. }
+            | LogParserError::MissingOwner { file, .. }
+            | LogParserError::InvalidParam { file, .. }
+            | LogParserError::InvalidTimestamp { file, .. }
+            | LogParserError::InvalidUtf8 { file, .. }
+            | LogParserError::InvalidAccessLog { file, .. }
+            | LogParserError::Overflow { file, .. } => file,
+        }
+    }
+    /// *Return the 1-based line number at which the error occured, if it is related to a single line*
+    pub fn get_line_no(&self) -> Option<usize> {
+        match self {
+            LogParserError::Io { .. } | LogParserError::DeadLetter { .. } => None,
+            LogParserError::MissingQueryString { line_no, .. }
+            | LogParserError::MissingOwner { line_no, .. }
+            | LogParserError::InvalidParam { line_no, .. }
+            | LogParserError::InvalidTimestamp { line_no, .. }
+            | LogParserError::InvalidUtf8 { line_no, .. }
+            | LogParserError::InvalidAccessLog { line_no, .. }
+            | LogParserError::Overflow { line_no, .. } => Some(*line_no),
+        }
+    }
+    /// *Return the category of the error, used to group the errors in the validation report*
+    pub fn get_category(&self) -> &'static str {
+        match self {
+            LogParserError::Io { .. } => "io error",
+            LogParserError::DeadLetter { .. } => "dead-letter error",
+            LogParserError::MissingQueryString { .. } => "missing query string",
+            LogParserError::MissingOwner { .. } => "missing owner",
+            LogParserError::InvalidParam { .. } => "invalid param",
+            LogParserError::InvalidTimestamp { .. } => "invalid timestamp",
+            LogParserError::InvalidUtf8 { .. } => "invalid utf-8",
+            LogParserError::InvalidAccessLog { .. } => "invalid access log line",
+            LogParserError::Overflow { .. } => "overflow",
+        }
+    }
+    /// *Return a short reason, without the file, line number and the line itself*
+    ///
+    /// ---
+    ///
+    /// Used for the dead-letter file, where the location and the line are written separately.
+    pub fn get_reason(&self) -> String {
+        match self {
+            LogParserError::Io { error, .. } => format!("IO error: {}", error),
+            LogParserError::DeadLetter { error, .. } => format!("Dead-letter error: {}", error),
+            LogParserError::MissingQueryString { .. } => "No query string found".to_string(),
+            LogParserError::MissingOwner { .. } => "No owner id found".to_string(),
+            LogParserError::InvalidParam { key, value, .. } => {
+                format!("Invalid value for parameter \"{}\": \"{}\"", key, value)
+            }
+            LogParserError::InvalidTimestamp { reason, .. } => reason.clone(),
+            LogParserError::InvalidUtf8 { error, .. } => format!("Invalid UTF-8: {}", error),
+            LogParserError::InvalidAccessLog { reason, .. } => {
+                format!("Invalid access log line: {}", reason)
+            }
+            LogParserError::Overflow { owner, metric, .. } => {
+                format!("Overflow when adding to {} for owner {}", metric, owner)
+            }
+        }
     }
 }
 
 impl std::fmt::Display for LogParserError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            LogParserError::Io(e) => write!(f, "IO error: {}", e),
-            LogParserError::ParseIntError(e) => write!(f, "Parse error: {}", e),
-            LogParserError::Custom(msg) => write!(f, "Custom error: {}", msg),
+            LogParserError::Io { file, error } => write!(f, "{}: IO error: {}", file, error),
+            LogParserError::DeadLetter { file, error } => write!(
+                f,
+                "Could not write the rejected line to the dead-letter file: {}: {}",
+                file, error
+            ),
+            LogParserError::MissingQueryString {
+                file,
+                line_no,
+                line,
+            } => write!(
+                f,
+                "{}:{}: No query string found in line: {}",
+                file, line_no, line
+            ),
+            LogParserError::MissingOwner {
+                file,
+                line_no,
+                line,
+            } => write!(
+                f,
+                "{}:{}: No owner id found in line: {}",
+                file, line_no, line
+            ),
+            LogParserError::InvalidParam {
+                file,
+                line_no,
+                key,
+                value,
+                error,
+            } => write!(
+                f,
+                "{}:{}: Invalid value for parameter \"{}\": \"{}\" ({})",
+                file, line_no, key, value, error
+            ),
+            LogParserError::InvalidTimestamp {
+                file,
+                line_no,
+                reason,
+            } => write!(f, "{}:{}: {}", file, line_no, reason),
+            LogParserError::InvalidUtf8 {
+                file,
+                line_no,
+                error,
+            } => write!(f, "{}:{}: Invalid UTF-8: {}", file, line_no, error),
+            LogParserError::InvalidAccessLog {
+                file,
+                line_no,
+                reason,
+            } => write!(
+                f,
+                "{}:{}: Invalid access log line: {}",
+                file, line_no, reason
+            ),
+            LogParserError::Overflow {
+                file,
+                line_no,
+                owner,
+                metric,
+            } => write!(
+                f,
+                "{}:{}: Overflow when adding to {} for owner {}",
+                file, line_no, metric, owner
+            ),
         }
     }
 }
 
-impl std::error::Error for LogParserError {}
+impl std::error::Error for LogParserError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LogParserError::Io { error, .. } => Some(error),
+            LogParserError::DeadLetter { error, .. } => Some(error),
+            LogParserError::InvalidParam { error, .. } => Some(error),
+            LogParserError::InvalidUtf8 { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,365 @@
+//! Schema describing which query string keys are counted, and how.
+//!
+//! Every metric maps a query string key to a metric name, and an aggregation kind:
+//!
+//! - `count` - Every line where the key is present adds 1 to the metric (for example, `v` -> `video_plays`).
+//! - `sum` - The integer value of the key is added to the metric (for example, `d` -> `watch_seconds`).
+//!
+//! Both kinds are merged by addition, so usage parsed from different files can always be summed up.
+//!
+//! A schema can also define entities (like videos or ad units), identified by a query string key.
+//! When grouping by an entity, every line holding that key adds its metrics to the usage of that single entity as well.
+//!
+//! A schema can be built in code, or loaded from a file. The file has one directive per line:
+//!
+//! ```text
+//! # Comments and empty lines are ignored.
+//! owner o
+//! metric v video_plays count
+//! metric i ad_impressions count
+//! metric d watch_seconds sum
+//! entity video v
+//! entity ad_unit i
+//! ```
+//!
+//! Adding a new counted event is just a new `metric` line. No code changes are needed.
+
+/// How a metric is aggregated, from the values of its query string key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregationKind {
+    /// Add 1, for every line where the key is present.
+    Count,
+    /// Add the integer value of the key.
+    Sum,
+}
+
+impl AggregationKind {
+    /// *Resolve the aggregation kind from its name*
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// assert_eq!(AggregationKind::resolve("count"), Ok(AggregationKind::Count));
+    /// ```
+    pub fn resolve(name: &str) -> Result<Self, String> {
+        match name {
+            "count" => Ok(AggregationKind::Count),
+            "sum" => Ok(AggregationKind::Sum),
+            unknown_kind => Err(format!(
+                "Unknown aggregation kind: {}. Available kinds: count, sum",
+                unknown_kind
+            )),
+        }
+    }
+}
+
+/// A single metric: which query string key it comes from, its name, and how it's aggregated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricDefinition {
+    query_key: String,
+    metric_name: String,
+    kind: AggregationKind,
+}
+
+impl MetricDefinition {
+    /// *Return the query string key, the metric is read from*
+    pub fn get_query_key(&self) -> &str {
+        &self.query_key
+    }
+    /// *Return the name of the metric, as shown in the output*
+    pub fn get_metric_name(&self) -> &str {
+        &self.metric_name
+    }
+    /// *Return the aggregation kind*
+    pub fn get_kind(&self) -> AggregationKind {
+        self.kind
+    }
+}
+
+/// An entity, usage can be broken down by: its name, and the query string key holding its id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityDefinition {
+    name: String,
+    query_key: String,
+}
+
+impl EntityDefinition {
+    /// *Return the name of the entity, as used in `--group-by`*
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    /// *Return the query string key, holding the entity id*
+    pub fn get_query_key(&self) -> &str {
+        &self.query_key
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricSchema {
+    owner_key: String,
+    metrics: Vec<MetricDefinition>,
+    entities: Vec<EntityDefinition>,
+}
+
+impl Default for MetricSchema {
+    /// The built-in schema: owner is `o`, `v` counts video plays, and `i` counts ad impressions.
+    /// Usage can be broken down per `video` (the `v` id) and per `ad_unit` (the `i` id).
+    fn default() -> Self {
+        Self::new("o")
+            .with_metric("v", "video_plays", AggregationKind::Count)
+            .and_then(|schema| schema.with_metric("i", "ad_impressions", AggregationKind::Count))
+            .and_then(|schema| schema.with_entity("video", "v"))
+            .and_then(|schema| schema.with_entity("ad_unit", "i"))
+            .expect("The default metric schema must be valid")
+    }
+}
+
+impl MetricSchema {
+    /// *Create an empty schema, with a given owner key*
+    ///
+    /// ## Arguments
+    ///
+    /// - `owner_key` - Query string key holding the owner id
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let schema = MetricSchema::new("o")
+    ///     .with_metric("v", "video_plays", AggregationKind::Count)?
+    ///     .with_metric("d", "watch_seconds", AggregationKind::Sum)?;
+    /// ```
+    pub fn new(owner_key: &str) -> Self {
+        Self {
+            owner_key: owner_key.to_string(),
+            metrics: Vec::new(),
+            entities: Vec::new(),
+        }
+    }
+    /// *Add a metric to the schema*
+    ///
+    /// ---
+    ///
+    /// Every query key and every metric name can appear only once. The owner key can't be used as a metric.
+    ///
+    /// ## Arguments
+    ///
+    /// - `query_key` - Query string key, the metric is read from
+    /// - `metric_name` - Name of the metric
+    /// - `kind` - How the metric is aggregated
+    pub fn with_metric(
+        mut self,
+        query_key: &str,
+        metric_name: &str,
+        kind: AggregationKind,
+    ) -> Result<Self, String> {
+        if query_key.is_empty() || metric_name.is_empty() {
+            return Err("Query key and metric name must not be empty".to_string());
+        }
+
+        if query_key == self.owner_key {
+            return Err(format!(
+                "Query key {} is already used for the owner id",
+                query_key
+            ));
+        }
+
+        for metric in &self.metrics {
+            if metric.query_key == query_key {
+                return Err(format!("Query key {} is used more than once", query_key));
+            }
+
+            if metric.metric_name == metric_name {
+                return Err(format!(
+                    "Metric name {} is used more than once",
+                    metric_name
+                ));
+            }
+        }
+
+        self.metrics.push(MetricDefinition {
+            query_key: query_key.to_string(),
+            metric_name: metric_name.to_string(),
+            kind,
+        });
+
+        Ok(self)
+    }
+    /// *Add an entity to the schema, so usage can be broken down by it*
+    ///
+    /// ---
+    ///
+    /// Every entity name can appear only once. `owner` is reserved, since usage is always grouped by the owner.
+    ///
+    /// ## Arguments
+    ///
+    /// - `name` - Name of the entity, as used in `--group-by`
+    /// - `query_key` - Query string key, holding the entity id
+    pub fn with_entity(mut self, name: &str, query_key: &str) -> Result<Self, String> {
+        if name.is_empty() || query_key.is_empty() {
+            return Err("Entity name and query key must not be empty".to_string());
+        }
+
+        if name == "owner" || self.get_entity(name).is_some() {
+            return Err(format!("Entity name {} is used more than once", name));
+        }
+
+        self.entities.push(EntityDefinition {
+            name: name.to_string(),
+            query_key: query_key.to_string(),
+        });
+
+        Ok(self)
+    }
+    /// *Return all entities, in the order they were defined*
+    pub fn get_entities(&self) -> &[EntityDefinition] {
+        &self.entities
+    }
+    /// *Find an entity by its name*
+    pub fn get_entity(&self, name: &str) -> Option<&EntityDefinition> {
+        self.entities.iter().find(|entity| entity.name == name)
+    }
+    /// *Return the query string key holding the owner id*
+    pub fn get_owner_key(&self) -> &str {
+        &self.owner_key
+    }
+    /// *Return all metrics, in the order they were defined*
+    pub fn get_metrics(&self) -> &[MetricDefinition] {
+        &self.metrics
+    }
+    /// *Check if a query string key is used by the schema (the owner, a metric or an entity key)*
+    pub fn is_known_query_key(&self, key: &str) -> bool {
+        key == self.owner_key
+            || self.metrics.iter().any(|metric| metric.query_key == key)
+            || self.entities.iter().any(|entity| entity.query_key == key)
+    }
+    /// *Parse a schema from the file format described in the module documentation*
+    ///
+    /// ## Arguments
+    ///
+    /// - `content` - Content of the schema file
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let schema = MetricSchema::parse("owner o\nmetric v video_plays count\n")?;
+    /// ```
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut owner_key: Option<String> = None;
+        let mut metrics: Vec<(String, String, AggregationKind)> = Vec::new();
+        let mut entities: Vec<(String, String)> = Vec::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            match parts.as_slice() {
+                ["owner", key] => {
+                    owner_key = Some(key.to_string());
+                }
+                ["metric", key, name, kind] => {
+                    let kind = AggregationKind::resolve(kind)
+                        .map_err(|error| format!("Schema line {}: {}", index + 1, error))?;
+
+                    metrics.push((key.to_string(), name.to_string(), kind));
+                }
+                ["entity", name, key] => {
+                    entities.push((name.to_string(), key.to_string()));
+                }
+                _ => {
+                    return Err(format!(
+                        "Schema line {}: expected \"owner <key>\", \"metric <key> <name> <kind>\" or \"entity <name> <key>\", found: {}",
+                        index + 1,
+                        line
+                    ));
+                }
+            }
+        }
+
+        let mut schema = Self::new(owner_key.as_deref().unwrap_or("o"));
+
+        for (key, name, kind) in metrics {
+            schema = schema.with_metric(&key, &name, kind)?;
+        }
+
+        for (name, key) in entities {
+            schema = schema.with_entity(&name, &key)?;
+        }
+
+        if schema.metrics.is_empty() {
+            return Err("Schema must define at least one metric".to_string());
+        }
+
+        Ok(schema)
+    }
+    /// *Load a schema from a file*
+    ///
+    /// ## Arguments
+    ///
+    /// - `path` - Path of the schema file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|error| format!("Could not read the schema file {}: {}", path, error))?;
+
+        Self::parse(&content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_schema() {
+        let schema = MetricSchema::default();
+
+        assert_eq!(schema.get_owner_key(), "o");
+        assert_eq!(schema.get_metrics().len(), 2);
+        assert_eq!(schema.get_metrics()[0].get_query_key(), "v");
+        assert_eq!(schema.get_metrics()[0].get_metric_name(), "video_plays");
+        assert_eq!(schema.get_metrics()[1].get_query_key(), "i");
+        assert_eq!(schema.get_metrics()[1].get_metric_name(), "ad_impressions");
+        assert_eq!(schema.get_entity("video").unwrap().get_query_key(), "v");
+        assert_eq!(schema.get_entity("ad_unit").unwrap().get_query_key(), "i");
+        assert!(schema.get_entity("player").is_none());
+    }
+
+    #[test]
+    fn test_parse_schema() {
+        let schema = MetricSchema::parse(
+            "# Custom schema\n\nowner owner\nmetric v video_plays count\nmetric d  watch_seconds   sum\nentity player p\n",
+        )
+        .unwrap();
+
+        assert_eq!(schema.get_owner_key(), "owner");
+        assert_eq!(schema.get_metrics()[1].get_query_key(), "d");
+        assert_eq!(schema.get_metrics()[1].get_kind(), AggregationKind::Sum);
+        assert_eq!(schema.get_entities().len(), 1);
+        assert_eq!(schema.get_entity("player").unwrap().get_query_key(), "p");
+
+        let cases = vec![
+            ("metric v video_plays average", "Unknown aggregation kind"),
+            ("metric v video_plays", "expected"),
+            ("owner o\n", "at least one metric"),
+            ("metric v a count\nmetric v b count", "used more than once"),
+            ("metric v a count\nmetric i a count", "used more than once"),
+            ("metric o a count", "owner id"),
+            ("metric v a count\nentity owner o", "used more than once"),
+            (
+                "metric v a count\nentity video v\nentity video i",
+                "used more than once",
+            ),
+            ("metric v a count\nentity video", "expected"),
+        ];
+
+        for (content, expected_error) in cases {
+            let error = MetricSchema::parse(content).unwrap_err();
+
+            assert!(error.contains(expected_error), "error: {}", error);
+        }
+    }
+}
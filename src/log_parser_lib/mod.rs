@@ -1,6 +1,16 @@
+pub mod access_log;
+pub mod compression;
+pub mod dead_letter;
+pub mod file_validation_struct;
+pub mod input_format_enum;
 pub mod log_parser;
 pub mod log_parser_error;
+pub mod metric_schema;
+pub mod on_error_policy_enum;
+pub mod overflow_policy_enum;
 pub mod owner_usage_struct;
-
-mod query_string_params_enum;
-mod utils;
+pub mod parse_options;
+pub mod parse_report_struct;
+pub mod query_string;
+pub mod time_bucket_enum;
+pub mod timestamp;
@@ -0,0 +1,58 @@
+//! Enum to represent what should happen when a malformed log line is found.
+//!
+//! By default, one bad line aborts the whole run, since it's not safe to have some partial data of the usage.
+//! For big runs, it is sometimes better to skip the bad lines (and optionally keep them in a dead-letter file),
+//! as long as not too much data is rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OnErrorPolicy {
+    /// Stop parsing, and fail the whole run.
+    #[default]
+    Abort,
+    /// Skip the line, and just count it.
+    Skip,
+    /// Skip the line, and write it with the reason to a dead-letter file.
+    Quarantine,
+}
+
+impl OnErrorPolicy {
+    /// *Resolve the policy from its name, as given on the command line*
+    ///
+    /// ## Arguments
+    ///
+    /// - `name` - One of abort | skip | quarantine
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// assert_eq!(OnErrorPolicy::resolve("skip"), Ok(OnErrorPolicy::Skip));
+    /// assert!(OnErrorPolicy::resolve("unknown").is_err());
+    /// ```
+    pub fn resolve(name: &str) -> Result<Self, String> {
+        match name {
+            "abort" => Ok(OnErrorPolicy::Abort),
+            "skip" => Ok(OnErrorPolicy::Skip),
+            "quarantine" => Ok(OnErrorPolicy::Quarantine),
+            unknown_policy => Err(format!(
+                "Unknown on-error policy: {}. Available policies: abort, skip, quarantine",
+                unknown_policy
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_resolve_policies() {
+        assert_eq!(OnErrorPolicy::resolve("abort"), Ok(OnErrorPolicy::Abort));
+        assert_eq!(OnErrorPolicy::resolve("skip"), Ok(OnErrorPolicy::Skip));
+        assert_eq!(
+            OnErrorPolicy::resolve("quarantine"),
+            Ok(OnErrorPolicy::Quarantine)
+        );
+        assert!(OnErrorPolicy::resolve("Skip").is_err());
+        assert_eq!(OnErrorPolicy::default(), OnErrorPolicy::Abort);
+    }
+}
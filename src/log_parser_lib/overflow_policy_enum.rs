@@ -0,0 +1,100 @@
+//! Enum to represent what should happen when adding to an usage metric would overflow.
+//!
+//! Metrics are u64, so an overflow means the data is already way off. By default, it fails the whole run.
+//! Some reports would rather keep going, with the metric pinned at the maximum, or wrapped around (with a warning for every wrap).
+//!
+//! The same policy is used when a line is added to the usage of its file, and when the usage of all files is merged.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OverflowPolicy {
+    /// Fail the file (or the merge), and the whole run with it.
+    #[default]
+    Error,
+    /// Keep the metric at u64::MAX.
+    Saturate,
+    /// Wrap around to the start, and report a warning.
+    WrapWithWarning,
+}
+
+impl OverflowPolicy {
+    /// *Resolve the policy from its name, as given on the command line*
+    ///
+    /// ## Arguments
+    ///
+    /// - `name` - One of error | saturate | wrap-with-warning
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// assert_eq!(OverflowPolicy::resolve("saturate"), Ok(OverflowPolicy::Saturate));
+    /// assert!(OverflowPolicy::resolve("unknown").is_err());
+    /// ```
+    pub fn resolve(name: &str) -> Result<Self, String> {
+        match name {
+            "error" => Ok(OverflowPolicy::Error),
+            "saturate" => Ok(OverflowPolicy::Saturate),
+            "wrap-with-warning" => Ok(OverflowPolicy::WrapWithWarning),
+            unknown_policy => Err(format!(
+                "Unknown overflow policy: {}. Available policies: error, saturate, wrap-with-warning",
+                unknown_policy
+            )),
+        }
+    }
+    /// *Add two metric values, following the policy*
+    ///
+    /// ---
+    ///
+    /// Returns the sum, and whether it overflowed. Returns None only for the `error` policy, when it overflowed.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// assert_eq!(OverflowPolicy::Saturate.add(u64::MAX, 1), Some((u64::MAX, true)));
+    /// assert_eq!(OverflowPolicy::Error.add(u64::MAX, 1), None);
+    /// ```
+    pub fn add(self, current: u64, param: u64) -> Option<(u64, bool)> {
+        match (self, current.checked_add(param)) {
+            (_, Some(result)) => Some((result, false)),
+            (OverflowPolicy::Error, None) => None,
+            (OverflowPolicy::Saturate, None) => Some((u64::MAX, true)),
+            (OverflowPolicy::WrapWithWarning, None) => Some((current.wrapping_add(param), true)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_resolve_and_apply_policies() {
+        assert_eq!(OverflowPolicy::resolve("error"), Ok(OverflowPolicy::Error));
+        assert_eq!(
+            OverflowPolicy::resolve("saturate"),
+            Ok(OverflowPolicy::Saturate)
+        );
+        assert_eq!(
+            OverflowPolicy::resolve("wrap-with-warning"),
+            Ok(OverflowPolicy::WrapWithWarning)
+        );
+        assert!(OverflowPolicy::resolve("wrap").is_err());
+        assert_eq!(OverflowPolicy::default(), OverflowPolicy::Error);
+
+        for policy in [
+            OverflowPolicy::Error,
+            OverflowPolicy::Saturate,
+            OverflowPolicy::WrapWithWarning,
+        ] {
+            assert_eq!(policy.add(u64::MAX - 1, 1), Some((u64::MAX, false)));
+        }
+
+        assert_eq!(OverflowPolicy::Error.add(u64::MAX, 2), None);
+        assert_eq!(
+            OverflowPolicy::Saturate.add(u64::MAX, 2),
+            Some((u64::MAX, true))
+        );
+        assert_eq!(
+            OverflowPolicy::WrapWithWarning.add(u64::MAX, 2),
+            Some((1, true))
+        );
+    }
+}
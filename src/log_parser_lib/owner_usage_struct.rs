@@ -1,108 +1,285 @@
+use super::overflow_policy_enum::OverflowPolicy;
+use std::collections::BTreeMap;
+
 /// Struct representing an usage group for a single owner.
 ///
-/// All new usage metrics should be added here...
+/// Every usage metric is stored by its name, as defined in the metric schema (see `metric_schema.rs`).
+/// So new usage metrics don't need any changes here.
+///
+/// Usage can also be broken down by entities (like videos or ad units).
+/// Every breakdown maps an entity name to the usage of every single entity id, for example `video` -> `2222` -> `video_plays: 3`.
 ///
-/// Note: Adding a value to the struct usage field can potentially lead to an overflow. In product, it will wrap up to the begining, which is bad.
-/// So always handle that, it must not overflow.
+/// Usage of timestamped lines can also be aggregated into time buckets, stored as a time series.
+/// Every bucket label (for example `2024-05-01`, see `time_bucket_enum.rs`) maps to the usage in that bucket.
+/// Labels sort in the same order as time, so the series is always ordered.
 ///
-/// Note: u32 type is used for all fields for convinience.
+/// Note: Adding a value to an usage metric can potentially lead to an overflow. In product, it will wrap up to the begining, which is bad.
+/// So always handle that, with the overflow policy (see `overflow_policy_enum.rs`). By default, it must not overflow.
+///
+/// Note: u64 type is used for all metrics (the biggest owners go over u64::MAX plays a month), and u32 for the entity ids.
 /// In production, change it to the mysql fields data types for example.
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct OwnerUsage {
-    video_plays: u32,
-    ad_impressions: u32,
+    metrics: BTreeMap<String, u64>,
+    breakdowns: BTreeMap<String, BTreeMap<u32, OwnerUsage>>,
+    series: BTreeMap<String, OwnerUsage>,
 }
 
 impl OwnerUsage {
-    #[cfg(test)]
     /// *Returns a owner usage struct, with predefined starting usages*
     ///
     /// ---
     ///
-    /// Note: This is not only used for tests, to test the overflow when adding to the fields.
-    /// Since other methods are just incrementing by 1, it would take forever to increment a field, up until the u32::MAX, to test the overflow.
-    /// Refactor this if necessery.
-    pub fn new(video_plays: u32, ad_impressions: u32) -> Self {
+    /// Useful for tests, to test the overflow when adding to the metrics.
+    /// Since other methods are mostly incrementing by 1, it would take forever to increment a metric, up until the u64::MAX.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let owner_usage = OwnerUsage::from_metrics(&[("video_plays", 10), ("ad_impressions", 2)]);
+    ///
+    /// assert_eq!(owner_usage.get_metric("video_plays"), 10);
+    /// ```
+    pub fn from_metrics(metrics: &[(&str, u64)]) -> Self {
+        Self {
+            metrics: metrics
+                .iter()
+                .map(|(name, value)| (name.to_string(), *value))
+                .collect(),
+            ..Default::default()
+        }
+    }
+    /// *Returns a owner usage struct, with every given metric set to 0*
+    ///
+    /// ---
+    ///
+    /// Used so that every owner has all metrics from the schema in the output, even those that never happened.
+    pub fn with_metric_names<'a>(metric_names: impl Iterator<Item = &'a str>) -> Self {
         Self {
-            video_plays,
-            ad_impressions,
+            metrics: metric_names.map(|name| (name.to_string(), 0)).collect(),
+            ..Default::default()
         }
     }
+    /// *Return the value of a metric. Metrics that never happened are 0.*
+    pub fn get_metric(&self, metric_name: &str) -> u64 {
+        self.metrics.get(metric_name).copied().unwrap_or(0)
+    }
+    /// *Return all metrics, sorted by the metric name*
+    pub fn get_metrics(&self) -> &BTreeMap<String, u64> {
+        &self.metrics
+    }
+    /// *Return all breakdowns, keyed by the entity name, and then by the entity id*
+    pub fn get_breakdowns(&self) -> &BTreeMap<String, BTreeMap<u32, OwnerUsage>> {
+        &self.breakdowns
+    }
+    /// *Return the usage of a single entity, if it was recorded*
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let video_usage = owner_usage.get_entity_usage("video", 2222);
+    /// ```
+    pub fn get_entity_usage(&self, entity: &str, entity_id: u32) -> Option<&OwnerUsage> {
+        self.breakdowns.get(entity)?.get(&entity_id)
+    }
+    /// *Return the usage of a single entity, creating an empty one if needed*
+    pub fn get_entity_usage_mut(&mut self, entity: &str, entity_id: u32) -> &mut OwnerUsage {
+        if !self.breakdowns.contains_key(entity) {
+            self.breakdowns.insert(entity.to_string(), BTreeMap::new());
+        }
 
-    /// *Return the video plays param*
-    pub fn get_video_plays(&self) -> u32 {
-        self.video_plays
+        self.breakdowns
+            .get_mut(entity)
+            .expect("Breakdown was just inserted")
+            .entry(entity_id)
+            .or_default()
     }
-    /// *Return the ad impressions param*
-    pub fn get_ad_impressions(&self) -> u32 {
-        self.ad_impressions
+    /// *Return the time series, keyed by the bucket label*
+    pub fn get_series(&self) -> &BTreeMap<String, OwnerUsage> {
+        &self.series
     }
-    /// *Try to add an integer to the video_plays param*
+    /// *Return the usage in a single time bucket, creating an empty one if needed*
+    pub fn get_bucket_usage_mut(&mut self, bucket: &str) -> &mut OwnerUsage {
+        if !self.series.contains_key(bucket) {
+            self.series
+                .insert(bucket.to_string(), OwnerUsage::default());
+        }
+
+        self.series
+            .get_mut(bucket)
+            .expect("Bucket was just inserted")
+    }
+    /// *Try to add an integer to a metric*
     ///
     /// ---
     ///
     /// Note: Method is using the `checked_add()` method, to check for overflow.
-    /// Returning None should signal an error.
+    /// Returning None should signal an error. On overflow, the metric is left unchanged.
     ///
     /// ---
     ///
     /// ## Arguments
     ///
+    /// - `metric_name` - Name of the metric
     /// - `param` - Integer to append
     ///
     /// ## Example
     ///
     /// ```
-    /// let owner_usage = OwnerUsage::default()
-    /// let add_result = owner_usage.add_video_plays(10);
+    /// let mut owner_usage = OwnerUsage::default();
+    /// let add_result = owner_usage.add_metric("video_plays", 10);
     ///
     /// if add_result.is_none() {
     ///     panic!("Overflow happened");
     /// }
     /// ```
-    pub fn add_video_plays(&mut self, param: u32) -> Option<u32> {
-        match self.video_plays.checked_add(param) {
-            Some(result) => {
-                self.video_plays = result;
-
-                Some(result)
-            }
+    pub fn add_metric(&mut self, metric_name: &str, param: u64) -> Option<u64> {
+        self.add_metric_with_policy(metric_name, param, OverflowPolicy::Error)?;
 
-            None => None,
-        }
+        Some(self.get_metric(metric_name))
     }
-    /// *Try to add an integer to the ad_impressions param*
+    /// *Add an integer to a metric, handling the overflow with the given policy*
     ///
     /// ---
     ///
-    /// Note: Method is using the `checked_add()` method, to check for overflow.
-    /// Returning None should signal an error.
-    ///
-    /// ---
+    /// Returns whether the addition overflowed (and was saturated or wrapped). Returns None only for the `error` policy,
+    /// in which case the metric is left unchanged.
     ///
     /// ## Arguments
     ///
+    /// - `metric_name` - Name of the metric
     /// - `param` - Integer to append
+    /// - `policy` - What to do on overflow
     ///
     /// ## Example
     ///
     /// ```
-    /// let owner_usage = OwnerUsage::default();
-    /// let add_result = owner_usage.add_ad_impressions(20);
+    /// let mut owner_usage = OwnerUsage::from_metrics(&[("video_plays", u64::MAX)]);
+    ///
+    /// assert_eq!(owner_usage.add_metric_with_policy("video_plays", 1, OverflowPolicy::Saturate), Some(true));
+    /// assert_eq!(owner_usage.get_metric("video_plays"), u64::MAX);
+    /// ```
+    pub fn add_metric_with_policy(
+        &mut self,
+        metric_name: &str,
+        param: u64,
+        policy: OverflowPolicy,
+    ) -> Option<bool> {
+        let (result, overflowed) = policy.add(self.get_metric(metric_name), param)?;
+
+        match self.metrics.get_mut(metric_name) {
+            Some(value) => *value = result,
+            None => {
+                self.metrics.insert(metric_name.to_string(), result);
+            }
+        }
+
+        Some(overflowed)
+    }
+    /// *Try to add every metric of another usage to this one*
+    ///
+    /// ---
+    ///
+    /// Breakdowns and time series are merged as well.
+    /// Returns the name of the first metric that would overflow. In that case, no metric is changed.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let mut owner_usage = OwnerUsage::from_metrics(&[("video_plays", 1)]);
+    ///
+    /// owner_usage.merge(&OwnerUsage::from_metrics(&[("video_plays", 2)])).unwrap();
+    ///
+    /// assert_eq!(owner_usage.get_metric("video_plays"), 3);
+    /// ```
+    pub fn merge(&mut self, other: &OwnerUsage) -> Result<(), String> {
+        self.merge_with_policy(other, OverflowPolicy::Error)?;
+
+        Ok(())
+    }
+    /// *Add every metric of another usage to this one, handling the overflow with the given policy*
+    ///
+    /// ---
+    ///
+    /// With the `error` policy, it works as `merge`. With the other policies, it never fails,
+    /// and returns the names of the metrics that overflowed (once for every overflow, also in the breakdowns and the time series).
+    ///
+    /// ## Example
     ///
-    /// if add_result.is_none() {
-    ///     panic!("Overflow happened");
-    /// }
     /// ```
+    /// let mut owner_usage = OwnerUsage::from_metrics(&[("video_plays", u64::MAX)]);
+    /// let overflowed = owner_usage
+    ///     .merge_with_policy(&OwnerUsage::from_metrics(&[("video_plays", 2)]), OverflowPolicy::WrapWithWarning)
+    ///     .unwrap();
     ///
-    pub fn add_ad_impressions(&mut self, param: u32) -> Option<u32> {
-        match self.ad_impressions.checked_add(param) {
-            Some(result) => {
-                self.ad_impressions = result;
+    /// assert_eq!(overflowed, vec!["video_plays".to_string()]);
+    /// assert_eq!(owner_usage.get_metric("video_plays"), 1);
+    /// ```
+    pub fn merge_with_policy(
+        &mut self,
+        other: &OwnerUsage,
+        policy: OverflowPolicy,
+    ) -> Result<Vec<String>, String> {
+        let mut overflowed: Vec<String> = Vec::new();
+
+        if policy == OverflowPolicy::Error {
+            self.check_merge(other)?;
+        }
+
+        self.apply_merge(other, policy, &mut overflowed);
+
+        Ok(overflowed)
+    }
+    /// *Check, without changing anything, that merging the other usage would not overflow*
+    fn check_merge(&self, other: &OwnerUsage) -> Result<(), String> {
+        for (metric_name, value) in &other.metrics {
+            if self.get_metric(metric_name).checked_add(*value).is_none() {
+                return Err(metric_name.clone());
+            }
+        }
+
+        for (entity, other_entities) in &other.breakdowns {
+            for (entity_id, other_entity_usage) in other_entities {
+                if let Some(entity_usage) = self.get_entity_usage(entity, *entity_id) {
+                    entity_usage.check_merge(other_entity_usage)?;
+                }
+            }
+        }
+
+        for (bucket, other_bucket_usage) in &other.series {
+            if let Some(bucket_usage) = self.series.get(bucket) {
+                bucket_usage.check_merge(other_bucket_usage)?;
+            }
+        }
 
-                Some(result)
+        Ok(())
+    }
+    /// *Merge the other usage, which was already checked for overflow (with the `error` policy)*
+    fn apply_merge(
+        &mut self,
+        other: &OwnerUsage,
+        policy: OverflowPolicy,
+        overflowed: &mut Vec<String>,
+    ) {
+        for (metric_name, value) in &other.metrics {
+            if self.add_metric_with_policy(metric_name, *value, policy) == Some(true) {
+                overflowed.push(metric_name.clone());
+            }
+        }
+
+        for (entity, other_entities) in &other.breakdowns {
+            for (entity_id, other_entity_usage) in other_entities {
+                self.get_entity_usage_mut(entity, *entity_id).apply_merge(
+                    other_entity_usage,
+                    policy,
+                    overflowed,
+                );
             }
-            None => None,
+        }
+
+        for (bucket, other_bucket_usage) in &other.series {
+            self.get_bucket_usage_mut(bucket)
+                .apply_merge(other_bucket_usage, policy, overflowed);
         }
     }
 }
@@ -115,21 +292,183 @@ mod tests {
     fn simple_add_to_multiple_parameters() {
         let mut owner_usage = OwnerUsage::default();
 
-        assert_eq!(owner_usage.add_video_plays(10), Some(10));
-        assert_eq!(owner_usage.add_ad_impressions(20), Some(20));
-        assert_eq!(owner_usage.get_video_plays(), 10);
-        assert_eq!(owner_usage.get_ad_impressions(), 20);
+        assert_eq!(owner_usage.add_metric("video_plays", 10), Some(10));
+        assert_eq!(owner_usage.add_metric("ad_impressions", 20), Some(20));
+        assert_eq!(owner_usage.get_metric("video_plays"), 10);
+        assert_eq!(owner_usage.get_metric("ad_impressions"), 20);
+        assert_eq!(owner_usage.get_metric("unknown"), 0);
     }
 
     #[test]
     fn adding_to_multiple_parameters_should_overflow() {
         let mut owner_usage = OwnerUsage::default();
 
-        assert_eq!(owner_usage.add_video_plays(u32::MAX), Some(u32::MAX));
-        assert_eq!(owner_usage.get_video_plays(), u32::MAX);
-        assert_eq!(owner_usage.add_video_plays(1), None);
-        assert_eq!(owner_usage.add_ad_impressions(u32::MAX), Some(u32::MAX));
-        assert_eq!(owner_usage.get_ad_impressions(), u32::MAX);
-        assert_eq!(owner_usage.add_ad_impressions(1), None);
+        assert_eq!(
+            owner_usage.add_metric("video_plays", u64::MAX),
+            Some(u64::MAX)
+        );
+        assert_eq!(owner_usage.get_metric("video_plays"), u64::MAX);
+        assert_eq!(owner_usage.add_metric("video_plays", 1), None);
+        assert_eq!(owner_usage.get_metric("video_plays"), u64::MAX);
+        assert_eq!(
+            owner_usage.add_metric("ad_impressions", u64::MAX),
+            Some(u64::MAX)
+        );
+        assert_eq!(owner_usage.get_metric("ad_impressions"), u64::MAX);
+        assert_eq!(owner_usage.add_metric("ad_impressions", 1), None);
+    }
+
+    #[test]
+    fn merge_should_be_all_or_nothing() {
+        let mut owner_usage =
+            OwnerUsage::with_metric_names(["video_plays", "ad_impressions"].into_iter());
+
+        assert_eq!(owner_usage.get_metrics().len(), 2);
+
+        owner_usage
+            .merge(&OwnerUsage::from_metrics(&[
+                ("video_plays", 5),
+                ("watch_seconds", 7),
+            ]))
+            .unwrap();
+
+        assert_eq!(owner_usage.get_metric("video_plays"), 5);
+        assert_eq!(owner_usage.get_metric("watch_seconds"), 7);
+
+        let overflowing =
+            OwnerUsage::from_metrics(&[("ad_impressions", 1), ("video_plays", u64::MAX)]);
+
+        assert_eq!(
+            owner_usage.merge(&overflowing),
+            Err("video_plays".to_string())
+        );
+        assert_eq!(owner_usage.get_metric("ad_impressions"), 0);
+    }
+
+    #[test]
+    fn merge_should_include_breakdowns() {
+        let mut owner_usage = OwnerUsage::default();
+        let mut other = OwnerUsage::default();
+
+        owner_usage
+            .get_entity_usage_mut("video", 2222)
+            .add_metric("video_plays", 2);
+        other
+            .get_entity_usage_mut("video", 2222)
+            .add_metric("video_plays", 3);
+        other
+            .get_entity_usage_mut("video", 3333)
+            .add_metric("video_plays", 1);
+        other
+            .get_entity_usage_mut("ad_unit", 555)
+            .add_metric("ad_impressions", 1);
+
+        owner_usage.merge(&other).unwrap();
+
+        assert_eq!(
+            owner_usage
+                .get_entity_usage("video", 2222)
+                .unwrap()
+                .get_metric("video_plays"),
+            5
+        );
+        assert_eq!(owner_usage.get_breakdowns().get("video").unwrap().len(), 2);
+        assert!(owner_usage.get_entity_usage("ad_unit", 555).is_some());
+        assert!(owner_usage.get_entity_usage("ad_unit", 556).is_none());
+        // Overflow in a breakdown leaves everything unchanged.
+        let mut overflowing = OwnerUsage::from_metrics(&[("video_plays", 1)]);
+
+        overflowing
+            .get_entity_usage_mut("video", 2222)
+            .add_metric("video_plays", u64::MAX);
+
+        assert_eq!(
+            owner_usage.merge(&overflowing),
+            Err("video_plays".to_string())
+        );
+        assert_eq!(owner_usage.get_metric("video_plays"), 0);
+    }
+
+    #[test]
+    fn merge_should_include_series() {
+        let mut owner_usage = OwnerUsage::default();
+        let mut other = OwnerUsage::default();
+
+        owner_usage
+            .get_bucket_usage_mut("2024-05-01")
+            .add_metric("video_plays", 2);
+        other
+            .get_bucket_usage_mut("2024-05-01")
+            .add_metric("video_plays", 3);
+        other
+            .get_bucket_usage_mut("2024-04-30")
+            .add_metric("video_plays", 1);
+
+        owner_usage.merge(&other).unwrap();
+
+        let buckets: Vec<(&String, u64)> = owner_usage
+            .get_series()
+            .iter()
+            .map(|(bucket, usage)| (bucket, usage.get_metric("video_plays")))
+            .collect();
+
+        assert_eq!(
+            buckets,
+            vec![
+                (&"2024-04-30".to_string(), 1),
+                (&"2024-05-01".to_string(), 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_should_follow_the_overflow_policy() {
+        let mut other = OwnerUsage::from_metrics(&[("video_plays", 2), ("ad_impressions", 1)]);
+
+        other
+            .get_entity_usage_mut("video", 2222)
+            .add_metric("video_plays", 2);
+
+        let full = || {
+            let mut owner_usage = OwnerUsage::from_metrics(&[("video_plays", u64::MAX)]);
+
+            owner_usage
+                .get_entity_usage_mut("video", 2222)
+                .add_metric("video_plays", u64::MAX);
+
+            owner_usage
+        };
+
+        let mut owner_usage = full();
+
+        assert_eq!(
+            owner_usage.merge_with_policy(&other, OverflowPolicy::Error),
+            Err("video_plays".to_string())
+        );
+        assert_eq!(owner_usage, full());
+
+        let mut owner_usage = full();
+
+        assert_eq!(
+            owner_usage.merge_with_policy(&other, OverflowPolicy::Saturate),
+            Ok(vec!["video_plays".to_string(), "video_plays".to_string()])
+        );
+        assert_eq!(owner_usage.get_metric("video_plays"), u64::MAX);
+        assert_eq!(owner_usage.get_metric("ad_impressions"), 1);
+
+        let mut owner_usage = full();
+
+        owner_usage
+            .merge_with_policy(&other, OverflowPolicy::WrapWithWarning)
+            .unwrap();
+
+        assert_eq!(owner_usage.get_metric("video_plays"), 1);
+        assert_eq!(
+            owner_usage
+                .get_entity_usage("video", 2222)
+                .unwrap()
+                .get_metric("video_plays"),
+            1
+        );
     }
 }
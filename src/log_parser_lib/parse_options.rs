@@ -0,0 +1,157 @@
+//! Options for the LogParser, shared by all workers.
+//!
+//! When adding new parsing options, add them here, so they reach every worker without changing the worker pool.
+use super::dead_letter::DeadLetter;
+use super::input_format_enum::InputFormat;
+use super::metric_schema::MetricSchema;
+use super::on_error_policy_enum::OnErrorPolicy;
+use super::overflow_policy_enum::OverflowPolicy;
+use super::time_bucket_enum::TimeBucket;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Clone, Default)]
+pub struct ParseOptions {
+    input_format: InputFormat,
+    on_error: OnErrorPolicy,
+    overflow_policy: OverflowPolicy,
+    dead_letter: Option<Arc<DeadLetter>>,
+    schema: Arc<MetricSchema>,
+    group_by: Vec<String>,
+    bucket: Option<TimeBucket>,
+    since: Option<i64>,
+    until: Option<i64>,
+    start_offsets: Arc<HashMap<String, u64>>,
+    complete_lines_only: bool,
+}
+
+impl ParseOptions {
+    /// *Set the format of the log lines*
+    pub fn with_input_format(mut self, input_format: InputFormat) -> Self {
+        self.input_format = input_format;
+
+        self
+    }
+    /// *Set what happens when a malformed line is found*
+    pub fn with_on_error(mut self, on_error: OnErrorPolicy) -> Self {
+        self.on_error = on_error;
+
+        self
+    }
+    /// *Set what happens when adding to a metric would overflow*
+    pub fn with_overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+
+        self
+    }
+    /// *Set the dead-letter file, used in the quarantine mode*
+    pub fn with_dead_letter(mut self, dead_letter: Option<Arc<DeadLetter>>) -> Self {
+        self.dead_letter = dead_letter;
+
+        self
+    }
+    /// *Set the metric schema, deciding which query keys are counted and how*
+    pub fn with_schema(mut self, schema: Arc<MetricSchema>) -> Self {
+        self.schema = schema;
+
+        self
+    }
+    /// *Set the entities (from the schema), the usage is broken down by*
+    pub fn with_group_by(mut self, group_by: Vec<String>) -> Self {
+        self.group_by = group_by;
+
+        self
+    }
+    /// *Set the size of the time buckets, the usage of timestamped lines is aggregated into*
+    pub fn with_bucket(mut self, bucket: Option<TimeBucket>) -> Self {
+        self.bucket = bucket;
+
+        self
+    }
+    /// *Only count lines with a timestamp at or after this one (seconds since the Unix epoch)*
+    pub fn with_since(mut self, since: Option<i64>) -> Self {
+        self.since = since;
+
+        self
+    }
+    /// *Only count lines with a timestamp before this one (seconds since the Unix epoch)*
+    pub fn with_until(mut self, until: Option<i64>) -> Self {
+        self.until = until;
+
+        self
+    }
+    /// *Set the offsets to start reading the files at, keyed by the file path. Files that are not in the map are read from the start.*
+    pub fn with_start_offsets(mut self, start_offsets: Arc<HashMap<String, u64>>) -> Self {
+        self.start_offsets = start_offsets;
+
+        self
+    }
+    /// *Set if the last line of a file should be ignored, when it does not end with a new line (it could still be being written)*
+    pub fn with_complete_lines_only(mut self, complete_lines_only: bool) -> Self {
+        self.complete_lines_only = complete_lines_only;
+
+        self
+    }
+    /// *Return the format of the log lines*
+    pub fn get_input_format(&self) -> InputFormat {
+        self.input_format
+    }
+    /// *Return the on-error policy*
+    pub fn get_on_error(&self) -> OnErrorPolicy {
+        self.on_error
+    }
+    /// *Return the overflow policy*
+    pub fn get_overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+    /// *Return the dead-letter file, if there is one*
+    pub fn get_dead_letter(&self) -> Option<&DeadLetter> {
+        self.dead_letter.as_deref()
+    }
+    /// *Return the metric schema*
+    pub fn get_schema(&self) -> &MetricSchema {
+        &self.schema
+    }
+    /// *Return the entities, the usage is broken down by*
+    pub fn get_group_by(&self) -> &[String] {
+        &self.group_by
+    }
+    /// *Return the size of the time buckets, if the usage should be bucketed*
+    pub fn get_bucket(&self) -> Option<TimeBucket> {
+        self.bucket
+    }
+    /// *Return the inclusive start of the time window*
+    pub fn get_since(&self) -> Option<i64> {
+        self.since
+    }
+    /// *Return the exclusive end of the time window*
+    pub fn get_until(&self) -> Option<i64> {
+        self.until
+    }
+    /// *Return the offset to start reading a file at. 0 means from the start.*
+    pub fn get_start_offset(&self, file: &str) -> u64 {
+        self.start_offsets.get(file).copied().unwrap_or(0)
+    }
+    /// *Return if only the lines ending with a new line are parsed*
+    pub fn get_complete_lines_only(&self) -> bool {
+        self.complete_lines_only
+    }
+    /// *Check if a line with the given timestamp is inside the time window*
+    ///
+    /// ---
+    ///
+    /// Without `since` and `until`, every line is inside. Otherwise, lines without a timestamp are outside, since they can't be placed in time.
+    pub fn is_in_time_window(&self, timestamp: Option<i64>) -> bool {
+        if self.since.is_none() && self.until.is_none() {
+            return true;
+        }
+
+        match timestamp {
+            Some(timestamp) => {
+                self.since.is_none_or(|since| timestamp >= since)
+                    && self.until.is_none_or(|until| timestamp < until)
+            }
+            None => false,
+        }
+    }
+}
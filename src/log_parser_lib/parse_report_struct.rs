@@ -0,0 +1,93 @@
+use super::owner_usage_struct::OwnerUsage;
+use std::collections::HashMap;
+
+/// Struct representing the result of parsing a single log file.
+///
+/// Holds the usage for every owner found in the file, and how many lines were read and skipped.
+/// Lines are skipped only when the on-error policy is not `abort`.
+/// Lines outside of the `since` / `until` time window, and access log hits without a 2xx / 3xx status, are filtered out.
+/// They are not errors, and are counted separately.
+///
+/// The offset is where the next read of the file should start, right after the last line that was read.
+///
+/// With the `wrap-with-warning` overflow policy, every metric that wrapped around is reported as a warning.
+#[derive(Debug, Default)]
+pub struct LogParseReport {
+    file: String,
+    usage: HashMap<u32, OwnerUsage>,
+    total_lines: usize,
+    skipped_lines: usize,
+    filtered_lines: usize,
+    offset: u64,
+    overflow_warnings: Vec<String>,
+}
+
+impl LogParseReport {
+    /// *Create an empty report, for a given file*
+    pub fn new(file: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            ..Default::default()
+        }
+    }
+    /// *Return the file this report is for*
+    pub fn get_file(&self) -> &str {
+        &self.file
+    }
+    /// *Return the usage, parsed from the file*
+    pub fn get_usage(&self) -> &HashMap<u32, OwnerUsage> {
+        &self.usage
+    }
+    /// *Return the usage, mutably. Used by the LogParser while parsing.*
+    pub fn get_usage_mut(&mut self) -> &mut HashMap<u32, OwnerUsage> {
+        &mut self.usage
+    }
+    /// *Take the usage out of the report, leaving an empty one in its place*
+    pub fn take_usage(&mut self) -> HashMap<u32, OwnerUsage> {
+        std::mem::take(&mut self.usage)
+    }
+    /// *Return the number of lines read from the file*
+    pub fn get_total_lines(&self) -> usize {
+        self.total_lines
+    }
+    /// *Return the number of rejected lines*
+    pub fn get_skipped_lines(&self) -> usize {
+        self.skipped_lines
+    }
+    /// *Return the number of lines that were filtered out (not counted, but not errors either)*
+    pub fn get_filtered_lines(&self) -> usize {
+        self.filtered_lines
+    }
+    /// *Return the offset right after the last line that was read, in the (decompressed) file*
+    pub fn get_offset(&self) -> u64 {
+        self.offset
+    }
+    /// *Set the offset the reading started at*
+    pub fn set_offset(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+    /// *Move the offset past a line that was read*
+    pub fn advance_offset(&mut self, bytes: usize) {
+        self.offset += bytes as u64;
+    }
+    /// *Return the warnings about the metrics that wrapped around*
+    pub fn get_overflow_warnings(&self) -> &[String] {
+        &self.overflow_warnings
+    }
+    /// *Record a warning about a metric that wrapped around*
+    pub fn add_overflow_warning(&mut self, warning: String) {
+        self.overflow_warnings.push(warning);
+    }
+    /// *Count a line that was read*
+    pub fn add_line(&mut self) {
+        self.total_lines += 1;
+    }
+    /// *Count a line that was rejected*
+    pub fn add_skipped_line(&mut self) {
+        self.skipped_lines += 1;
+    }
+    /// *Count a line that was filtered out*
+    pub fn add_filtered_line(&mut self) {
+        self.filtered_lines += 1;
+    }
+}
@@ -0,0 +1,281 @@
+//! Tokenizer for the query strings found in the log lines.
+//!
+//! Query strings are parsed as `key=value&key=value...` pairs, following the `application/x-www-form-urlencoded` rules browsers use:
+//!
+//! - Pairs are separated by `&`. Empty pairs (`a=1&&b=2`) are skipped.
+//! - Key and value are separated by the first `=`. A pair without `=` has an empty value.
+//! - `+` is decoded as a space, and `%XX` sequences are percent-decoded. Invalid sequences (`%zz`, or a trailing `%`) are kept as they are.
+//! - Decoded bytes that are not valid UTF-8 are replaced with the replacement character.
+//! - Keys can have any length (`v`, `xv`, `owner` are all different keys).
+//!
+//! Duplicate keys: the first occurrence wins. All values are still available, through [`QueryString::get_all`].
+
+/// *Find the query string in a log line.*
+///
+/// ---
+///
+/// In a full URL, query string is considered group of characters, after the first '?' character, and before the first '#' character (the fragment).
+/// Trailing new line characters (`\n`, `\r\n`) are not part of the query string.
+///
+/// ---
+///
+/// Note: This method could return None, so make sure to handle that.
+///
+/// ## Arguments
+///
+/// - `line` - A line in a log file, which is the full URL.
+///
+/// ## Example
+///
+/// ```
+/// assert_eq!(get_query_string("https://mysite.com/route?param=value#top\n"), Some("param=value"));
+/// ```
+pub fn get_query_string(line: &str) -> Option<&str> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let query_string_start = line.find('?')? + 1;
+    let query_string = &line[query_string_start..];
+    let query_string = match query_string.find('#') {
+        Some(fragment_start) => &query_string[..fragment_start],
+        None => query_string,
+    };
+    // For a url like this, https://www.mysite.com?, query string will be empty.
+    // So signal that it is not correct query string.
+    if query_string.is_empty() {
+        None
+    } else {
+        Some(query_string)
+    }
+}
+
+/// Parsed query string, as a list of decoded key / value pairs, in the original order.
+#[derive(Debug, Default, PartialEq)]
+pub struct QueryString {
+    pairs: Vec<(String, String)>,
+}
+
+impl QueryString {
+    /// *Tokenize a query string into decoded key / value pairs*
+    ///
+    /// ---
+    ///
+    /// Parsing never fails. See the module documentation for the exact rules.
+    ///
+    /// ## Arguments
+    ///
+    /// - `query_string` - Entire query string, without the leading '?'
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let query_string = QueryString::parse("o=111&v=222&title=a+b%21");
+    ///
+    /// assert_eq!(query_string.get("o"), Some("111"));
+    /// assert_eq!(query_string.get("title"), Some("a b!"));
+    /// ```
+    pub fn parse(query_string: &str) -> Self {
+        let pairs = query_string
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| match pair.split_once('=') {
+                Some((key, value)) => (decode_component(key), decode_component(value)),
+                None => (decode_component(pair), String::new()),
+            })
+            .collect();
+
+        Self { pairs }
+    }
+    /// *Get the value of a given key*
+    ///
+    /// ---
+    ///
+    /// If the key appears more than once, the first occurrence wins.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let query_string = QueryString::parse("o=111&o=222");
+    ///
+    /// assert_eq!(query_string.get("o"), Some("111"));
+    /// assert_eq!(query_string.get("x"), None);
+    /// ```
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(pair_key, _)| pair_key == key)
+            .map(|(_, value)| value.as_str())
+    }
+    /// *Get all values of a given key, in the original order*
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.pairs
+            .iter()
+            .filter(|(pair_key, _)| pair_key == key)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+    /// *Iterate over all decoded key / value pairs, in the original order*
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+/// *Decode a single key or value: `+` becomes a space, and `%XX` sequences are percent-decoded*
+fn decode_component(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            b'%' if is_percent_sequence(bytes, index) => {
+                decoded.push(hex_value(bytes[index + 1]) * 16 + hex_value(bytes[index + 2]));
+                index += 3;
+            }
+            byte => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// *Check if there is a valid `%XX` sequence at the given index*
+fn is_percent_sequence(bytes: &[u8], index: usize) -> bool {
+    index + 2 < bytes.len()
+        && bytes[index + 1].is_ascii_hexdigit()
+        && bytes[index + 2].is_ascii_hexdigit()
+}
+
+/// *Value of a single, already validated, hex digit*
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_query_string() {
+        let cases: Vec<(&str, Option<&str>)> = vec![
+            ("https://www.mysite.com", None),
+            // Nothing after the first ? char.
+            ("https://www.mysite.com?", None),
+            ("https://www.mysite.com?\n", None),
+            ("https://www.mysite.com?#fragment", None),
+            ("https://mysite.com/route?param=value", Some("param=value")),
+            // Trailing new line characters are not part of the query string.
+            (
+                "https://mysite.com/route?param=value\n",
+                Some("param=value"),
+            ),
+            (
+                "https://mysite.com/route?param=value\r\n",
+                Some("param=value"),
+            ),
+            // Fragment is stripped.
+            (
+                "https://mysite.com/route?param=value#top",
+                Some("param=value"),
+            ),
+            ("https://mysite.com/route?a=1&b=2#x?y=3\n", Some("a=1&b=2")),
+            // Query string is everything after the first ? char.
+            (
+                "https://mysite.com/route?param=value&param2=param2?param3=param3",
+                Some("param=value&param2=param2?param3=param3"),
+            ),
+            ("?o=1", Some("o=1")),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(get_query_string(line), expected, "line: {:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_tokenizer() {
+        let cases: Vec<(&str, Vec<(&str, &str)>)> = vec![
+            ("", vec![]),
+            (
+                "o=111&v=222&i=333",
+                vec![("o", "111"), ("v", "222"), ("i", "333")],
+            ),
+            // Multi character keys, which end with the same character as other keys.
+            ("xv=1&v=2", vec![("xv", "1"), ("v", "2")]),
+            ("owner=5&o=6", vec![("owner", "5"), ("o", "6")]),
+            // A value containing something that looks like a key.
+            ("t=v=1&v=2", vec![("t", "v=1"), ("v", "2")]),
+            // Empty pairs are skipped.
+            ("&&o=1&&v=2&", vec![("o", "1"), ("v", "2")]),
+            // Missing value, missing key.
+            ("o", vec![("o", "")]),
+            ("o=", vec![("o", "")]),
+            ("=1", vec![("", "1")]),
+            // Plus is a space.
+            ("q=a+b+c", vec![("q", "a b c")]),
+            ("a+b=c", vec![("a b", "c")]),
+            // Percent decoding.
+            ("q=%41%62%2b", vec![("q", "Ab+")]),
+            ("q=%26%3D", vec![("q", "&=")]),
+            ("%6F=1", vec![("o", "1")]),
+            ("q=caf%C3%A9", vec![("q", "café")]),
+            // Invalid percent sequences are kept as they are.
+            ("q=%zz", vec![("q", "%zz")]),
+            ("q=%4", vec![("q", "%4")]),
+            ("q=%", vec![("q", "%")]),
+            ("q=100%", vec![("q", "100%")]),
+            // Invalid UTF-8 is replaced.
+            ("q=%FF", vec![("q", "\u{FFFD}")]),
+            // Duplicate keys are all kept, in order.
+            ("o=1&o=2", vec![("o", "1"), ("o", "2")]),
+        ];
+
+        for (query_string, expected) in cases {
+            let parsed = QueryString::parse(query_string);
+            let pairs: Vec<(&str, &str)> = parsed.iter().collect();
+
+            assert_eq!(pairs, expected, "query string: {:?}", query_string);
+        }
+    }
+
+    #[test]
+    fn test_get() {
+        let query_string = QueryString::parse("o=111&v=222&i=333&xv=444&o=555");
+        let cases: Vec<(&str, Option<&str>)> = vec![
+            ("o", Some("111")),
+            ("v", Some("222")),
+            ("i", Some("333")),
+            ("xv", Some("444")),
+            ("x", None),
+            ("X", None),
+            ("", None),
+        ];
+
+        for (key, expected) in cases {
+            assert_eq!(query_string.get(key), expected, "key: {:?}", key);
+        }
+
+        // The first occurrence wins, but all of them are available.
+        assert_eq!(query_string.get_all("o"), vec!["111", "555"]);
+        assert!(query_string.get_all("x").is_empty());
+        // The last parameter is not cut off, with or without the new line char.
+        let line = "https://www.mysite.com/pixel.gif?o=123&v=2222\n";
+        let query_string = QueryString::parse(get_query_string(line).unwrap());
+
+        assert_eq!(query_string.get("v"), Some("2222"));
+        // A key as the very last character must not panic.
+        assert_eq!(QueryString::parse("o=1&v").get("v"), Some(""));
+        assert_eq!(QueryString::parse("v").get("v"), Some(""));
+    }
+}
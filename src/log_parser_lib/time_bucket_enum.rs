@@ -0,0 +1,83 @@
+//! Enum to represent the size of the time buckets, the usage is aggregated into.
+//!
+//! Every timestamped line adds its usage to the bucket its timestamp falls into (all in UTC).
+//! Buckets are identified by a label, which sorts in the same order as time:
+//!
+//! - `hour` - `2024-05-01T13:00:00Z`
+//! - `day` - `2024-05-01`
+//! - `month` - `2024-05`
+use super::timestamp::to_civil;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeBucket {
+    Hour,
+    Day,
+    Month,
+}
+
+impl TimeBucket {
+    /// *Resolve the bucket size from its name, as given on the command line*
+    ///
+    /// ## Arguments
+    ///
+    /// - `name` - One of hour | day | month
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// assert_eq!(TimeBucket::resolve("day"), Ok(TimeBucket::Day));
+    /// ```
+    pub fn resolve(name: &str) -> Result<Self, String> {
+        match name {
+            "hour" => Ok(TimeBucket::Hour),
+            "day" => Ok(TimeBucket::Day),
+            "month" => Ok(TimeBucket::Month),
+            unknown_bucket => Err(format!(
+                "Unknown bucket: {}. Available buckets: hour, day, month",
+                unknown_bucket
+            )),
+        }
+    }
+    /// *Return the label of the bucket, a timestamp falls into*
+    ///
+    /// ## Arguments
+    ///
+    /// - `timestamp` - Seconds since the Unix epoch
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// assert_eq!(TimeBucket::Month.get_label(0), "1970-01");
+    /// ```
+    pub fn get_label(&self, timestamp: i64) -> String {
+        let (year, month, day, hour) = to_civil(timestamp);
+
+        match self {
+            TimeBucket::Hour => format!("{:04}-{:02}-{:02}T{:02}:00:00Z", year, month, day, hour),
+            TimeBucket::Day => format!("{:04}-{:02}-{:02}", year, month, day),
+            TimeBucket::Month => format!("{:04}-{:02}", year, month),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_resolve_and_label_buckets() {
+        assert_eq!(TimeBucket::resolve("hour"), Ok(TimeBucket::Hour));
+        assert_eq!(TimeBucket::resolve("month"), Ok(TimeBucket::Month));
+        assert!(TimeBucket::resolve("week").is_err());
+
+        // 2024-02-29T12:30:15Z
+        let timestamp = 1_709_209_815;
+
+        assert_eq!(
+            TimeBucket::Hour.get_label(timestamp),
+            "2024-02-29T12:00:00Z"
+        );
+        assert_eq!(TimeBucket::Day.get_label(timestamp), "2024-02-29");
+        assert_eq!(TimeBucket::Month.get_label(timestamp), "2024-02");
+    }
+}
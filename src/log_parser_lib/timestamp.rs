@@ -0,0 +1,398 @@
+//! Parsing of the RFC 3339 timestamps, found at the beginning of the log lines.
+//!
+//! Collectors can prefix every pixel URL with the time of the event:
+//!
+//! ```text
+//! 2024-05-01T13:45:10Z https://www.mysite.com/pixel.gif?o=123&v=2222
+//! 2024-05-01T15:45:10.250+02:00 https://www.mysite.com/pixel.gif?o=123&i=555
+//! ```
+//!
+//! The timestamp is optional. Every timestamp is converted to the number of seconds since the Unix epoch (UTC).
+//! Fractions of a second are ignored, and a leap second (`:60`) is counted as the last second of that minute.
+//!
+//! Access logs (see `access_log.rs`) use the common log format timestamp instead (`10/Oct/2000:13:55:36 -0700`), which is parsed here as well.
+//!
+//! No external crates are used, dates are converted with the "days from civil" algorithm (proleptic Gregorian calendar).
+
+/// *Split the optional leading timestamp from a log line*
+///
+/// ---
+///
+/// A line is considered to start with a timestamp, if its first character is a digit (URLs never start with one).
+/// The timestamp ends at the first whitespace, and the rest of the line (without the leading whitespace) is returned as well.
+///
+/// Note: An error means the line starts with something that looks like a timestamp, but is not a valid one.
+///
+/// ## Arguments
+///
+/// - `line` - A line in a log file
+///
+/// ## Example
+///
+/// ```
+/// let (timestamp, url) = split_leading_timestamp("1970-01-01T00:01:00Z https://mysite.com/p?o=1")?;
+///
+/// assert_eq!(timestamp, Some(60));
+/// assert_eq!(url, "https://mysite.com/p?o=1");
+/// ```
+pub fn split_leading_timestamp(line: &str) -> Result<(Option<i64>, &str), String> {
+    if !line.starts_with(|c: char| c.is_ascii_digit()) {
+        return Ok((None, line));
+    }
+
+    let (timestamp, rest) = match line.find(char::is_whitespace) {
+        Some(timestamp_end) => (&line[..timestamp_end], &line[timestamp_end..]),
+        None => (line, ""),
+    };
+
+    Ok((Some(parse_rfc3339(timestamp)?), rest.trim_start()))
+}
+
+/// *Parse a full RFC 3339 timestamp, into seconds since the Unix epoch*
+///
+/// ---
+///
+/// Format: `YYYY-MM-DDTHH:MM:SS[.fraction](Z|+HH:MM|-HH:MM)`. The `T` and `Z` can be lowercase too.
+///
+/// ## Arguments
+///
+/// - `value` - The timestamp
+///
+/// ## Example
+///
+/// ```
+/// assert_eq!(parse_rfc3339("1970-01-02T00:00:00+01:00"), Ok(82800));
+/// ```
+pub fn parse_rfc3339(value: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid RFC 3339 timestamp: {}", value);
+    let bytes = value.as_bytes();
+
+    if bytes.len() < 20 || !matches!(bytes[10], b'T' | b't') {
+        return Err(invalid());
+    }
+
+    let days = parse_date(&value[..10]).ok_or_else(invalid)?;
+
+    if bytes[13] != b':' || bytes[16] != b':' {
+        return Err(invalid());
+    }
+
+    let hour = parse_digits(&bytes[11..13]).ok_or_else(invalid)?;
+    let minute = parse_digits(&bytes[14..16]).ok_or_else(invalid)?;
+    let second = parse_digits(&bytes[17..19]).ok_or_else(invalid)?;
+
+    if hour > 23 || minute > 59 || second > 60 {
+        return Err(invalid());
+    }
+
+    let mut position = 19;
+
+    if bytes[position] == b'.' {
+        position += 1;
+
+        let fraction_start = position;
+
+        while position < bytes.len() && bytes[position].is_ascii_digit() {
+            position += 1;
+        }
+
+        if position == fraction_start {
+            return Err(invalid());
+        }
+    }
+
+    let offset_seconds = match &bytes[position..] {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), offset @ ..] if offset.len() == 5 && offset[2] == b':' => {
+            let offset_hour = parse_digits(&offset[..2]).ok_or_else(invalid)?;
+            let offset_minute = parse_digits(&offset[3..]).ok_or_else(invalid)?;
+
+            if offset_hour > 23 || offset_minute > 59 {
+                return Err(invalid());
+            }
+
+            let offset_seconds = (offset_hour * 3600 + offset_minute * 60) as i64;
+
+            if *sign == b'-' {
+                -offset_seconds
+            } else {
+                offset_seconds
+            }
+        }
+        _ => return Err(invalid()),
+    };
+
+    Ok(days * 86_400 + (hour * 3600 + minute * 60 + second.min(59)) as i64 - offset_seconds)
+}
+
+/// *Parse a common log format timestamp, into seconds since the Unix epoch*
+///
+/// ---
+///
+/// Format: `DD/Mon/YYYY:HH:MM:SS +HHMM`, as written by Nginx and Apache (without the surrounding square brackets).
+///
+/// ## Arguments
+///
+/// - `value` - The timestamp
+///
+/// ## Example
+///
+/// ```
+/// assert_eq!(parse_clf_timestamp("02/Jan/1970:01:00:00 +0100"), Ok(86400));
+/// ```
+pub fn parse_clf_timestamp(value: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid access log timestamp: {}", value);
+    let bytes = value.as_bytes();
+
+    if bytes.len() != 26
+        || bytes[2] != b'/'
+        || bytes[6] != b'/'
+        || bytes[11] != b':'
+        || bytes[14] != b':'
+        || bytes[17] != b':'
+        || bytes[20] != b' '
+    {
+        return Err(invalid());
+    }
+
+    let month = match &value[3..6] {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return Err(invalid()),
+    };
+    let day = parse_digits(&bytes[..2]).ok_or_else(invalid)?;
+    let year = parse_digits(&bytes[7..11]).ok_or_else(invalid)? as i64;
+    let hour = parse_digits(&bytes[12..14]).ok_or_else(invalid)?;
+    let minute = parse_digits(&bytes[15..17]).ok_or_else(invalid)?;
+    let second = parse_digits(&bytes[18..20]).ok_or_else(invalid)?;
+    let offset_hour = parse_digits(&bytes[22..24]).ok_or_else(invalid)?;
+    let offset_minute = parse_digits(&bytes[24..26]).ok_or_else(invalid)?;
+
+    if day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 60
+        || offset_hour > 23
+        || offset_minute > 59
+    {
+        return Err(invalid());
+    }
+
+    let offset_seconds = (offset_hour * 3600 + offset_minute * 60) as i64;
+    let offset_seconds = match bytes[21] {
+        b'+' => offset_seconds,
+        b'-' => -offset_seconds,
+        _ => return Err(invalid()),
+    };
+
+    Ok(days_from_civil(year, month, day) * 86_400
+        + (hour * 3600 + minute * 60 + second.min(59)) as i64
+        - offset_seconds)
+}
+
+/// *Parse a time bound, as given on the command line, into seconds since the Unix epoch*
+///
+/// ---
+///
+/// Either a full RFC 3339 timestamp, or just a date (`YYYY-MM-DD`), meaning the midnight (UTC) at the start of that day.
+///
+/// ## Example
+///
+/// ```
+/// assert_eq!(parse_time_bound("1970-01-02"), Ok(86400));
+/// ```
+pub fn parse_time_bound(value: &str) -> Result<i64, String> {
+    if value.len() == 10 {
+        return parse_date(value)
+            .map(|days| days * 86_400)
+            .ok_or_else(|| format!("Invalid date: {}. Expected YYYY-MM-DD", value));
+    }
+
+    parse_rfc3339(value)
+}
+
+/// *Convert seconds since the Unix epoch into a (year, month, day, hour) tuple, in UTC*
+///
+/// ---
+///
+/// Used for building the labels of the time buckets.
+pub fn to_civil(timestamp: i64) -> (i64, u32, u32, u32) {
+    let (year, month, day) = civil_from_days(timestamp.div_euclid(86_400));
+    let hour = (timestamp.rem_euclid(86_400) / 3600) as u32;
+
+    (year, month, day, hour)
+}
+
+/// *Parse a `YYYY-MM-DD` date, into days since the Unix epoch*
+fn parse_date(value: &str) -> Option<i64> {
+    let bytes = value.as_bytes();
+
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+
+    let year = parse_digits(&bytes[..4])? as i64;
+    let month = parse_digits(&bytes[5..7])?;
+    let day = parse_digits(&bytes[8..10])?;
+
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day))
+}
+
+/// *Parse a fixed number of ASCII digits. Signs and whitespace are not allowed.*
+fn parse_digits(bytes: &[u8]) -> Option<u32> {
+    if bytes.is_empty() || !bytes.iter().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    Some(
+        bytes
+            .iter()
+            .fold(0, |number, byte| number * 10 + (byte - b'0') as u32),
+    )
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// *Number of days since 1970-01-01, for a given date*
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    // Years start in March, so the leap day is the last day of the year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// *Date, for a given number of days since 1970-01-01. Inverse of [`days_from_civil`].*
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400;
+
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rfc3339() {
+        let cases: Vec<(&str, Result<i64, ()>)> = vec![
+            ("1970-01-01T00:00:00Z", Ok(0)),
+            ("1970-01-01t00:00:01z", Ok(1)),
+            ("2024-02-29T12:30:15Z", Ok(1_709_209_815)),
+            ("2024-02-29T12:30:15.123456Z", Ok(1_709_209_815)),
+            ("2024-02-29T14:30:15+02:00", Ok(1_709_209_815)),
+            ("2024-02-29T07:00:15-05:30", Ok(1_709_209_815)),
+            ("1969-12-31T23:59:59Z", Ok(-1)),
+            // Leap second is counted as the last second of the minute.
+            ("2016-12-31T23:59:60Z", Ok(1_483_228_799)),
+            ("2023-02-29T12:30:15Z", Err(())),
+            ("2024-13-01T12:30:15Z", Err(())),
+            ("2024-01-01T24:00:00Z", Err(())),
+            ("2024-01-01T12:30:15", Err(())),
+            ("2024-01-01 12:30:15Z", Err(())),
+            ("2024-01-01T12:30:15.Z", Err(())),
+            ("2024-01-01T12:30:15+0200", Err(())),
+            ("2024-1-01T12:30:15Z", Err(())),
+            ("2024-01-01", Err(())),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(parse_rfc3339(value).map_err(|_| ()), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_parse_clf_timestamp() {
+        let cases: Vec<(&str, Result<i64, ()>)> = vec![
+            ("01/Jan/1970:00:00:00 +0000", Ok(0)),
+            ("29/Feb/2024:14:30:15 +0200", Ok(1_709_209_815)),
+            ("29/Feb/2024:07:00:15 -0530", Ok(1_709_209_815)),
+            ("29/Feb/2023:12:30:15 +0000", Err(())),
+            ("29/Fab/2024:12:30:15 +0000", Err(())),
+            ("29/Feb/2024:12:30:15 0000", Err(())),
+            ("29/Feb/2024:12:30:15", Err(())),
+            ("2024-02-29T12:30:15Z", Err(())),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(
+                parse_clf_timestamp(value).map_err(|_| ()),
+                expected,
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_split_leading_timestamp() {
+        assert_eq!(
+            split_leading_timestamp("https://mysite.com/p?o=1"),
+            Ok((None, "https://mysite.com/p?o=1"))
+        );
+        assert_eq!(
+            split_leading_timestamp("1970-01-01T00:01:00Z \t https://mysite.com/p?o=1\n"),
+            Ok((Some(60), "https://mysite.com/p?o=1\n"))
+        );
+        assert!(split_leading_timestamp("2024-99-01T00:00:00Z https://mysite.com/p?o=1").is_err());
+    }
+
+    #[test]
+    fn test_time_bounds_and_civil_dates() {
+        assert_eq!(parse_time_bound("2024-03-01"), Ok(1_709_251_200));
+        assert_eq!(parse_time_bound("2024-03-01T00:00:00Z"), Ok(1_709_251_200));
+        assert!(parse_time_bound("2024-03-32").is_err());
+        assert_eq!(to_civil(1_709_209_815), (2024, 2, 29, 12));
+        assert_eq!(to_civil(-1), (1969, 12, 31, 23));
+        // Every day in a long range survives the round trip.
+        for days in -800_000..800_000 {
+            let (year, month, day) = civil_from_days(days);
+
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+}
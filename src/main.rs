@@ -2,147 +2,300 @@
 //! Should correctly get CLI arguments, and successfully parse all logs.
 //! Final aggregate result must be valid, in order to do further processing correctly.
 //!
-//! Hardcode : let number_of_workers = 5;
-//! The logs directory could potentially contain hundreds of files.
-//! In order to make this efficient, we should start n threads, depending on the CPU, and when one of the threads has finished, spawn a new one.
-//! This way, we will avoid creating hundreds of threads, and avoid bottlenecks.
-//! This should definitely be implemented at some point.
+//! All of the logic lives in the library (see `lib.rs` and the `Pipeline`).
+//! This is just a thin CLI wrapper around it, that runs the command (see `command_enum.rs`).
 //!
-//! Consider a rewrite, to move all to logic to the lib and lib.rs
-mod arguments_lib;
-mod formatters;
-mod log_parser_lib;
-mod utils;
-
-use arguments_lib::cli_args::CLIArgs;
-use formatters::formatter_factory::FormatterFactory;
-use log_parser_lib::log_parser::LogParser;
-use log_parser_lib::owner_usage_struct::OwnerUsage;
-use std::{collections::HashMap, thread::JoinHandle};
-use utils::fs_utils::get_file_names;
+//! Only the formatted output goes to the stdout, so it can be piped into other tools.
+//! Everything else (errors, warnings, the parse summary) goes to the stderr.
+//! The exit code tells what kind of failure happened (see `exit_code_enum.rs`).
+use std::io::Write;
+use usage_parse::arguments_lib::cli_args::CLIArgs;
+use usage_parse::arguments_lib::command_enum::Command;
+use usage_parse::arguments_lib::db_query_args::DbQueryArgs;
+use usage_parse::arguments_lib::exit_code_enum::ExitCode;
+use usage_parse::arguments_lib::merge_args::MergeArgs;
+use usage_parse::arguments_lib::settings::DEFAULT_CONFIG_FILE;
+use usage_parse::follow_lib::follower::Follower;
+use usage_parse::follow_lib::termination_signal::install_termination_handler;
+use usage_parse::formatters::formatter_factory::FormatterFactory;
+use usage_parse::pipeline_lib::aggregate_merge::merge_aggregate_files;
+use usage_parse::pipeline_lib::parse_summary_struct::ParseSummary;
+use usage_parse::pipeline_lib::run_id::{fingerprint_input, run_id_for_inputs};
+use usage_parse::sinks::sink_factory::SinkFactory;
+use usage_parse::state_lib::usage_store::{RecordOutcome, UsageStore};
+use usage_parse::{Aggregate, Pipeline, PipelineConfig, PipelineError};
 
 fn main() {
-    let cli_args = CLIArgs::build(&mut std::env::args().skip(1)).unwrap_or_else(|error| {
-        eprint!("CLI Arguments parsing error: {}", error);
+    let env_vars = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+    let command = Command::parse(&mut std::env::args().skip(1), env_vars, DEFAULT_CONFIG_FILE)
+        .unwrap_or_else(|error| {
+            eprintln!("CLI Arguments parsing error: {}", error);
+            eprintln!("Run usage-parse --help, to see the available commands and options.");
 
-        std::process::exit(1);
-    });
-
-    let log_dir = cli_args.get_logs_dir();
-    let log_files = get_file_names(log_dir);
+            std::process::exit(ExitCode::Usage.code());
+        });
+    let result = match command {
+        Command::Parse(cli_args) => parse_logs(&cli_args),
+        Command::Stats(cli_args) => print_stats(&cli_args),
+        Command::Validate(cli_args) => validate_logs(&cli_args),
+        Command::Follow(cli_args) => follow_logs(&cli_args),
+        Command::Merge(merge_args) => merge_reports(&merge_args),
+        Command::DbQuery(db_query_args) => print_owner_usage(&db_query_args),
+        Command::Print(text) => {
+            print!("{}", text);
 
-    if log_files.is_empty() {
-        eprint!("No files found in the given dir!");
-
-        std::process::exit(1);
-    }
+            Ok(())
+        }
+    };
 
-    let start = std::time::Instant::now();
-    // Initialize variables for thread sharing and com.
-    let mut handles: Vec<JoinHandle<()>> = Vec::with_capacity(log_files.len());
-    let (tx, rx) = std::sync::mpsc::channel();
-    let mut aggregate: HashMap<u32, OwnerUsage> = HashMap::new();
-    let number_of_workers = 5;
-
-    let mut log_files_iter = log_files.into_iter();
-
-    for _ in 0..number_of_workers {
-        match log_files_iter.next() {
-            Some(log_file) => {
-                let log_file_full_path = format!("{}/{}", log_dir, log_file);
-                let tx_clone = tx.clone();
-
-                let log_handle = std::thread::spawn(move || {
-                    let log_parse_result = LogParser::new(&log_file_full_path);
-
-                    if tx_clone.send(log_parse_result.parse()).is_err() {
-                        panic!(
-                            "One of the threads could not send the result through the channel. It is not safe to continue."
-                        );
-                    }
-                });
-
-                handles.push(log_handle);
-            }
+    if let Err(error) = result {
+        eprintln!("FATAL ERROR OCCURED : {}", error);
+        // Point to the exact place in the logs, so it can be found in a big run.
+        if let PipelineError::Parse(parse_error) = &error {
+            eprintln!("  File: {}", parse_error.get_file());
 
-            None => {
-                break;
+            if let Some(line_no) = parse_error.get_line_no() {
+                eprintln!("  Line: {}", line_no);
             }
         }
+
+        std::process::exit(ExitCode::from(&error).code());
     }
+}
+
+/// *Parse all logs, record the run into the usage store (if any), and write the report with the sink from the CLI*
+fn parse_logs(cli_args: &CLIArgs) -> Result<(), PipelineError> {
+    let start = std::time::Instant::now();
+    let started_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+    let pipeline = Pipeline::new(PipelineConfig::from(cli_args));
     /*
-     * tx has not changed ownership, so rx blocks indefinitely.
-     * So drop it manually.
-     */
-    drop(tx);
-    /*
-     * Results are comming in here. Each of them must succeed.
+     * Every log file must be parsed successfully.
      * If that's not the case, just terminate the program.
      *
      * It's not safe to have some partial data of the usage.
      */
-    for log_parser_result in rx {
-        match log_parser_result {
-            Ok(owner_usage_hash_map) => {
-                for (owner_id, owner_usage) in owner_usage_hash_map {
-                    let entry = aggregate.entry(owner_id).or_default();
-
-                    if entry
-                        .add_video_plays(owner_usage.get_video_plays())
-                        .is_none()
-                    {
-                        println!(
-                            "[FATAL ERROR]: Possible overflow occured when adding video_plays!"
-                        );
-
-                        std::process::exit(1);
-                    }
-
-                    if entry
-                        .add_ad_impressions(owner_usage.get_ad_impressions())
-                        .is_none()
-                    {
-                        println!(
-                            "[FATAL ERROR]: Possible overflow occured when adding ad_impressions!"
-                        );
-                    }
+    let (aggregate, parse_summary) = match cli_args.get_store() {
+        /*
+         * Only what was not recorded yet is parsed, and recorded into the usage store, before the output is written.
+         * If nothing new was read, the store is left as it is.
+         */
+        Some(store) => {
+            let (aggregate, parse_summary, record_outcome) =
+                pipeline.run_into_store(&mut UsageStore::open(store)?, started_at)?;
+
+            print_parse_summary(&parse_summary);
+
+            match record_outcome {
+                RecordOutcome::Recorded(run_id) => {
+                    eprintln!("Recorded the run {} into {}", run_id, store)
                 }
+                RecordOutcome::NothingNew => eprintln!(
+                    "Everything was already recorded in {}, nothing was added",
+                    store
+                ),
             }
 
-            Err(error) => {
-                println!("FATAL ERROR OCCURED : {}", error);
+            (aggregate, parse_summary)
+        }
+        None => {
+            let (aggregate, parse_summary) = pipeline.run_with_summary()?;
 
-                std::process::exit(1);
-            }
-        };
-    }
-    // Check if any od the spawned threads paniced.
-    for handle in handles {
-        if let Err(e) = handle.join() {
-            if let Some(s) = e.downcast_ref::<&str>() {
-                println!("Panic message: {}", s);
-            } else if let Some(s) = e.downcast_ref::<String>() {
-                println!("Panic message: {}", s);
-            } else {
-                println!("Thread panicked with unknown type.");
-            }
+            print_parse_summary(&parse_summary);
 
-            std::process::exit(1);
+            (aggregate, parse_summary)
         }
-    }
+    };
 
-    println!("Final aggregate result : {:?}", aggregate);
+    eprintln!("Final aggregate result : {:?}", aggregate);
 
     let duration = start.elapsed();
 
-    println!("Done! Finished in {:.2?} seconds", duration);
+    eprintln!("Done! Finished in {:.2?} seconds", duration);
     /*
-     * Now find the correct formatter, and print the result.
+     * Now format the result, with the formatter from the CLI, and write it to the output file or the stdout.
      */
-    // This was already checked to be correct. We can safely unwrap here.
-    let formatter = FormatterFactory::resolve_formatter(cli_args.get_formatter()).unwrap();
-    let result = formatter.format(&aggregate);
+    pipeline.write_output(&aggregate, parse_summary.get_run_id())
+}
+
+/// *Parse all logs, and print the statistics of the run to the stdout, instead of the report*
+fn print_stats(cli_args: &CLIArgs) -> Result<(), PipelineError> {
+    let (aggregate, parse_summary) =
+        Pipeline::new(PipelineConfig::from(cli_args)).run_with_summary()?;
+
+    print_parse_summary(&parse_summary);
+
+    let mut metric_totals: std::collections::BTreeMap<&str, u128> =
+        std::collections::BTreeMap::new();
+    // Summed as u128, so the totals across all owners can't overflow.
+    for owner_usage in aggregate.values() {
+        for (metric_name, value) in owner_usage.get_metrics() {
+            *metric_totals.entry(metric_name.as_str()).or_default() += *value as u128;
+        }
+    }
+
+    let mut stats = format!(
+        "Files: {}\nLines: {}\nSkipped lines: {}\nFiltered lines: {}\nOwners: {}\n",
+        parse_summary.get_files().len(),
+        parse_summary.get_total_lines(),
+        parse_summary.get_skipped_lines(),
+        parse_summary.get_filtered_lines(),
+        aggregate.len()
+    );
+
+    for (metric_name, total) in metric_totals {
+        stats.push_str(&format!("Total {}: {}\n", metric_name, total));
+    }
+
+    std::io::stdout()
+        .lock()
+        .write_all(stats.as_bytes())
+        .map_err(PipelineError::Output)
+}
+
+/// *Check every line of the logs, and print the data quality report to the stdout*
+///
+/// ---
+///
+/// Exits with `ExitCode::InvalidData`, if any line was rejected. Nothing is aggregated or written.
+fn validate_logs(cli_args: &CLIArgs) -> Result<(), PipelineError> {
+    let validation_report = Pipeline::new(PipelineConfig::from(cli_args)).validate()?;
+    let mut stdout = std::io::stdout().lock();
+
+    stdout
+        .write_all(validation_report.to_string().as_bytes())
+        .and_then(|_| stdout.flush())
+        .map_err(PipelineError::Output)?;
+
+    if !validation_report.is_clean() {
+        std::process::exit(ExitCode::NotClean.code());
+    }
+
+    Ok(())
+}
+
+/// *Print the warnings and the parse summary of every file to the stderr*
+fn print_parse_summary(parse_summary: &ParseSummary) {
+    for non_utf8_path in parse_summary.get_non_utf8_paths() {
+        eprintln!(
+            "WARNING: Skipped a path which is not valid UTF-8: {}",
+            non_utf8_path.display()
+        );
+    }
+
+    for overflow_warning in parse_summary.get_overflow_warnings() {
+        eprintln!("WARNING: {}", overflow_warning);
+    }
+
+    eprintln!("Parse summary:");
+
+    for file_summary in parse_summary.get_files() {
+        eprintln!(
+            "  {}: {} lines, {} skipped, {} filtered",
+            file_summary.file,
+            file_summary.total_lines,
+            file_summary.skipped_lines,
+            file_summary.filtered_lines
+        );
+    }
+
+    if parse_summary.get_unchanged_files() > 0 {
+        eprintln!(
+            "  {} unchanged files since the previous run",
+            parse_summary.get_unchanged_files()
+        );
+    }
+}
+
+/// *Sum the reports of several runs, and write the combined report to the output file or the stdout*
+///
+/// ---
+///
+/// The reports are summed with the same overflow checks as the log files in a run. By default, an overflow fails the merge.
+fn merge_reports(merge_args: &MergeArgs) -> Result<(), PipelineError> {
+    // Fingerprinted before they are read, so the same reports always make the same run. Only if the sink needs the run id.
+    let run_id = if SinkFactory::needs_run_id(merge_args.get_sink()) {
+        let fingerprints = merge_args
+            .get_inputs()
+            .iter()
+            .map(|input| {
+                fingerprint_input(input).map_err(|error| PipelineError::InvalidAggregate {
+                    path: input.clone(),
+                    reason: error.to_string(),
+                })
+            })
+            .collect::<Result<Vec<String>, PipelineError>>()?;
+
+        Some(run_id_for_inputs("merge", &fingerprints))
+    } else {
+        None
+    };
+    let (aggregate, overflow_warnings) =
+        merge_aggregate_files(merge_args.get_inputs(), merge_args.get_overflow_policy())?;
+
+    for overflow_warning in overflow_warnings {
+        eprintln!("WARNING: {}", overflow_warning);
+    }
+
+    eprintln!(
+        "Merged {} reports, with {} owners",
+        merge_args.get_inputs().len(),
+        aggregate.len()
+    );
+
+    let pipeline = Pipeline::new(
+        PipelineConfig::default()
+            .with_formatter(merge_args.get_formatter())
+            .with_formatter_options(merge_args.get_formatter_options().clone())
+            .with_sink(merge_args.get_sink())
+            .with_sink_options(merge_args.get_sink_options().clone()),
+    );
+
+    pipeline.write_output(&aggregate, run_id.as_deref())
+}
+
+/// *Query the usage store, and write the owner usage to the stdout*
+///
+/// ---
+///
+/// An owner without any usage in the period is an empty report, not an error.
+fn print_owner_usage(db_query_args: &DbQueryArgs) -> Result<(), PipelineError> {
+    let formatter = FormatterFactory::resolve_formatter(db_query_args.get_formatter())
+        .map_err(PipelineError::UnknownFormatter)?;
+    let owner_usage = UsageStore::open(db_query_args.get_store())?
+        .query(db_query_args.get_owner(), db_query_args.get_period())?;
+    let aggregate: Aggregate = owner_usage
+        .map(|owner_usage| (db_query_args.get_owner(), owner_usage))
+        .into_iter()
+        .collect();
+
+    formatter
+        .format(&aggregate, &mut std::io::stdout().lock())
+        .map_err(PipelineError::Output)
+}
+
+/// *Follow the logs until SIGTERM, and write the formatted aggregate to the output file or the stdout on every flush*
+fn follow_logs(cli_args: &CLIArgs) -> Result<(), PipelineError> {
+    let termination_requested = install_termination_handler().unwrap_or_else(|error| {
+        eprintln!(
+            "FATAL ERROR OCCURED : Could not install the signal handler: {}",
+            error
+        );
+
+        std::process::exit(ExitCode::Failure.code());
+    });
+    let follower = Follower::new(Pipeline::new(PipelineConfig::from(cli_args)))
+        .with_flush_interval(std::time::Duration::from_secs(
+            cli_args.get_flush_interval(),
+        ));
+
+    follower.run(&termination_requested, &mut |aggregate, run_id| {
+        follower
+            .get_pipeline()
+            .write_output(aggregate, Some(run_id))
+    })?;
 
-    println!("OUTPUT:");
-    println!("{}", result);
+    Ok(())
 }
@@ -0,0 +1,152 @@
+//! Merge the reports of several runs (for example, one per datacenter) into a combined aggregate.
+//!
+//! Every report is read back with the aggregate reader (see `aggregate_reader.rs`), so it must be the json, ndjson, csv or tsv output of usage-parse.
+//! Reports can be compressed with gzip or zstd, and `-` is the standard input.
+//!
+//! The reports are summed exactly like the results of the log files in a run (see `merge_into_aggregate`),
+//! so an overflow is handled by the overflow policy, and by default it fails the merge.
+use super::super::formatters::aggregate_reader::read_aggregate;
+use super::super::log_parser_lib::compression::open_log_reader;
+use super::super::log_parser_lib::overflow_policy_enum::OverflowPolicy;
+use super::pipeline::{Aggregate, merge_into_aggregate};
+use super::pipeline_error::PipelineError;
+use std::io::Read;
+
+/// *Read every report, and sum them into a single aggregate*
+///
+/// ---
+///
+/// Returns the combined aggregate, and a warning for every metric that wrapped around (only with the `wrap-with-warning` policy).
+/// A report that can't be read, or is not valid, fails the whole merge. It's not safe to have some partial data of the usage.
+///
+/// ## Arguments
+///
+/// - `inputs` - Paths of the reports, or `-` for the standard input
+/// - `overflow_policy` - What to do on overflow
+///
+/// ## Example
+///
+/// ```
+/// let inputs = vec!["eu/usage.json".to_string(), "us/usage.json".to_string()];
+/// let (aggregate, overflow_warnings) = merge_aggregate_files(&inputs, OverflowPolicy::Error)?;
+/// ```
+pub fn merge_aggregate_files(
+    inputs: &[String],
+    overflow_policy: OverflowPolicy,
+) -> Result<(Aggregate, Vec<String>), PipelineError> {
+    let mut aggregate = Aggregate::new();
+    let mut overflow_warnings: Vec<String> = Vec::new();
+
+    for input in inputs {
+        let invalid_aggregate = |reason: String| PipelineError::InvalidAggregate {
+            path: input.to_string(),
+            reason,
+        };
+        let mut content = String::new();
+
+        open_log_reader(input)
+            .and_then(|mut reader| reader.read_to_string(&mut content))
+            .map_err(|error| match error.kind() {
+                std::io::ErrorKind::NotFound => PipelineError::NoFilesFound(input.to_string()),
+                _ => invalid_aggregate(error.to_string()),
+            })?;
+
+        let report = read_aggregate(&content).map_err(invalid_aggregate)?;
+
+        overflow_warnings.extend(merge_into_aggregate(
+            &mut aggregate,
+            report,
+            overflow_policy,
+        )?);
+    }
+
+    Ok((aggregate, overflow_warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+    use super::*;
+
+    #[test]
+    fn test_merge_aggregate_files() {
+        let test_reports = [
+            "test_merge_eu.json",
+            "test_merge_us.ndjson",
+            "test_merge_asia.csv",
+            "test_merge_invalid.txt",
+        ];
+
+        std::fs::write(
+            test_reports[0],
+            r#"[{"owner_id": 1, "usage": {"video_plays": 18446744073709551610}, "breakdown": {"video": [{"id": 7, "usage": {"video_plays": 2}}]}}]"#,
+        )
+        .unwrap();
+        std::fs::write(
+            test_reports[1],
+            "{\"owner_id\":1,\"usage\":{\"video_plays\":5},\"breakdown\":{\"video\":[{\"id\":7,\"usage\":{\"video_plays\":3}}]}}\n\
+             {\"owner_id\":2,\"usage\":{\"ad_impressions\":1}}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            test_reports[2],
+            "owner_id,ad_impressions,video_plays\r\n1,0,1\r\n2,4,0\r\ntotal,4,1\r\n",
+        )
+        .unwrap();
+        std::fs::write(test_reports[3], "Owner 1\n").unwrap();
+
+        let to_inputs = |reports: &[&str]| -> Vec<String> {
+            reports.iter().map(|report| report.to_string()).collect()
+        };
+        let merged = merge_aggregate_files(&to_inputs(&test_reports[..2]), OverflowPolicy::Error);
+        let overflow = merge_aggregate_files(&to_inputs(&test_reports[..3]), OverflowPolicy::Error);
+        let wrapped = merge_aggregate_files(
+            &to_inputs(&test_reports[..3]),
+            OverflowPolicy::WrapWithWarning,
+        );
+        let invalid = merge_aggregate_files(
+            &to_inputs(&[test_reports[0], test_reports[3]]),
+            OverflowPolicy::Error,
+        );
+        let missing = merge_aggregate_files(
+            &to_inputs(&["test_merge_missing.json"]),
+            OverflowPolicy::Error,
+        );
+
+        for test_report in test_reports {
+            std::fs::remove_file(test_report).unwrap();
+        }
+
+        let (aggregate, overflow_warnings) = merged.unwrap();
+
+        assert!(overflow_warnings.is_empty());
+        assert_eq!(aggregate[&1].get_metric("video_plays"), u64::MAX);
+        assert_eq!(
+            aggregate[&1]
+                .get_entity_usage("video", 7)
+                .map(|video_usage| video_usage.get_metric("video_plays")),
+            Some(5)
+        );
+        assert_eq!(
+            aggregate[&2],
+            OwnerUsage::from_metrics(&[("ad_impressions", 1)])
+        );
+        // Checked like the merge of the log files in a run.
+        assert!(matches!(
+            overflow,
+            Err(PipelineError::Overflow { owner: 1, ref metric }) if metric == "video_plays"
+        ));
+
+        let (aggregate, overflow_warnings) = wrapped.unwrap();
+
+        assert_eq!(aggregate[&1].get_metric("video_plays"), 0);
+        assert_eq!(aggregate[&2].get_metric("ad_impressions"), 5);
+        assert_eq!(overflow_warnings.len(), 1);
+        assert!(matches!(
+            invalid,
+            Err(PipelineError::InvalidAggregate { ref path, ref reason })
+                if path == test_reports[3] && reason.contains("Unknown format")
+        ));
+        assert!(matches!(missing, Err(PipelineError::NoFilesFound(_))));
+    }
+}
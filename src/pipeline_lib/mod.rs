@@ -0,0 +1,6 @@
+pub mod aggregate_merge;
+pub mod parse_summary_struct;
+pub mod pipeline;
+pub mod pipeline_error;
+pub mod run_id;
+pub mod validation_report_struct;
@@ -0,0 +1,133 @@
+use std::path::PathBuf;
+
+/// Struct representing how many lines were read and skipped, in every parsed file.
+///
+/// Lines are skipped only when the on-error policy is not `abort`.
+/// Lines are filtered out when they are outside of the `since` / `until` time window, or are unsuccessful access log hits. Those are not errors.
+///
+/// It also holds the paths found during the discovery, which could not be parsed, since they are not valid UTF-8.
+/// In the incremental mode, files that did not change since the previous run are not parsed at all, and are only counted.
+/// With the `wrap-with-warning` overflow policy, it also holds a warning for every metric that wrapped around.
+/// It also holds the identity of the run (see `run_id.rs`), which the report is delivered with, if the sink needs it.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ParseSummary {
+    files: Vec<FileSummary>,
+    non_utf8_paths: Vec<PathBuf>,
+    unchanged_files: usize,
+    overflow_warnings: Vec<String>,
+    run_id: Option<String>,
+}
+
+/// Lines read and skipped, for a single file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileSummary {
+    pub file: String,
+    pub total_lines: usize,
+    pub skipped_lines: usize,
+    pub filtered_lines: usize,
+}
+
+impl ParseSummary {
+    /// *Record the summary for a single file*
+    pub fn add_file(
+        &mut self,
+        file: &str,
+        total_lines: usize,
+        skipped_lines: usize,
+        filtered_lines: usize,
+    ) {
+        self.files.push(FileSummary {
+            file: file.to_string(),
+            total_lines,
+            skipped_lines,
+            filtered_lines,
+        });
+    }
+    /// *Record the paths that were skipped during the discovery, since they are not valid UTF-8*
+    pub fn set_non_utf8_paths(&mut self, non_utf8_paths: Vec<PathBuf>) {
+        self.non_utf8_paths = non_utf8_paths;
+    }
+    /// *Return the paths that were skipped during the discovery, since they are not valid UTF-8*
+    pub fn get_non_utf8_paths(&self) -> &[PathBuf] {
+        &self.non_utf8_paths
+    }
+    /// *Record how many files were not parsed, since they did not change since the previous run*
+    pub fn set_unchanged_files(&mut self, unchanged_files: usize) {
+        self.unchanged_files = unchanged_files;
+    }
+    /// *Return how many files were not parsed, since they did not change since the previous run*
+    pub fn get_unchanged_files(&self) -> usize {
+        self.unchanged_files
+    }
+    /// *Record warnings about the metrics that wrapped around*
+    pub fn add_overflow_warnings(&mut self, warnings: impl IntoIterator<Item = String>) {
+        self.overflow_warnings.extend(warnings);
+    }
+    /// *Return the warnings about the metrics that wrapped around*
+    pub fn get_overflow_warnings(&self) -> &[String] {
+        &self.overflow_warnings
+    }
+    /// *Record the identity of the run*
+    pub fn set_run_id(&mut self, run_id: String) {
+        self.run_id = Some(run_id);
+    }
+    /// *Return the identity of the run. None, if it was not needed (see `SinkFactory::needs_run_id`).*
+    pub fn get_run_id(&self) -> Option<&str> {
+        self.run_id.as_deref()
+    }
+    /// *Return the summaries of all files, sorted by the file path*
+    pub fn get_files(&self) -> Vec<&FileSummary> {
+        let mut files: Vec<&FileSummary> = self.files.iter().collect();
+
+        files.sort_by(|a, b| a.file.cmp(&b.file));
+
+        files
+    }
+    /// *Return the number of lines read, across all files*
+    pub fn get_total_lines(&self) -> usize {
+        self.files.iter().map(|file| file.total_lines).sum()
+    }
+    /// *Return the number of lines skipped, across all files*
+    pub fn get_skipped_lines(&self) -> usize {
+        self.files.iter().map(|file| file.skipped_lines).sum()
+    }
+    /// *Return the number of lines filtered out, across all files*
+    pub fn get_filtered_lines(&self) -> usize {
+        self.files.iter().map(|file| file.filtered_lines).sum()
+    }
+    /// *Return the share of the skipped lines, between 0 and 1*
+    ///
+    /// ---
+    ///
+    /// If no lines were read at all, the error rate is 0.
+    pub fn get_error_rate(&self) -> f64 {
+        let total_lines = self.get_total_lines();
+
+        if total_lines == 0 {
+            return 0.0;
+        }
+
+        self.get_skipped_lines() as f64 / total_lines as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_summary_totals() {
+        let mut summary = ParseSummary::default();
+
+        assert_eq!(summary.get_error_rate(), 0.0);
+
+        summary.add_file("logs/b.txt", 10, 1, 0);
+        summary.add_file("logs/a.txt", 30, 3, 5);
+
+        assert_eq!(summary.get_total_lines(), 40);
+        assert_eq!(summary.get_skipped_lines(), 4);
+        assert_eq!(summary.get_filtered_lines(), 5);
+        assert_eq!(summary.get_error_rate(), 0.1);
+        assert_eq!(summary.get_files()[0].file, "logs/a.txt");
+    }
+}
@@ -0,0 +1,1458 @@
+//! The whole usage parsing process, as a reusable library API.
+//!
+//! A Pipeline discovers all log files in a directory, parses them in parallel with a bounded worker pool,
+//! merges the partial results into a single aggregate, and formats it with the chosen formatter.
+//!
+//! The binary is just a thin CLI wrapper around it. Other services can use it directly, without shelling out to the binary.
+use super::super::arguments_lib::cli_args::CLIArgs;
+use super::super::formatters::formatter_factory::FormatterFactory;
+use super::super::formatters::formatter_options::FormatterOptions;
+use super::super::log_parser_lib::compression::STDIN_PATH;
+use super::super::log_parser_lib::dead_letter::DeadLetter;
+use super::super::log_parser_lib::input_format_enum::InputFormat;
+use super::super::log_parser_lib::log_parser::LogParser;
+use super::super::log_parser_lib::log_parser_error::LogParserError;
+use super::super::log_parser_lib::metric_schema::MetricSchema;
+use super::super::log_parser_lib::on_error_policy_enum::OnErrorPolicy;
+use super::super::log_parser_lib::overflow_policy_enum::OverflowPolicy;
+use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+use super::super::log_parser_lib::parse_options::ParseOptions;
+use super::super::log_parser_lib::time_bucket_enum::TimeBucket;
+use super::super::sinks::sink_factory::SinkFactory;
+use super::super::sinks::sink_options::SinkOptions;
+use super::super::state_lib::state_store::StateStore;
+use super::super::state_lib::usage_store::{RecordOutcome, UsageStore};
+use super::super::utils::fnv::fnv1a_64;
+use super::super::utils::fs_utils::{Discovery, DiscoveryOptions, discover_files, read_manifest};
+use super::super::utils::worker_pool::{resolve_number_of_workers, spawn_log_workers};
+use super::parse_summary_struct::ParseSummary;
+use super::pipeline_error::PipelineError;
+use super::run_id::{fingerprint_input, run_id_for_inputs, run_id_for_state};
+use super::validation_report_struct::ValidationReport;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Final usage, for every owner found in the logs, keyed by the owner id.
+pub type Aggregate = HashMap<u32, OwnerUsage>;
+
+/// Everything the Pipeline needs to know, in order to run.
+///
+/// Built with `PipelineConfig::new`, and optionally adjusted with the `with_*` methods.
+/// Log files can come from any number of directories, files given directly (`-` being the standard input), and a manifest file.
+#[derive(Debug, Clone)]
+pub struct PipelineConfig {
+    logs_dirs: Vec<String>,
+    inputs: Vec<String>,
+    files_from: Option<String>,
+    formatter: String,
+    formatter_options: FormatterOptions,
+    workers: Option<usize>,
+    input_format: InputFormat,
+    on_error: OnErrorPolicy,
+    overflow_policy: OverflowPolicy,
+    dead_letter: Option<String>,
+    max_error_rate: Option<f64>,
+    schema: Arc<MetricSchema>,
+    group_by: Vec<String>,
+    discovery: DiscoveryOptions,
+    bucket: Option<TimeBucket>,
+    since: Option<i64>,
+    until: Option<i64>,
+    incremental: Option<String>,
+    sink: String,
+    sink_options: SinkOptions,
+}
+
+impl Default for PipelineConfig {
+    /// A config without any inputs. Add them with `with_logs_dirs`, `with_inputs` or `with_files_from`.
+    fn default() -> Self {
+        Self {
+            logs_dirs: Vec::new(),
+            inputs: Vec::new(),
+            files_from: None,
+            formatter: String::from("stdout"),
+            formatter_options: FormatterOptions::default(),
+            workers: None,
+            input_format: InputFormat::Url,
+            on_error: OnErrorPolicy::Abort,
+            overflow_policy: OverflowPolicy::Error,
+            dead_letter: None,
+            max_error_rate: None,
+            schema: Arc::new(MetricSchema::default()),
+            group_by: Vec::new(),
+            discovery: DiscoveryOptions::default(),
+            bucket: None,
+            since: None,
+            until: None,
+            incremental: None,
+            sink: String::from("stdout"),
+            sink_options: SinkOptions::default(),
+        }
+    }
+}
+
+impl PipelineConfig {
+    /// *Create a new config, for a given logs directory*
+    ///
+    /// ---
+    ///
+    /// The formatter defaults to `stdout`, and the number of workers is decided depending on the CPU.
+    /// By default, the first malformed line aborts the whole run, and so does an overflow.
+    ///
+    /// ## Arguments
+    ///
+    /// - `logs_dir` - Directory with all the log files
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let config = PipelineConfig::new("logs").with_formatter("json").with_workers(Some(4));
+    /// ```
+    pub fn new(logs_dir: &str) -> Self {
+        Self::default().with_logs_dirs(vec![logs_dir.to_string()])
+    }
+    /// *Set the directories, where the log files are discovered*
+    pub fn with_logs_dirs(mut self, logs_dirs: Vec<String>) -> Self {
+        self.logs_dirs = logs_dirs;
+
+        self
+    }
+    /// *Set the log files to parse directly. `-` means the standard input.*
+    pub fn with_inputs(mut self, inputs: Vec<String>) -> Self {
+        self.inputs = inputs;
+
+        self
+    }
+    /// *Set the manifest file, listing the log files to parse*
+    pub fn with_files_from(mut self, files_from: Option<String>) -> Self {
+        self.files_from = files_from;
+
+        self
+    }
+    /// *Set the formatter, used by the `Pipeline::format` method*
+    pub fn with_formatter(mut self, formatter: &str) -> Self {
+        self.formatter = formatter.to_string();
+
+        self
+    }
+    /// *Set the options of the formatter, like the totals row*
+    pub fn with_formatter_options(mut self, formatter_options: FormatterOptions) -> Self {
+        self.formatter_options = formatter_options;
+
+        self
+    }
+    /// *Set the number of workers. None means one worker per available CPU.*
+    pub fn with_workers(mut self, workers: Option<usize>) -> Self {
+        self.workers = workers;
+
+        self
+    }
+    /// *Set the format of the log lines*
+    pub fn with_input_format(mut self, input_format: InputFormat) -> Self {
+        self.input_format = input_format;
+
+        self
+    }
+    /// *Set what happens when a malformed line is found*
+    pub fn with_on_error(mut self, on_error: OnErrorPolicy) -> Self {
+        self.on_error = on_error;
+
+        self
+    }
+    /// *Set what happens when adding to a metric would overflow, both while parsing and while merging*
+    pub fn with_overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+
+        self
+    }
+    /// *Set the dead-letter file, for the lines rejected in the quarantine mode*
+    pub fn with_dead_letter(mut self, dead_letter: Option<String>) -> Self {
+        self.dead_letter = dead_letter;
+
+        self
+    }
+    /// *Set the maximum share (between 0 and 1) of lines that can be rejected, before the run fails. None means no limit.*
+    pub fn with_max_error_rate(mut self, max_error_rate: Option<f64>) -> Self {
+        self.max_error_rate = max_error_rate;
+
+        self
+    }
+    /// *Set the metric schema, deciding which query keys are counted and how*
+    pub fn with_schema(mut self, schema: MetricSchema) -> Self {
+        self.schema = Arc::new(schema);
+
+        self
+    }
+    /// *Set the entities (from the schema), the usage is broken down by. Empty means usage per owner only.*
+    pub fn with_group_by(mut self, group_by: Vec<String>) -> Self {
+        self.group_by = group_by;
+
+        self
+    }
+    /// *Set the include / exclude globs and the symlink policy, used to find the log files*
+    pub fn with_discovery(mut self, discovery: DiscoveryOptions) -> Self {
+        self.discovery = discovery;
+
+        self
+    }
+    /// *Set the size of the time buckets. Without it, the usage is not bucketed by time.*
+    pub fn with_bucket(mut self, bucket: Option<TimeBucket>) -> Self {
+        self.bucket = bucket;
+
+        self
+    }
+    /// *Only count lines with a timestamp at or after this one (seconds since the Unix epoch)*
+    pub fn with_since(mut self, since: Option<i64>) -> Self {
+        self.since = since;
+
+        self
+    }
+    /// *Only count lines with a timestamp before this one (seconds since the Unix epoch)*
+    pub fn with_until(mut self, until: Option<i64>) -> Self {
+        self.until = until;
+
+        self
+    }
+    /// *Only parse what was appended since the previous run, and merge it into the aggregate, stored in the given state file*
+    pub fn with_incremental(mut self, incremental: Option<String>) -> Self {
+        self.incremental = incremental;
+
+        self
+    }
+    /// *Set the sink, used by the `Pipeline::write_output` method*
+    pub fn with_sink(mut self, sink: &str) -> Self {
+        self.sink = sink.to_string();
+
+        self
+    }
+    /// *Set the options of the sinks, like the output file or the webhook URL*
+    pub fn with_sink_options(mut self, sink_options: SinkOptions) -> Self {
+        self.sink_options = sink_options;
+
+        self
+    }
+    /// *Return the logs directory*
+    pub fn get_logs_dirs(&self) -> &[String] {
+        &self.logs_dirs
+    }
+    /// *Return the log files given directly*
+    pub fn get_inputs(&self) -> &[String] {
+        &self.inputs
+    }
+    /// *Return the manifest file*
+    pub fn get_files_from(&self) -> Option<&str> {
+        self.files_from.as_deref()
+    }
+    /// *Return the formatter name*
+    pub fn get_formatter(&self) -> &str {
+        &self.formatter
+    }
+    /// *Return the options of the formatter*
+    pub fn get_formatter_options(&self) -> &FormatterOptions {
+        &self.formatter_options
+    }
+    /// *Return the requested number of workers*
+    pub fn get_workers(&self) -> Option<usize> {
+        self.workers
+    }
+    /// *Return the on-error policy*
+    pub fn get_on_error(&self) -> OnErrorPolicy {
+        self.on_error
+    }
+    /// *Return the overflow policy*
+    pub fn get_overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+    /// *Return the format of the log lines*
+    pub fn get_input_format(&self) -> InputFormat {
+        self.input_format
+    }
+    /// *Return the dead-letter file path*
+    pub fn get_dead_letter(&self) -> Option<&str> {
+        self.dead_letter.as_deref()
+    }
+    /// *Return the maximum allowed error rate*
+    pub fn get_max_error_rate(&self) -> Option<f64> {
+        self.max_error_rate
+    }
+    /// *Return the metric schema*
+    pub fn get_schema(&self) -> &MetricSchema {
+        &self.schema
+    }
+    /// *Return the entities, the usage is broken down by*
+    pub fn get_group_by(&self) -> &[String] {
+        &self.group_by
+    }
+    /// *Return the options, used to find the log files*
+    pub fn get_discovery(&self) -> &DiscoveryOptions {
+        &self.discovery
+    }
+    /// *Return the size of the time buckets*
+    pub fn get_bucket(&self) -> Option<TimeBucket> {
+        self.bucket
+    }
+    /// *Return the inclusive start of the time window*
+    pub fn get_since(&self) -> Option<i64> {
+        self.since
+    }
+    /// *Return the exclusive end of the time window*
+    pub fn get_until(&self) -> Option<i64> {
+        self.until
+    }
+    /// *Return the incremental state file, if the runs are incremental*
+    pub fn get_incremental(&self) -> Option<&str> {
+        self.incremental.as_deref()
+    }
+    /// *Return the sink name*
+    pub fn get_sink(&self) -> &str {
+        &self.sink
+    }
+    /// *Return the options of the sinks*
+    pub fn get_sink_options(&self) -> &SinkOptions {
+        &self.sink_options
+    }
+    /// *Fingerprint everything that changes how the lines are counted*
+    ///
+    /// ---
+    ///
+    /// An incremental state can only be continued with the same fingerprint. Otherwise the stored and the new usage would not add up.
+    pub fn get_settings_fingerprint(&self) -> u64 {
+        let settings = format!(
+            "{:?}|{:?}|{:?}|{:?}|{:?}|{:?}",
+            self.schema, self.group_by, self.input_format, self.bucket, self.since, self.until
+        );
+
+        fnv1a_64(settings.as_bytes())
+    }
+}
+
+impl From<&CLIArgs> for PipelineConfig {
+    fn from(cli_args: &CLIArgs) -> PipelineConfig {
+        PipelineConfig::default()
+            .with_logs_dirs(cli_args.get_logs_dirs().to_vec())
+            .with_inputs(cli_args.get_inputs().to_vec())
+            .with_files_from(cli_args.get_files_from().cloned())
+            .with_formatter(cli_args.get_formatter())
+            .with_formatter_options(
+                FormatterOptions::default()
+                    .with_totals(cli_args.get_totals())
+                    .with_compact(cli_args.get_compact()),
+            )
+            .with_workers(cli_args.get_workers())
+            .with_on_error(cli_args.get_on_error())
+            .with_overflow_policy(cli_args.get_overflow_policy())
+            .with_dead_letter(cli_args.get_dead_letter().cloned())
+            .with_max_error_rate(cli_args.get_max_error_rate())
+            .with_schema(cli_args.get_schema().clone())
+            .with_group_by(cli_args.get_group_by().to_vec())
+            .with_discovery(cli_args.get_discovery().clone())
+            .with_input_format(cli_args.get_input_format())
+            .with_bucket(cli_args.get_bucket())
+            .with_since(cli_args.get_since())
+            .with_until(cli_args.get_until())
+            .with_incremental(cli_args.get_incremental().cloned())
+            .with_sink(cli_args.get_sink())
+            .with_sink_options(cli_args.get_sink_options().clone())
+    }
+}
+
+pub struct Pipeline {
+    config: PipelineConfig,
+}
+
+/// Result of parsing a batch of log files.
+struct ParsedFiles {
+    aggregate: Aggregate,
+    parse_summary: ParseSummary,
+    end_offsets: HashMap<String, u64>,
+}
+
+impl Pipeline {
+    /// *Construct a new Pipeline, with a given config*
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let pipeline = Pipeline::new(PipelineConfig::new("logs"));
+    /// ```
+    pub fn new(config: PipelineConfig) -> Self {
+        Self { config }
+    }
+    /// *Return the config the Pipeline was constructed with*
+    pub fn get_config(&self) -> &PipelineConfig {
+        &self.config
+    }
+    /// *Find all log files that should be parsed*
+    ///
+    /// ---
+    ///
+    /// Every logs directory is walked recursively, see `fs_utils.rs` for the filters and the symlink policy.
+    /// Then come the files given directly, and those listed in the manifest file. Filters don't apply to those.
+    ///
+    /// Returns full paths of the files. A file found more than once is returned only once, so it's not counted twice.
+    /// It is an error if a directory does not exist, or there are no files to parse at all.
+    pub fn discover(&self) -> Result<Discovery, PipelineError> {
+        let mut log_files: Vec<String> = Vec::new();
+        let mut non_utf8_paths: Vec<PathBuf> = Vec::new();
+
+        for logs_dir in self.config.get_logs_dirs() {
+            let mut discovery = match discover_files(logs_dir, self.config.get_discovery()) {
+                Ok(discovery) => discovery,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(PipelineError::NoFilesFound(logs_dir.to_string()));
+                }
+                Err(error) => {
+                    return Err(PipelineError::Discovery {
+                        path: logs_dir.to_string(),
+                        error,
+                    });
+                }
+            };
+
+            log_files.extend(discovery.take_files());
+            non_utf8_paths.extend_from_slice(discovery.get_non_utf8_paths());
+        }
+
+        log_files.extend_from_slice(self.config.get_inputs());
+
+        if let Some(files_from) = self.config.get_files_from() {
+            let manifest_files =
+                read_manifest(files_from).map_err(|error| PipelineError::Discovery {
+                    path: files_from.to_string(),
+                    error,
+                })?;
+
+            log_files.extend(manifest_files);
+        }
+
+        let mut seen: HashSet<String> = HashSet::new();
+
+        log_files.retain(|log_file| seen.insert(log_file.clone()));
+
+        if log_files.is_empty() {
+            return Err(PipelineError::NoFilesFound(self.describe_inputs()));
+        }
+
+        Ok(Discovery::new(log_files, non_utf8_paths))
+    }
+    /// *Describe all inputs from the config, for the error messages*
+    fn describe_inputs(&self) -> String {
+        let mut inputs: Vec<&str> = self
+            .config
+            .get_logs_dirs()
+            .iter()
+            .chain(self.config.get_inputs())
+            .map(|input| input.as_str())
+            .collect();
+
+        inputs.extend(self.config.get_files_from());
+
+        inputs.join(", ")
+    }
+    /// *Discover, parse and merge all log files into a single aggregate*
+    ///
+    /// ---
+    ///
+    /// Every log file must be parsed successfully. If that's not the case, the whole run fails.
+    /// It's not safe to have some partial data of the usage.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let pipeline = Pipeline::new(PipelineConfig::new("logs"));
+    /// let aggregate = pipeline.run()?;
+    /// ```
+    pub fn run(&self) -> Result<Aggregate, PipelineError> {
+        self.run_with_summary()
+            .map(|(aggregate, _parse_summary)| aggregate)
+    }
+    /// *Same as `run`, but also return how many lines were read and skipped in every file*
+    ///
+    /// ---
+    ///
+    /// Malformed lines are handled depending on the on-error policy from the config.
+    /// If the share of the skipped lines is above the maximum allowed error rate, the whole run fails.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let pipeline = Pipeline::new(PipelineConfig::new("logs").with_on_error(OnErrorPolicy::Skip));
+    /// let (aggregate, parse_summary) = pipeline.run_with_summary()?;
+    /// ```
+    pub fn run_with_summary(&self) -> Result<(Aggregate, ParseSummary), PipelineError> {
+        self.validate_config()?;
+
+        if let Some(state_path) = self.config.get_incremental() {
+            let state_error = |reason: String| PipelineError::State {
+                path: state_path.to_string(),
+                reason,
+            };
+            let mut state = StateStore::load(state_path)
+                .map_err(state_error)?
+                .unwrap_or_else(|| StateStore::new(self.config.get_settings_fingerprint()));
+            let parse_summary = self.run_incremental(&mut state)?;
+            // Nothing is stored, unless the whole run succeeded. The next run then starts from the same checkpoints.
+            state
+                .save(state_path)
+                .map_err(|error| state_error(error.to_string()))?;
+
+            return Ok((state.into_aggregate(), parse_summary));
+        }
+
+        let mut discovery = self.discover()?;
+        let log_files = discovery.take_files();
+        // Fingerprinted before they are parsed, since they could still grow. Only if the sink delivers the report with the run id.
+        let run_id = if SinkFactory::needs_run_id(self.config.get_sink()) {
+            let fingerprints = log_files
+                .iter()
+                .map(|log_file| {
+                    fingerprint_input(log_file).map_err(|error| LogParserError::Io {
+                        file: log_file.clone(),
+                        error,
+                    })
+                })
+                .collect::<Result<Vec<String>, LogParserError>>()?;
+
+            Some(run_id_for_inputs(
+                &format!("{:016x}", self.config.get_settings_fingerprint()),
+                &fingerprints,
+            ))
+        } else {
+            None
+        };
+        let parsed_files = self.parse_files(log_files, None)?;
+        let mut parse_summary = parsed_files.parse_summary;
+
+        parse_summary.set_non_utf8_paths(discovery.get_non_utf8_paths().to_vec());
+
+        if let Some(run_id) = run_id {
+            parse_summary.set_run_id(run_id);
+        }
+
+        Ok((parsed_files.aggregate, parse_summary))
+    }
+    /// *Check every line of all log files, without aggregating anything (a dry run)*
+    ///
+    /// ---
+    ///
+    /// Lines go through the same checks as in `run`, but all malformed lines are collected into the report, regardless of the on-error policy.
+    /// The time window is not applied, so every line is checked. Nothing is written: no output, dead-letter file, state or usage store.
+    /// Files are validated one by one. Only a file that can't be read fails the validation.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let validation_report = Pipeline::new(PipelineConfig::new("logs")).validate()?;
+    ///
+    /// if !validation_report.is_clean() {
+    ///     println!("{}", validation_report);
+    /// }
+    /// ```
+    pub fn validate(&self) -> Result<ValidationReport, PipelineError> {
+        self.validate_config()?;
+
+        let mut discovery = self.discover()?;
+        let parse_options = ParseOptions::default()
+            .with_input_format(self.config.get_input_format())
+            .with_schema(Arc::clone(&self.config.schema));
+        let mut validation_report = ValidationReport::default();
+
+        for log_file in discovery.take_files() {
+            let file_validation = LogParser::with_options(&log_file, parse_options.clone())
+                .validate()
+                .map_err(PipelineError::Parse)?;
+
+            validation_report.add_file(file_validation);
+        }
+
+        validation_report.set_non_utf8_paths(discovery.get_non_utf8_paths().to_vec());
+
+        Ok(validation_report)
+    }
+    /// *Parse only what was appended since the given state, and merge it into the state*
+    ///
+    /// ---
+    ///
+    /// Used by the incremental runs (with the state stored in a file), and by the follow mode (with the state kept in memory).
+    /// Only complete lines are parsed. See `state_store.rs` for how renamed, truncated and compressed files are handled.
+    /// On error, the state must not be saved, since it could be partially updated.
+    ///
+    /// ## Arguments
+    ///
+    /// - `state` - State after the previous run. Use `StateStore::new(config.get_settings_fingerprint())` for the first one.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let pipeline = Pipeline::new(PipelineConfig::new("logs"));
+    /// let mut state = StateStore::new(pipeline.get_config().get_settings_fingerprint());
+    /// let parse_summary = pipeline.run_incremental(&mut state)?;
+    /// ```
+    pub fn run_incremental(&self, state: &mut StateStore) -> Result<ParseSummary, PipelineError> {
+        self.validate_config()?;
+
+        if state.get_settings() != self.config.get_settings_fingerprint() {
+            return Err(PipelineError::State {
+                path: self.config.get_incremental().unwrap_or_default().to_string(),
+                reason: "It was created with different settings (schema, group by, input format, bucket or time window). Use the same settings, or another state file"
+                    .to_string(),
+            });
+        }
+
+        let mut discovery = self.discover()?;
+        let log_files = discovery.take_files();
+
+        if log_files.iter().any(|log_file| log_file == STDIN_PATH) {
+            return Err(PipelineError::InvalidConfig(
+                "The standard input can't be read incrementally".to_string(),
+            ));
+        }
+
+        let state_error = |error: std::io::Error| PipelineError::State {
+            path: self
+                .config
+                .get_incremental()
+                .unwrap_or_default()
+                .to_string(),
+            reason: error.to_string(),
+        };
+        let plan = state.plan(&log_files).map_err(state_error)?;
+        let parsed_files =
+            self.parse_files(plan.get_files_to_parse(), Some(plan.get_start_offsets()))?;
+        let mut parse_summary = parsed_files.parse_summary;
+
+        parse_summary.set_non_utf8_paths(discovery.get_non_utf8_paths().to_vec());
+        parse_summary.set_unchanged_files(plan.get_unchanged_files());
+        // Built before anything is merged into the state, so a failure leaves the state as it was.
+        let checkpoints = plan
+            .into_checkpoints(&parsed_files.end_offsets)
+            .map_err(state_error)?;
+        let overflow_warnings = merge_into_aggregate(
+            state.get_aggregate_mut(),
+            parsed_files.aggregate,
+            self.config.get_overflow_policy(),
+        )?;
+
+        parse_summary.add_overflow_warnings(overflow_warnings);
+        state.set_checkpoints(checkpoints);
+        parse_summary.set_run_id(run_id_for_state(state));
+
+        Ok(parse_summary)
+    }
+    /// *Parse only what was not recorded in the usage store yet, and record it*
+    ///
+    /// ---
+    ///
+    /// An incremental run, that starts from the checkpoints in the store instead of a state file (see `usage_store.rs`).
+    /// Returns the usage of this run only, and whether it was recorded. The totals of all runs are in the store.
+    ///
+    /// ## Arguments
+    ///
+    /// - `usage_store` - The store to record the run into
+    /// - `started_at` - When the run started, in seconds since the Unix epoch
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let mut usage_store = UsageStore::open("usage.db")?;
+    /// let (aggregate, parse_summary, record_outcome) = pipeline.run_into_store(&mut usage_store, started_at)?;
+    /// ```
+    pub fn run_into_store(
+        &self,
+        usage_store: &mut UsageStore,
+        started_at: i64,
+    ) -> Result<(Aggregate, ParseSummary, RecordOutcome), PipelineError> {
+        let previous_checkpoints = usage_store.get_checkpoints()?;
+        let mut state = StateStore::new(self.config.get_settings_fingerprint());
+
+        state.set_checkpoints(previous_checkpoints.clone());
+
+        let parse_summary = self.run_incremental(&mut state)?;
+        let record_outcome = usage_store.record_run(
+            &previous_checkpoints,
+            state.get_checkpoints(),
+            state.get_aggregate(),
+            started_at,
+        )?;
+
+        Ok((state.into_aggregate(), parse_summary, record_outcome))
+    }
+    /// *Check the parts of the config, that can't be checked while building it*
+    fn validate_config(&self) -> Result<(), PipelineError> {
+        for entity_name in self.config.get_group_by() {
+            if self.config.get_schema().get_entity(entity_name).is_none() {
+                return Err(PipelineError::InvalidConfig(format!(
+                    "Unknown entity to group by: {}",
+                    entity_name
+                )));
+            }
+        }
+
+        if let (Some(since), Some(until)) = (self.config.get_since(), self.config.get_until())
+            && since >= until
+        {
+            return Err(PipelineError::InvalidConfig(
+                "The start of the time window must be before its end".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+    /// *Parse the given log files with the worker pool, and merge the results*
+    ///
+    /// ---
+    ///
+    /// ## Arguments
+    ///
+    /// - `log_files` - Full paths of the files to parse
+    /// - `start_offsets` - Where to start reading the files, in an incremental run. None for a full run.
+    fn parse_files(
+        &self,
+        log_files: Vec<String>,
+        start_offsets: Option<HashMap<String, u64>>,
+    ) -> Result<ParsedFiles, PipelineError> {
+        let incremental = start_offsets.is_some();
+        let number_of_workers = resolve_number_of_workers(self.config.get_workers());
+        let dead_letter = self.create_dead_letter(incremental)?;
+        let parse_options = ParseOptions::default()
+            .with_input_format(self.config.get_input_format())
+            .with_on_error(self.config.get_on_error())
+            .with_overflow_policy(self.config.get_overflow_policy())
+            .with_dead_letter(dead_letter.clone())
+            .with_schema(Arc::clone(&self.config.schema))
+            .with_group_by(self.config.get_group_by().to_vec())
+            .with_bucket(self.config.get_bucket())
+            .with_since(self.config.get_since())
+            .with_until(self.config.get_until())
+            .with_start_offsets(Arc::new(start_offsets.unwrap_or_default()))
+            // A line without the new line character could still be being written. It is left for the next run.
+            .with_complete_lines_only(incremental);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut aggregate: Aggregate = HashMap::new();
+        let mut parse_summary = ParseSummary::default();
+        let mut end_offsets: HashMap<String, u64> = HashMap::new();
+        /*
+         * tx is moved into the pool, and dropped once all workers are done.
+         * So rx will not block indefinitely.
+         */
+        let handles = spawn_log_workers(log_files, number_of_workers, parse_options, tx);
+        let mut first_error: Option<PipelineError> = None;
+        /*
+         * Results are comming in here. Each of them must succeed.
+         * Keep draining the channel on error, so the workers can finish and be joined.
+         */
+        for log_parser_result in rx {
+            if first_error.is_some() {
+                continue;
+            }
+
+            let merge_result = match log_parser_result {
+                Ok(mut log_parse_report) => {
+                    parse_summary.add_file(
+                        log_parse_report.get_file(),
+                        log_parse_report.get_total_lines(),
+                        log_parse_report.get_skipped_lines(),
+                        log_parse_report.get_filtered_lines(),
+                    );
+                    end_offsets.insert(
+                        log_parse_report.get_file().to_string(),
+                        log_parse_report.get_offset(),
+                    );
+                    parse_summary
+                        .add_overflow_warnings(log_parse_report.get_overflow_warnings().to_vec());
+
+                    merge_into_aggregate(
+                        &mut aggregate,
+                        log_parse_report.take_usage(),
+                        self.config.get_overflow_policy(),
+                    )
+                    .map(|overflow_warnings| parse_summary.add_overflow_warnings(overflow_warnings))
+                }
+                Err(error) => Err(PipelineError::from(error)),
+            };
+
+            if let Err(error) = merge_result {
+                first_error = Some(error);
+            }
+        }
+        // Check if any of the spawned threads panicked.
+        for handle in handles {
+            if let Err(e) = handle.join() {
+                let message = if let Some(s) = e.downcast_ref::<&str>() {
+                    s.to_string()
+                } else if let Some(s) = e.downcast_ref::<String>() {
+                    s.clone()
+                } else {
+                    "Thread panicked with unknown type.".to_string()
+                };
+
+                return Err(PipelineError::WorkerPanic(message));
+            }
+        }
+
+        if let Some(error) = first_error {
+            return Err(error);
+        }
+
+        if let Some(dead_letter) = dead_letter
+            && let Err(error) = dead_letter.flush()
+        {
+            return Err(PipelineError::DeadLetter {
+                path: dead_letter.get_path().to_string(),
+                error,
+            });
+        }
+
+        if let Some(max_error_rate) = self.config.get_max_error_rate()
+            && parse_summary.get_error_rate() > max_error_rate
+        {
+            return Err(PipelineError::ErrorBudgetExceeded {
+                skipped_lines: parse_summary.get_skipped_lines(),
+                total_lines: parse_summary.get_total_lines(),
+                max_error_rate,
+            });
+        }
+
+        Ok(ParsedFiles {
+            aggregate,
+            parse_summary,
+            end_offsets,
+        })
+    }
+    /// *Create the dead-letter file, if the quarantine mode is on*
+    ///
+    /// In an incremental run, the rejected lines are appended, since the earlier ones are not parsed again.
+    fn create_dead_letter(&self, append: bool) -> Result<Option<Arc<DeadLetter>>, PipelineError> {
+        if self.config.get_on_error() != OnErrorPolicy::Quarantine {
+            return Ok(None);
+        }
+
+        let path = self.config.get_dead_letter().unwrap_or("dead_letter.txt");
+
+        let dead_letter = if append {
+            DeadLetter::open_append(path)
+        } else {
+            DeadLetter::create(path)
+        };
+
+        match dead_letter {
+            Ok(dead_letter) => Ok(Some(Arc::new(dead_letter))),
+            Err(error) => Err(PipelineError::DeadLetter {
+                path: path.to_string(),
+                error,
+            }),
+        }
+    }
+    /// *Format the aggregate with the formatter from the config, streaming it into a writer*
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let pipeline = Pipeline::new(PipelineConfig::new("logs").with_formatter("json"));
+    /// let aggregate = pipeline.run()?;
+    ///
+    /// pipeline.format(&aggregate, &mut std::io::stdout().lock())?;
+    /// ```
+    pub fn format(
+        &self,
+        aggregate: &Aggregate,
+        writer: &mut dyn std::io::Write,
+    ) -> Result<(), PipelineError> {
+        let formatter = FormatterFactory::resolve_formatter_with_options(
+            self.config.get_formatter(),
+            self.config.get_formatter_options(),
+        )
+        .map_err(PipelineError::UnknownFormatter)?;
+
+        formatter
+            .format(aggregate, writer)
+            .map_err(PipelineError::Output)
+    }
+    /// *Format the aggregate with the formatter from the config, and deliver it with the sink from the config*
+    ///
+    /// ---
+    ///
+    /// See the `sinks` module, for where the aggregate can go (the stdout, a file, a webhook or a database).
+    /// The identity of the run that produced the aggregate (see `run_id.rs`) lets the receiver tell the re-sent reports apart from the new ones.
+    ///
+    /// ## Arguments
+    ///
+    /// - `aggregate` - Aggregate to deliver
+    /// - `run_id` - Identity of the run, for example `ParseSummary::get_run_id`. None means a new identity, for the sinks that need one.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let pipeline = Pipeline::new(
+    ///     PipelineConfig::new("logs")
+    ///         .with_sink("file")
+    ///         .with_sink_options(SinkOptions::default().with_output(Some("usage.json".to_string()))),
+    /// );
+    /// let (aggregate, parse_summary) = pipeline.run_with_summary()?;
+    ///
+    /// pipeline.write_output(&aggregate, parse_summary.get_run_id())?;
+    /// ```
+    pub fn write_output(
+        &self,
+        aggregate: &Aggregate,
+        run_id: Option<&str>,
+    ) -> Result<(), PipelineError> {
+        let formatter = FormatterFactory::resolve_formatter_with_options(
+            self.config.get_formatter(),
+            self.config.get_formatter_options(),
+        )
+        .map_err(PipelineError::UnknownFormatter)?;
+        let sink_options = self
+            .config
+            .get_sink_options()
+            .clone()
+            .with_run_id(run_id.map(|run_id| run_id.to_string()));
+        let sink = SinkFactory::resolve_sink(self.config.get_sink(), &sink_options)
+            .map_err(PipelineError::UnknownSink)?;
+
+        sink.write(aggregate, formatter.as_ref())
+    }
+}
+
+/// *Merge the usage parsed from a single log file into the aggregate*
+///
+/// ---
+///
+/// Every addition is checked, and an overflow on any metric is handled by the overflow policy.
+/// With the `error` policy, it fails the merge, and nothing of that owner is merged.
+/// With `wrap-with-warning`, a warning is returned for every metric that wrapped around.
+///
+/// ## Arguments
+///
+/// - `aggregate` - Aggregate usage, for all files merged so far
+/// - `owner_usage_hash_map` - Usage parsed from a single log file
+/// - `overflow_policy` - What to do on overflow
+pub fn merge_into_aggregate(
+    aggregate: &mut Aggregate,
+    owner_usage_hash_map: HashMap<u32, OwnerUsage>,
+    overflow_policy: OverflowPolicy,
+) -> Result<Vec<String>, PipelineError> {
+    let mut overflow_warnings: Vec<String> = Vec::new();
+
+    for (owner_id, owner_usage) in owner_usage_hash_map {
+        let entry = aggregate.entry(owner_id).or_default();
+        let overflowed = entry
+            .merge_with_policy(&owner_usage, overflow_policy)
+            .map_err(|metric| PipelineError::Overflow {
+                owner: owner_id,
+                metric,
+            })?;
+
+        if overflow_policy == OverflowPolicy::WrapWithWarning {
+            overflow_warnings.extend(overflowed.into_iter().map(|metric| {
+                format!(
+                    "Overflow when merging {} for owner {}, the value wrapped around",
+                    metric, owner_id
+                )
+            }));
+        }
+    }
+
+    Ok(overflow_warnings)
+}
+
+/// *Run the whole pipeline for a given config, and return the aggregate*
+///
+/// ---
+///
+/// Shorthand for `Pipeline::new(config).run()`.
+///
+/// ## Example
+///
+/// ```
+/// let aggregate = usage_parse::run(PipelineConfig::new("logs"))?;
+/// ```
+pub fn run(config: PipelineConfig) -> Result<Aggregate, PipelineError> {
+    Pipeline::new(config).run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::Digest;
+    use std::io::Write;
+
+    #[test]
+    fn test_pipeline_run_and_format() {
+        let test_log_dir = "test_pipeline_dir";
+
+        std::fs::create_dir(test_log_dir).unwrap();
+
+        for (i, owner_id) in [123, 123, 444].iter().enumerate() {
+            let mut file_handle =
+                std::fs::File::create(format!("{}/log{}.txt", test_log_dir, i)).unwrap();
+
+            writeln!(
+                file_handle,
+                "https://www.mysite.com/pixel.gif?o={}&v=2222&i=555",
+                owner_id
+            )
+            .unwrap();
+        }
+
+        let pipeline = Pipeline::new(
+            PipelineConfig::new(test_log_dir)
+                .with_formatter("json")
+                .with_workers(Some(2)),
+        );
+        let aggregate = pipeline.run();
+        let stdout_summary = pipeline.run_with_summary();
+        let sqlite_summary =
+            Pipeline::new(PipelineConfig::new(test_log_dir).with_sink("sqlite")).run_with_summary();
+
+        std::fs::remove_dir_all(test_log_dir).unwrap();
+
+        let aggregate = aggregate.unwrap();
+
+        // The inputs are only fingerprinted for the sinks, that deliver the report with the run id.
+        assert_eq!(stdout_summary.unwrap().1.get_run_id(), None);
+        assert_eq!(
+            sqlite_summary
+                .unwrap()
+                .1
+                .get_run_id()
+                .map(|run_id| run_id.len()),
+            Some(64)
+        );
+        assert_eq!(aggregate.len(), 2);
+        assert_eq!(aggregate.get(&123).unwrap().get_metric("video_plays"), 2);
+        assert_eq!(aggregate.get(&123).unwrap().get_metric("ad_impressions"), 2);
+        assert_eq!(aggregate.get(&444).unwrap().get_metric("video_plays"), 1);
+
+        let mut output: Vec<u8> = Vec::new();
+
+        pipeline.format(&aggregate, &mut output).unwrap();
+
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .contains("\"owner_id\": 444")
+        );
+    }
+
+    #[test]
+    fn test_pipeline_multiple_inputs() {
+        let test_log_dirs = ["test_pipeline_inputs_dir1", "test_pipeline_inputs_dir2"];
+        let test_manifest_path = "test_pipeline_inputs_manifest.txt";
+
+        for (i, test_log_dir) in test_log_dirs.iter().enumerate() {
+            std::fs::create_dir(test_log_dir).unwrap();
+            std::fs::write(
+                format!("{}/log.txt", test_log_dir),
+                format!("https://www.mysite.com/pixel.gif?o={}&v=1\n", i + 1),
+            )
+            .unwrap();
+        }
+
+        // The first file is also listed directly, and in the manifest, but must be counted only once.
+        std::fs::write(
+            test_manifest_path,
+            "# rotated\ntest_pipeline_inputs_dir1/log.txt\ntest_pipeline_inputs_dir2/log.txt\n",
+        )
+        .unwrap();
+
+        let pipeline = Pipeline::new(
+            PipelineConfig::default()
+                .with_logs_dirs(test_log_dirs.iter().map(|dir| dir.to_string()).collect())
+                .with_inputs(vec!["test_pipeline_inputs_dir1/log.txt".to_string()])
+                .with_files_from(Some(test_manifest_path.to_string())),
+        );
+        let result = pipeline.run_with_summary();
+        for test_log_dir in test_log_dirs {
+            std::fs::remove_dir_all(test_log_dir).unwrap();
+        }
+
+        // The manifest now lists files that don't exist anymore.
+        let missing_files_result = Pipeline::new(
+            PipelineConfig::default().with_files_from(Some(test_manifest_path.to_string())),
+        )
+        .run();
+
+        std::fs::remove_file(test_manifest_path).unwrap();
+
+        let (aggregate, parse_summary) = result.unwrap();
+
+        assert_eq!(parse_summary.get_files().len(), 2);
+        assert_eq!(aggregate.get(&1).unwrap().get_metric("video_plays"), 1);
+        assert_eq!(aggregate.get(&2).unwrap().get_metric("video_plays"), 1);
+        assert!(matches!(missing_files_result, Err(PipelineError::Parse(_))));
+        assert!(matches!(
+            Pipeline::new(PipelineConfig::default()).run(),
+            Err(PipelineError::NoFilesFound(_))
+        ));
+        assert!(matches!(
+            Pipeline::new(
+                PipelineConfig::default().with_files_from(Some(test_manifest_path.to_string()))
+            )
+            .run(),
+            Err(PipelineError::Discovery { .. })
+        ));
+    }
+
+    #[test]
+    fn test_pipeline_incremental_runs() {
+        let test_log_dir = "test_pipeline_incremental_dir";
+        let test_state_path = "test_pipeline_incremental.state";
+        let test_log_file = format!("{}/access.log", test_log_dir);
+        let config = PipelineConfig::new(test_log_dir)
+            .with_workers(Some(2))
+            .with_incremental(Some(test_state_path.to_string()));
+        let append = |content: &str| {
+            let mut file_handle = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&test_log_file)
+                .unwrap();
+
+            file_handle.write_all(content.as_bytes()).unwrap();
+        };
+
+        std::fs::create_dir(test_log_dir).unwrap();
+        // The last line is still being written.
+        append("https://x/p.gif?o=1&v=1\nhttps://x/p.gif?o=1&v=1\nhttps://x/p.gif?o=2&v=");
+
+        let first_result = Pipeline::new(config.clone()).run_with_summary();
+
+        append("1\nhttps://x/p.gif?o=1&i=1\n");
+
+        let second_result = Pipeline::new(config.clone()).run_with_summary();
+        let third_result = Pipeline::new(config.clone()).run_with_summary();
+        // Rotated: the file is renamed, and a new one is started.
+        std::fs::rename(&test_log_file, format!("{}/access.log.1", test_log_dir)).unwrap();
+        append("https://x/p.gif?o=3&v=1\n");
+
+        let rotated_result = Pipeline::new(config.clone()).run();
+        let changed_settings_result =
+            Pipeline::new(config.clone().with_group_by(vec!["video".to_string()])).run();
+        let stdin_result = Pipeline::new(config.clone().with_inputs(vec!["-".to_string()])).run();
+
+        std::fs::remove_dir_all(test_log_dir).unwrap();
+        std::fs::remove_file(test_state_path).unwrap();
+
+        let (aggregate, parse_summary) = first_result.unwrap();
+
+        assert_eq!(aggregate.get(&1).unwrap().get_metric("video_plays"), 2);
+        assert!(!aggregate.contains_key(&2));
+        assert_eq!(parse_summary.get_total_lines(), 2);
+
+        // Only the new lines are parsed, but the whole aggregate is returned.
+        let (aggregate, parse_summary) = second_result.unwrap();
+
+        assert_eq!(parse_summary.get_total_lines(), 2);
+        assert_eq!(aggregate.get(&1).unwrap().get_metric("video_plays"), 2);
+        assert_eq!(aggregate.get(&1).unwrap().get_metric("ad_impressions"), 1);
+        assert_eq!(aggregate.get(&2).unwrap().get_metric("video_plays"), 1);
+
+        let (aggregate, parse_summary) = third_result.unwrap();
+
+        assert_eq!(parse_summary.get_unchanged_files(), 1);
+        assert!(parse_summary.get_files().is_empty());
+        assert_eq!(aggregate.get(&1).unwrap().get_metric("video_plays"), 2);
+
+        let aggregate = rotated_result.unwrap();
+
+        assert_eq!(aggregate.get(&1).unwrap().get_metric("video_plays"), 2);
+        assert_eq!(aggregate.get(&3).unwrap().get_metric("video_plays"), 1);
+
+        assert!(matches!(
+            changed_settings_result,
+            Err(PipelineError::State { .. })
+        ));
+        assert!(matches!(stdin_result, Err(PipelineError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_pipeline_incremental_new_file_with_the_same_start() {
+        let test_log_dir = "test_pipeline_incremental_same_start_dir";
+        let test_state_path = "test_pipeline_incremental_same_start.state";
+        let line = "https://x/p.gif?o=1&v=1\n";
+        let config =
+            PipelineConfig::new(test_log_dir).with_incremental(Some(test_state_path.to_string()));
+
+        std::fs::create_dir(test_log_dir).unwrap();
+        std::fs::write(format!("{}/a.log", test_log_dir), line.repeat(2)).unwrap();
+
+        let first_result = Pipeline::new(config.clone()).run();
+
+        // A new file, that starts with the same lines as a.log, is not a copy of it.
+        std::fs::write(format!("{}/b.log", test_log_dir), line.repeat(3)).unwrap();
+
+        let second_result = Pipeline::new(config.clone()).run();
+
+        std::fs::remove_dir_all(test_log_dir).unwrap();
+        std::fs::remove_file(test_state_path).unwrap();
+
+        assert_eq!(
+            first_result
+                .unwrap()
+                .get(&1)
+                .unwrap()
+                .get_metric("video_plays"),
+            2
+        );
+        assert_eq!(
+            second_result
+                .unwrap()
+                .get(&1)
+                .unwrap()
+                .get_metric("video_plays"),
+            5
+        );
+    }
+
+    #[test]
+    fn test_pipeline_run_into_store() {
+        let test_log_dir = "test_pipeline_store_dir";
+        let test_log_file = format!("{}/access.log", test_log_dir);
+        let test_db = format!("{}/usage.db", test_log_dir);
+        let config = PipelineConfig::default().with_inputs(vec![test_log_file.clone()]);
+        let append = |content: &str| {
+            let mut file_handle = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&test_log_file)
+                .unwrap();
+
+            file_handle.write_all(content.as_bytes()).unwrap();
+        };
+
+        std::fs::create_dir(test_log_dir).unwrap();
+        append("https://x/p.gif?o=1&v=1\nhttps://x/p.gif?o=1&v=1\n");
+
+        let mut usage_store = UsageStore::open(&test_db).unwrap();
+        let first_result = Pipeline::new(config.clone()).run_into_store(&mut usage_store, 100);
+
+        // The log grew between the runs.
+        append("https://x/p.gif?o=1&v=1\n");
+
+        let second_result = Pipeline::new(config.clone()).run_into_store(&mut usage_store, 200);
+        let third_result = Pipeline::new(config.clone()).run_into_store(&mut usage_store, 300);
+        let totals = usage_store.query(1, None);
+
+        std::fs::remove_dir_all(test_log_dir).unwrap();
+
+        let (aggregate, _, record_outcome) = first_result.unwrap();
+
+        assert_eq!(aggregate.get(&1).unwrap().get_metric("video_plays"), 2);
+        assert!(matches!(record_outcome, RecordOutcome::Recorded(_)));
+
+        // Only the appended line is added.
+        let (aggregate, parse_summary, record_outcome) = second_result.unwrap();
+
+        assert_eq!(parse_summary.get_total_lines(), 1);
+        assert_eq!(aggregate.get(&1).unwrap().get_metric("video_plays"), 1);
+        assert!(matches!(record_outcome, RecordOutcome::Recorded(_)));
+
+        let (aggregate, _, record_outcome) = third_result.unwrap();
+
+        assert!(aggregate.is_empty());
+        assert_eq!(record_outcome, RecordOutcome::NothingNew);
+        assert_eq!(totals.unwrap().unwrap().get_metric("video_plays"), 3);
+    }
+
+    #[test]
+    fn test_pipeline_errors() {
+        let pipeline = Pipeline::new(PipelineConfig::new("test_pipeline_missing_dir"));
+
+        assert!(matches!(
+            pipeline.run(),
+            Err(PipelineError::NoFilesFound(_))
+        ));
+
+        let pipeline =
+            Pipeline::new(PipelineConfig::new("logs").with_group_by(vec!["player".to_string()]));
+
+        assert!(matches!(
+            pipeline.run(),
+            Err(PipelineError::InvalidConfig(_))
+        ));
+
+        let pipeline = Pipeline::new(
+            PipelineConfig::new("logs")
+                .with_since(Some(100))
+                .with_until(Some(100)),
+        );
+
+        assert!(matches!(
+            pipeline.run(),
+            Err(PipelineError::InvalidConfig(_))
+        ));
+
+        let pipeline = Pipeline::new(PipelineConfig::new("logs").with_formatter("unknown"));
+
+        assert!(matches!(
+            pipeline.format(&HashMap::new(), &mut Vec::new()),
+            Err(PipelineError::UnknownFormatter(_))
+        ));
+    }
+
+    #[test]
+    fn test_merge_into_aggregate_overflow() {
+        let mut aggregate: Aggregate = HashMap::new();
+        let mut first = HashMap::new();
+        let mut second = HashMap::new();
+
+        first.insert(
+            1,
+            OwnerUsage::from_metrics(&[("ad_impressions", u64::MAX), ("video_plays", 1)]),
+        );
+        second.insert(
+            1,
+            OwnerUsage::from_metrics(&[("ad_impressions", 2), ("video_plays", 1)]),
+        );
+
+        assert!(merge_into_aggregate(&mut aggregate, first, OverflowPolicy::Error).is_ok());
+        assert!(matches!(
+            merge_into_aggregate(&mut aggregate, second.clone(), OverflowPolicy::Error),
+            Err(PipelineError::Overflow { owner: 1, ref metric }) if metric == "ad_impressions"
+        ));
+        // Nothing from the overflowing usage is merged.
+        assert_eq!(aggregate.get(&1).unwrap().get_metric("video_plays"), 1);
+
+        let mut saturated = aggregate.clone();
+
+        assert_eq!(
+            merge_into_aggregate(&mut saturated, second.clone(), OverflowPolicy::Saturate).unwrap(),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            saturated.get(&1).unwrap().get_metric("ad_impressions"),
+            u64::MAX
+        );
+        assert_eq!(saturated.get(&1).unwrap().get_metric("video_plays"), 2);
+        assert_eq!(
+            merge_into_aggregate(&mut aggregate, second, OverflowPolicy::WrapWithWarning).unwrap(),
+            vec![
+                "Overflow when merging ad_impressions for owner 1, the value wrapped around"
+                    .to_string()
+            ]
+        );
+        assert_eq!(aggregate.get(&1).unwrap().get_metric("ad_impressions"), 1);
+    }
+
+    #[test]
+    fn test_pipeline_error_budget() {
+        let test_log_dir = "test_pipeline_budget_dir";
+        let test_dead_letter_path = "test_pipeline_budget_dead_letter.txt";
+
+        std::fs::create_dir(test_log_dir).unwrap();
+        std::fs::write(
+            format!("{}/log1.txt", test_log_dir),
+            "https://x/p?o=1&v=1\nhttps://x/p?v=1\nhttps://x/p?o=1&v=1\nhttps://x/p?o=1&v=1\n",
+        )
+        .unwrap();
+        std::fs::write(
+            format!("{}/log2.txt", test_log_dir),
+            "https://x/p?o=2&v=1\n",
+        )
+        .unwrap();
+
+        let abort_result = Pipeline::new(PipelineConfig::new(test_log_dir)).run();
+        let skip_result = Pipeline::new(
+            PipelineConfig::new(test_log_dir)
+                .with_on_error(OnErrorPolicy::Skip)
+                .with_max_error_rate(Some(0.2)),
+        )
+        .run_with_summary();
+        let over_budget_result = Pipeline::new(
+            PipelineConfig::new(test_log_dir)
+                .with_on_error(OnErrorPolicy::Quarantine)
+                .with_dead_letter(Some(test_dead_letter_path.to_string()))
+                .with_max_error_rate(Some(0.1)),
+        )
+        .run();
+        let dead_letter_content = std::fs::read_to_string(test_dead_letter_path).unwrap();
+
+        std::fs::remove_dir_all(test_log_dir).unwrap();
+        std::fs::remove_file(test_dead_letter_path).unwrap();
+
+        assert!(matches!(abort_result, Err(PipelineError::Parse(_))));
+
+        let (aggregate, parse_summary) = skip_result.unwrap();
+
+        assert_eq!(aggregate.get(&1).unwrap().get_metric("video_plays"), 3);
+        assert_eq!(parse_summary.get_total_lines(), 5);
+        assert_eq!(parse_summary.get_skipped_lines(), 1);
+        assert_eq!(parse_summary.get_files()[0].skipped_lines, 1);
+        assert_eq!(parse_summary.get_files()[1].skipped_lines, 0);
+
+        assert!(matches!(
+            over_budget_result,
+            Err(PipelineError::ErrorBudgetExceeded {
+                skipped_lines: 1,
+                total_lines: 5,
+                ..
+            })
+        ));
+        assert_eq!(dead_letter_content.lines().count(), 1);
+    }
+
+    #[test]
+    fn test_pipeline_write_output() {
+        let test_dir = "test_pipeline_output_dir";
+        let test_output = format!("{}/usage.csv", test_dir);
+        let mut aggregate: Aggregate = HashMap::new();
+
+        aggregate.insert(1, OwnerUsage::from_metrics(&[("video_plays", 2)]));
+        std::fs::create_dir(test_dir).unwrap();
+
+        let result = Pipeline::new(
+            PipelineConfig::new("logs")
+                .with_formatter("csv")
+                .with_sink("file")
+                .with_sink_options(
+                    SinkOptions::default()
+                        .with_output(Some(test_output.clone()))
+                        .with_checksum(true),
+                ),
+        )
+        .write_output(&aggregate, Some("run-1"));
+        let content = std::fs::read_to_string(&test_output);
+        let checksum = std::fs::read_to_string(format!("{}.sha256", test_output));
+        let files = std::fs::read_dir(test_dir).unwrap().count();
+        let missing_dir_result = Pipeline::new(
+            PipelineConfig::new("logs")
+                .with_sink("file")
+                .with_sink_options(
+                    SinkOptions::default()
+                        .with_output(Some("test_pipeline_output_missing/usage.txt".to_string())),
+                ),
+        )
+        .write_output(&aggregate, Some("run-1"));
+
+        std::fs::remove_dir_all(test_dir).unwrap();
+
+        result.unwrap();
+
+        let content = content.unwrap();
+
+        assert_eq!(content, "owner_id,video_plays\r\n1,2\r\n");
+        assert_eq!(
+            checksum.unwrap(),
+            format!(
+                "{:x}  usage.csv\n",
+                sha2::Sha256::digest(content.as_bytes())
+            )
+        );
+        // Just the output and the checksum, no temporary files are left behind.
+        assert_eq!(files, 2);
+        assert!(matches!(
+            missing_dir_result,
+            Err(PipelineError::OutputFile { .. })
+        ));
+
+        let unknown_sink_result = Pipeline::new(PipelineConfig::new("logs").with_sink("webhook"))
+            .write_output(&aggregate, Some("run-1"));
+
+        assert!(matches!(
+            unknown_sink_result,
+            Err(PipelineError::UnknownSink(_))
+        ));
+    }
+}
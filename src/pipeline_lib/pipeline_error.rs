@@ -0,0 +1,117 @@
+//! Custom error, returned by the Pipeline.
+//!
+//! Wraps all errors that can happen during discovery, parsing, merging and formatting.
+//! Check the enum bellow.
+use super::super::log_parser_lib::log_parser_error::LogParserError;
+
+#[derive(Debug)]
+pub enum PipelineError {
+    /// A logs directory does not exist, or there are no files to parse at all.
+    NoFilesFound(String),
+    /// A logs directory (or one of its subdirectories), or the manifest file, could not be read.
+    Discovery { path: String, error: std::io::Error },
+    /// One of the log files could not be parsed.
+    /// It's not safe to have some partial data of the usage, so the whole run fails.
+    Parse(LogParserError),
+    /// Merging the results of two log files would overflow the given usage metric, for the given owner.
+    Overflow { owner: u32, metric: String },
+    /// One of the worker threads panicked. Contains the panic message, if it could be extracted.
+    WorkerPanic(String),
+    /// The incremental state file could not be read or written, or it does not fit the config.
+    State { path: String, reason: String },
+    /// The config is not valid, for example grouping by an entity that is not in the schema.
+    InvalidConfig(String),
+    /// The requested formatter does not exist.
+    UnknownFormatter(String),
+    /// The formatted output could not be written (for example, the stdout was closed).
+    Output(std::io::Error),
+    /// The dead-letter file could not be created, or the rejected lines could not be written to it.
+    DeadLetter { path: String, error: std::io::Error },
+    /// The output file (or its checksum file) could not be written. The previous one, if any, is left as it was.
+    OutputFile { path: String, error: std::io::Error },
+    /// The requested sink does not exist, or it is missing a required option.
+    UnknownSink(String),
+    /// The webhook did not accept the report, even after all the retries.
+    Webhook { url: String, reason: String },
+    /// The aggregate could not be written to the database. Nothing was written, since it is done in a single transaction.
+    Database { path: String, reason: String },
+    /// An aggregate file, given to the merge, could not be read, or it is not the output of our formatters.
+    InvalidAggregate { path: String, reason: String },
+    /// Too many lines were rejected, compared to the allowed error rate.
+    ErrorBudgetExceeded {
+        skipped_lines: usize,
+        total_lines: usize,
+        max_error_rate: f64,
+    },
+}
+
+impl From<LogParserError> for PipelineError {
+    fn from(err: LogParserError) -> PipelineError {
+        match err {
+            LogParserError::DeadLetter { file, error } => {
+                PipelineError::DeadLetter { path: file, error }
+            }
+            err => PipelineError::Parse(err),
+        }
+    }
+}
+
+impl std::fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PipelineError::NoFilesFound(inputs) => {
+                write!(f, "No files found in the given inputs: {}", inputs)
+            }
+            PipelineError::Discovery { path, error } => {
+                write!(f, "Could not read the logs in: {}: {}", path, error)
+            }
+            PipelineError::Parse(e) => write!(f, "Log parsing error: {}", e),
+            PipelineError::Overflow { owner, metric } => write!(
+                f,
+                "Possible overflow occured when adding {} for owner {}!",
+                metric, owner
+            ),
+            PipelineError::WorkerPanic(msg) => write!(f, "Worker panicked: {}", msg),
+            PipelineError::State { path, reason } => {
+                write!(f, "Incremental state error: {}: {}", path, reason)
+            }
+            PipelineError::InvalidConfig(msg) => write!(f, "Invalid config: {}", msg),
+            PipelineError::UnknownFormatter(msg) => write!(f, "{}", msg),
+            PipelineError::Output(error) => write!(f, "Could not write the output: {}", error),
+            PipelineError::DeadLetter { path, error } => {
+                write!(
+                    f,
+                    "Could not write the dead-letter file: {}: {}",
+                    path, error
+                )
+            }
+            PipelineError::OutputFile { path, error } => {
+                write!(f, "Could not write the output file: {}: {}", path, error)
+            }
+            PipelineError::UnknownSink(msg) => write!(f, "{}", msg),
+            PipelineError::Webhook { url, reason } => {
+                write!(f, "Webhook error: {}: {}", url, reason)
+            }
+            PipelineError::Database { path, reason } => {
+                write!(f, "Database error: {}: {}", path, reason)
+            }
+            PipelineError::InvalidAggregate { path, reason } => {
+                write!(f, "Could not read the aggregate: {}: {}", path, reason)
+            }
+            PipelineError::ErrorBudgetExceeded {
+                skipped_lines,
+                total_lines,
+                max_error_rate,
+            } => write!(
+                f,
+                "Too many rejected lines: {} of {} ({:.4}), while the maximum allowed error rate is {}",
+                skipped_lines,
+                total_lines,
+                *skipped_lines as f64 / (*total_lines).max(1) as f64,
+                max_error_rate
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
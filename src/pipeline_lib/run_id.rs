@@ -0,0 +1,151 @@
+//! Identity of a run, so its report can be told apart from the reports of other runs.
+//!
+//! The webhook sink sends it as the `Idempotency-Key`, and the sqlite sink keys its rows by it. A re-run over the same inputs, with the same settings, has the same identity,
+//! so the receiver can ignore the duplicate. A run over other inputs, or another time window, has another identity,
+//! even if it produced exactly the same report (for example, an empty one).
+//!
+//! Every input file is fingerprinted by its identity (device and inode), size, modification time, and the fingerprint of its first bytes
+//! (see `file_checkpoint_struct.rs`). An incremental run is identified by its whole state, so by everything read so far.
+//! The standard input can't be fingerprinted, so a run that reads it always has a new identity.
+use super::super::log_parser_lib::compression::STDIN_PATH;
+use super::super::state_lib::file_checkpoint_struct::FileCheckpoint;
+use super::super::state_lib::state_store::StateStore;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Tells apart the unique identities created by the same process, in the same nanosecond.
+static UNIQUE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// *Fingerprint a single input file*
+///
+/// ---
+///
+/// The standard input can't be fingerprinted, so it gets a new fingerprint every time.
+///
+/// ## Arguments
+///
+/// - `file` - Path of the input file, or `-` for the standard input
+pub fn fingerprint_input(file: &str) -> std::io::Result<String> {
+    if file == STDIN_PATH {
+        return Ok(unique_run_id());
+    }
+
+    let checkpoint = FileCheckpoint::read(file)?;
+    let (device, inode) = checkpoint.get_identity();
+    let (fingerprint, fingerprint_len) = checkpoint.get_fingerprint();
+
+    Ok(format!(
+        "{} {} {} {} {:016x} {} {}",
+        device,
+        inode,
+        checkpoint.get_size(),
+        checkpoint.get_mtime(),
+        fingerprint,
+        fingerprint_len,
+        file
+    ))
+}
+
+/// *Return the identity of a run over the given inputs*
+///
+/// ---
+///
+/// ## Arguments
+///
+/// - `settings` - Everything else that changes the report, for example the settings fingerprint (with the time window)
+/// - `fingerprints` - Fingerprints of all input files (see `fingerprint_input`), in any order
+///
+/// ## Example
+///
+/// ```
+/// let fingerprints = log_files.iter().map(|log_file| fingerprint_input(log_file)).collect::<std::io::Result<Vec<String>>>()?;
+/// let run_id = run_id_for_inputs(&format!("{:016x}", config.get_settings_fingerprint()), &fingerprints);
+/// ```
+pub fn run_id_for_inputs(settings: &str, fingerprints: &[String]) -> String {
+    let mut sorted_fingerprints: Vec<&String> = fingerprints.iter().collect();
+    let mut hasher = Sha256::new();
+
+    sorted_fingerprints.sort();
+    hasher.update(settings.as_bytes());
+
+    for fingerprint in sorted_fingerprints {
+        hasher.update(b"\n");
+        hasher.update(fingerprint.as_bytes());
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+/// *Return the identity of an incremental run, from the state after it*
+///
+/// ---
+///
+/// The state holds the settings, and the checkpoint of every file read so far, so two runs have the same identity
+/// only if they read exactly the same bytes.
+pub fn run_id_for_state(state: &StateStore) -> String {
+    format!("{:x}", Sha256::digest(state.to_string().as_bytes()))
+}
+
+/// *Return an identity, which no other run has*
+///
+/// ---
+///
+/// Used when the inputs can't be fingerprinted (the standard input), or when the report is not written by a run.
+pub fn unique_run_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos());
+    let unique = format!(
+        "{}-{}-{}",
+        nanos,
+        std::process::id(),
+        UNIQUE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+
+    format!("{:x}", Sha256::digest(unique.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_ids() {
+        let test_log_dir = "test_run_id_dir";
+        let path = |name: &str| format!("{}/{}", test_log_dir, name);
+
+        std::fs::create_dir(test_log_dir).unwrap();
+        std::fs::write(path("a.log"), "").unwrap();
+        std::fs::write(path("b.log"), "").unwrap();
+
+        let a = fingerprint_input(&path("a.log")).unwrap();
+        let b = fingerprint_input(&path("b.log")).unwrap();
+        let missing = fingerprint_input(&path("missing.log"));
+
+        std::fs::remove_dir_all(test_log_dir).unwrap();
+
+        let run_id = run_id_for_inputs("window-1", &[a.clone(), b.clone()]);
+
+        assert_eq!(run_id.len(), 64);
+        assert_eq!(
+            run_id,
+            run_id_for_inputs("window-1", &[b.clone(), a.clone()])
+        );
+        // The same (empty) report, from other inputs or another window, is another run.
+        assert_ne!(
+            run_id,
+            run_id_for_inputs("window-2", &[a.clone(), b.clone()])
+        );
+        assert_ne!(run_id, run_id_for_inputs("window-1", &[a]));
+        assert!(missing.is_err());
+        assert_ne!(
+            fingerprint_input(STDIN_PATH).unwrap(),
+            fingerprint_input(STDIN_PATH).unwrap()
+        );
+        assert_ne!(
+            run_id_for_state(&StateStore::new(1)),
+            run_id_for_state(&StateStore::new(2))
+        );
+    }
+}
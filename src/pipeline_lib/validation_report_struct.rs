@@ -0,0 +1,193 @@
+use super::super::log_parser_lib::file_validation_struct::FileValidation;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Struct representing the data quality of all log files, as checked by `Pipeline::validate`.
+///
+/// The data is clean, when no line in any of the files was rejected.
+/// Unknown query keys are only reported, see `file_validation_struct.rs`.
+/// It is shown as a plain text report, with the `Display` implementation.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ValidationReport {
+    files: Vec<FileValidation>,
+    non_utf8_paths: Vec<PathBuf>,
+}
+
+impl ValidationReport {
+    /// *Record the validation of a single file*
+    pub fn add_file(&mut self, file_validation: FileValidation) {
+        self.files.push(file_validation);
+    }
+    /// *Record the paths that were skipped during the discovery, since they are not valid UTF-8*
+    pub fn set_non_utf8_paths(&mut self, non_utf8_paths: Vec<PathBuf>) {
+        self.non_utf8_paths = non_utf8_paths;
+    }
+    /// *Return the paths that were skipped during the discovery, since they are not valid UTF-8*
+    pub fn get_non_utf8_paths(&self) -> &[PathBuf] {
+        &self.non_utf8_paths
+    }
+    /// *Return the validation of every file, in the order they were validated*
+    pub fn get_files(&self) -> &[FileValidation] {
+        &self.files
+    }
+    /// *Return the number of lines read from all files*
+    pub fn get_total_lines(&self) -> usize {
+        self.files.iter().map(|file| file.get_total_lines()).sum()
+    }
+    /// *Return the number of lines rejected with an error, in all files*
+    pub fn get_invalid_lines(&self) -> usize {
+        self.files.iter().map(|file| file.get_invalid_lines()).sum()
+    }
+    /// *Return the query keys that are not in the metric schema, with the number of lines they were found in, across all files*
+    pub fn get_unknown_query_keys(&self) -> BTreeMap<&str, usize> {
+        let mut unknown_query_keys: BTreeMap<&str, usize> = BTreeMap::new();
+
+        for file in &self.files {
+            for (key, lines) in file.get_unknown_query_keys() {
+                *unknown_query_keys.entry(key.as_str()).or_default() += lines;
+            }
+        }
+
+        unknown_query_keys
+    }
+    /// *Check if no line in any of the files was rejected*
+    pub fn is_clean(&self) -> bool {
+        self.files.iter().all(|file| file.is_clean())
+    }
+}
+
+impl std::fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Validation report:")?;
+
+        for file in &self.files {
+            writeln!(
+                f,
+                "  {}: {} lines, {} valid, {} invalid, {} filtered",
+                file.get_file(),
+                file.get_total_lines(),
+                file.get_valid_lines(),
+                file.get_invalid_lines(),
+                file.get_filtered_lines()
+            )?;
+
+            for (category, error_category) in file.get_errors() {
+                writeln!(f, "    {}: {} lines", category, error_category.lines)?;
+
+                for sample in &error_category.samples {
+                    writeln!(f, "      line {}", sample)?;
+                }
+            }
+
+            if !file.get_unknown_query_keys().is_empty() {
+                writeln!(
+                    f,
+                    "    unknown query keys: {}",
+                    describe_unknown_query_keys(
+                        file.get_unknown_query_keys()
+                            .iter()
+                            .map(|(key, lines)| (key.as_str(), *lines))
+                    )
+                )?;
+            }
+        }
+
+        for non_utf8_path in &self.non_utf8_paths {
+            writeln!(
+                f,
+                "  Skipped a path which is not valid UTF-8: {}",
+                non_utf8_path.display()
+            )?;
+        }
+
+        let unknown_query_keys = self.get_unknown_query_keys();
+
+        if !unknown_query_keys.is_empty() {
+            writeln!(
+                f,
+                "Unknown query keys (not in the metric schema, ignored): {}",
+                describe_unknown_query_keys(unknown_query_keys.into_iter())
+            )?;
+        }
+
+        if self.is_clean() {
+            writeln!(
+                f,
+                "Result: clean, all {} lines in {} files are valid",
+                self.get_total_lines(),
+                self.files.len()
+            )
+        } else {
+            writeln!(
+                f,
+                "Result: not clean, {} of {} lines are invalid, in {} of {} files",
+                self.get_invalid_lines(),
+                self.get_total_lines(),
+                self.files.iter().filter(|file| !file.is_clean()).count(),
+                self.files.len()
+            )
+        }
+    }
+}
+
+/// *Describe the unknown query keys, like `ref (2 lines), utm_source (1 lines)`*
+fn describe_unknown_query_keys<'a>(
+    unknown_query_keys: impl Iterator<Item = (&'a str, usize)>,
+) -> String {
+    unknown_query_keys
+        .map(|(key, lines)| format!("{} ({} lines)", key, lines))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::log_parser_lib::log_parser::LogParser;
+    use super::*;
+
+    #[test]
+    fn test_validation_report() {
+        let mut validation_report = ValidationReport::default();
+        let log_parser = LogParser::new("logs/a.txt");
+
+        validation_report.add_file(
+            log_parser
+                .validate_reader("https://www.mysite.com/pixel.gif?o=1&v=2&ref=1\n".as_bytes())
+                .unwrap(),
+        );
+
+        assert!(validation_report.is_clean());
+        assert!(
+            validation_report
+                .to_string()
+                .ends_with("Result: clean, all 1 lines in 1 files are valid\n")
+        );
+
+        let log_parser = LogParser::new("logs/b.txt");
+
+        validation_report.add_file(
+            log_parser
+                .validate_reader(
+                    "https://www.mysite.com/pixel.gif?o=x&ref=2\nhttps://www.mysite.com/pixel.gif?o=1\n"
+                        .as_bytes(),
+                )
+                .unwrap(),
+        );
+
+        assert!(!validation_report.is_clean());
+        assert_eq!(validation_report.get_total_lines(), 3);
+        assert_eq!(
+            validation_report.to_string(),
+            "Validation report:
+  logs/a.txt: 1 lines, 1 valid, 0 invalid, 0 filtered
+    unknown query keys: ref (1 lines)
+  logs/b.txt: 2 lines, 1 valid, 1 invalid, 0 filtered
+    invalid param: 1 lines
+      line 1: Invalid value for parameter \"o\": \"x\": https://www.mysite.com/pixel.gif?o=x&ref=2
+    unknown query keys: ref (1 lines)
+Unknown query keys (not in the metric schema, ignored): ref (2 lines)
+Result: not clean, 1 of 3 lines are invalid, in 1 of 2 files
+"
+        );
+    }
+}
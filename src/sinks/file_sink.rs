@@ -0,0 +1,114 @@
+//! Write the formatted aggregate to a file, atomically.
+//!
+//! The file is written to a temporary sibling, flushed to the disk and renamed into place (see `atomic_file.rs`),
+//! so whoever watches it never sees a partial report.
+//! With the checksum option, `<output>.sha256` is written (also atomically) right after it, in the `sha256sum` format.
+//! The hash is computed while the output is streamed, so the file is never read back.
+use super::super::formatters::formatter_trait::Formatter;
+use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+use super::super::pipeline_lib::pipeline_error::PipelineError;
+use super::super::utils::atomic_file::{AtomicFile, write_atomically};
+use super::sink_trait::Sink;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+pub struct FileSink {
+    path: String,
+    checksum: bool,
+}
+
+impl FileSink {
+    /// *Create a file sink, with or without the checksum file*
+    pub fn new(path: &str, checksum: bool) -> Self {
+        Self {
+            path: path.to_string(),
+            checksum,
+        }
+    }
+    fn output_error(&self, error: std::io::Error) -> PipelineError {
+        PipelineError::OutputFile {
+            path: self.path.clone(),
+            error,
+        }
+    }
+}
+
+impl Sink for FileSink {
+    /// Stream the formatted aggregate into the file, and only then replace the previous one.
+    ///
+    /// ---
+    ///
+    /// For the Arguments and Example, see [`Sink`] trait.
+    fn write(
+        &self,
+        aggregate: &HashMap<u32, OwnerUsage>,
+        formatter: &dyn Formatter,
+    ) -> Result<(), PipelineError> {
+        let atomic_file =
+            AtomicFile::create(&self.path).map_err(|error| self.output_error(error))?;
+        let mut writer = std::io::BufWriter::new(Sha256Writer {
+            writer: atomic_file,
+            hasher: Sha256::new(),
+        });
+
+        formatter
+            .format(aggregate, &mut writer)
+            .map_err(|error| self.output_error(error))?;
+
+        let Sha256Writer {
+            writer: atomic_file,
+            hasher,
+        } = writer
+            .into_inner()
+            .map_err(|error| self.output_error(error.into_error()))?;
+        let hash = format!("{:x}", hasher.finalize());
+
+        atomic_file
+            .commit()
+            .map_err(|error| self.output_error(error))?;
+
+        if self.checksum {
+            let file_name = Path::new(&self.path)
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy())
+                .unwrap_or_default();
+            let checksum_path = format!("{}.sha256", self.path);
+
+            write_atomically(
+                &checksum_path,
+                format!("{}  {}\n", hash, file_name).as_bytes(),
+            )
+            .map_err(|error| PipelineError::OutputFile {
+                path: checksum_path,
+                error,
+            })?;
+        }
+
+        Ok(())
+    }
+    /// @see [`Sink`] trait.
+    fn identifier(&self) -> &'static str {
+        "file"
+    }
+}
+
+/// Wraps a writer, and hashes everything that is written through it.
+struct Sha256Writer<W: Write> {
+    writer: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for Sha256Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        // Only what the inner writer accepted, the rest is written again by the caller.
+        self.hasher.update(&buf[..written]);
+
+        Ok(written)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,8 @@
+mod file_sink;
+mod sqlite_sink;
+mod stdout_sink;
+mod webhook_sink;
+
+pub mod sink_factory;
+pub mod sink_options;
+pub mod sink_trait;
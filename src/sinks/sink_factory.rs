@@ -0,0 +1,102 @@
+//! A factory struct, to return a correct Sink instance, depending on the CLI arguments.
+use super::file_sink::FileSink;
+use super::sink_options::SinkOptions;
+use super::sink_trait::Sink;
+use super::sqlite_sink::SqliteSink;
+use super::stdout_sink::StdoutSink;
+use super::webhook_sink::WebhookSink;
+
+pub struct SinkFactory {}
+
+/// Names of all sinks, in the order they are listed in the help text.
+const AVAILABLE_SINKS: [&str; 4] = ["stdout", "file", "webhook", "sqlite"];
+
+impl SinkFactory {
+    /// *Return the names of all sinks, that `resolve_sink` accepts*
+    ///
+    /// ---
+    ///
+    /// When adding a sink, add its name here too, so it shows up in the help text.
+    pub fn available_sinks() -> &'static [&'static str] {
+        &AVAILABLE_SINKS
+    }
+    /// *Check if a sink delivers the report with the identity of the run (see `run_id.rs`)*
+    ///
+    /// ---
+    ///
+    /// The identity of a run fingerprints all its inputs, so it is only worth it for the sinks that use it.
+    pub fn needs_run_id(sink_from_cli: &str) -> bool {
+        matches!(sink_from_cli, "webhook" | "sqlite")
+    }
+    /// *Method for resolving and returing a correct instance of Sink.*
+    ///
+    /// ---
+    ///
+    /// All available sinks should be here.
+    /// Returns an error if the sink is unknown, or if an option it needs is missing (for example, the webhook URL).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let sink = SinkFactory::resolve_sink("file", &SinkOptions::default().with_output(Some("usage.json".to_string())))?;
+    /// ```
+    pub fn resolve_sink(
+        sink_from_cli: &str,
+        options: &SinkOptions,
+    ) -> Result<Box<dyn Sink>, String> {
+        match sink_from_cli {
+            "stdout" => Ok(Box::new(StdoutSink {})),
+
+            "file" => match options.get_output() {
+                Some(output) => Ok(Box::new(FileSink::new(output, options.get_checksum()))),
+                None => Err("The file sink needs an output file (--output)".to_string()),
+            },
+
+            "webhook" => match options.get_webhook_url() {
+                Some(webhook_url) => Ok(Box::new(WebhookSink::new(
+                    webhook_url,
+                    options.get_webhook_retries(),
+                    options.get_webhook_timeout(),
+                    options.get_webhook_retry_delay(),
+                    options.get_run_id(),
+                )?)),
+                None => Err("The webhook sink needs a URL (--webhook-url)".to_string()),
+            },
+
+            "sqlite" => match options.get_database() {
+                Some(database) => Ok(Box::new(SqliteSink::new(database, options.get_run_id()))),
+                None => Err("The sqlite sink needs a database file (--database)".to_string()),
+            },
+
+            unknown_sink => Err(format!("Unknown sink: {}", unknown_sink)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_resolve_correct_sink() {
+        let options = SinkOptions::default()
+            .with_output(Some("usage.json".to_string()))
+            .with_webhook_url(Some("http://localhost:8080/usage".to_string()))
+            .with_database(Some("usage.db".to_string()));
+
+        for sink in SinkFactory::available_sinks() {
+            assert_eq!(
+                SinkFactory::resolve_sink(sink, &options)
+                    .unwrap()
+                    .identifier(),
+                *sink
+            );
+        }
+
+        for sink in ["file", "webhook", "sqlite"] {
+            assert!(SinkFactory::resolve_sink(sink, &SinkOptions::default()).is_err());
+        }
+
+        assert!(SinkFactory::resolve_sink("unknown", &options).is_err());
+    }
+}
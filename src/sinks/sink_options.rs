@@ -0,0 +1,115 @@
+//! Options for the sinks, like the output file or the webhook URL.
+//!
+//! When adding new sink options, add them here, so they reach every sink through the factory.
+//! Options a sink does not use are ignored.
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SinkOptions {
+    output: Option<String>,
+    checksum: bool,
+    webhook_url: Option<String>,
+    webhook_retries: u32,
+    webhook_timeout: Duration,
+    webhook_retry_delay: Duration,
+    database: Option<String>,
+    run_id: Option<String>,
+}
+
+impl Default for SinkOptions {
+    fn default() -> Self {
+        Self {
+            output: None,
+            checksum: false,
+            webhook_url: None,
+            webhook_retries: 3,
+            webhook_timeout: Duration::from_secs(10),
+            webhook_retry_delay: Duration::from_secs(1),
+            database: None,
+            run_id: None,
+        }
+    }
+}
+
+impl SinkOptions {
+    /// *Set the output file of the file sink*
+    pub fn with_output(mut self, output: Option<String>) -> Self {
+        self.output = output;
+
+        self
+    }
+    /// *Set if the file sink also writes a `.sha256` checksum file, next to the output file*
+    pub fn with_checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+
+        self
+    }
+    /// *Set the URL, the webhook sink posts the report to*
+    pub fn with_webhook_url(mut self, webhook_url: Option<String>) -> Self {
+        self.webhook_url = webhook_url;
+
+        self
+    }
+    /// *Set how many times a failed webhook request is retried. Defaults to 3.*
+    pub fn with_webhook_retries(mut self, webhook_retries: u32) -> Self {
+        self.webhook_retries = webhook_retries;
+
+        self
+    }
+    /// *Set the timeout for connecting to the webhook, and for every read and write. Defaults to 10 seconds.*
+    pub fn with_webhook_timeout(mut self, webhook_timeout: Duration) -> Self {
+        self.webhook_timeout = webhook_timeout;
+
+        self
+    }
+    /// *Set the delay before the first retry. It doubles with every next retry. Defaults to 1 second.*
+    pub fn with_webhook_retry_delay(mut self, webhook_retry_delay: Duration) -> Self {
+        self.webhook_retry_delay = webhook_retry_delay;
+
+        self
+    }
+    /// *Set the SQLite database file of the sqlite sink*
+    pub fn with_database(mut self, database: Option<String>) -> Self {
+        self.database = database;
+
+        self
+    }
+    /// *Set the identity of the run, that produced the report. The webhook sink sends it as the `Idempotency-Key`.*
+    pub fn with_run_id(mut self, run_id: Option<String>) -> Self {
+        self.run_id = run_id;
+
+        self
+    }
+    /// *Return the output file*
+    pub fn get_output(&self) -> Option<&str> {
+        self.output.as_deref()
+    }
+    /// *Return if a checksum file is written next to the output file*
+    pub fn get_checksum(&self) -> bool {
+        self.checksum
+    }
+    /// *Return the webhook URL*
+    pub fn get_webhook_url(&self) -> Option<&str> {
+        self.webhook_url.as_deref()
+    }
+    /// *Return how many times a failed webhook request is retried*
+    pub fn get_webhook_retries(&self) -> u32 {
+        self.webhook_retries
+    }
+    /// *Return the webhook timeout*
+    pub fn get_webhook_timeout(&self) -> Duration {
+        self.webhook_timeout
+    }
+    /// *Return the delay before the first webhook retry*
+    pub fn get_webhook_retry_delay(&self) -> Duration {
+        self.webhook_retry_delay
+    }
+    /// *Return the SQLite database file*
+    pub fn get_database(&self) -> Option<&str> {
+        self.database.as_deref()
+    }
+    /// *Return the identity of the run, that produced the report*
+    pub fn get_run_id(&self) -> Option<&str> {
+        self.run_id.as_deref()
+    }
+}
@@ -0,0 +1,48 @@
+//! Trait to represent all places, the aggregate can be delivered to.
+//!
+//! Should be used with a factory struct. A factory should decide which sink should be returned.
+//!
+//! The formatter decides how the aggregate looks, and the sink decides where it goes.
+//! When adding new sinks, they should implement the trait as well.
+use super::super::formatters::formatter_trait::Formatter;
+use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+use super::super::pipeline_lib::pipeline_error::PipelineError;
+use std::collections::HashMap;
+pub trait Sink {
+    /// *Deliver the aggregate*
+    ///
+    /// ---
+    ///
+    /// Sinks that take a document (stdout, file, webhook) format the aggregate with the given formatter.
+    /// Sinks that store the values (a database) ignore the formatter.
+    ///
+    /// A sink either delivers the whole aggregate, or returns an error. It never leaves a partial report behind.
+    ///
+    /// ---
+    ///
+    /// ## Arguments
+    ///
+    /// - `aggregate` - Aggregate usage data for all users
+    /// - `formatter` - Formatter, chosen with `--formatter`
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let sink = SinkFactory::resolve_sink("stdout", &SinkOptions::default())?;
+    /// let formatter = FormatterFactory::resolve_formatter("json")?;
+    ///
+    /// sink.write(&aggregate, formatter.as_ref())?;
+    /// ```
+    fn write(
+        &self,
+        aggregate: &HashMap<u32, OwnerUsage>,
+        formatter: &dyn Formatter,
+    ) -> Result<(), PipelineError>;
+    /// *Return the identifier of the sink, as a plain string*
+    ///
+    /// ---
+    ///
+    /// Used for testing, to make sure the correct sink is returned from the factory.
+    #[allow(dead_code)]
+    fn identifier(&self) -> &'static str;
+}
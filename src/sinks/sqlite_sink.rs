@@ -0,0 +1,226 @@
+//! Write the report of a run into a SQLite database.
+//!
+//! Every metric of every owner, in every period, is a row in the `run_usage` table (created if needed):
+//!
+//! ```sql
+//! run_id TEXT, owner_id INTEGER, period TEXT, metric TEXT, value INTEGER, updated_at INTEGER,
+//! PRIMARY KEY (run_id, owner_id, period, metric)
+//! ```
+//!
+//! The rows are keyed by the identity of the run (see `run_id.rs`), which has the fingerprints of the inputs and the settings (with the time window) in it.
+//! Writing the report of the same run again replaces its rows, so a retried or repeated run leaves the same table.
+//! The report of another run (other files, a part of them, or other settings) gets its own rows, and never overwrites the usage of an earlier one.
+//! The period is the time bucket, and the usage that is not in any bucket goes to the `unbucketed` period, like in the usage store.
+//! `updated_at` is in seconds since the Unix epoch. Everything is written in a single transaction, so the table never has half of a report.
+//!
+//! Every row is the usage as reported by that run, so the runs over the same files can't be summed. To keep running totals, use the usage store (`--store`).
+//! The formatter is not used. SQLite integers are signed, so a value above `i64::MAX` fails the whole write.
+use super::super::formatters::formatter_trait::Formatter;
+use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+use super::super::pipeline_lib::pipeline_error::PipelineError;
+use super::super::pipeline_lib::run_id::unique_run_id;
+use super::super::state_lib::usage_store::split_into_periods;
+use super::sink_trait::Sink;
+use rusqlite::{Connection, params};
+use std::collections::HashMap;
+
+pub struct SqliteSink {
+    path: String,
+    run_id: Option<String>,
+}
+
+impl SqliteSink {
+    /// *Create a sink for the given database file. The file is created on the first write.*
+    ///
+    /// ---
+    ///
+    /// ## Arguments
+    ///
+    /// - `path` - Path of the database file
+    /// - `run_id` - Identity of the run that produced the report, the rows are keyed by. None means a new identity for every write.
+    pub fn new(path: &str, run_id: Option<&str>) -> Self {
+        Self {
+            path: path.to_string(),
+            run_id: run_id.map(|run_id| run_id.to_string()),
+        }
+    }
+    fn database_error(&self, reason: impl std::fmt::Display) -> PipelineError {
+        PipelineError::Database {
+            path: self.path.clone(),
+            reason: reason.to_string(),
+        }
+    }
+    /// *Replace the rows of the run, in a single transaction*
+    fn upsert(&self, aggregate: &HashMap<u32, OwnerUsage>) -> Result<(), PipelineError> {
+        let mut connection =
+            Connection::open(&self.path).map_err(|error| self.database_error(error))?;
+        let run_id = self.run_id.clone().unwrap_or_else(unique_run_id);
+        let updated_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs() as i64);
+
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS run_usage (
+                    run_id TEXT NOT NULL,
+                    owner_id INTEGER NOT NULL,
+                    period TEXT NOT NULL,
+                    metric TEXT NOT NULL,
+                    value INTEGER NOT NULL,
+                    updated_at INTEGER NOT NULL,
+                    PRIMARY KEY (run_id, owner_id, period, metric)
+                )",
+            )
+            .map_err(|error| self.database_error(error))?;
+
+        let transaction = connection
+            .transaction()
+            .map_err(|error| self.database_error(error))?;
+
+        // The same run could have been written before, with other rows (for example, before a file was fixed).
+        transaction
+            .execute("DELETE FROM run_usage WHERE run_id = ?1", params![run_id])
+            .map_err(|error| self.database_error(error))?;
+
+        {
+            let mut statement = transaction
+                .prepare(
+                    "INSERT INTO run_usage (run_id, owner_id, period, metric, value, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )
+                .map_err(|error| self.database_error(error))?;
+
+            for (owner_id, period, metric_name, value) in split_into_periods(aggregate) {
+                let value = i64::try_from(value).map_err(|_| {
+                    self.database_error(format!(
+                        "The value of {} for owner {} does not fit into a SQLite integer: {}",
+                        metric_name, owner_id, value
+                    ))
+                })?;
+
+                statement
+                    .execute(params![
+                        run_id,
+                        owner_id,
+                        period,
+                        metric_name,
+                        value,
+                        updated_at
+                    ])
+                    .map_err(|error| self.database_error(error))?;
+            }
+        }
+        // Dropping the transaction without the commit (on any error above) rolls it back.
+        transaction
+            .commit()
+            .map_err(|error| self.database_error(error))
+    }
+}
+
+impl Sink for SqliteSink {
+    /// Replace the rows of the run in the database.
+    ///
+    /// ---
+    ///
+    /// For the Arguments and Example, see [`Sink`] trait.
+    fn write(
+        &self,
+        aggregate: &HashMap<u32, OwnerUsage>,
+        _formatter: &dyn Formatter,
+    ) -> Result<(), PipelineError> {
+        self.upsert(aggregate)
+    }
+    /// @see [`Sink`] trait.
+    fn identifier(&self) -> &'static str {
+        "sqlite"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::formatters::formatter_factory::FormatterFactory;
+    use super::*;
+
+    fn read_rows(path: &str) -> Vec<(String, u32, String, String, i64)> {
+        let connection = Connection::open(path).unwrap();
+        let mut statement = connection
+            .prepare("SELECT run_id, owner_id, period, metric, value FROM run_usage ORDER BY run_id, owner_id, period, metric")
+            .unwrap();
+
+        statement
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn should_replace_the_rows_of_the_run_in_a_transaction() {
+        let test_db = "test_sqlite_sink.db";
+        let formatter = FormatterFactory::resolve_formatter("stdout").unwrap();
+        let mut aggregate: HashMap<u32, OwnerUsage> = HashMap::new();
+        let mut owner_usage =
+            OwnerUsage::from_metrics(&[("video_plays", 5), ("ad_impressions", 1)]);
+
+        owner_usage
+            .get_bucket_usage_mut("2026-10-01")
+            .add_metric("video_plays", 3);
+        aggregate.insert(1, owner_usage);
+
+        let first_result =
+            SqliteSink::new(test_db, Some("run-a")).write(&aggregate, formatter.as_ref());
+        // Running the same report again replaces the rows of the run.
+        let second_result =
+            SqliteSink::new(test_db, Some("run-a")).write(&aggregate, formatter.as_ref());
+        // A partial run (over a part of the files) does not overwrite the usage of the first one.
+        let mut partial_aggregate: HashMap<u32, OwnerUsage> = HashMap::new();
+
+        partial_aggregate.insert(1, OwnerUsage::from_metrics(&[("video_plays", 1)]));
+
+        let partial_result =
+            SqliteSink::new(test_db, Some("run-b")).write(&partial_aggregate, formatter.as_ref());
+        let rows_after_runs = read_rows(test_db);
+        // A value that does not fit fails the whole write, so nothing changes.
+        aggregate.insert(3, OwnerUsage::from_metrics(&[("video_plays", u64::MAX)]));
+
+        let overflow_result =
+            SqliteSink::new(test_db, Some("run-a")).write(&aggregate, formatter.as_ref());
+        let rows_after_overflow = read_rows(test_db);
+
+        std::fs::remove_file(test_db).unwrap();
+
+        first_result.unwrap();
+        second_result.unwrap();
+        partial_result.unwrap();
+
+        let row = |run_id: &str, period: &str, metric: &str, value: i64| {
+            (
+                run_id.to_string(),
+                1,
+                period.to_string(),
+                metric.to_string(),
+                value,
+            )
+        };
+        let expected_rows = vec![
+            row("run-a", "2026-10-01", "video_plays", 3),
+            row("run-a", "unbucketed", "ad_impressions", 1),
+            row("run-a", "unbucketed", "video_plays", 2),
+            row("run-b", "unbucketed", "video_plays", 1),
+        ];
+
+        assert_eq!(rows_after_runs, expected_rows);
+        assert!(matches!(
+            overflow_result,
+            Err(PipelineError::Database { reason, .. }) if reason.contains("does not fit")
+        ));
+        assert_eq!(rows_after_overflow, expected_rows);
+    }
+}
@@ -0,0 +1,34 @@
+//! Write the formatted aggregate to the stdout. The default sink.
+//!
+//! Only the payload is written, so it can be piped into other tools (diagnostics go to the stderr).
+use super::super::formatters::formatter_trait::Formatter;
+use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+use super::super::pipeline_lib::pipeline_error::PipelineError;
+use super::sink_trait::Sink;
+use std::collections::HashMap;
+
+pub struct StdoutSink;
+
+impl Sink for StdoutSink {
+    /// Stream the formatted aggregate to the stdout.
+    ///
+    /// ---
+    ///
+    /// For the Arguments and Example, see [`Sink`] trait.
+    fn write(
+        &self,
+        aggregate: &HashMap<u32, OwnerUsage>,
+        formatter: &dyn Formatter,
+    ) -> Result<(), PipelineError> {
+        formatter
+            .format(
+                aggregate,
+                &mut std::io::BufWriter::new(std::io::stdout().lock()),
+            )
+            .map_err(PipelineError::Output)
+    }
+    /// @see [`Sink`] trait.
+    fn identifier(&self) -> &'static str {
+        "stdout"
+    }
+}
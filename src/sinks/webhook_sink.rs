@@ -0,0 +1,358 @@
+//! Post the formatted aggregate to a webhook, over `http://` or `https://` (with the `ureq` client).
+//!
+//! The body is the formatted aggregate, and the Content-Type comes from the formatter.
+//! Every request has an `Idempotency-Key` header, which is the identity of the run that produced the report (see `run_id.rs`).
+//! So the retries of the same report, and re-runs over the same inputs, all have the same key, and the receiver can safely ignore the duplicates.
+//! Runs over other inputs, or another time window, have another key, even if their reports are the same.
+//! Without a run identity, every report gets a new key.
+//!
+//! A request is retried on connection errors and timeouts, and on 408, 425, 429 and 5xx responses,
+//! with a delay that doubles on every retry. Any other non 2xx response fails right away, since retrying would not help.
+use super::super::formatters::formatter_trait::Formatter;
+use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+use super::super::pipeline_lib::pipeline_error::PipelineError;
+use super::super::pipeline_lib::run_id::unique_run_id;
+use super::sink_trait::Sink;
+use std::collections::HashMap;
+use std::time::Duration;
+use ureq::Agent;
+use ureq::http::Uri;
+
+/// How much of the response body goes into the error message.
+const MAX_ERROR_BODY_LENGTH: usize = 200;
+/// How much of the response body is read at most, for the error message.
+const MAX_READ_BODY_LENGTH: u64 = 64 * 1024;
+
+pub struct WebhookSink {
+    agent: Agent,
+    url: String,
+    retries: u32,
+    retry_delay: Duration,
+    run_id: Option<String>,
+}
+
+impl WebhookSink {
+    /// *Create a webhook sink*
+    ///
+    /// ---
+    ///
+    /// Returns an error, if the URL is not a valid `http://` or `https://` URL.
+    ///
+    /// ## Arguments
+    ///
+    /// - `url` - Where to post the report
+    /// - `retries` - How many times a failed request is retried
+    /// - `timeout` - Timeout for connecting, sending the report, and receiving the response
+    /// - `retry_delay` - Delay before the first retry, doubled on every next one
+    /// - `run_id` - Identity of the run that produced the report, sent as the `Idempotency-Key`
+    pub fn new(
+        url: &str,
+        retries: u32,
+        timeout: Duration,
+        retry_delay: Duration,
+        run_id: Option<&str>,
+    ) -> Result<Self, String> {
+        let uri = url
+            .parse::<Uri>()
+            .map_err(|error| format!("Invalid webhook URL: {}: {}", url, error))?;
+
+        if !matches!(uri.scheme_str(), Some("http" | "https")) || uri.host().is_none() {
+            return Err(format!(
+                "The webhook URL must be an http:// or https:// URL: {}",
+                url
+            ));
+        }
+
+        let agent: Agent = Agent::config_builder()
+            .timeout_connect(Some(timeout))
+            .timeout_send_body(Some(timeout))
+            .timeout_recv_response(Some(timeout))
+            .timeout_recv_body(Some(timeout))
+            // Every status is checked here, to decide if it is retried.
+            .http_status_as_error(false)
+            .user_agent(concat!("usage-parse/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .into();
+
+        Ok(Self {
+            agent,
+            url: url.to_string(),
+            retries,
+            retry_delay,
+            run_id: run_id.map(|run_id| run_id.to_string()),
+        })
+    }
+    fn webhook_error(&self, reason: String) -> PipelineError {
+        PipelineError::Webhook {
+            url: self.url.clone(),
+            reason,
+        }
+    }
+}
+
+impl Sink for WebhookSink {
+    /// Post the formatted aggregate, retrying until it is accepted, or the retries run out.
+    ///
+    /// ---
+    ///
+    /// For the Arguments and Example, see [`Sink`] trait.
+    fn write(
+        &self,
+        aggregate: &HashMap<u32, OwnerUsage>,
+        formatter: &dyn Formatter,
+    ) -> Result<(), PipelineError> {
+        // The Content-Length must be known up front, so the body is formatted into memory.
+        let mut body: Vec<u8> = Vec::new();
+
+        formatter
+            .format(aggregate, &mut body)
+            .map_err(PipelineError::Output)?;
+
+        // The same for every retry of this report.
+        let idempotency_key = self.run_id.clone().unwrap_or_else(unique_run_id);
+        let mut delay = self.retry_delay;
+        let mut last_error = String::new();
+
+        for attempt in 0..=self.retries {
+            if attempt > 0 {
+                std::thread::sleep(delay);
+                delay = delay.saturating_mul(2);
+            }
+
+            let result = self
+                .agent
+                .post(&self.url)
+                .header("Content-Type", formatter.content_type())
+                .header("Idempotency-Key", &idempotency_key)
+                .send(&body[..]);
+
+            match result {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(mut response) => {
+                    let status = response.status().as_u16();
+                    let response_body = response
+                        .body_mut()
+                        .with_config()
+                        .limit(MAX_READ_BODY_LENGTH)
+                        .read_to_string()
+                        .unwrap_or_default();
+                    let response_body: String = response_body
+                        .trim()
+                        .chars()
+                        .take(MAX_ERROR_BODY_LENGTH)
+                        .collect();
+
+                    last_error = format!("status {}: {}", status, response_body);
+
+                    if !is_retryable(status) {
+                        return Err(self.webhook_error(format!(
+                            "The report was rejected with {}",
+                            last_error
+                        )));
+                    }
+                }
+                Err(error) => last_error = error.to_string(),
+            }
+        }
+
+        Err(self.webhook_error(format!(
+            "Gave up after {} attempts, the last one failed with {}",
+            self.retries + 1,
+            last_error
+        )))
+    }
+    /// @see [`Sink`] trait.
+    fn identifier(&self) -> &'static str {
+        "webhook"
+    }
+}
+
+/// *Check if a request that got this status could succeed, if it is sent again*
+fn is_retryable(status: u16) -> bool {
+    matches!(status, 408 | 425 | 429 | 500..=599)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::formatters::formatter_factory::FormatterFactory;
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// A stand-in webhook server. Answers every request with the next status, and returns what it received.
+    fn serve(listener: TcpListener, statuses: Vec<u16>) -> std::thread::JoinHandle<Vec<String>> {
+        std::thread::spawn(move || {
+            let mut requests: Vec<String> = Vec::new();
+
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request = String::new();
+                let mut content_length = 0;
+
+                loop {
+                    let mut line = String::new();
+
+                    reader.read_line(&mut line).unwrap();
+
+                    if let Some(length) = line.to_lowercase().strip_prefix("content-length: ") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+
+                    request.push_str(&line);
+
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+
+                let mut body = vec![0; content_length];
+
+                reader.read_exact(&mut body).unwrap();
+                request.push_str(&String::from_utf8(body).unwrap());
+                requests.push(request);
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {} Status\r\nContent-Length: 4\r\nConnection: close\r\n\r\nbusy",
+                    status
+                )
+                .unwrap();
+            }
+
+            requests
+        })
+    }
+
+    #[test]
+    fn should_retry_with_the_same_idempotency_key() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/usage", listener.local_addr().unwrap());
+        let server = serve(listener, vec![503, 429, 200]);
+        let mut aggregate: HashMap<u32, OwnerUsage> = HashMap::new();
+
+        aggregate.insert(1, OwnerUsage::from_metrics(&[("video_plays", 2)]));
+
+        let sink = WebhookSink::new(
+            &url,
+            3,
+            Duration::from_secs(5),
+            Duration::from_millis(1),
+            Some("run-1"),
+        )
+        .unwrap();
+        let formatter = FormatterFactory::resolve_formatter("ndjson").unwrap();
+
+        sink.write(&aggregate, formatter.as_ref()).unwrap();
+
+        let requests = server.join().unwrap();
+        let body = "{\"owner_id\":1,\"usage\":{\"video_plays\":2}}\n";
+
+        assert_eq!(requests.len(), 3);
+
+        for request in requests {
+            let request = request.to_lowercase();
+
+            assert!(request.starts_with("post /hooks/usage http/1.1\r\n"));
+            assert!(request.contains("content-type: application/x-ndjson\r\n"));
+            assert!(request.contains("idempotency-key: run-1\r\n"));
+            assert!(request.ends_with(&format!("\r\n\r\n{}", body)));
+        }
+    }
+
+    #[test]
+    fn should_send_the_same_report_of_other_runs_with_other_keys() {
+        let formatter = FormatterFactory::resolve_formatter("json").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = serve(listener, vec![200, 200, 200, 200]);
+
+        for run_id in [Some("run-1"), Some("run-2"), None, None] {
+            WebhookSink::new(&url, 0, Duration::from_secs(5), Duration::ZERO, run_id)
+                .unwrap()
+                .write(&HashMap::new(), formatter.as_ref())
+                .unwrap();
+        }
+
+        let idempotency_keys: Vec<String> = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|request| {
+                request
+                    .lines()
+                    .find_map(|line| {
+                        line.to_lowercase()
+                            .strip_prefix("idempotency-key: ")
+                            .map(str::to_string)
+                    })
+                    .unwrap()
+            })
+            .collect();
+
+        assert_eq!(idempotency_keys[0], "run-1");
+        assert_eq!(idempotency_keys[1], "run-2");
+        // Without a run identity, the reports are never taken for duplicates.
+        assert_ne!(idempotency_keys[2], idempotency_keys[3]);
+    }
+
+    #[test]
+    fn should_give_up_on_errors_that_retrying_would_not_fix() {
+        let formatter = FormatterFactory::resolve_formatter("json").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = serve(listener, vec![400]);
+        let sink = WebhookSink::new(
+            &url,
+            3,
+            Duration::from_secs(5),
+            Duration::from_millis(1),
+            None,
+        )
+        .unwrap();
+        let result = sink.write(&HashMap::new(), formatter.as_ref());
+
+        assert_eq!(server.join().unwrap().len(), 1);
+        assert!(matches!(
+            result,
+            Err(PipelineError::Webhook { reason, .. }) if reason.contains("status 400: busy")
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = serve(listener, vec![500, 500]);
+        let sink = WebhookSink::new(
+            &url,
+            1,
+            Duration::from_secs(5),
+            Duration::from_millis(1),
+            None,
+        )
+        .unwrap();
+        let result = sink.write(&HashMap::new(), formatter.as_ref());
+
+        assert_eq!(server.join().unwrap().len(), 2);
+        assert!(matches!(
+            result,
+            Err(PipelineError::Webhook { reason, .. }) if reason.contains("Gave up after 2 attempts")
+        ));
+        assert!(
+            WebhookSink::new(
+                "https://example.com/usage",
+                3,
+                Duration::from_secs(5),
+                Duration::ZERO,
+                None
+            )
+            .is_ok()
+        );
+
+        for invalid_url in ["ftp://example.com", "example.com/usage", "http://"] {
+            assert!(
+                WebhookSink::new(invalid_url, 3, Duration::from_secs(5), Duration::ZERO, None)
+                    .is_err(),
+                "url: {}",
+                invalid_url
+            );
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! Checkpoint of a single log file, for the incremental runs.
+//!
+//! A checkpoint records which file was read (its device and inode), what it looked like (size, modification time, and a fingerprint of its first bytes),
+//! and how far it was read (the offset right after the last complete line, and a fingerprint of everything before it).
+//!
+//! The fingerprint is the FNV-1a hash of the first (up to 1 KiB) decompressed bytes. It tells if a file still has the same content at its start,
+//! so a truncated or replaced file is read again from the start, while a renamed file is continued from its offset.
+//!
+//! The prefix fingerprint is the FNV-1a hash of all decompressed bytes before the offset. A file with another identity (a rotated file that was compressed,
+//! or the copy made by `copytruncate`) is only continued if its whole prefix matches, since a new file can easily start with the same 1 KiB as an old one.
+use super::super::log_parser_lib::compression::open_log_reader_at;
+use super::super::log_parser_lib::compression::{
+    Compression, detect_file_compression, open_log_reader,
+};
+use super::super::utils::fnv::{fnv1a_64, fnv1a_64_continue};
+use std::io::Read;
+use std::time::UNIX_EPOCH;
+
+/// How many bytes, from the start of a file, are fingerprinted.
+pub const FINGERPRINT_SIZE: u64 = 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileCheckpoint {
+    path: String,
+    device: u64,
+    inode: u64,
+    size: u64,
+    mtime: i64,
+    fingerprint: u64,
+    fingerprint_len: u64,
+    offset: u64,
+    prefix_fingerprint: u64,
+    compressed: bool,
+}
+
+impl FileCheckpoint {
+    /// *Create a checkpoint, from already known values*
+    ///
+    /// ---
+    ///
+    /// Used when loading the state file. To check a file on the disk, use `FileCheckpoint::read`.
+    ///
+    /// ## Arguments
+    ///
+    /// - `path` - Path of the file
+    /// - `identity` - Device and inode of the file (both 0, where not supported)
+    /// - `size` - Size of the file on the disk, in bytes
+    /// - `mtime` - Modification time, in nanoseconds since the Unix epoch
+    /// - `fingerprint` - FNV-1a hash of the first `fingerprint_len` (decompressed) bytes
+    /// - `fingerprint_len` - Number of the fingerprinted bytes, at most `FINGERPRINT_SIZE`
+    /// - `offset` - Offset right after the last line that was read, in the decompressed content, and the FNV-1a hash of the bytes before it
+    pub fn new(
+        path: &str,
+        identity: (u64, u64),
+        size: u64,
+        mtime: i64,
+        (fingerprint, fingerprint_len): (u64, u64),
+        (offset, prefix_fingerprint): (u64, u64),
+    ) -> Self {
+        Self {
+            path: path.to_string(),
+            device: identity.0,
+            inode: identity.1,
+            size,
+            mtime,
+            fingerprint,
+            fingerprint_len,
+            offset,
+            prefix_fingerprint,
+            compressed: false,
+        }
+    }
+    /// *Check a file on the disk, and create a checkpoint for it, at offset 0*
+    ///
+    /// ---
+    ///
+    /// ## Arguments
+    ///
+    /// - `path` - Path of the log file
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let checkpoint = FileCheckpoint::read("logs/access.log")?;
+    /// ```
+    pub fn read(path: &str) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX))
+            .unwrap_or(0);
+        let (device, inode) = get_identity(&metadata);
+        let (fingerprint, fingerprint_len) = fingerprint_file(path, FINGERPRINT_SIZE)?;
+
+        Ok(Self {
+            path: path.to_string(),
+            device,
+            inode,
+            size: metadata.len(),
+            mtime,
+            fingerprint,
+            fingerprint_len,
+            offset: 0,
+            prefix_fingerprint: fnv1a_64(b""),
+            compressed: detect_file_compression(path)? != Compression::None,
+        })
+    }
+    /// *Return the path of the file*
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+    /// *Return the device and the inode of the file*
+    pub fn get_identity(&self) -> (u64, u64) {
+        (self.device, self.inode)
+    }
+    /// *Return the size of the file, in bytes*
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+    /// *Return the modification time, in nanoseconds since the Unix epoch*
+    pub fn get_mtime(&self) -> i64 {
+        self.mtime
+    }
+    /// *Return the fingerprint, and the number of the fingerprinted bytes*
+    pub fn get_fingerprint(&self) -> (u64, u64) {
+        (self.fingerprint, self.fingerprint_len)
+    }
+    /// *Return the offset right after the last line that was read*
+    pub fn get_offset(&self) -> u64 {
+        self.offset
+    }
+    /// *Return the FNV-1a hash of all (decompressed) bytes before the offset*
+    pub fn get_prefix_fingerprint(&self) -> u64 {
+        self.prefix_fingerprint
+    }
+    /// *Continue the file from the offset of a stored checkpoint, matched to it*
+    pub fn with_offset_of(mut self, stored: &FileCheckpoint) -> Self {
+        self.offset = stored.offset;
+        self.prefix_fingerprint = stored.prefix_fingerprint;
+
+        self
+    }
+    /// *Move the offset forward, to where the reading stopped*
+    ///
+    /// ---
+    ///
+    /// Reads the bytes between the current and the new offset, to extend the prefix fingerprint.
+    ///
+    /// ## Arguments
+    ///
+    /// - `offset` - Offset right after the last line that was read
+    pub fn advance_to(mut self, offset: u64) -> std::io::Result<Self> {
+        if offset <= self.offset {
+            return Ok(self);
+        }
+
+        let reader = open_log_reader_at(&self.path, self.offset)?.take(offset - self.offset);
+        let (prefix_fingerprint, len) = fingerprint_reader(self.prefix_fingerprint, reader)?;
+
+        if self.offset + len < offset {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!(
+                    "{}: The file is shorter than the offset {}",
+                    self.path, offset
+                ),
+            ));
+        }
+
+        self.offset = offset;
+        self.prefix_fingerprint = prefix_fingerprint;
+
+        Ok(self)
+    }
+    /// *Check if the identity of the file is known. It is not, on platforms without inodes.*
+    pub fn has_identity(&self) -> bool {
+        self.device != 0 || self.inode != 0
+    }
+    /// *Check if the file of this checkpoint is still at its path, with the same identity, and was not truncated or rewritten*
+    ///
+    /// ---
+    ///
+    /// Otherwise the file was rotated away (renamed, removed, or copied and truncated), and its content can only be continued by another file.
+    /// A file that can't be read any more is not intact either.
+    pub fn is_intact_at_path(&self) -> bool {
+        FileCheckpoint::read(&self.path).is_ok_and(|current| {
+            current.get_identity() == self.get_identity()
+                && self.is_continued_by(&current).unwrap_or(false)
+        })
+    }
+    /// *Check if the file is exactly the same as the one in this checkpoint (same identity, size and modification time)*
+    pub fn is_unchanged(&self, current: &FileCheckpoint) -> bool {
+        self.has_identity()
+            && self.get_identity() == current.get_identity()
+            && self.size == current.size
+            && self.mtime == current.mtime
+    }
+    /// *Check if a file on the disk continues the content of this checkpoint, so it can be read from the checkpoint offset*
+    ///
+    /// ---
+    ///
+    /// The start of the file must have the same fingerprint. A plain file must also be at least as big as the offset, otherwise it was truncated.
+    ///
+    /// ## Arguments
+    ///
+    /// - `current` - Checkpoint of the file, as it is on the disk now (see `FileCheckpoint::read`)
+    pub fn is_continued_by(&self, current: &FileCheckpoint) -> std::io::Result<bool> {
+        if current.fingerprint_len < self.fingerprint_len
+            || (!current.compressed && current.size < self.offset)
+        {
+            return Ok(false);
+        }
+
+        let fingerprint = if current.fingerprint_len == self.fingerprint_len {
+            current.fingerprint
+        } else {
+            fingerprint_file(&current.path, self.fingerprint_len)?.0
+        };
+
+        Ok(fingerprint == self.fingerprint)
+    }
+    /// *Check if a file on the disk starts with exactly the same bytes, that were read up to the offset of this checkpoint*
+    ///
+    /// ---
+    ///
+    /// Stricter than `is_continued_by`, since the whole prefix is read and compared, not only its first bytes.
+    ///
+    /// ## Arguments
+    ///
+    /// - `current` - Checkpoint of the file, as it is on the disk now (see `FileCheckpoint::read`)
+    pub fn is_prefix_of(&self, current: &FileCheckpoint) -> std::io::Result<bool> {
+        if !self.is_continued_by(current)? {
+            return Ok(false);
+        }
+
+        let (prefix_fingerprint, prefix_len) = fingerprint_file(&current.path, self.offset)?;
+
+        Ok(prefix_len == self.offset && prefix_fingerprint == self.prefix_fingerprint)
+    }
+}
+
+/// *Fingerprint the first bytes of a file, decompressed*
+///
+/// ---
+///
+/// Returns the fingerprint, and the number of the fingerprinted bytes (less than `len`, if the file is smaller).
+fn fingerprint_file(path: &str, len: u64) -> std::io::Result<(u64, u64)> {
+    fingerprint_reader(fnv1a_64(b""), open_log_reader(path)?.take(len))
+}
+
+/// *Continue a fingerprint with everything a reader returns*
+///
+/// ---
+///
+/// Returns the fingerprint, and the number of the bytes read.
+fn fingerprint_reader(mut hash: u64, mut reader: impl Read) -> std::io::Result<(u64, u64)> {
+    let mut buffer = [0u8; 8192];
+    let mut len: u64 = 0;
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok((hash, len)),
+            Ok(read) => read,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+
+        hash = fnv1a_64_continue(hash, &buffer[..read]);
+        len += read as u64;
+    }
+}
+
+#[cfg(unix)]
+fn get_identity(metadata: &std::fs::Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+
+    (metadata.dev(), metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn get_identity(_metadata: &std::fs::Metadata) -> (u64, u64) {
+    (0, 0)
+}
@@ -0,0 +1,3 @@
+pub mod file_checkpoint_struct;
+pub mod state_store;
+pub mod usage_store;
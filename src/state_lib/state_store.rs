@@ -0,0 +1,699 @@
+//! Persistent state of the incremental runs.
+//!
+//! With `--incremental=path`, every run only parses the bytes appended to the log files since the previous run,
+//! and merges the new usage into the aggregate stored by the previous runs.
+//! The state file holds a checkpoint for every file (see `file_checkpoint_struct.rs`), and the aggregate itself.
+//!
+//! Files on the disk are matched to the stored checkpoints in this order:
+//!
+//! 1. By identity (device and inode). A renamed file (`access.log` -> `access.log.1`) is continued where it was left.
+//! 2. By path. A file replaced by an editor, or copied over, is continued if its start did not change.
+//! 3. By content, among the checkpoints not matched so far, whose file was rotated away: it is gone from its path, replaced there,
+//!    or truncated (`copytruncate`). A rotated file that was compressed (`access.log.1` -> `access.log.2.gz`), or the copy made
+//!    by `copytruncate` (`access.log` -> `access.log.1`), is continued from the same (decompressed) offset.
+//!
+//! A checkpoint only matches if the file still starts with the same bytes, and is not shorter than the offset.
+//! Matching by content is stricter: all bytes up to the offset must be the same, not only the first ones.
+//! Otherwise the file was truncated or replaced, and it is read again from the start. Files without a match are new, and read from the start.
+//! While the original is still intact at its path, a file with the same content is a new one (or a copy, that was not truncated yet),
+//! so it is read from the start as well.
+//!
+//! The state file is a plain text file, written atomically:
+//!
+//! ```text
+//! version 1
+//! settings 9a4c3e1f0b2d7a65
+//! file <device> <inode> <size> <mtime> <fingerprint> <fingerprint length> <offset> <prefix fingerprint> <path>
+//! usage <owner> <scope> <metric> <value>
+//! ```
+//!
+//! The scope is `-` for the owner totals, or the path to a breakdown (`video=2222`) or a time bucket (`@2024-05-01`), joined with `/`.
+//! The settings line is a fingerprint of everything that changes how lines are counted, so a state is never continued with different settings.
+use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+use super::super::pipeline_lib::pipeline::Aggregate;
+use super::super::utils::atomic_file::write_atomically;
+use super::file_checkpoint_struct::FileCheckpoint;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const STATE_VERSION: &str = "1";
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StateStore {
+    settings: u64,
+    checkpoints: Vec<FileCheckpoint>,
+    aggregate: Aggregate,
+}
+
+/// What to do with every log file, in an incremental run.
+#[derive(Debug, Default)]
+pub struct IncrementalPlan {
+    files: Vec<PlannedFile>,
+}
+
+/// How a file on the disk is matched to a stored checkpoint. See the module docs.
+#[derive(Debug, Clone, Copy)]
+enum MatchBy {
+    Identity,
+    Path,
+    Content,
+}
+
+impl MatchBy {
+    /// *Check if the stored checkpoint could be for the current file. Its content is checked separately.*
+    fn is_candidate(self, stored: &FileCheckpoint, current: &FileCheckpoint) -> bool {
+        match self {
+            MatchBy::Identity => {
+                stored.has_identity() && stored.get_identity() == current.get_identity()
+            }
+            MatchBy::Path => stored.get_path() == current.get_path(),
+            // Empty files can't be told apart by their content.
+            MatchBy::Content => stored.get_fingerprint().1 > 0 && current.get_fingerprint().1 > 0,
+        }
+    }
+    /// *Check if the content of the current file continues the stored checkpoint*
+    fn is_continued(
+        self,
+        stored: &FileCheckpoint,
+        current: &FileCheckpoint,
+    ) -> std::io::Result<bool> {
+        match self {
+            MatchBy::Identity | MatchBy::Path => stored.is_continued_by(current),
+            MatchBy::Content => stored.is_prefix_of(current),
+        }
+    }
+}
+
+/// A single log file in the plan, with the offset to start reading at.
+#[derive(Debug)]
+struct PlannedFile {
+    checkpoint: FileCheckpoint,
+    unchanged: bool,
+}
+
+impl StateStore {
+    /// *Create an empty state, for the given settings fingerprint*
+    pub fn new(settings: u64) -> Self {
+        Self {
+            settings,
+            ..Default::default()
+        }
+    }
+    /// *Load the state from a file*
+    ///
+    /// ---
+    ///
+    /// Returns None if the file does not exist yet, which is the case for the first run.
+    ///
+    /// ## Arguments
+    ///
+    /// - `path` - Path of the state file
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let state = StateStore::load("usage-parse.state")?.unwrap_or_else(|| StateStore::new(settings));
+    /// ```
+    pub fn load(path: &str) -> Result<Option<Self>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content).map(Some),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+    /// *Parse the content of a state file*
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut state = StateStore::default();
+        let mut version: Option<&str> = None;
+
+        for (index, line) in content.lines().enumerate() {
+            let line_no = index + 1;
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+
+            match kind {
+                "version" => version = Some(rest),
+                "settings" => {
+                    state.settings = u64::from_str_radix(rest, 16)
+                        .map_err(|_| format!("Line {}: Invalid settings: {}", line_no, rest))?;
+                }
+                "file" => {
+                    let checkpoint = parse_checkpoint(rest)
+                        .map_err(|error| format!("Line {}: {}", line_no, error))?;
+
+                    state.checkpoints.push(checkpoint);
+                }
+                "usage" => parse_usage(rest, &mut state.aggregate)
+                    .map_err(|error| format!("Line {}: {}", line_no, error))?,
+                unknown => return Err(format!("Line {}: Unknown entry: {}", line_no, unknown)),
+            }
+        }
+
+        match version {
+            Some(STATE_VERSION) => Ok(state),
+            Some(version) => Err(format!("Unsupported state version: {}", version)),
+            None => Err("Missing state version".to_string()),
+        }
+    }
+    /// *Write the state to a file, atomically*
+    ///
+    /// ---
+    ///
+    /// A run that is killed in the middle leaves the previous state in place, so nothing is counted twice or lost.
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        write_atomically(path, self.to_string().as_bytes())
+    }
+    /// *Return the fingerprint of the settings the state was created with*
+    pub fn get_settings(&self) -> u64 {
+        self.settings
+    }
+    /// *Return the checkpoints of all files read so far*
+    pub fn get_checkpoints(&self) -> &[FileCheckpoint] {
+        &self.checkpoints
+    }
+    /// *Replace the checkpoints, after a successful run*
+    pub fn set_checkpoints(&mut self, checkpoints: Vec<FileCheckpoint>) {
+        self.checkpoints = checkpoints;
+    }
+    /// *Return the aggregate of all runs so far*
+    pub fn get_aggregate(&self) -> &Aggregate {
+        &self.aggregate
+    }
+    /// *Return the aggregate mutably, to merge the usage of a new run into it*
+    pub fn get_aggregate_mut(&mut self) -> &mut Aggregate {
+        &mut self.aggregate
+    }
+    /// *Consume the state, and return the aggregate of all runs*
+    pub fn into_aggregate(self) -> Aggregate {
+        self.aggregate
+    }
+    /// *Decide where every log file should be read from*
+    ///
+    /// ---
+    ///
+    /// Matches the files to the stored checkpoints (see the module docs). Reads only the metadata and the first bytes of every file
+    /// (and of the stored paths), except for the files matched by content, which are read up to the stored offset.
+    /// Files that were removed in the meantime are left out.
+    ///
+    /// ## Arguments
+    ///
+    /// - `log_files` - Paths of all log files found in this run
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let plan = state.plan(&log_files)?;
+    /// let files_to_parse = plan.get_files_to_parse();
+    /// ```
+    pub fn plan(&self, log_files: &[String]) -> std::io::Result<IncrementalPlan> {
+        let mut currents: Vec<FileCheckpoint> = Vec::with_capacity(log_files.len());
+
+        for log_file in log_files {
+            match FileCheckpoint::read(log_file) {
+                Ok(current) => currents.push(current),
+                // Removed since it was found, for example by the log rotation. Its checkpoint is dropped.
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+                Err(error) => {
+                    return Err(std::io::Error::new(
+                        error.kind(),
+                        format!("{}: {}", log_file, error),
+                    ));
+                }
+            }
+        }
+
+        let mut used = vec![false; self.checkpoints.len()];
+        let mut matches: Vec<Option<usize>> = vec![None; currents.len()];
+
+        // A checkpoint is only matched by content, if its file was rotated away (it is not continued by a file with its identity,
+        // checked with the other matches, and it is not intact at its path). Otherwise the file with the same content is a new one.
+        let rotated_away: Vec<bool> = self
+            .checkpoints
+            .iter()
+            .map(|stored| stored.has_identity() && !stored.is_intact_at_path())
+            .collect();
+
+        for match_by in [MatchBy::Identity, MatchBy::Path, MatchBy::Content] {
+            for (current_index, current) in currents.iter().enumerate() {
+                if matches[current_index].is_some() {
+                    continue;
+                }
+
+                for (stored_index, stored) in self.checkpoints.iter().enumerate() {
+                    if !used[stored_index]
+                        && (!matches!(match_by, MatchBy::Content) || rotated_away[stored_index])
+                        && match_by.is_candidate(stored, current)
+                        && match_by.is_continued(stored, current)?
+                    {
+                        used[stored_index] = true;
+                        matches[current_index] = Some(stored_index);
+
+                        break;
+                    }
+                }
+            }
+        }
+
+        let files = currents
+            .into_iter()
+            .zip(matches)
+            .map(|(current, stored_index)| match stored_index {
+                Some(stored_index) => {
+                    let stored = &self.checkpoints[stored_index];
+
+                    PlannedFile {
+                        unchanged: stored.is_unchanged(&current),
+                        checkpoint: current.with_offset_of(stored),
+                    }
+                }
+                None => PlannedFile {
+                    checkpoint: current,
+                    unchanged: false,
+                },
+            })
+            .collect();
+
+        Ok(IncrementalPlan { files })
+    }
+}
+
+impl std::fmt::Display for StateStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "# Incremental state of usage-parse. Do not edit.")?;
+        writeln!(f, "version {}", STATE_VERSION)?;
+        writeln!(f, "settings {:016x}", self.settings)?;
+
+        for checkpoint in &self.checkpoints {
+            let (device, inode) = checkpoint.get_identity();
+            let (fingerprint, fingerprint_len) = checkpoint.get_fingerprint();
+
+            writeln!(
+                f,
+                "file {} {} {} {} {:016x} {} {} {:016x} {}",
+                device,
+                inode,
+                checkpoint.get_size(),
+                checkpoint.get_mtime(),
+                fingerprint,
+                fingerprint_len,
+                checkpoint.get_offset(),
+                checkpoint.get_prefix_fingerprint(),
+                escape_path(checkpoint.get_path())
+            )?;
+        }
+
+        let mut owner_ids: Vec<&u32> = self.aggregate.keys().collect();
+
+        owner_ids.sort();
+
+        for owner_id in owner_ids {
+            let mut lines = String::new();
+
+            write_usage(&mut lines, *owner_id, "-", &self.aggregate[owner_id])?;
+            write!(f, "{}", lines)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl IncrementalPlan {
+    /// *Return the files that have to be parsed. Unchanged files are left out.*
+    pub fn get_files_to_parse(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|file| !file.unchanged)
+            .map(|file| file.checkpoint.get_path().to_string())
+            .collect()
+    }
+    /// *Return the offset to start reading at, for every file to parse*
+    pub fn get_start_offsets(&self) -> HashMap<String, u64> {
+        self.files
+            .iter()
+            .filter(|file| !file.unchanged && file.checkpoint.get_offset() > 0)
+            .map(|file| {
+                (
+                    file.checkpoint.get_path().to_string(),
+                    file.checkpoint.get_offset(),
+                )
+            })
+            .collect()
+    }
+    /// *Return the number of files that did not change at all, since the previous run*
+    pub fn get_unchanged_files(&self) -> usize {
+        self.files.iter().filter(|file| file.unchanged).count()
+    }
+    /// *Build the checkpoints for the next run*
+    ///
+    /// ---
+    ///
+    /// Checkpoints of files that are gone are dropped. The bytes read in this run are read once more, to extend the prefix fingerprints.
+    ///
+    /// ## Arguments
+    ///
+    /// - `end_offsets` - Offset where the reading stopped, for every parsed file
+    pub fn into_checkpoints(
+        self,
+        end_offsets: &HashMap<String, u64>,
+    ) -> std::io::Result<Vec<FileCheckpoint>> {
+        self.files
+            .into_iter()
+            .map(|file| match end_offsets.get(file.checkpoint.get_path()) {
+                Some(offset) => file.checkpoint.advance_to(*offset),
+                None => Ok(file.checkpoint),
+            })
+            .collect()
+    }
+}
+
+/// *Write all metrics of an usage, and of its breakdowns and time buckets, as `usage` lines*
+fn write_usage(
+    out: &mut String,
+    owner_id: u32,
+    scope: &str,
+    usage: &OwnerUsage,
+) -> std::fmt::Result {
+    for (metric_name, value) in usage.get_metrics() {
+        writeln!(
+            out,
+            "usage {} {} {} {}",
+            owner_id, scope, metric_name, value
+        )?;
+    }
+
+    for (entity, entities) in usage.get_breakdowns() {
+        for (entity_id, entity_usage) in entities {
+            let segment = format!("{}={}", entity, entity_id);
+
+            write_usage(out, owner_id, &join_scope(scope, &segment), entity_usage)?;
+        }
+    }
+
+    for (bucket, bucket_usage) in usage.get_series() {
+        let segment = format!("@{}", bucket);
+
+        write_usage(out, owner_id, &join_scope(scope, &segment), bucket_usage)?;
+    }
+
+    Ok(())
+}
+
+fn join_scope(scope: &str, segment: &str) -> String {
+    if scope == "-" {
+        segment.to_string()
+    } else {
+        format!("{}/{}", scope, segment)
+    }
+}
+
+/// *Parse a `usage` line (without the `usage` kind), and add it to the aggregate*
+fn parse_usage(line: &str, aggregate: &mut Aggregate) -> Result<(), String> {
+    let fields: Vec<&str> = line.split(' ').collect();
+
+    let [owner_id, scope, metric_name, value] = fields[..] else {
+        return Err(format!("Invalid usage: {}", line));
+    };
+    let owner_id = owner_id
+        .parse::<u32>()
+        .map_err(|_| format!("Invalid owner id: {}", owner_id))?;
+    let value = value
+        .parse::<u64>()
+        .map_err(|_| format!("Invalid value: {}", value))?;
+    let mut usage = aggregate.entry(owner_id).or_default();
+
+    if scope != "-" {
+        for segment in scope.split('/') {
+            usage = match (segment.strip_prefix('@'), segment.split_once('=')) {
+                (Some(bucket), _) => usage.get_bucket_usage_mut(bucket),
+                (None, Some((entity, entity_id))) => {
+                    let entity_id = entity_id
+                        .parse::<u32>()
+                        .map_err(|_| format!("Invalid entity id: {}", entity_id))?;
+
+                    usage.get_entity_usage_mut(entity, entity_id)
+                }
+                (None, None) => return Err(format!("Invalid scope: {}", scope)),
+            };
+        }
+    }
+
+    usage
+        .add_metric(metric_name, value)
+        .ok_or_else(|| format!("Overflow of {} for owner {}", metric_name, owner_id))?;
+
+    Ok(())
+}
+
+/// *Parse a `file` line (without the `file` kind)*
+fn parse_checkpoint(line: &str) -> Result<FileCheckpoint, String> {
+    let fields: Vec<&str> = line.splitn(9, ' ').collect();
+
+    let [
+        device,
+        inode,
+        size,
+        mtime,
+        fingerprint,
+        fingerprint_len,
+        offset,
+        prefix_fingerprint,
+        path,
+    ] = fields[..]
+    else {
+        return Err(format!("Invalid file checkpoint: {}", line));
+    };
+    let invalid = |name: &str, value: &str| format!("Invalid {}: {}", name, value);
+
+    Ok(FileCheckpoint::new(
+        &unescape_path(path),
+        (
+            device.parse().map_err(|_| invalid("device", device))?,
+            inode.parse().map_err(|_| invalid("inode", inode))?,
+        ),
+        size.parse().map_err(|_| invalid("size", size))?,
+        mtime.parse().map_err(|_| invalid("mtime", mtime))?,
+        (
+            u64::from_str_radix(fingerprint, 16)
+                .map_err(|_| invalid("fingerprint", fingerprint))?,
+            fingerprint_len
+                .parse()
+                .map_err(|_| invalid("fingerprint length", fingerprint_len))?,
+        ),
+        (
+            offset.parse().map_err(|_| invalid("offset", offset))?,
+            u64::from_str_radix(prefix_fingerprint, 16)
+                .map_err(|_| invalid("prefix fingerprint", prefix_fingerprint))?,
+        ),
+    ))
+}
+
+/// *Escape the characters that would break the line based format*
+fn escape_path(path: &str) -> String {
+    path.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape_path(path: &str) -> String {
+    let mut unescaped = String::with_capacity(path.len());
+    let mut chars = path.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as IoWrite;
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut owner_usage =
+            OwnerUsage::from_metrics(&[("video_plays", 3), ("ad_impressions", 0)]);
+
+        owner_usage
+            .get_entity_usage_mut("video", 2222)
+            .add_metric("video_plays", 2);
+        owner_usage
+            .get_bucket_usage_mut("2024-05-01T13:00:00Z")
+            .add_metric("video_plays", 1);
+
+        let mut state = StateStore::new(0xabc);
+
+        state.get_aggregate_mut().insert(123, owner_usage);
+        state
+            .get_aggregate_mut()
+            .insert(7, OwnerUsage::from_metrics(&[("video_plays", 1)]));
+        state.set_checkpoints(vec![FileCheckpoint::new(
+            "logs/access log\n.1",
+            (2049, 131),
+            4096,
+            1_714_571_110_000_000_000,
+            (0xdeadbeef, 1024),
+            (4000, 0xfeedface),
+        )]);
+
+        let parsed_state = StateStore::parse(&state.to_string()).unwrap();
+
+        assert_eq!(parsed_state, state);
+        assert_eq!(parsed_state.get_settings(), 0xabc);
+        assert_eq!(
+            StateStore::load("test_state_store_missing.state").unwrap(),
+            None
+        );
+
+        let invalid_states = vec![
+            ("", "Missing state version"),
+            ("version 2\n", "Unsupported state version"),
+            (
+                "version 1\nusage 1 - video_plays x\n",
+                "Line 2: Invalid value",
+            ),
+            (
+                "version 1\nusage 1 video - video_plays 1\n",
+                "Line 2: Invalid usage",
+            ),
+            (
+                "version 1\nusage 1 video video_plays 1\n",
+                "Line 2: Invalid scope",
+            ),
+            ("version 1\nfile 1 2 3\n", "Line 2: Invalid file checkpoint"),
+            ("version 1\nfiles 1\n", "Line 2: Unknown entry"),
+        ];
+
+        for (content, expected_error) in invalid_states {
+            let error = StateStore::parse(content).unwrap_err();
+
+            assert!(error.contains(expected_error), "error: {}", error);
+        }
+    }
+
+    #[test]
+    fn test_incremental_plan() {
+        let test_log_dir = "test_state_store_plan_dir";
+        let path = |name: &str| format!("{}/{}", test_log_dir, name);
+        let line = "https://www.mysite.com/pixel.gif?o=1&v=2\n";
+
+        std::fs::create_dir(test_log_dir).unwrap();
+        std::fs::write(path("access.log"), line.repeat(2)).unwrap();
+        std::fs::write(path("rotated.log"), line.repeat(3)).unwrap();
+        std::fs::write(
+            path("truncated.log"),
+            "https://www.mysite.com/pixel.gif?o=2&v=2\n".repeat(3),
+        )
+        .unwrap();
+        std::fs::write(
+            path("static.log"),
+            "https://www.mysite.com/pixel.gif?o=3&v=2\n",
+        )
+        .unwrap();
+        std::fs::write(
+            path("copied.log"),
+            "https://www.mysite.com/pixel.gif?o=4&v=2\n".repeat(2),
+        )
+        .unwrap();
+
+        let first_files: Vec<String> = [
+            "access.log",
+            "rotated.log",
+            "truncated.log",
+            "static.log",
+            "copied.log",
+        ]
+        .iter()
+        .map(|name| path(name))
+        .collect();
+        let first_plan = StateStore::new(1).plan(&first_files).unwrap();
+
+        assert_eq!(first_plan.get_files_to_parse(), first_files);
+        assert!(first_plan.get_start_offsets().is_empty());
+
+        // Pretend everything was read to the end.
+        let mut state = StateStore::new(1);
+        let end_offsets: HashMap<String, u64> = first_files
+            .iter()
+            .map(|file| (file.clone(), std::fs::metadata(file).unwrap().len()))
+            .collect();
+
+        state.set_checkpoints(first_plan.into_checkpoints(&end_offsets).unwrap());
+
+        // access.log grows, rotated.log is compressed into a new file, truncated.log is truncated and rewritten.
+        let mut access_log = std::fs::OpenOptions::new()
+            .append(true)
+            .open(path("access.log"))
+            .unwrap();
+
+        access_log.write_all(line.as_bytes()).unwrap();
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+
+        gzip.write_all(line.repeat(3).as_bytes()).unwrap();
+        std::fs::write(path("rotated.log.1.gz"), gzip.finish().unwrap()).unwrap();
+        std::fs::remove_file(path("rotated.log")).unwrap();
+        std::fs::write(path("truncated.log"), line).unwrap();
+        // Copied by copytruncate, but not truncated yet.
+        std::fs::copy(path("static.log"), path("static.log.1")).unwrap();
+        // Copied and truncated by copytruncate.
+        std::fs::copy(path("copied.log"), path("copied.log.1")).unwrap();
+        std::fs::write(path("copied.log"), "").unwrap();
+
+        let second_files: Vec<String> = [
+            "access.log",
+            "rotated.log.1.gz",
+            "truncated.log",
+            "static.log",
+            "static.log.1",
+            "copied.log",
+            "copied.log.1",
+        ]
+        .iter()
+        .map(|name| path(name))
+        .collect();
+        let second_plan = state.plan(&second_files);
+
+        std::fs::remove_dir_all(test_log_dir).unwrap();
+
+        let second_plan = second_plan.unwrap();
+        let start_offsets = second_plan.get_start_offsets();
+
+        assert_eq!(second_plan.get_unchanged_files(), 1);
+        assert_eq!(second_plan.get_files_to_parse().len(), 6);
+        assert_eq!(
+            start_offsets.get(&path("access.log")),
+            Some(&(2 * line.len() as u64))
+        );
+        assert_eq!(
+            start_offsets.get(&path("rotated.log.1.gz")),
+            Some(&(3 * line.len() as u64))
+        );
+        assert_eq!(start_offsets.get(&path("truncated.log")), None);
+        // While the original is intact, a copy can't be told apart from a new file, that starts with the same lines.
+        assert_eq!(start_offsets.get(&path("static.log.1")), None);
+        // Once the original is truncated, the copy continues it, and the original starts over.
+        assert_eq!(start_offsets.get(&path("copied.log.1")), Some(&82));
+        assert_eq!(start_offsets.get(&path("copied.log")), None);
+
+        let checkpoints = second_plan.into_checkpoints(&HashMap::new()).unwrap();
+
+        assert_eq!(checkpoints.len(), 7);
+        assert_eq!(checkpoints[3].get_offset(), 41);
+    }
+}
@@ -0,0 +1,709 @@
+//! A persistent local usage database (SQLite), that the runs add their usage to.
+//!
+//! Tables (created if needed):
+//!
+//! - `runs` - `run_id`, `started_at`, `finished_at` (seconds since the Unix epoch) and the number of files
+//! - `run_files` - `run_id`, `path`, `device`, `inode`, `offset` and `prefix_fingerprint` of every file the run read, where the run stopped reading it
+//! - `file_checkpoints` - the checkpoint of every file read so far (see `file_checkpoint_struct.rs`), keyed by `path`
+//! - `usage_totals` - `owner_id`, `period`, `metric`, `value`, `updated_at`, keyed by `(owner_id, period, metric)`
+//!
+//! A run adds its aggregate to `usage_totals`, records itself and its files, and replaces the checkpoints, all in one transaction.
+//! The period is the time bucket (see `time_bucket_enum.rs`). Usage that is not in any bucket (no `--bucket`, or lines without a timestamp)
+//! goes to the `unbucketed` period.
+//!
+//! The checkpoints are what make the runs idempotent. A run is an incremental run (see `state_store.rs`), that starts from the stored checkpoints,
+//! so only the bytes that were not recorded yet are parsed and added:
+//!
+//! - Running the same files again adds nothing.
+//! - A file that grew since the previous run only adds its new lines. Rotated (renamed, compressed or copied) files are continued,
+//!   and truncated files start over, like in the incremental runs.
+//! - A run only succeeds, if the stored checkpoints did not change while it was parsing. So two runs at the same time can't count anything twice.
+//!
+//! SQLite integers are signed, so a total above `i64::MAX` fails the whole run, and nothing is written.
+use super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+use super::super::pipeline_lib::pipeline::Aggregate;
+use super::super::pipeline_lib::pipeline_error::PipelineError;
+use super::super::utils::fnv::fnv1a_64;
+use super::file_checkpoint_struct::FileCheckpoint;
+use rusqlite::{Connection, TransactionBehavior, params};
+use std::collections::BTreeMap;
+
+/// The period of the usage, that is not in any time bucket.
+pub const UNBUCKETED_PERIOD: &str = "unbucketed";
+
+/// Version of the tables, in `PRAGMA user_version`.
+const STORE_VERSION: i64 = 1;
+
+/// What happened with a run, that was given to the store.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordOutcome {
+    /// The usage was added, under this run id.
+    Recorded(String),
+    /// Nothing was read, that was not recorded already, so nothing was added.
+    NothingNew,
+}
+
+pub struct UsageStore {
+    connection: Connection,
+    path: String,
+}
+
+impl UsageStore {
+    /// *Open the store, creating the database file and the tables if needed*
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let mut usage_store = UsageStore::open("usage.db")?;
+    /// ```
+    pub fn open(path: &str) -> Result<UsageStore, PipelineError> {
+        let database_error = |error: rusqlite::Error| PipelineError::Database {
+            path: path.to_string(),
+            reason: error.to_string(),
+        };
+        let connection = Connection::open(path).map_err(database_error)?;
+        let version: i64 = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(database_error)?;
+        let has_runs: bool = connection
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'runs')",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(database_error)?;
+
+        if has_runs && version != STORE_VERSION {
+            return Err(PipelineError::Database {
+                path: path.to_string(),
+                reason: format!(
+                    "Unsupported store version {}. It was created by another version of usage-parse",
+                    version
+                ),
+            });
+        }
+
+        connection
+            .execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS runs (
+                    run_id TEXT PRIMARY KEY,
+                    started_at INTEGER NOT NULL,
+                    finished_at INTEGER NOT NULL,
+                    files INTEGER NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS run_files (
+                    run_id TEXT NOT NULL REFERENCES runs (run_id),
+                    path TEXT NOT NULL,
+                    device INTEGER NOT NULL,
+                    inode INTEGER NOT NULL,
+                    offset INTEGER NOT NULL,
+                    prefix_fingerprint TEXT NOT NULL,
+                    PRIMARY KEY (run_id, path)
+                );
+                CREATE TABLE IF NOT EXISTS file_checkpoints (
+                    path TEXT PRIMARY KEY,
+                    device INTEGER NOT NULL,
+                    inode INTEGER NOT NULL,
+                    size INTEGER NOT NULL,
+                    mtime INTEGER NOT NULL,
+                    fingerprint TEXT NOT NULL,
+                    fingerprint_len INTEGER NOT NULL,
+                    offset INTEGER NOT NULL,
+                    prefix_fingerprint TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS usage_totals (
+                    owner_id INTEGER NOT NULL,
+                    period TEXT NOT NULL,
+                    metric TEXT NOT NULL,
+                    value INTEGER NOT NULL CHECK (typeof(value) = 'integer'),
+                    updated_at INTEGER NOT NULL,
+                    PRIMARY KEY (owner_id, period, metric)
+                );
+                PRAGMA user_version = {};",
+                STORE_VERSION
+            ))
+            .map_err(database_error)?;
+
+        Ok(UsageStore {
+            connection,
+            path: path.to_string(),
+        })
+    }
+    /// *Return the checkpoint of every file recorded so far, to start the next run from*
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let mut state = StateStore::new(config.get_settings_fingerprint());
+    ///
+    /// state.set_checkpoints(usage_store.get_checkpoints()?);
+    /// ```
+    pub fn get_checkpoints(&self) -> Result<Vec<FileCheckpoint>, PipelineError> {
+        load_checkpoints(&self.connection).map_err(|error| database_error(&self.path, error))
+    }
+    /// *Add the aggregate of a run to the totals, and store where it stopped reading every file*
+    ///
+    /// ---
+    ///
+    /// Everything is done in a single transaction. See the module docs, for how the runs are kept idempotent.
+    /// Fails if the stored checkpoints are not the ones the run started from anymore (another run was recorded in the meantime).
+    ///
+    /// ## Arguments
+    ///
+    /// - `previous_checkpoints` - The checkpoints the run started from (see `get_checkpoints`)
+    /// - `checkpoints` - The checkpoints after the run (see `StateStore::get_checkpoints`)
+    /// - `aggregate` - The usage of everything the run read, after the previous checkpoints
+    /// - `started_at` - When the run started, in seconds since the Unix epoch
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let previous_checkpoints = usage_store.get_checkpoints()?;
+    /// let mut state = StateStore::new(pipeline.get_config().get_settings_fingerprint());
+    ///
+    /// state.set_checkpoints(previous_checkpoints.clone());
+    /// pipeline.run_incremental(&mut state)?;
+    /// usage_store.record_run(&previous_checkpoints, state.get_checkpoints(), state.get_aggregate(), started_at)?;
+    /// ```
+    pub fn record_run(
+        &mut self,
+        previous_checkpoints: &[FileCheckpoint],
+        checkpoints: &[FileCheckpoint],
+        aggregate: &Aggregate,
+        started_at: i64,
+    ) -> Result<RecordOutcome, PipelineError> {
+        let mut changed_checkpoints: Vec<&FileCheckpoint> = checkpoints
+            .iter()
+            .filter(|checkpoint| !previous_checkpoints.contains(checkpoint))
+            .collect();
+
+        changed_checkpoints.sort_by(|a, b| a.get_path().cmp(b.get_path()));
+
+        let mut run_key = String::new();
+
+        for checkpoint in &changed_checkpoints {
+            let (device, inode) = checkpoint.get_identity();
+
+            run_key.push_str(&format!(
+                "{} {} {} {:016x} {}\n",
+                device,
+                inode,
+                checkpoint.get_offset(),
+                checkpoint.get_prefix_fingerprint(),
+                checkpoint.get_path()
+            ));
+        }
+
+        let run_id = format!("{:016x}", fnv1a_64(run_key.as_bytes()));
+        let finished_at = unix_now();
+        let path = self.path.clone();
+        let sql_error = |error: rusqlite::Error| database_error(&path, error);
+        // Immediate, so no other run can record between the check of the checkpoints and the commit.
+        let transaction = self
+            .connection
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(sql_error)?;
+
+        if !same_checkpoints(
+            &load_checkpoints(&transaction).map_err(sql_error)?,
+            previous_checkpoints,
+        ) {
+            return Err(database_error(
+                &self.path,
+                "Another run was recorded in the meantime, so this one could count the same lines twice. Run it again",
+            ));
+        }
+
+        if changed_checkpoints.is_empty() {
+            return Ok(RecordOutcome::NothingNew);
+        }
+
+        transaction
+            .execute("DELETE FROM file_checkpoints", [])
+            .map_err(sql_error)?;
+        transaction
+            .execute(
+                "INSERT INTO runs (run_id, started_at, finished_at, files) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    run_id,
+                    started_at,
+                    finished_at,
+                    changed_checkpoints.len() as i64
+                ],
+            )
+            .map_err(sql_error)?;
+
+        {
+            let mut insert_checkpoint = transaction
+                .prepare(
+                    "INSERT INTO file_checkpoints (path, device, inode, size, mtime, fingerprint, fingerprint_len, offset, prefix_fingerprint)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                )
+                .map_err(sql_error)?;
+
+            for checkpoint in checkpoints {
+                let (device, inode) = checkpoint.get_identity();
+                let (fingerprint, fingerprint_len) = checkpoint.get_fingerprint();
+
+                insert_checkpoint
+                    .execute(params![
+                        checkpoint.get_path(),
+                        device as i64,
+                        inode as i64,
+                        checkpoint.get_size() as i64,
+                        checkpoint.get_mtime(),
+                        format!("{:016x}", fingerprint),
+                        fingerprint_len as i64,
+                        checkpoint.get_offset() as i64,
+                        format!("{:016x}", checkpoint.get_prefix_fingerprint()),
+                    ])
+                    .map_err(sql_error)?;
+            }
+
+            let mut insert_file = transaction
+                .prepare(
+                    "INSERT INTO run_files (run_id, path, device, inode, offset, prefix_fingerprint) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )
+                .map_err(sql_error)?;
+
+            for checkpoint in &changed_checkpoints {
+                let (device, inode) = checkpoint.get_identity();
+
+                insert_file
+                    .execute(params![
+                        run_id,
+                        checkpoint.get_path(),
+                        device as i64,
+                        inode as i64,
+                        checkpoint.get_offset() as i64,
+                        format!("{:016x}", checkpoint.get_prefix_fingerprint()),
+                    ])
+                    .map_err(sql_error)?;
+            }
+
+            let mut upsert_total = transaction
+                .prepare(
+                    "INSERT INTO usage_totals (owner_id, period, metric, value, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)
+                     ON CONFLICT (owner_id, period, metric) DO UPDATE SET value = value + excluded.value, updated_at = excluded.updated_at",
+                )
+                .map_err(sql_error)?;
+
+            for (owner_id, period, metric_name, value) in split_into_periods(aggregate) {
+                // On an overflow, SQLite turns the sum into a float, which the CHECK constraint rejects.
+                let overflow = || {
+                    database_error(
+                        &self.path,
+                        format!(
+                            "The total of {} for owner {} in {} does not fit into a SQLite integer",
+                            metric_name, owner_id, period
+                        ),
+                    )
+                };
+                let value = i64::try_from(value).map_err(|_| overflow())?;
+
+                upsert_total
+                    .execute(params![owner_id, period, metric_name, value, finished_at])
+                    .map_err(|error| match error {
+                        rusqlite::Error::SqliteFailure(failure, _)
+                            if failure.code == rusqlite::ErrorCode::ConstraintViolation =>
+                        {
+                            overflow()
+                        }
+                        error => database_error(&self.path, error),
+                    })?;
+            }
+        }
+        // Dropping the transaction without the commit (on any error above) rolls it back.
+        transaction.commit().map_err(sql_error)?;
+
+        Ok(RecordOutcome::Recorded(run_id))
+    }
+    /// *Return the usage of an owner, summed over the matching periods*
+    ///
+    /// ---
+    ///
+    /// A period matches itself, and every finer period inside of it. For example, `2026-10` matches `2026-10`, `2026-10-05`
+    /// and `2026-10-05T13:00:00Z`, and `2026` the whole year. Without a period, all of them match (including `unbucketed`).
+    /// The returned usage has the summed metrics, and a series entry for every matching time bucket.
+    /// Returns `None`, if nothing matches.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let usage = usage_store.query(123, Some("2026-10"))?;
+    /// ```
+    pub fn query(
+        &self,
+        owner_id: u32,
+        period: Option<&str>,
+    ) -> Result<Option<OwnerUsage>, PipelineError> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT period, metric, value FROM usage_totals
+                 WHERE owner_id = ?1
+                   AND (?2 IS NULL OR period = ?2 OR substr(period, 1, length(?2) + 1) IN (?2 || '-', ?2 || 'T'))
+                 ORDER BY period, metric",
+            )
+            .map_err(|error| database_error(&self.path, error))?;
+        let rows = statement
+            .query_map(params![owner_id, period], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
+            })
+            .map_err(|error| database_error(&self.path, error))?;
+        let mut owner_usage: Option<OwnerUsage> = None;
+
+        for row in rows {
+            let (row_period, metric_name, value) =
+                row.map_err(|error| database_error(&self.path, error))?;
+            let value = u64::try_from(value).map_err(|_| {
+                database_error(
+                    &self.path,
+                    format!("Negative total of {} in {}", metric_name, row_period),
+                )
+            })?;
+            let owner_usage = owner_usage.get_or_insert_with(|| OwnerUsage::from_metrics(&[]));
+
+            if owner_usage.add_metric(&metric_name, value).is_none() {
+                return Err(database_error(
+                    &self.path,
+                    format!(
+                        "The sum of {} for owner {} overflows",
+                        metric_name, owner_id
+                    ),
+                ));
+            }
+
+            if row_period != UNBUCKETED_PERIOD
+                && owner_usage
+                    .get_bucket_usage_mut(&row_period)
+                    .add_metric(&metric_name, value)
+                    .is_none()
+            {
+                return Err(database_error(
+                    &self.path,
+                    format!(
+                        "The sum of {} for owner {} in {} overflows",
+                        metric_name, owner_id, row_period
+                    ),
+                ));
+            }
+        }
+
+        Ok(owner_usage)
+    }
+}
+
+/// *Load all stored checkpoints, ordered by the path*
+fn load_checkpoints(connection: &Connection) -> rusqlite::Result<Vec<FileCheckpoint>> {
+    let mut statement = connection.prepare(
+        "SELECT path, device, inode, size, mtime, fingerprint, fingerprint_len, offset, prefix_fingerprint
+         FROM file_checkpoints ORDER BY path",
+    )?;
+    let hex = |index: usize, value: String| {
+        u64::from_str_radix(&value, 16).map_err(|error| {
+            rusqlite::Error::FromSqlConversionFailure(
+                index,
+                rusqlite::types::Type::Text,
+                Box::new(error),
+            )
+        })
+    };
+    let rows = statement.query_map([], |row| {
+        Ok(FileCheckpoint::new(
+            &row.get::<_, String>(0)?,
+            (row.get::<_, i64>(1)? as u64, row.get::<_, i64>(2)? as u64),
+            row.get::<_, i64>(3)? as u64,
+            row.get(4)?,
+            (hex(5, row.get(5)?)?, row.get::<_, i64>(6)? as u64),
+            (row.get::<_, i64>(7)? as u64, hex(8, row.get(8)?)?),
+        ))
+    })?;
+
+    rows.collect()
+}
+
+/// *Check if two lists have the same checkpoints, in any order*
+fn same_checkpoints(stored: &[FileCheckpoint], expected: &[FileCheckpoint]) -> bool {
+    stored.len() == expected.len()
+        && expected
+            .iter()
+            .all(|checkpoint| stored.contains(checkpoint))
+}
+
+/// *Split the usage of every owner into periods: one per time bucket, and the rest as `unbucketed`*
+///
+/// ---
+///
+/// Returns the owner, the period, the metric and the value. An `unbucketed` rest of 0 is left out. Also used by the sqlite sink.
+pub fn split_into_periods(aggregate: &Aggregate) -> Vec<(u32, String, String, u64)> {
+    let mut totals: Vec<(u32, String, String, u64)> = Vec::new();
+
+    for (owner_id, owner_usage) in aggregate {
+        let mut unbucketed: BTreeMap<&String, u64> = owner_usage
+            .get_metrics()
+            .iter()
+            .map(|(metric_name, value)| (metric_name, *value))
+            .collect();
+
+        for (bucket, bucket_usage) in owner_usage.get_series() {
+            for (metric_name, value) in bucket_usage.get_metrics() {
+                totals.push((*owner_id, bucket.clone(), metric_name.clone(), *value));
+
+                if let Some(rest) = unbucketed.get_mut(metric_name) {
+                    *rest = rest.saturating_sub(*value);
+                }
+            }
+        }
+
+        for (metric_name, value) in unbucketed {
+            if value > 0 {
+                totals.push((
+                    *owner_id,
+                    UNBUCKETED_PERIOD.to_string(),
+                    metric_name.clone(),
+                    value,
+                ));
+            }
+        }
+    }
+
+    totals
+}
+
+fn database_error(path: &str, reason: impl std::fmt::Display) -> PipelineError {
+    PipelineError::Database {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// *Return the current time, in seconds since the Unix epoch*
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn checkpoint(path: &str, inode: u64, offset: u64) -> FileCheckpoint {
+        FileCheckpoint::new(
+            path,
+            (2049, inode),
+            offset,
+            0,
+            (0xabc, 10),
+            (offset, offset),
+        )
+    }
+
+    #[test]
+    fn test_usage_store_runs_are_idempotent() {
+        let test_dir = "test_usage_store_dir";
+        let test_db = format!("{}/usage.db", test_dir);
+
+        std::fs::create_dir(test_dir).unwrap();
+
+        let mut first_aggregate: Aggregate = HashMap::new();
+        let mut owner_usage = OwnerUsage::from_metrics(&[("video_plays", 5)]);
+
+        owner_usage
+            .get_bucket_usage_mut("2026-10-01")
+            .add_metric("video_plays", 3);
+        owner_usage
+            .get_bucket_usage_mut("2026-11-01")
+            .add_metric("video_plays", 1);
+        first_aggregate.insert(123, owner_usage);
+
+        let mut second_aggregate: Aggregate = HashMap::new();
+        let mut owner_usage = OwnerUsage::from_metrics(&[("video_plays", 2)]);
+
+        owner_usage
+            .get_bucket_usage_mut("2026-10-20")
+            .add_metric("video_plays", 2);
+        second_aggregate.insert(123, owner_usage);
+
+        let first_checkpoints = vec![checkpoint("access.log", 1, 100)];
+        // access.log grew, and a new file was started.
+        let second_checkpoints = vec![
+            checkpoint("access.log", 1, 150),
+            checkpoint("other.log", 2, 10),
+        ];
+        let mut usage_store = UsageStore::open(&test_db).unwrap();
+        let first = usage_store.record_run(&[], &first_checkpoints, &first_aggregate, 100);
+        // Started from the same checkpoints as the first run, while it was running.
+        let concurrent = usage_store.record_run(&[], &first_checkpoints, &first_aggregate, 100);
+        let second = usage_store.record_run(
+            &first_checkpoints,
+            &second_checkpoints,
+            &second_aggregate,
+            200,
+        );
+        let repeated = usage_store.record_run(
+            &second_checkpoints,
+            &second_checkpoints,
+            &HashMap::new(),
+            300,
+        );
+        // Reopened, like in the next run of the program.
+        let usage_store = UsageStore::open(&test_db).unwrap();
+        let stored_checkpoints = usage_store.get_checkpoints();
+        let october = usage_store.query(123, Some("2026-10"));
+        let everything = usage_store.query(123, None);
+        let other_owner = usage_store.query(7, None);
+        let year_prefix_only = usage_store.query(123, Some("202"));
+        let run_files: i64 = usage_store
+            .connection
+            .query_row("SELECT COUNT(*) FROM run_files", [], |row| row.get(0))
+            .unwrap();
+
+        std::fs::remove_dir_all(test_dir).unwrap();
+
+        assert!(matches!(first.unwrap(), RecordOutcome::Recorded(_)));
+        assert!(matches!(
+            concurrent,
+            Err(PipelineError::Database { reason, .. }) if reason.contains("Another run")
+        ));
+        assert!(matches!(second.unwrap(), RecordOutcome::Recorded(_)));
+        assert_eq!(repeated.unwrap(), RecordOutcome::NothingNew);
+        assert_eq!(stored_checkpoints.unwrap(), second_checkpoints);
+        assert_eq!(run_files, 3);
+
+        let october = october.unwrap().unwrap();
+
+        assert_eq!(october.get_metric("video_plays"), 5);
+        assert_eq!(
+            october
+                .get_series()
+                .keys()
+                .map(|bucket| bucket.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["2026-10-01", "2026-10-20"]
+        );
+
+        let everything = everything.unwrap().unwrap();
+
+        // 5 + 2, the 1 without a bucket included.
+        assert_eq!(everything.get_metric("video_plays"), 7);
+        assert_eq!(everything.get_series().len(), 3);
+        assert_eq!(other_owner.unwrap(), None);
+        assert_eq!(year_prefix_only.unwrap(), None);
+    }
+
+    #[test]
+    fn test_usage_store_query_overflow() {
+        let test_dir = "test_usage_store_query_overflow_dir";
+        let test_db = format!("{}/usage.db", test_dir);
+
+        std::fs::create_dir(test_dir).unwrap();
+
+        let usage_store = UsageStore::open(&test_db).unwrap();
+
+        for period in ["2026-10-01", "2026-10-02", "2026-10-03"] {
+            usage_store
+                .connection
+                .execute(
+                    "INSERT INTO usage_totals (owner_id, period, metric, value, updated_at) VALUES (1, ?1, 'video_plays', ?2, 0)",
+                    params![period, i64::MAX],
+                )
+                .unwrap();
+        }
+
+        let everything = usage_store.query(1, None);
+        let one_day = usage_store.query(1, Some("2026-10-01"));
+
+        std::fs::remove_dir_all(test_dir).unwrap();
+
+        assert!(matches!(
+            everything,
+            Err(PipelineError::Database { reason, .. }) if reason.contains("overflows")
+        ));
+        assert_eq!(
+            one_day.unwrap().unwrap().get_metric("video_plays"),
+            i64::MAX as u64
+        );
+    }
+
+    #[test]
+    fn test_usage_store_overflow_rolls_back() {
+        let test_dir = "test_usage_store_overflow_dir";
+        let test_db = format!("{}/usage.db", test_dir);
+
+        std::fs::create_dir(test_dir).unwrap();
+
+        let mut aggregate: Aggregate = HashMap::new();
+
+        aggregate.insert(
+            1,
+            OwnerUsage::from_metrics(&[("video_plays", i64::MAX as u64)]),
+        );
+
+        let first_checkpoints = vec![checkpoint("first.log", 1, 10)];
+        let second_checkpoints = vec![
+            checkpoint("first.log", 1, 10),
+            checkpoint("second.log", 2, 10),
+        ];
+        let mut usage_store = UsageStore::open(&test_db).unwrap();
+        let first = usage_store.record_run(&[], &first_checkpoints, &aggregate, 100);
+
+        aggregate.insert(1, OwnerUsage::from_metrics(&[("video_plays", 1)]));
+        aggregate.insert(2, OwnerUsage::from_metrics(&[("video_plays", 1)]));
+
+        let overflow =
+            usage_store.record_run(&first_checkpoints, &second_checkpoints, &aggregate, 200);
+        let owner_1 = usage_store.query(1, None);
+        let owner_2 = usage_store.query(2, None);
+        // The failed run was not recorded, so it can be retried.
+        let runs: i64 = usage_store
+            .connection
+            .query_row("SELECT COUNT(*) FROM runs", [], |row| row.get(0))
+            .unwrap();
+        let stored_checkpoints = usage_store.get_checkpoints();
+
+        std::fs::remove_dir_all(test_dir).unwrap();
+
+        first.unwrap();
+
+        assert!(matches!(
+            overflow,
+            Err(PipelineError::Database { reason, .. }) if reason.contains("does not fit")
+        ));
+        assert_eq!(
+            owner_1.unwrap().unwrap().get_metric("video_plays"),
+            i64::MAX as u64
+        );
+        assert_eq!(owner_2.unwrap(), None);
+        assert_eq!(runs, 1);
+        assert_eq!(stored_checkpoints.unwrap(), first_checkpoints);
+    }
+
+    #[test]
+    fn test_usage_store_version() {
+        let test_dir = "test_usage_store_version_dir";
+        let test_db = format!("{}/usage.db", test_dir);
+
+        std::fs::create_dir(test_dir).unwrap();
+        UsageStore::open(&test_db)
+            .unwrap()
+            .connection
+            .execute_batch("PRAGMA user_version = 0")
+            .unwrap();
+
+        let result = UsageStore::open(&test_db);
+
+        std::fs::remove_dir_all(test_dir).unwrap();
+
+        assert!(matches!(
+            result,
+            Err(PipelineError::Database { reason, .. }) if reason.contains("Unsupported store version 0")
+        ));
+    }
+}
@@ -0,0 +1,195 @@
+//! Atomic file writes.
+//!
+//! A file that other runs (or other programs) depend on must never be seen half written, even if the process is killed in the middle.
+//! So the content is written to a temporary sibling file first, flushed to the disk, and only then renamed over the target.
+//! A rename inside of the same directory is atomic, so readers see either the old file, or the new one.
+//!
+//! `AtomicFile` can be written in parts (for example, by a formatter streaming the output), and `write_atomically` writes a whole buffer.
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A file that is being written atomically.
+///
+/// Everything is written to the temporary sibling, and the target only appears (or is replaced) on `commit`.
+/// If it is dropped without the commit (for example, on an error while writing), the temporary file is removed.
+pub struct AtomicFile {
+    file: Option<std::fs::File>,
+    temp_path: PathBuf,
+    target: PathBuf,
+}
+
+impl AtomicFile {
+    /// *Create the temporary sibling of the target, for writing*
+    ///
+    /// ---
+    ///
+    /// The temporary file is created next to the target (a rename can't cross file systems).
+    ///
+    /// ## Arguments
+    ///
+    /// - `path` - Path of the target file
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let mut atomic_file = AtomicFile::create("usage.json")?;
+    ///
+    /// atomic_file.write_all(b"[]")?;
+    /// atomic_file.commit()?;
+    /// ```
+    pub fn create(path: &str) -> std::io::Result<AtomicFile> {
+        let target = Path::new(path);
+        let file_name = target
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Not a file path: {}", path),
+                )
+            })?;
+        let temp_path = target.with_file_name(format!(".{}.tmp-{}", file_name, std::process::id()));
+        let file = std::fs::File::create(&temp_path)?;
+
+        Ok(AtomicFile {
+            file: Some(file),
+            temp_path,
+            target: target.to_path_buf(),
+        })
+    }
+    /// *Flush the content to the disk, and rename the temporary file over the target*
+    pub fn commit(mut self) -> std::io::Result<()> {
+        let file = self.file.take().expect("The file is only taken on commit");
+
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&self.temp_path, &self.target)?;
+        // Make the rename itself durable. Not every platform can open a directory, so this is best effort.
+        if let Some(parent) = self.target.parent() {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+
+            if let Ok(dir) = std::fs::File::open(parent) {
+                let _ = dir.sync_all();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file
+            .as_mut()
+            .expect("The file is only taken on commit")
+            .write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file
+            .as_mut()
+            .expect("The file is only taken on commit")
+            .flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        // Not committed (or the commit failed), so the target stays as it was.
+        if self.file.is_some() || self.temp_path.exists() {
+            self.file = None;
+
+            let _ = std::fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// *Write the whole content to a file, atomically replacing it if it exists*
+///
+/// ---
+///
+/// The temporary file is removed if anything fails. See `AtomicFile`, for writing the content in parts.
+///
+/// ## Arguments
+///
+/// - `path` - Path of the target file
+/// - `content` - The whole new content of the file
+///
+/// ## Example
+///
+/// ```
+/// write_atomically("usage-parse.state", b"version 1\n")?;
+/// ```
+pub fn write_atomically(path: &str, content: &[u8]) -> std::io::Result<()> {
+    let mut atomic_file = AtomicFile::create(path)?;
+
+    atomic_file.write_all(content)?;
+    atomic_file.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomically() {
+        let test_dir = "test_atomic_file_dir";
+        let test_path = format!("{}/state.txt", test_dir);
+
+        std::fs::create_dir(test_dir).unwrap();
+
+        let first_result = write_atomically(&test_path, b"first");
+        let second_result = write_atomically(&test_path, b"second");
+        let content = std::fs::read_to_string(&test_path);
+        let leftovers = std::fs::read_dir(test_dir).unwrap().count();
+        let missing_dir_result = write_atomically("test_atomic_file_missing/state.txt", b"");
+
+        std::fs::remove_dir_all(test_dir).unwrap();
+
+        first_result.unwrap();
+        second_result.unwrap();
+
+        assert_eq!(content.unwrap(), "second");
+        // No temporary files are left behind.
+        assert_eq!(leftovers, 1);
+        assert!(missing_dir_result.is_err());
+    }
+
+    #[test]
+    fn test_atomic_file_is_only_visible_on_commit() {
+        let test_dir = "test_atomic_file_commit_dir";
+        let test_path = format!("{}/usage.json", test_dir);
+
+        std::fs::create_dir(test_dir).unwrap();
+        std::fs::write(&test_path, "old").unwrap();
+
+        let mut atomic_file = AtomicFile::create(&test_path).unwrap();
+
+        atomic_file.write_all(b"new ").unwrap();
+        atomic_file.write_all(b"content").unwrap();
+
+        let content_before_commit = std::fs::read_to_string(&test_path).unwrap();
+
+        atomic_file.commit().unwrap();
+
+        let content_after_commit = std::fs::read_to_string(&test_path).unwrap();
+        // Dropped without the commit, like on an error in the middle of the output.
+        let mut atomic_file = AtomicFile::create(&test_path).unwrap();
+
+        atomic_file.write_all(b"partial").unwrap();
+        drop(atomic_file);
+
+        let content_after_drop = std::fs::read_to_string(&test_path).unwrap();
+        let leftovers = std::fs::read_dir(test_dir).unwrap().count();
+
+        std::fs::remove_dir_all(test_dir).unwrap();
+
+        assert_eq!(content_before_commit, "old");
+        assert_eq!(content_after_commit, "new content");
+        assert_eq!(content_after_drop, "new content");
+        assert_eq!(leftovers, 1);
+    }
+}
@@ -0,0 +1,65 @@
+//! The FNV-1a hash, 64 bit variant.
+//!
+//! It is not a cryptographic hash. It is used for cheap content fingerprints (for example, of the first bytes of a log file),
+//! where it only has to tell different contents apart, and stay the same between runs and versions.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// *Hash the given bytes with FNV-1a (64 bit)*
+///
+/// ---
+///
+/// ## Arguments
+///
+/// - `bytes` - Bytes to hash
+///
+/// ## Example
+///
+/// ```
+/// assert_eq!(fnv1a_64(b""), 0xcbf29ce484222325);
+/// assert_eq!(fnv1a_64(b"a"), 0xaf63dc4c8601ec8c);
+/// ```
+pub fn fnv1a_64(bytes: &[u8]) -> u64 {
+    fnv1a_64_continue(FNV_OFFSET_BASIS, bytes)
+}
+
+/// *Continue a hash with more bytes, for hashing a stream in parts*
+///
+/// ---
+///
+/// Start with `fnv1a_64(b"")` (the offset basis), and pass the result of every call to the next one.
+///
+/// ## Example
+///
+/// ```
+/// assert_eq!(fnv1a_64_continue(fnv1a_64(b"foo"), b"bar"), fnv1a_64(b"foobar"));
+/// ```
+pub fn fnv1a_64_continue(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnv1a_64() {
+        // Reference values, from the FNV specification test suite.
+        let cases: Vec<(&[u8], u64)> = vec![
+            (b"", 0xcbf29ce484222325),
+            (b"a", 0xaf63dc4c8601ec8c),
+            (b"foobar", 0x85944171f73967e8),
+        ];
+
+        for (bytes, expected) in cases {
+            assert_eq!(fnv1a_64(bytes), expected);
+        }
+
+        assert_eq!(
+            fnv1a_64_continue(fnv1a_64(b"foo"), b"bar"),
+            0x85944171f73967e8
+        );
+    }
+}
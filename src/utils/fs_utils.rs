@@ -1,45 +1,247 @@
-/// *Try to get all files, inside of a given directory*
+//! Discovery of the log files, inside of a given directory.
+//!
+//! The directory is walked recursively. Only regular files are returned, everything else (sockets, pipes, devices...) is skipped.
+//! Hidden files and directories (starting with a `.`, like `.DS_Store`) are skipped as well.
+//!
+//! Files can be filtered with include and exclude globs (see `glob.rs`):
+//!
+//! - A pattern without a `/` is matched against the file name (`*.log*`).
+//! - A pattern with a `/` is matched against the path, relative to the walked directory (`2024/**/*.gz`).
+//!
+//! If there are include patterns, a file must match at least one of them. A file matching any exclude pattern is skipped.
+//! Exclude patterns also apply to directories, so an excluded directory is not walked at all.
+//!
+//! What happens with symbolic links is decided by the symlink policy (see `symlink_policy_enum.rs`).
+//!
+//! Paths that are not valid UTF-8 can't be passed on to the parser. They are not dropped silently, but reported back to the caller.
+use super::glob::glob_matches;
+use super::symlink_policy_enum::SymlinkPolicy;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Filters, and the symlink policy, for the discovery.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiscoveryOptions {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    symlinks: SymlinkPolicy,
+}
+
+impl DiscoveryOptions {
+    /// *Set the include globs. Empty means every file is included.*
+    pub fn with_include(mut self, include: Vec<String>) -> Self {
+        self.include = include;
+
+        self
+    }
+    /// *Set the exclude globs*
+    pub fn with_exclude(mut self, exclude: Vec<String>) -> Self {
+        self.exclude = exclude;
+
+        self
+    }
+    /// *Set what happens with symbolic links*
+    pub fn with_symlinks(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+
+        self
+    }
+    /// *Return the include globs*
+    pub fn get_include(&self) -> &[String] {
+        &self.include
+    }
+    /// *Return the exclude globs*
+    pub fn get_exclude(&self) -> &[String] {
+        &self.exclude
+    }
+    /// *Return the symlink policy*
+    pub fn get_symlinks(&self) -> SymlinkPolicy {
+        self.symlinks
+    }
+    /// *Check if a path matches any of the given globs*
+    fn matches_any(patterns: &[String], name: &str, relative_path: &str) -> bool {
+        patterns.iter().any(|pattern| {
+            if pattern.contains('/') {
+                glob_matches(pattern, relative_path)
+            } else {
+                glob_matches(pattern, name)
+            }
+        })
+    }
+}
+
+/// Result of the discovery: all files to parse, and all paths that could not be used.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Discovery {
+    files: Vec<String>,
+    non_utf8_paths: Vec<PathBuf>,
+}
+
+impl Discovery {
+    /// *Create a discovery result, from already found files*
+    pub fn new(files: Vec<String>, non_utf8_paths: Vec<PathBuf>) -> Self {
+        Self {
+            files,
+            non_utf8_paths,
+        }
+    }
+    /// *Return the full paths of all files to parse*
+    pub fn get_files(&self) -> &[String] {
+        &self.files
+    }
+    /// *Take the files out of the discovery, leaving an empty list in their place*
+    pub fn take_files(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.files)
+    }
+    /// *Return all paths that were skipped, because they are not valid UTF-8*
+    pub fn get_non_utf8_paths(&self) -> &[PathBuf] {
+        &self.non_utf8_paths
+    }
+}
+
+/// *Find all log files, inside of a given directory and all of its subdirectories*
 ///
 /// ---
 ///
-/// Method will include additional checks, such as verifying if a file name is properly encoded. Therefore, not all files are guaranteed to be included in the returned vector.
-/// Ordering of the files is also not guaranteed. So the example below does not include the .get(0) and .get(1) checks. Use sorting if ordering is needed.
+/// Returned paths are the given directory, joined with the path relative to it (`logs/2024/05/access.log`), and are sorted.
+/// Returns an IO error, if the given directory, or any of its subdirectories, can't be read.
 ///
 /// ---
 ///
 /// ## Arguments
 ///
-/// - `dir` - Directory to read
+/// - `dir` - Directory to walk
+/// - `options` - Include / exclude globs, and the symlink policy
 ///
 /// ## Example
 ///
 /// ```
-/// // Assuming there is a logs directory, with log1.txt and log2.txt inside.
-/// let files = get_file_names("logs");
+/// let options = DiscoveryOptions::default()
+///     .with_include(vec!["*.log*".to_string()])
+///     .with_exclude(vec!["*.tmp".to_string()]);
+/// let discovery = discover_files("logs", &options)?;
 ///
-/// assert!(!files.is_empty());
-/// assert!(files.len() == 2);
+/// for log_file in discovery.get_files() {
+///     // Parse the file...
+/// }
 /// ```
-pub fn get_file_names(dir: &str) -> Vec<String> {
-    let mut files = Vec::new();
-
-    if let Ok(entries) = std::fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            /*
-             * into_string, on the file_name, could return an error, indicating that the file name is not in a valid utf8 format.
-             * So just skip this for now.
-             */
-            let log_file = if let Ok(log_file) = entry.file_name().into_string() {
-                log_file
-            } else {
+pub fn discover_files(dir: &str, options: &DiscoveryOptions) -> std::io::Result<Discovery> {
+    let mut discovery = Discovery::default();
+    let mut visited_dirs: HashSet<PathBuf> = HashSet::new();
+
+    visited_dirs.insert(std::fs::canonicalize(dir)?);
+    walk_dir(
+        dir,
+        Path::new(dir),
+        "",
+        options,
+        &mut visited_dirs,
+        &mut discovery,
+    )?;
+
+    discovery.files.sort();
+    discovery.non_utf8_paths.sort();
+
+    Ok(discovery)
+}
+
+/// *Read a manifest file, listing the log files to parse*
+///
+/// ---
+///
+/// The manifest has one path per line. Surrounding whitespace is trimmed, and empty lines and `#` comments are skipped.
+/// Relative paths are relative to the current working directory, not to the manifest.
+///
+/// ## Arguments
+///
+/// - `path` - Path of the manifest file
+///
+/// ## Example
+///
+/// ```
+/// let log_files = read_manifest("manifest.txt")?;
+/// ```
+pub fn read_manifest(path: &str) -> std::io::Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)?;
+
+    Ok(content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect())
+}
+
+/// *Walk a single directory, and recurse into its subdirectories*
+fn walk_dir(
+    root: &str,
+    dir: &Path,
+    relative_dir: &str,
+    options: &DiscoveryOptions,
+    visited_dirs: &mut HashSet<PathBuf>,
+    discovery: &mut Discovery,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => {
+                discovery.non_utf8_paths.push(path);
+
                 continue;
-            };
+            }
+        };
+
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let relative_path = if relative_dir.is_empty() {
+            name.clone()
+        } else {
+            format!("{}/{}", relative_dir, name)
+        };
+        let mut file_type = entry.file_type()?;
+
+        if file_type.is_symlink() {
+            match options.symlinks {
+                SymlinkPolicy::Skip => continue,
+                SymlinkPolicy::Follow => match std::fs::metadata(&path) {
+                    Ok(metadata) => file_type = metadata.file_type(),
+                    // Broken link.
+                    Err(_) => continue,
+                },
+            }
+        }
+
+        if DiscoveryOptions::matches_any(&options.exclude, &name, &relative_path) {
+            continue;
+        }
 
-            files.push(log_file);
+        if file_type.is_dir() {
+            // Walk every directory only once, even if links point to it from multiple places.
+            if visited_dirs.insert(std::fs::canonicalize(&path)?) {
+                walk_dir(
+                    root,
+                    &path,
+                    &relative_path,
+                    options,
+                    visited_dirs,
+                    discovery,
+                )?;
+            }
+        } else if file_type.is_file()
+            && (options.include.is_empty()
+                || DiscoveryOptions::matches_any(&options.include, &name, &relative_path))
+        {
+            discovery
+                .files
+                .push(format!("{}/{}", root.trim_end_matches('/'), relative_path));
         }
     }
 
-    files
+    Ok(())
 }
 
 #[cfg(test)]
@@ -60,17 +262,176 @@ mod tests {
         std::fs::File::create(&test_log_file_two_full_path).unwrap();
         // Create the instance, and immediately remove the directory and the files.
         // If something fails below, we'll have leftover resources.
-        let mut test_log_files = get_file_names(&test_log_dir);
+        let discovery = discover_files(test_log_dir, &DiscoveryOptions::default());
 
         std::fs::remove_file(&test_log_file_one_full_path).unwrap();
         std::fs::remove_file(&test_log_file_two_full_path).unwrap();
         std::fs::remove_dir(test_log_dir).unwrap();
 
-        test_log_files.sort();
+        let test_log_files = discovery.unwrap().take_files();
 
-        assert!(!test_log_files.is_empty());
         assert!(test_log_files.len() == 2);
-        assert_eq!(test_log_files.get(0), Some(&test_log_file_one.to_string()));
-        assert_eq!(test_log_files.get(1), Some(&test_log_file_two.to_string()));
+        assert_eq!(test_log_files.first(), Some(&test_log_file_one_full_path));
+        assert_eq!(test_log_files.get(1), Some(&test_log_file_two_full_path));
+        assert!(discover_files(test_log_dir, &DiscoveryOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_recursive_discovery_with_filters() {
+        let test_log_dir = "test_discovery_dir";
+
+        for dir in ["2024/05", "2024/06", "archive", ".git"] {
+            std::fs::create_dir_all(format!("{}/{}", test_log_dir, dir)).unwrap();
+        }
+
+        for file in [
+            "access.log",
+            "access.log.1.gz",
+            "access.log.tmp",
+            ".DS_Store",
+            "notes.txt",
+            "2024/05/access.log",
+            "2024/06/access.log.zst",
+            "archive/access.log",
+            ".git/access.log",
+        ] {
+            std::fs::write(format!("{}/{}", test_log_dir, file), "").unwrap();
+        }
+
+        let everything = discover_files(test_log_dir, &DiscoveryOptions::default()).unwrap();
+        let filtered = discover_files(
+            test_log_dir,
+            &DiscoveryOptions::default()
+                .with_include(vec!["*.log*".to_string()])
+                .with_exclude(vec!["*.tmp".to_string(), "archive".to_string()]),
+        )
+        .unwrap();
+        let by_path = discover_files(
+            test_log_dir,
+            &DiscoveryOptions::default().with_include(vec!["2024/**".to_string()]),
+        )
+        .unwrap();
+
+        std::fs::remove_dir_all(test_log_dir).unwrap();
+
+        let relative = |discovery: &Discovery| -> Vec<String> {
+            discovery
+                .get_files()
+                .iter()
+                .map(|file| file.replacen("test_discovery_dir/", "", 1))
+                .collect()
+        };
+
+        // Hidden files and directories are skipped, and directories are never returned as files.
+        assert_eq!(
+            relative(&everything),
+            vec![
+                "2024/05/access.log",
+                "2024/06/access.log.zst",
+                "access.log",
+                "access.log.1.gz",
+                "access.log.tmp",
+                "archive/access.log",
+                "notes.txt",
+            ]
+        );
+        assert_eq!(
+            relative(&filtered),
+            vec![
+                "2024/05/access.log",
+                "2024/06/access.log.zst",
+                "access.log",
+                "access.log.1.gz",
+            ]
+        );
+        assert_eq!(
+            relative(&by_path),
+            vec!["2024/05/access.log", "2024/06/access.log.zst"]
+        );
+        assert!(everything.get_non_utf8_paths().is_empty());
+    }
+
+    #[test]
+    fn test_read_manifest() {
+        let manifest_path = "test_manifest.txt";
+
+        std::fs::write(
+            manifest_path,
+            "# Generated by the scheduler\n/var/log/nginx/access.log.1.gz\n\n  logs/log4.txt  \r\n",
+        )
+        .unwrap();
+
+        let manifest = read_manifest(manifest_path);
+
+        std::fs::remove_file(manifest_path).unwrap();
+
+        assert_eq!(
+            manifest.unwrap(),
+            vec!["/var/log/nginx/access.log.1.gz", "logs/log4.txt"]
+        );
+        assert!(read_manifest(manifest_path).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_discovery_symlinks_and_special_files() {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::symlink;
+
+        let test_log_dir = "test_discovery_links_dir";
+        let outside_dir = "test_discovery_links_outside";
+
+        std::fs::create_dir_all(format!("{}/nested", test_log_dir)).unwrap();
+        std::fs::create_dir_all(outside_dir).unwrap();
+        std::fs::write(format!("{}/access.log", test_log_dir), "").unwrap();
+        std::fs::write(format!("{}/rotated.log", outside_dir), "").unwrap();
+        // Link to a file, link to a directory, a broken link, and a link back up the tree.
+        symlink(
+            "../test_discovery_links_outside/rotated.log",
+            format!("{}/linked.log", test_log_dir),
+        )
+        .unwrap();
+        symlink(
+            "../test_discovery_links_outside",
+            format!("{}/linked_dir", test_log_dir),
+        )
+        .unwrap();
+        symlink("missing.log", format!("{}/broken.log", test_log_dir)).unwrap();
+        symlink("..", format!("{}/nested/loop", test_log_dir)).unwrap();
+        // A unix socket is not a regular file.
+        let _socket =
+            std::os::unix::net::UnixListener::bind(format!("{}/collector.sock", test_log_dir))
+                .unwrap();
+        // A file name which is not valid UTF-8.
+        let non_utf8_name = std::ffi::OsStr::from_bytes(b"access\xff.log");
+
+        std::fs::write(Path::new(test_log_dir).join(non_utf8_name), "").unwrap();
+
+        let followed = discover_files(test_log_dir, &DiscoveryOptions::default()).unwrap();
+        let skipped = discover_files(
+            test_log_dir,
+            &DiscoveryOptions::default().with_symlinks(SymlinkPolicy::Skip),
+        )
+        .unwrap();
+
+        std::fs::remove_dir_all(test_log_dir).unwrap();
+        std::fs::remove_dir_all(outside_dir).unwrap();
+
+        assert_eq!(
+            followed.get_files(),
+            &[
+                "test_discovery_links_dir/access.log".to_string(),
+                "test_discovery_links_dir/linked.log".to_string(),
+                "test_discovery_links_dir/linked_dir/rotated.log".to_string(),
+            ]
+        );
+        assert_eq!(
+            skipped.get_files(),
+            &["test_discovery_links_dir/access.log".to_string()]
+        );
+        assert_eq!(
+            followed.get_non_utf8_paths(),
+            &[Path::new(test_log_dir).join(non_utf8_name)]
+        );
     }
 }
@@ -0,0 +1,178 @@
+//! A small glob matcher, for the `--include` and `--exclude` filters.
+//!
+//! Supported syntax:
+//!
+//! - `*` - Any number of characters, except `/`.
+//! - `**` - Any number of characters, including `/`. `**/` also matches no directory at all (`a/**/b` matches `a/b`).
+//! - `?` - A single character, except `/`.
+//! - `[abc]`, `[a-z]`, `[!a-z]` - A single character from (or not from) the set. `]` can be the first character of the set.
+//! - `\` - Escapes the next character, so `\*` matches a literal `*`.
+//!
+//! Everything else matches literally. An unterminated `[` matches a literal `[`.
+
+/// *Check if a text matches a glob pattern, as a whole*
+///
+/// ---
+///
+/// ## Arguments
+///
+/// - `pattern` - The glob pattern
+/// - `text` - Text to check, usually a file name or a relative path, with `/` as the separator
+///
+/// ## Example
+///
+/// ```
+/// assert!(glob_matches("*.log*", "access.log.1.gz"));
+/// assert!(glob_matches("2024/**/*.gz", "2024/05/01/access.log.gz"));
+/// assert!(!glob_matches("*.log", "2024/access.log"));
+/// ```
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    matches_from(&pattern, &text)
+}
+
+fn matches_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            // Zero or more whole directories.
+            matches_from(rest, text)
+                || text
+                    .iter()
+                    .enumerate()
+                    .any(|(index, c)| *c == '/' && matches_from(rest, &text[index + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|index| matches_from(rest, &text[index..])),
+        ['*', rest @ ..] => {
+            // Never crosses a directory separator.
+            let segment_end = text.iter().position(|c| *c == '/').unwrap_or(text.len());
+
+            (0..=segment_end).any(|index| matches_from(rest, &text[index..]))
+        }
+        ['?', rest @ ..] => match text {
+            [c, text_rest @ ..] if *c != '/' => matches_from(rest, text_rest),
+            _ => false,
+        },
+        ['[', class @ ..] => match CharClass::parse(class) {
+            Some((char_class, rest)) => match text {
+                [c, text_rest @ ..] if *c != '/' => {
+                    char_class.contains(*c) && matches_from(rest, text_rest)
+                }
+                _ => false,
+            },
+            None => matches_literal('[', class, text),
+        },
+        ['\\', escaped, rest @ ..] => matches_literal(*escaped, rest, text),
+        [literal, rest @ ..] => matches_literal(*literal, rest, text),
+    }
+}
+
+fn matches_literal(literal: char, rest: &[char], text: &[char]) -> bool {
+    match text {
+        [c, text_rest @ ..] if *c == literal => matches_from(rest, text_rest),
+        _ => false,
+    }
+}
+
+/// A `[...]` character class.
+struct CharClass {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl CharClass {
+    /// *Parse a character class (after the opening `[`)*
+    ///
+    /// ---
+    ///
+    /// Returns the class, and the rest of the pattern after the closing `]`.
+    /// Returns None if there is no closing `]`.
+    fn parse(class: &[char]) -> Option<(Self, &[char])> {
+        let (negated, mut index) = match class.first() {
+            Some('!') | Some('^') => (true, 1),
+            _ => (false, 0),
+        };
+        let start = index;
+        let mut ranges: Vec<(char, char)> = Vec::new();
+
+        loop {
+            let c = *class.get(index)?;
+
+            if c == ']' && index > start {
+                return Some((Self { negated, ranges }, &class[index + 1..]));
+            }
+
+            match (class.get(index + 1), class.get(index + 2)) {
+                (Some('-'), Some(end)) if *end != ']' => {
+                    ranges.push((c, *end));
+                    index += 3;
+                }
+                _ => {
+                    ranges.push((c, c));
+                    index += 1;
+                }
+            }
+        }
+    }
+    fn contains(&self, c: char) -> bool {
+        let in_ranges = self
+            .ranges
+            .iter()
+            .any(|(start, end)| (*start..=*end).contains(&c));
+
+        in_ranges != self.negated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        let cases: Vec<(&str, &str, bool)> = vec![
+            ("*.log*", "access.log", true),
+            ("*.log*", "access.log.1.gz", true),
+            ("*.log*", ".DS_Store", false),
+            ("*.tmp", "access.log.tmp", true),
+            ("*.tmp", "access.tmp.log", false),
+            // * and ? never cross a directory separator.
+            ("*.log", "2024/access.log", false),
+            ("*/*.log", "2024/access.log", true),
+            ("access?.log", "access1.log", true),
+            ("access?.log", "access.log", false),
+            ("a?b", "a/b", false),
+            ("**", "2024/05/access.log", true),
+            ("**/*.log", "access.log", true),
+            ("**/*.log", "2024/05/access.log", true),
+            ("2024/**/*.gz", "2024/access.gz", true),
+            ("2024/**/*.gz", "2024/05/01/access.gz", true),
+            ("2024/**/*.gz", "2023/05/access.gz", false),
+            ("2024/**", "2024/05/access.gz", true),
+            ("log[0-9].txt", "log7.txt", true),
+            ("log[0-9].txt", "logx.txt", false),
+            ("log[!0-9].txt", "logx.txt", true),
+            ("log[!0-9].txt", "log7.txt", false),
+            ("log[]x].txt", "log].txt", true),
+            ("log[ab-].txt", "log-.txt", true),
+            ("log[.txt", "log[.txt", true),
+            ("\\*.log", "*.log", true),
+            ("\\*.log", "a.log", false),
+            ("", "", true),
+            ("", "a", false),
+            ("*", "", true),
+        ];
+
+        for (pattern, text, expected) in cases {
+            assert_eq!(
+                glob_matches(pattern, text),
+                expected,
+                "pattern: {}, text: {}",
+                pattern,
+                text
+            );
+        }
+    }
+}
@@ -0,0 +1,405 @@
+//! A small JSON parser, for reading JSON documents (like our own json and ndjson output) back.
+//!
+//! Parses the whole grammar from RFC 8259, and rejects everything else (trailing commas, single quotes, trailing content, etc.).
+//! Numbers are kept as they were written, so big integers (like u64 metrics) don't lose precision. Use `as_u64` to read them.
+//! Object members keep their order. Duplicate keys are kept as well, and `get` returns the first one.
+//!
+//! Nesting is limited to `MAX_DEPTH` levels, so a malicious input can't overflow the stack.
+
+/// How deep objects and arrays can be nested.
+pub const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    /// The number, exactly as it was written.
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// *Parse a whole JSON document*
+    ///
+    /// ---
+    ///
+    /// Whitespace around the value is allowed. Returns the reason and the byte offset, if the document is not valid.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let value = JsonValue::parse(r#"{"owner_id": 1}"#)?;
+    ///
+    /// assert_eq!(value.get("owner_id").and_then(JsonValue::as_u64), Some(1));
+    /// ```
+    pub fn parse(input: &str) -> Result<JsonValue, String> {
+        let mut parser = Parser {
+            input: input.as_bytes(),
+            position: 0,
+        };
+
+        parser.skip_whitespace();
+
+        let value = parser.parse_value(0)?;
+
+        parser.skip_whitespace();
+
+        if parser.position != input.len() {
+            return Err(parser.error("Unexpected content after the value"));
+        }
+
+        Ok(value)
+    }
+    /// *Return the value of a key, if this is an object that has it*
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members
+                .iter()
+                .find(|(member_key, _)| member_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+    /// *Return the number, if this is a non-negative integer that fits into u64*
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            JsonValue::Number(number) => number.parse::<u64>().ok(),
+            _ => None,
+        }
+    }
+    /// *Return the string, if this is a string*
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+    /// *Return the items, if this is an array*
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+    /// *Return the members, in order, if this is an object*
+    pub fn as_object(&self) -> Option<&[(String, JsonValue)]> {
+        match self {
+            JsonValue::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &str) -> String {
+        format!("{} at byte {}", reason, self.position)
+    }
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.position += 1;
+        }
+    }
+    fn expect_literal(&mut self, literal: &str, value: JsonValue) -> Result<JsonValue, String> {
+        if self.input[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+
+            Ok(value)
+        } else {
+            Err(self.error("Invalid literal"))
+        }
+    }
+    fn parse_value(&mut self, depth: usize) -> Result<JsonValue, String> {
+        match self.peek() {
+            Some(b'{') => self.parse_object(depth + 1),
+            Some(b'[') => self.parse_array(depth + 1),
+            Some(b'"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some(b't') => self.expect_literal("true", JsonValue::Bool(true)),
+            Some(b'f') => self.expect_literal("false", JsonValue::Bool(false)),
+            Some(b'n') => self.expect_literal("null", JsonValue::Null),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.error("Unexpected character")),
+            None => Err(self.error("Unexpected end of the input")),
+        }
+    }
+    fn parse_object(&mut self, depth: usize) -> Result<JsonValue, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("Nested too deep"));
+        }
+
+        let mut members: Vec<(String, JsonValue)> = Vec::new();
+
+        self.position += 1;
+        self.skip_whitespace();
+
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+
+            return Ok(JsonValue::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+
+            if self.peek() != Some(b'"') {
+                return Err(self.error("Expected a key"));
+            }
+
+            let key = self.parse_string()?;
+
+            self.skip_whitespace();
+
+            if self.peek() != Some(b':') {
+                return Err(self.error("Expected ':'"));
+            }
+
+            self.position += 1;
+            self.skip_whitespace();
+            members.push((key, self.parse_value(depth)?));
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+
+                    return Ok(JsonValue::Object(members));
+                }
+                _ => return Err(self.error("Expected ',' or '}'")),
+            }
+        }
+    }
+    fn parse_array(&mut self, depth: usize) -> Result<JsonValue, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("Nested too deep"));
+        }
+
+        let mut items: Vec<JsonValue> = Vec::new();
+
+        self.position += 1;
+        self.skip_whitespace();
+
+        if self.peek() == Some(b']') {
+            self.position += 1;
+
+            return Ok(JsonValue::Array(items));
+        }
+
+        loop {
+            self.skip_whitespace();
+            items.push(self.parse_value(depth)?);
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+
+                    return Ok(JsonValue::Array(items));
+                }
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+    fn parse_number(&mut self) -> Result<JsonValue, String> {
+        let start = self.position;
+        let digits = |parser: &mut Self| {
+            let digits_start = parser.position;
+
+            while let Some(b'0'..=b'9') = parser.peek() {
+                parser.position += 1;
+            }
+
+            parser.position - digits_start
+        };
+
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+
+        match self.peek() {
+            Some(b'0') => self.position += 1,
+            Some(b'1'..=b'9') => {
+                digits(self);
+            }
+            _ => return Err(self.error("Invalid number")),
+        }
+
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+
+            if digits(self) == 0 {
+                return Err(self.error("Invalid number"));
+            }
+        }
+
+        if let Some(b'e' | b'E') = self.peek() {
+            self.position += 1;
+
+            if let Some(b'+' | b'-') = self.peek() {
+                self.position += 1;
+            }
+
+            if digits(self) == 0 {
+                return Err(self.error("Invalid number"));
+            }
+        }
+
+        Ok(JsonValue::Number(
+            String::from_utf8_lossy(&self.input[start..self.position]).into_owned(),
+        ))
+    }
+    fn parse_string(&mut self) -> Result<String, String> {
+        let mut bytes: Vec<u8> = Vec::new();
+
+        self.position += 1;
+
+        loop {
+            let Some(byte) = self.peek() else {
+                return Err(self.error("Unterminated string"));
+            };
+
+            self.position += 1;
+
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(escape) = self.peek() else {
+                        return Err(self.error("Unterminated string"));
+                    };
+
+                    self.position += 1;
+
+                    let escaped = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{08}',
+                        b'f' => '\u{0c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("Invalid escape")),
+                    };
+
+                    bytes.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                control if control < 0x20 => {
+                    self.position -= 1;
+
+                    return Err(self.error("Unescaped control character in a string"));
+                }
+                _ => bytes.push(byte),
+            }
+        }
+
+        // The input is a &str, and escapes are always encoded as valid UTF-8.
+        String::from_utf8(bytes).map_err(|_| self.error("Invalid UTF-8 in a string"))
+    }
+    /// *Parse the 4 hex digits after `\u` (and a following low surrogate, if needed)*
+    fn parse_unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.parse_hex4()?;
+
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("Invalid unicode escape"));
+        }
+
+        if !self.input[self.position..].starts_with(b"\\u") {
+            return Err(self.error("Unpaired surrogate"));
+        }
+
+        self.position += 2;
+
+        let low = self.parse_hex4()?;
+
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("Unpaired surrogate"));
+        }
+
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            .ok_or_else(|| self.error("Invalid unicode escape"))
+    }
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let hex = self
+            .input
+            .get(self.position..self.position + 4)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .filter(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("Invalid unicode escape"))?;
+        let code =
+            u32::from_str_radix(hex, 16).map_err(|_| self.error("Invalid unicode escape"))?;
+
+        self.position += 4;
+
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_value_parse() {
+        let value = JsonValue::parse(
+            r#" {"a": [1, -2.5e3, true, false, null], "b": {"c": "x\"\\\/\n\u00e9\ud83d\ude00"}, "big": 18446744073709551615} "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value.get("a"),
+            Some(&JsonValue::Array(vec![
+                JsonValue::Number("1".to_string()),
+                JsonValue::Number("-2.5e3".to_string()),
+                JsonValue::Bool(true),
+                JsonValue::Bool(false),
+                JsonValue::Null,
+            ]))
+        );
+        assert_eq!(
+            value
+                .get("b")
+                .and_then(|b| b.get("c"))
+                .and_then(JsonValue::as_str),
+            Some("x\"\\/\né😀")
+        );
+        assert_eq!(value.get("big").and_then(JsonValue::as_u64), Some(u64::MAX));
+        assert_eq!(value.get("missing"), None);
+
+        for invalid in [
+            "",
+            "[1,]",
+            "{\"a\" 1}",
+            "{'a': 1}",
+            "[1] 2",
+            "01",
+            "1.",
+            "\"unterminated",
+            "\"tab\tinside\"",
+            "\"\\ud83d\"",
+            "\"\\x\"",
+            "tru",
+        ] {
+            assert!(JsonValue::parse(invalid).is_err(), "{:?}", invalid);
+        }
+
+        let deep = "[".repeat(MAX_DEPTH + 1) + &"]".repeat(MAX_DEPTH + 1);
+
+        assert!(
+            JsonValue::parse(&deep)
+                .unwrap_err()
+                .contains("Nested too deep")
+        );
+        assert!(JsonValue::parse(&deep[1..deep.len() - 1]).is_ok());
+    }
+}
@@ -0,0 +1,231 @@
+//! A streaming JSON writer.
+//!
+//! Values are written to the underlying writer as they come, so even a huge aggregate never has to be in memory as a single string.
+//! The writer only takes care of the syntax: commas, colons, indentation and string escaping (as described in RFC 8259).
+//! The caller is responsible for a valid structure (for example, a key before every value inside of an object).
+//!
+//! In the pretty mode, every value in an object or an array goes on its own line, indented by two spaces per level.
+//! In the compact mode, there is no whitespace at all.
+use std::io::Write;
+
+/// An object or an array, that is currently being written.
+struct Container {
+    is_empty: bool,
+}
+
+pub struct JsonWriter<W: Write> {
+    writer: W,
+    pretty: bool,
+    containers: Vec<Container>,
+    after_key: bool,
+}
+
+impl<W: Write> JsonWriter<W> {
+    /// *Create a writer, in the pretty or the compact mode*
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let mut json_writer = JsonWriter::new(std::io::stdout().lock(), true);
+    ///
+    /// json_writer.begin_object()?;
+    /// json_writer.key("owner_id")?;
+    /// json_writer.number(1)?;
+    /// json_writer.end_object()?;
+    /// ```
+    pub fn new(writer: W, pretty: bool) -> Self {
+        Self {
+            writer,
+            pretty,
+            containers: Vec::new(),
+            after_key: false,
+        }
+    }
+    /// *Return the underlying writer*
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+    /// *Start an object, as a value*
+    pub fn begin_object(&mut self) -> std::io::Result<()> {
+        self.begin_container(b'{')
+    }
+    /// *End the current object*
+    pub fn end_object(&mut self) -> std::io::Result<()> {
+        self.end_container(b'}')
+    }
+    /// *Start an array, as a value*
+    pub fn begin_array(&mut self) -> std::io::Result<()> {
+        self.begin_container(b'[')
+    }
+    /// *End the current array*
+    pub fn end_array(&mut self) -> std::io::Result<()> {
+        self.end_container(b']')
+    }
+    /// *Write the key of the next value, in the current object*
+    pub fn key(&mut self, key: &str) -> std::io::Result<()> {
+        self.before_value()?;
+        write_escaped(&mut self.writer, key)?;
+        self.writer
+            .write_all(if self.pretty { b": " } else { b":" })?;
+        self.after_key = true;
+
+        Ok(())
+    }
+    /// *Write a string value, escaped*
+    pub fn string(&mut self, value: &str) -> std::io::Result<()> {
+        self.before_value()?;
+
+        write_escaped(&mut self.writer, value)
+    }
+    /// *Write an unsigned integer value*
+    pub fn number(&mut self, value: u64) -> std::io::Result<()> {
+        self.before_value()?;
+
+        write!(self.writer, "{}", value)
+    }
+    /// *Write a key, and an unsigned integer value for it*
+    pub fn key_number(&mut self, key: &str, value: u64) -> std::io::Result<()> {
+        self.key(key)?;
+
+        self.number(value)
+    }
+    /// *Write a key, and a string value for it*
+    pub fn key_string(&mut self, key: &str, value: &str) -> std::io::Result<()> {
+        self.key(key)?;
+
+        self.string(value)
+    }
+    /// *Write the separator (and the indentation in the pretty mode) before a value, or a key*
+    fn before_value(&mut self) -> std::io::Result<()> {
+        if self.after_key {
+            self.after_key = false;
+
+            return Ok(());
+        }
+
+        let depth = self.containers.len();
+
+        if let Some(container) = self.containers.last_mut() {
+            if !container.is_empty {
+                self.writer.write_all(b",")?;
+            }
+
+            container.is_empty = false;
+
+            if self.pretty {
+                self.write_indentation(depth)?;
+            }
+        }
+
+        Ok(())
+    }
+    fn begin_container(&mut self, open: u8) -> std::io::Result<()> {
+        self.before_value()?;
+        self.writer.write_all(&[open])?;
+        self.containers.push(Container { is_empty: true });
+
+        Ok(())
+    }
+    fn end_container(&mut self, close: u8) -> std::io::Result<()> {
+        let container = self.containers.pop().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "No object or array to end",
+            )
+        })?;
+
+        if self.pretty && !container.is_empty {
+            self.write_indentation(self.containers.len())?;
+        }
+
+        self.writer.write_all(&[close])
+    }
+    fn write_indentation(&mut self, depth: usize) -> std::io::Result<()> {
+        self.writer.write_all(b"\n")?;
+
+        for _ in 0..depth {
+            self.writer.write_all(b"  ")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// *Write a string in double quotes, escaping the quotes, the backslashes and the control characters*
+fn write_escaped(writer: &mut impl Write, value: &str) -> std::io::Result<()> {
+    writer.write_all(b"\"")?;
+
+    let mut unescaped_start = 0;
+
+    for (index, char) in value.char_indices() {
+        let escaped = match char {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            '\n' => "\\n".to_string(),
+            '\r' => "\\r".to_string(),
+            '\t' => "\\t".to_string(),
+            '\u{08}' => "\\b".to_string(),
+            '\u{0c}' => "\\f".to_string(),
+            control if control < ' ' => format!("\\u{:04x}", control as u32),
+            _ => continue,
+        };
+
+        writer.write_all(&value.as_bytes()[unescaped_start..index])?;
+        writer.write_all(escaped.as_bytes())?;
+        unescaped_start = index + char.len_utf8();
+    }
+
+    writer.write_all(&value.as_bytes()[unescaped_start..])?;
+    writer.write_all(b"\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_sample(pretty: bool) -> String {
+        let mut json_writer = JsonWriter::new(Vec::new(), pretty);
+
+        json_writer.begin_object().unwrap();
+        json_writer.key_number("id", 1).unwrap();
+        json_writer
+            .key_string("name", "a \"quoted\"\\ name\n\u{01}é")
+            .unwrap();
+        json_writer.key("empty").unwrap();
+        json_writer.begin_array().unwrap();
+        json_writer.end_array().unwrap();
+        json_writer.key("items").unwrap();
+        json_writer.begin_array().unwrap();
+        json_writer.number(u64::MAX).unwrap();
+        json_writer.begin_object().unwrap();
+        json_writer.end_object().unwrap();
+        json_writer.end_array().unwrap();
+        json_writer.end_object().unwrap();
+
+        String::from_utf8(json_writer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_json_writer_modes() {
+        assert_eq!(
+            write_sample(false),
+            r#"{"id":1,"name":"a \"quoted\"\\ name\n\u0001é","empty":[],"items":[18446744073709551615,{}]}"#
+        );
+        assert_eq!(
+            write_sample(true),
+            r#"{
+  "id": 1,
+  "name": "a \"quoted\"\\ name\n\u0001é",
+  "empty": [],
+  "items": [
+    18446744073709551615,
+    {}
+  ]
+}"#
+        );
+
+        let mut json_writer = JsonWriter::new(Vec::new(), false);
+
+        assert!(json_writer.end_array().is_err());
+    }
+}
@@ -1 +1,9 @@
+pub mod atomic_file;
+pub mod fnv;
 pub mod fs_utils;
+pub mod glob;
+pub mod json_value;
+pub mod json_writer;
+pub mod symlink_policy_enum;
+pub mod toml;
+pub mod worker_pool;
@@ -0,0 +1,50 @@
+//! Enum to represent what happens with symbolic links, found while discovering the log files.
+//!
+//! By default, links are followed: a link to a file is parsed like the file itself, and a link to a directory is walked.
+//! Every directory is walked at most once (compared by its real path), so links pointing back up the tree can't cause an endless walk.
+//! Broken links are skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SymlinkPolicy {
+    /// Follow links to files and directories.
+    #[default]
+    Follow,
+    /// Ignore all links.
+    Skip,
+}
+
+impl SymlinkPolicy {
+    /// *Resolve the policy from its name, as given on the command line*
+    ///
+    /// ## Arguments
+    ///
+    /// - `name` - One of follow | skip
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// assert_eq!(SymlinkPolicy::resolve("skip"), Ok(SymlinkPolicy::Skip));
+    /// ```
+    pub fn resolve(name: &str) -> Result<Self, String> {
+        match name {
+            "follow" => Ok(SymlinkPolicy::Follow),
+            "skip" => Ok(SymlinkPolicy::Skip),
+            unknown_policy => Err(format!(
+                "Unknown symlink policy: {}. Available policies: follow, skip",
+                unknown_policy
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_resolve_policies() {
+        assert_eq!(SymlinkPolicy::resolve("follow"), Ok(SymlinkPolicy::Follow));
+        assert_eq!(SymlinkPolicy::resolve("skip"), Ok(SymlinkPolicy::Skip));
+        assert!(SymlinkPolicy::resolve("ignore").is_err());
+        assert_eq!(SymlinkPolicy::default(), SymlinkPolicy::Follow);
+    }
+}
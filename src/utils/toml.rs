@@ -0,0 +1,433 @@
+//! A small TOML parser, for reading the config file (see `settings.rs`).
+//!
+//! Only the subset a config file needs is supported:
+//!
+//! - `key = value` pairs, with bare keys (`A-Za-z0-9_-`)
+//! - `[table]` and `[table.subtable]` headers. Keys under a table are returned as `table.key`
+//! - Basic strings (`"..."`, with the `\" \\ \n \t \r \uXXXX` escapes) and literal strings (`'...'`)
+//! - Integers (with `_` separators), floats, `true` and `false`
+//! - Arrays of the above, which can span multiple lines, and have a trailing comma
+//! - `#` comments
+//!
+//! Everything else (inline tables, arrays of tables, dates, multi-line strings, dotted and quoted keys) is rejected with an error,
+//! instead of being misread. Defining a key or a table twice is an error too.
+
+/// How deep arrays can be nested.
+pub const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TomlValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<TomlValue>),
+}
+
+impl TomlValue {
+    /// *Parse a whole TOML document into its keys and values, in the order they were written*
+    ///
+    /// ---
+    ///
+    /// Returns the reason and the line number, if the document is not valid (or uses something that is not supported).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// let values = TomlValue::parse_document("workers = 4\n[webhook]\nurl = \"http://localhost/usage\"\n")?;
+    ///
+    /// assert_eq!(values[1], ("webhook.url".to_string(), TomlValue::String("http://localhost/usage".to_string())));
+    /// ```
+    pub fn parse_document(input: &str) -> Result<Vec<(String, TomlValue)>, String> {
+        let mut parser = Parser {
+            input: input.as_bytes(),
+            position: 0,
+        };
+        let mut values: Vec<(String, TomlValue)> = Vec::new();
+        let mut tables: Vec<String> = Vec::new();
+        let mut table = String::new();
+
+        loop {
+            parser.skip_whitespace_and_comments(true);
+
+            match parser.peek() {
+                None => return Ok(values),
+                Some(b'[') => {
+                    parser.position += 1;
+
+                    if parser.peek() == Some(b'[') {
+                        return Err(parser.error("Arrays of tables are not supported"));
+                    }
+
+                    parser.skip_whitespace_and_comments(false);
+
+                    let mut name = parser.parse_key()?;
+
+                    while parser.peek() == Some(b'.') {
+                        parser.position += 1;
+                        name.push('.');
+                        name.push_str(&parser.parse_key()?);
+                    }
+
+                    parser.skip_whitespace_and_comments(false);
+
+                    if parser.peek() != Some(b']') {
+                        return Err(parser.error("Expected ']'"));
+                    }
+
+                    parser.position += 1;
+
+                    if tables.contains(&name) {
+                        return Err(parser.error(&format!("The table {} is defined twice", name)));
+                    }
+
+                    tables.push(name.clone());
+                    table = name;
+                }
+                Some(_) => {
+                    let key = parser.parse_key()?;
+                    let key = if table.is_empty() {
+                        key
+                    } else {
+                        format!("{}.{}", table, key)
+                    };
+
+                    parser.skip_whitespace_and_comments(false);
+
+                    if parser.peek() != Some(b'=') {
+                        return Err(parser.error("Expected '=' after the key"));
+                    }
+
+                    parser.position += 1;
+                    parser.skip_whitespace_and_comments(false);
+
+                    let value = parser.parse_value(0)?;
+
+                    if values.iter().any(|(existing_key, _)| *existing_key == key) {
+                        return Err(parser.error(&format!("The key {} is defined twice", key)));
+                    }
+
+                    values.push((key, value));
+                }
+            }
+
+            // Every key and table header ends its line.
+            parser.skip_whitespace_and_comments(false);
+
+            match parser.peek() {
+                None | Some(b'\n') => {}
+                Some(b'\r') if parser.input.get(parser.position + 1) == Some(&b'\n') => {}
+                Some(_) => return Err(parser.error("Expected the end of the line")),
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &str) -> String {
+        let line = self.input[..self.position.min(self.input.len())]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count()
+            + 1;
+
+        format!("{} on line {}", reason, line)
+    }
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+    /// *Skip spaces, tabs and comments, and also the line breaks if `newlines` is true*
+    fn skip_whitespace_and_comments(&mut self, newlines: bool) {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t') => self.position += 1,
+                Some(b'\n' | b'\r') if newlines => self.position += 1,
+                Some(b'#') => {
+                    while !matches!(self.peek(), None | Some(b'\n' | b'\r')) {
+                        self.position += 1;
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+    fn parse_key(&mut self) -> Result<String, String> {
+        let start = self.position;
+
+        while let Some(b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-') = self.peek() {
+            self.position += 1;
+        }
+
+        if start == self.position {
+            return Err(match self.peek() {
+                Some(b'"' | b'\'') => self.error("Quoted keys are not supported"),
+                _ => self.error("Expected a key"),
+            });
+        }
+
+        Ok(String::from_utf8_lossy(&self.input[start..self.position]).into_owned())
+    }
+    fn parse_value(&mut self, depth: usize) -> Result<TomlValue, String> {
+        match self.peek() {
+            Some(b'"') => {
+                if self.input[self.position..].starts_with(b"\"\"\"") {
+                    return Err(self.error("Multi-line strings are not supported"));
+                }
+
+                Ok(TomlValue::String(self.parse_basic_string()?))
+            }
+            Some(b'\'') => {
+                if self.input[self.position..].starts_with(b"'''") {
+                    return Err(self.error("Multi-line strings are not supported"));
+                }
+
+                Ok(TomlValue::String(self.parse_literal_string()?))
+            }
+            Some(b'[') => self.parse_array(depth + 1),
+            Some(b'{') => Err(self.error("Inline tables are not supported")),
+            Some(b't' | b'f') => self.parse_boolean(),
+            Some(b'+' | b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.error("Invalid value (strings must be quoted)")),
+            None => Err(self.error("Expected a value")),
+        }
+    }
+    fn parse_boolean(&mut self) -> Result<TomlValue, String> {
+        for (literal, value) in [("true", true), ("false", false)] {
+            if self.input[self.position..].starts_with(literal.as_bytes()) {
+                self.position += literal.len();
+
+                return Ok(TomlValue::Boolean(value));
+            }
+        }
+
+        Err(self.error("Invalid value (strings must be quoted)"))
+    }
+    fn parse_number(&mut self) -> Result<TomlValue, String> {
+        let start = self.position;
+
+        while let Some(b'+' | b'-' | b'.' | b'_' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+            self.position += 1;
+        }
+
+        let raw = &self.input[start..self.position];
+        // Underscores are only allowed between digits.
+        let valid_underscores = raw.iter().enumerate().all(|(index, byte)| {
+            *byte != b'_'
+                || (index > 0
+                    && raw[index - 1].is_ascii_digit()
+                    && raw.get(index + 1).is_some_and(u8::is_ascii_digit))
+        });
+        let number = String::from_utf8_lossy(raw).replace('_', "");
+
+        if valid_underscores {
+            if let Ok(integer) = number.parse::<i64>() {
+                return Ok(TomlValue::Integer(integer));
+            }
+
+            // Rust also accepts "inf", "nan" and a missing digit around the '.', TOML does not.
+            let digits_around_dot = number.split_once('.').is_none_or(|(before, after)| {
+                before.ends_with(|character: char| character.is_ascii_digit())
+                    && after.starts_with(|character: char| character.is_ascii_digit())
+            });
+
+            if digits_around_dot && let Ok(float) = number.parse::<f64>() {
+                return Ok(TomlValue::Float(float));
+            }
+        }
+
+        self.position = start;
+
+        Err(self.error("Invalid number"))
+    }
+    fn parse_array(&mut self, depth: usize) -> Result<TomlValue, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("Nested too deep"));
+        }
+
+        let mut items: Vec<TomlValue> = Vec::new();
+
+        self.position += 1;
+
+        loop {
+            self.skip_whitespace_and_comments(true);
+
+            if self.peek() == Some(b']') {
+                self.position += 1;
+
+                return Ok(TomlValue::Array(items));
+            }
+
+            items.push(self.parse_value(depth)?);
+            self.skip_whitespace_and_comments(true);
+
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+
+                    return Ok(TomlValue::Array(items));
+                }
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+    fn parse_literal_string(&mut self) -> Result<String, String> {
+        self.position += 1;
+
+        let start = self.position;
+
+        loop {
+            match self.peek() {
+                Some(b'\'') => break,
+                None | Some(b'\n' | b'\r') => return Err(self.error("Unterminated string")),
+                _ => self.position += 1,
+            }
+        }
+
+        let string = String::from_utf8_lossy(&self.input[start..self.position]).into_owned();
+
+        self.position += 1;
+
+        Ok(string)
+    }
+    fn parse_basic_string(&mut self) -> Result<String, String> {
+        let mut bytes: Vec<u8> = Vec::new();
+
+        self.position += 1;
+
+        loop {
+            let Some(byte) = self.peek() else {
+                return Err(self.error("Unterminated string"));
+            };
+
+            match byte {
+                b'"' => {
+                    self.position += 1;
+
+                    break;
+                }
+                b'\n' | b'\r' => return Err(self.error("Unterminated string")),
+                b'\\' => {
+                    self.position += 1;
+
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'n') => '\n',
+                        Some(b't') => '\t',
+                        Some(b'r') => '\r',
+                        Some(b'u') => {
+                            let hex = self
+                                .input
+                                .get(self.position + 1..self.position + 5)
+                                .and_then(|hex| std::str::from_utf8(hex).ok())
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("Invalid unicode escape"))?;
+
+                            self.position += 4;
+
+                            hex
+                        }
+                        _ => return Err(self.error("Invalid escape")),
+                    };
+
+                    self.position += 1;
+                    bytes.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                _ => {
+                    self.position += 1;
+                    bytes.push(byte);
+                }
+            }
+        }
+
+        // The input is a &str, and escapes are always encoded as valid UTF-8.
+        String::from_utf8(bytes).map_err(|_| self.error("Invalid UTF-8 in a string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_parse_document() {
+        let document = "# usage-parse config
+log_dir = [
+    \"logs/nginx\", # the main servers
+    'C:\\logs\\apache',
+]
+workers = 1_000
+max_error_rate = 0.05
+totals = true
+
+[webhook]
+url = \"http://localhost:8080/usage?key=\\u00e9\"   # trailing comment
+retries = -1
+
+[schema.extra]
+empty = []
+";
+
+        assert_eq!(
+            TomlValue::parse_document(document).unwrap(),
+            vec![
+                (
+                    "log_dir".to_string(),
+                    TomlValue::Array(vec![
+                        TomlValue::String("logs/nginx".to_string()),
+                        TomlValue::String("C:\\logs\\apache".to_string()),
+                    ])
+                ),
+                ("workers".to_string(), TomlValue::Integer(1000)),
+                ("max_error_rate".to_string(), TomlValue::Float(0.05)),
+                ("totals".to_string(), TomlValue::Boolean(true)),
+                (
+                    "webhook.url".to_string(),
+                    TomlValue::String("http://localhost:8080/usage?key=é".to_string())
+                ),
+                ("webhook.retries".to_string(), TomlValue::Integer(-1)),
+                ("schema.extra.empty".to_string(), TomlValue::Array(vec![])),
+            ]
+        );
+        assert_eq!(
+            TomlValue::parse_document("\n# only a comment\n"),
+            Ok(vec![])
+        );
+
+        for (document, error) in [
+            ("formatter = json", "strings must be quoted"),
+            ("workers = 4 5", "Expected the end of the line on line 1"),
+            ("a = 1\na = 2", "The key a is defined twice on line 2"),
+            ("[a]\n[a]", "The table a is defined twice"),
+            ("a = {b = 1}", "Inline tables are not supported"),
+            ("[[a]]", "Arrays of tables are not supported"),
+            ("\"a\" = 1", "Quoted keys are not supported"),
+            ("a = \"\"\"b\"\"\"", "Multi-line strings"),
+            ("a = \"b", "Unterminated string"),
+            ("a = [1, 2", "Expected ',' or ']'"),
+            ("a = 1__0", "Invalid number"),
+            ("a = .5", "Invalid value"),
+            ("a = 1.", "Invalid number"),
+            ("a = 1979-05-27", "Invalid number"),
+            ("a = \"\\x\"", "Invalid escape"),
+            ("a =", "Expected a value"),
+            ("= 1", "Expected a key"),
+        ] {
+            let result = TomlValue::parse_document(document);
+
+            assert!(
+                result.as_ref().is_err_and(|reason| reason.contains(error)),
+                "{:?}: {:?}",
+                document,
+                result
+            );
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! A bounded pool of worker threads, for parsing all log files in a directory.
+//!
+//! The pool starts a fixed number of threads (by default, one per available CPU).
+//! All log file paths are put in a shared queue, and every idle worker pulls the next path from it, until the queue is exhausted.
+//! This way, we avoid creating hundreds of threads for hundreds of files, while still processing every single file.
+//!
+//! Results are sent back through a channel, so the caller decides how to merge them.
+use super::super::log_parser_lib::log_parser::LogParser;
+use super::super::log_parser_lib::log_parser_error::LogParserError;
+use super::super::log_parser_lib::parse_options::ParseOptions;
+use super::super::log_parser_lib::parse_report_struct::LogParseReport;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// Result of parsing a single log file, as sent through the channel by the workers.
+pub type LogParseResult = Result<LogParseReport, LogParserError>;
+
+/// *Decide how many workers should be started*
+///
+/// ---
+///
+/// If the user requested a specific number of workers, use it. Otherwise, use the available parallelism of the machine.
+/// If that can't be determined, fall back to a single worker.
+///
+/// ---
+///
+/// ## Arguments
+///
+/// - `requested` - Number of workers, requested by the user (for example from the CLI). Must be greater than 0.
+///
+/// ## Example
+///
+/// ```
+/// assert_eq!(resolve_number_of_workers(Some(3)), 3);
+/// assert!(resolve_number_of_workers(None) >= 1);
+/// ```
+pub fn resolve_number_of_workers(requested: Option<usize>) -> usize {
+    match requested {
+        Some(number_of_workers) => number_of_workers.max(1),
+        None => std::thread::available_parallelism()
+            .map(|parallelism| parallelism.get())
+            .unwrap_or(1),
+    }
+}
+
+/// *Spawn the workers, and start parsing all given log files*
+///
+/// ---
+///
+/// Never starts more workers than there are files. Every worker sends exactly one result per file through the `tx` channel.
+/// The caller is responsible for joining the returned handles, to check if any of the workers panicked.
+///
+/// Note: `tx` is moved into the pool. Once all workers finish, the channel is closed, so the receiving end will not block indefinitely.
+///
+/// ---
+///
+/// ## Arguments
+///
+/// - `log_files` - Full paths of all log files to parse
+/// - `number_of_workers` - How many threads to start at most
+/// - `options` - Parse options, shared by all workers
+/// - `tx` - Sending end of the channel, for the parse results
+///
+/// ## Example
+///
+/// ```
+/// let (tx, rx) = std::sync::mpsc::channel();
+/// let handles = spawn_log_workers(vec!["logs/log1.txt".to_string()], 4, ParseOptions::default(), tx);
+///
+/// for log_parse_result in rx {
+///     // Merge the results...
+/// }
+/// ```
+pub fn spawn_log_workers(
+    log_files: Vec<String>,
+    number_of_workers: usize,
+    options: ParseOptions,
+    tx: Sender<LogParseResult>,
+) -> Vec<JoinHandle<()>> {
+    let number_of_workers = number_of_workers.max(1).min(log_files.len());
+    let queue = Arc::new(Mutex::new(log_files.into_iter()));
+    let mut handles: Vec<JoinHandle<()>> = Vec::with_capacity(number_of_workers);
+
+    for _ in 0..number_of_workers {
+        let queue_clone = Arc::clone(&queue);
+        let tx_clone = tx.clone();
+        let options_clone = options.clone();
+
+        let handle = std::thread::spawn(move || {
+            loop {
+                // Hold the lock only while taking the next path, so other workers are not blocked while parsing.
+                let next_log_file = match queue_clone.lock() {
+                    Ok(mut queue) => queue.next(),
+                    Err(_) => panic!(
+                        "One of the workers panicked while holding the queue lock. It is not safe to continue."
+                    ),
+                };
+
+                let log_file = match next_log_file {
+                    Some(log_file) => log_file,
+                    None => break,
+                };
+
+                let log_parser = LogParser::with_options(&log_file, options_clone.clone());
+
+                if tx_clone.send(log_parser.parse()).is_err() {
+                    panic!(
+                        "One of the threads could not send the result through the channel. It is not safe to continue."
+                    );
+                }
+            }
+        });
+
+        handles.push(handle);
+    }
+
+    handles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::log_parser_lib::owner_usage_struct::OwnerUsage;
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    #[test]
+    fn test_resolve_number_of_workers() {
+        assert_eq!(resolve_number_of_workers(Some(3)), 3);
+        assert_eq!(resolve_number_of_workers(Some(0)), 1);
+        assert!(resolve_number_of_workers(None) >= 1);
+    }
+
+    #[test]
+    fn test_every_file_is_processed() {
+        let test_log_dir = "test_worker_pool_dir";
+        let number_of_files = 500;
+        let mut log_files = Vec::with_capacity(number_of_files);
+
+        std::fs::create_dir(test_log_dir).unwrap();
+
+        for i in 0..number_of_files {
+            let log_file = format!("{}/log{}.txt", test_log_dir, i);
+            let mut file_handle = std::fs::File::create(&log_file).unwrap();
+
+            // Every file has 2 video plays and 1 ad impression, spread across 7 owners.
+            writeln!(
+                file_handle,
+                "https://www.mysite.com/pixel.gif?o={}&v=2222&i=555",
+                i % 7
+            )
+            .unwrap();
+            writeln!(
+                file_handle,
+                "https://www.mysite.com/pixel.gif?o={}&v=3333",
+                i % 7
+            )
+            .unwrap();
+
+            log_files.push(log_file);
+        }
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handles = spawn_log_workers(log_files, 4, ParseOptions::default(), tx);
+        let mut aggregate: HashMap<u32, OwnerUsage> = HashMap::new();
+        let mut number_of_results = 0;
+
+        for log_parse_result in rx {
+            number_of_results += 1;
+
+            for (owner_id, owner_usage) in log_parse_result.unwrap().take_usage() {
+                aggregate
+                    .entry(owner_id)
+                    .or_default()
+                    .merge(&owner_usage)
+                    .unwrap();
+            }
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        std::fs::remove_dir_all(test_log_dir).unwrap();
+
+        assert_eq!(number_of_results, number_of_files);
+        assert_eq!(aggregate.len(), 7);
+
+        let total_video_plays: u64 = aggregate
+            .values()
+            .map(|u| u.get_metric("video_plays"))
+            .sum();
+        let total_ad_impressions: u64 = aggregate
+            .values()
+            .map(|u| u.get_metric("ad_impressions"))
+            .sum();
+
+        assert_eq!(total_video_plays, 1000);
+        assert_eq!(total_ad_impressions, 500);
+    }
+}